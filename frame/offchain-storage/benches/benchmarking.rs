@@ -0,0 +1,297 @@
+//! Benchmarks of the offchain-storage calls over key and value lengths.
+//!
+//! Each call runs in a fresh example runtime with an in-memory backend, so the figures
+//! are those of the module itself. Fit a `WeightInfo` of the runtime to them, see
+//! `offchain_storage::weights`.
+
+use criterion::{criterion_group, criterion_main, Bencher, Criterion};
+use frame_support::{impl_outer_event, impl_outer_origin, parameter_types, weights::Weight};
+use frame_system as system;
+use offchain_storage::{self, transaction::Op, ExternalStorage, StorageError};
+use sp_core::H256;
+use sp_runtime::{
+    testing::Header,
+    traits::{BlakeTwo256, IdentifyAccount, IdentityLookup, Lazy, Verify},
+    Perbill,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+impl_outer_origin! {
+    pub enum Origin for Runtime {}
+}
+
+impl_outer_event! {
+    pub enum MetaEvent for Runtime {
+        offchain_storage<T>,
+    }
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Runtime;
+parameter_types! {
+    pub const BlockHashCount: u64 = 250;
+    pub const MaximumBlockWeight: Weight = 1024;
+    pub const MaximumBlockLength: u32 = 2 * 1024;
+    pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+    pub const ChunkSize: u32 = 1024;
+    pub const Provider: u64 = 9;
+    pub const ChallengePeriod: u64 = 10;
+    pub const GcKeysPerBlock: u32 = 16;
+    pub const ExpiriesPerBlock: u32 = 64;
+    pub const MaxDataIdLength: u32 = 256;
+    pub const MaxValueLength: u32 = 1024 * 1024;
+    pub const MaxEntriesPerAccount: u32 = 1024;
+    pub const MaxBytesPerAccount: u64 = u64::max_value();
+    pub const MaxWritesPerBlock: u32 = u32::max_value();
+    pub const MaxUploadAttempts: u32 = 5;
+    pub const ChunkedUploadPeriod: u64 = 100;
+    pub const MaxChunkedUploads: u32 = 16;
+    pub const MaxEndpointLength: u32 = 256;
+    pub const MaxReviewers: u32 = 16;
+}
+impl system::Trait for Runtime {
+    type Origin = Origin;
+    type Call = ();
+    type Index = u64;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = MetaEvent;
+    type BlockHashCount = BlockHashCount;
+    type MaximumBlockWeight = MaximumBlockWeight;
+    type MaximumBlockLength = MaximumBlockLength;
+    type AvailableBlockRatio = AvailableBlockRatio;
+    type Version = ();
+    type ModuleToIndex = ();
+}
+impl pallet_balances::Trait for Runtime {
+    type Balance = u64;
+    type OnFreeBalanceZero = ();
+    type OnNewAccount = ();
+    type Event = ();
+    type TransferPayment = ();
+    type DustRemoval = ();
+    type ExistentialDeposit = ();
+    type TransferFee = ();
+    type CreationFee = ();
+}
+impl pallet_randomness_collective_flip::Trait for Runtime {}
+impl offchain_storage::Trait for Runtime {
+    type Event = MetaEvent;
+    type Storage = MemStorage;
+    type AdminOrigin = frame_system::EnsureRoot<u64>;
+    type RemoteHeaders = ();
+    type SovereignAccount = offchain_storage::bridge::HashedSovereignAccount;
+    type ChunkSize = ChunkSize;
+    type Providers = Provider;
+    type Randomness = pallet_randomness_collective_flip::Module<Runtime>;
+    type ChallengePeriod = ChallengePeriod;
+    type Replicas = ();
+    type ErasureCoding = ();
+    type Currency = pallet_balances::Module<Runtime>;
+    type DepositBase = ();
+    type DepositPerByte = ();
+    type RentPerByte = ();
+    type RentPayment = ();
+    type RentGracePeriod = ();
+    type CurrentSession = ();
+    type ProtocolFee = ();
+    type ProtocolFeeDestination = ();
+    type Operators = ();
+    type UnbondingDelay = ();
+    type SlashFraction = ();
+    type Slash = ();
+    type Attestation = ();
+    type BundleSignature = TestSignature;
+    type BundleSigner = TestSigner;
+    type GcKeysPerBlock = GcKeysPerBlock;
+    type GcWeightPerKey = ();
+    type ExpiriesPerBlock = ExpiriesPerBlock;
+    type ExpiryWeight = ();
+    type TrashPeriod = ();
+    type MaxMessageLen = ();
+    type MaxRevisions = ();
+    type SnapshotPeriod = ();
+    type WeightInfo = ();
+    type IdempotencyWindow = ();
+    type StatusRetention = ();
+    type RequestDeposit = ();
+    type MaxRequestAttempts = ();
+    type MaxDataIdLength = MaxDataIdLength;
+    type MaxValueLength = MaxValueLength;
+    type MaxEntriesPerAccount = MaxEntriesPerAccount;
+    type MaxBytesPerAccount = MaxBytesPerAccount;
+    type MaxWritesPerBlock = MaxWritesPerBlock;
+    type DeferredUploads = ();
+    type Confirmations = ();
+    type MaxUploadAttempts = MaxUploadAttempts;
+    type OnAccessGranted = ();
+    type NamespaceDeposit = ();
+    type Backends = ();
+    type TypedDataIds = ();
+    type OnDataWritten = ();
+    type OnDataDeleted = ();
+    type Compressor = ();
+    type DefaultCompression = ();
+    type Deduplication = ();
+    type PinDeposit = ();
+    type MaxAuditRecords = ();
+    type ChunkedUploadPeriod = ChunkedUploadPeriod;
+    type MaxChunkedUploads = MaxChunkedUploads;
+    type MaxEndpointLength = MaxEndpointLength;
+    type ProviderDeposit = ();
+    type MaxReviewers = MaxReviewers;
+}
+
+// A signature of account .0, valid for the message .1.
+#[derive(codec::Encode, codec::Decode, Clone, PartialEq, Eq, Debug)]
+pub struct TestSignature(pub u64, pub Vec<u8>);
+
+pub struct TestSigner(pub u64);
+
+impl IdentifyAccount for TestSigner {
+    type AccountId = u64;
+    fn into_account(self) -> u64 {
+        self.0
+    }
+}
+
+impl Verify for TestSignature {
+    type Signer = TestSigner;
+    fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &u64) -> bool {
+        self.0 == *signer && self.1[..] == *msg.get()
+    }
+}
+
+type OffchainStorage = offchain_storage::Module<Runtime>;
+
+thread_local! {
+    static STORE: RefCell<HashMap<Vec<u8>, Vec<u8>>> = RefCell::new(HashMap::new());
+}
+
+// An in-memory backend.
+pub struct MemStorage;
+
+impl ExternalStorage for MemStorage {
+    fn get(key: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        Ok(STORE.with(|s| s.borrow().get(&key).cloned().unwrap_or_default()))
+    }
+
+    fn set(key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        STORE.with(|s| s.borrow_mut().insert(key, value));
+        Ok(())
+    }
+
+    fn delete(key: Vec<u8>) -> Result<(), StorageError> {
+        STORE.with(|s| s.borrow_mut().remove(&key));
+        Ok(())
+    }
+}
+
+fn new_test_ext() -> sp_io::TestExternalities {
+    frame_system::GenesisConfig::default()
+        .build_storage::<Runtime>()
+        .unwrap()
+        .into()
+}
+
+const KEY_LENS: [u32; 3] = [8, 64, 256];
+const VALUE_LENS: [u32; 4] = [32, 1024, 16 * 1024, 256 * 1024];
+const OP_COUNTS: [u32; 3] = [1, 4, 16];
+
+fn key(len: u32) -> Vec<u8> {
+    vec![1; len as usize]
+}
+
+fn write_data(b: &mut Bencher, &(key_len, len): &(u32, u32)) {
+    b.iter(|| {
+        new_test_ext().execute_with(|| {
+            OffchainStorage::write_data(Origin::signed(1), key(key_len), vec![2; len as usize])
+                .unwrap();
+        })
+    });
+}
+
+fn read_data(b: &mut Bencher, &key_len: &u32) {
+    b.iter(|| {
+        new_test_ext().execute_with(|| {
+            OffchainStorage::write_data(Origin::signed(1), key(key_len), vec![2; 32]).unwrap();
+            OffchainStorage::read_data(Origin::signed(2), key(key_len)).unwrap();
+        })
+    });
+}
+
+fn delete_data(b: &mut Bencher, &key_len: &u32) {
+    b.iter(|| {
+        new_test_ext().execute_with(|| {
+            OffchainStorage::write_data(Origin::signed(1), key(key_len), vec![2; 32]).unwrap();
+            OffchainStorage::delete_data(Origin::signed(1), key(key_len)).unwrap();
+        })
+    });
+}
+
+fn copy_data(b: &mut Bencher, &(key_len, len): &(u32, u32)) {
+    b.iter(|| {
+        new_test_ext().execute_with(|| {
+            OffchainStorage::write_data(Origin::signed(1), key(key_len), vec![2; len as usize])
+                .unwrap();
+            OffchainStorage::copy_data(Origin::signed(1), key(key_len), vec![3; key_len as usize])
+                .unwrap();
+        })
+    });
+}
+
+fn append_data(b: &mut Bencher, &(key_len, len): &(u32, u32)) {
+    b.iter(|| {
+        new_test_ext().execute_with(|| {
+            OffchainStorage::write_data(Origin::signed(1), key(key_len), vec![2; len as usize])
+                .unwrap();
+            OffchainStorage::append_data(Origin::signed(1), key(key_len), vec![3; 32]).unwrap();
+        })
+    });
+}
+
+fn register_provider(b: &mut Bencher, &len: &u32) {
+    b.iter(|| {
+        new_test_ext().execute_with(|| {
+            OffchainStorage::register_provider(Origin::signed(1), vec![2; len as usize], 1, 0)
+                .unwrap();
+        })
+    });
+}
+
+fn transact(b: &mut Bencher, &(ops, len): &(u32, u32)) {
+    b.iter(|| {
+        new_test_ext().execute_with(|| {
+            let ops = (0..ops)
+                .map(|i| Op::Write(vec![i as u8; 8], vec![2; len as usize]))
+                .collect();
+            OffchainStorage::transact(Origin::signed(1), ops).unwrap();
+        })
+    });
+}
+
+fn offchain_storage_benchmark(c: &mut Criterion) {
+    let inputs: Vec<(u32, u32)> = KEY_LENS
+        .iter()
+        .flat_map(|&k| VALUE_LENS.iter().map(move |&v| (k, v)))
+        .collect();
+    c.bench_function_over_inputs("write_data (key_len, len)", write_data, inputs.clone());
+    c.bench_function_over_inputs("copy_data (key_len, len)", copy_data, inputs.clone());
+    c.bench_function_over_inputs("append_data (key_len, len)", append_data, inputs);
+    let ops: Vec<(u32, u32)> = OP_COUNTS
+        .iter()
+        .flat_map(|&n| VALUE_LENS.iter().map(move |&v| (n, v)))
+        .collect();
+    c.bench_function_over_inputs("transact (ops, len)", transact, ops);
+    c.bench_function_over_inputs("read_data key_len", read_data, KEY_LENS.to_vec());
+    c.bench_function_over_inputs("delete_data key_len", delete_data, KEY_LENS.to_vec());
+    c.bench_function_over_inputs("register_provider len", register_provider, KEY_LENS.to_vec());
+}
+
+criterion_group!(benches, offchain_storage_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,71 @@
+//! Binary diffs between two values.
+//!
+//! A gateway holding both values of two revisions sends a [`Delta`] instead of the new
+//! value. The delta names the content hashes of both revisions, as the chain committed to
+//! them, so a reviewer holding the old value can rebuild the new one and know it is the
+//! one the chain has.
+
+use codec::{Decode, Encode};
+use sp_runtime::traits::Hash;
+
+/// A step rebuilding the new value.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub enum DeltaOp {
+    /// Copy `len` bytes of the old value from `offset`.
+    Copy { offset: u32, len: u32 },
+    /// Add bytes not taken from the old value.
+    Insert(Vec<u8>),
+}
+
+/// What changed from the value hashing to `from` to the one hashing to `to`.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct Delta<Hash> {
+    pub from: Hash,
+    pub to: Hash,
+    pub ops: Vec<DeltaOp>,
+}
+
+impl<Output: PartialEq> Delta<Output> {
+    /// The delta turning `old` into `new`. It keeps what both share at their start and
+    /// end and replaces what lies between.
+    pub fn compute<H: Hash<Output = Output>>(old: &[u8], new: &[u8]) -> Self {
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let mut ops = Vec::new();
+        if prefix > 0 {
+            ops.push(DeltaOp::Copy { offset: 0, len: prefix as u32 });
+        }
+        if new.len() > prefix + suffix {
+            ops.push(DeltaOp::Insert(new[prefix..new.len() - suffix].to_vec()));
+        }
+        if suffix > 0 {
+            let offset = (old.len() - suffix) as u32;
+            ops.push(DeltaOp::Copy { offset, len: suffix as u32 });
+        }
+        Delta { from: H::hash(old), to: H::hash(new), ops }
+    }
+
+    /// The new value, if `old` is the value the delta starts from and applying it gives
+    /// the value it commits to.
+    pub fn apply<H: Hash<Output = Output>>(&self, old: &[u8]) -> Option<Vec<u8>> {
+        if H::hash(old) != self.from {
+            return None;
+        }
+        let mut new = Vec::new();
+        for op in &self.ops {
+            match op {
+                DeltaOp::Copy { offset, len } => {
+                    let start = *offset as usize;
+                    new.extend_from_slice(old.get(start..start.checked_add(*len as usize)?)?);
+                }
+                DeltaOp::Insert(bytes) => new.extend_from_slice(bytes),
+            }
+        }
+        Some(new).filter(|new| H::hash(new) == self.to)
+    }
+}
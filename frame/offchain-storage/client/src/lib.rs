@@ -0,0 +1,386 @@
+//! Client for data stored through the offchain-storage module.
+//!
+//! dApps read entries through a [`Transport`] (a node RPC, a gateway, a bridge
+//! relayer...) and this crate verifies everything that comes back against a
+//! finalized header the caller already trusts. The `Data` entry must be proven
+//! by the header's state root, and the value must match the content hash of the
+//! entry. Nothing returned by the transport is trusted. The crate builds for
+//! native and `wasm32` targets alike.
+//!
+//! Values entries share are fetched under the key of their object, which the state proves
+//! as well. Values compressed with snappy are decompressed before they are checked. The
+//! client can't decompress zstd and fails to read such values with [`Error::Unsupported`].
+//!
+//! Past revisions are read the same way, and [`Client::diff`] turns two of them into a
+//! [`Delta`](diff::Delta) a reviewer can check against the chain.
+
+pub mod diff;
+
+use codec::{Decode, Encode};
+pub use offchain_storage_runtime_api::{compression::Compression, EncryptionInfo, ErrorCode};
+use offchain_storage_runtime_api::compression::snappy;
+use sp_core::hashing::{blake2_256, twox_128};
+use sp_runtime::traits::{Hash, Header};
+use sp_trie::{read_trie_value, trie_types::Layout, HashDBT, MemoryDB, EMPTY_PREFIX};
+use std::marker::PhantomData;
+
+/// The access of an entry, as the module stores it.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Access {
+    Avoid,
+    Read,
+    Write,
+}
+
+/// An entry of the module's `Data` map.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct Entry<AccountId, Hash, BlockNumber> {
+    pub author: AccountId,
+    pub access: Access,
+    pub content_hash: Hash,
+    pub replication: u32,
+    pub providers: Vec<AccountId>,
+    pub size: u32,
+    pub created_at: BlockNumber,
+    pub updated_at: BlockNumber,
+    pub encryption: Option<EncryptionInfo>,
+    pub backend: u8,
+    pub compression: Compression,
+}
+
+/// A revision of an entry, as the module stores it.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct Revision<AccountId, Hash, BlockNumber> {
+    pub writer: AccountId,
+    pub content_hash: Hash,
+    pub key: Vec<u8>,
+    pub message: Vec<u8>,
+    pub at: BlockNumber,
+}
+
+/// Where the client gets its (untrusted) data from.
+pub trait Transport<BlockHash> {
+    type Error;
+
+    /// The storage proof of `keys` in the state of block `at`.
+    fn read_proof(&self, at: &BlockHash, keys: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>, Self::Error>;
+
+    /// The value stored under the backend key `key`, a data_id or the key of an object.
+    fn fetch(&self, key: &[u8]) -> Result<Vec<u8>, Self::Error>;
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error<E> {
+    /// The transport failed.
+    Transport(E),
+    /// The proof doesn't match the state root.
+    InvalidProof,
+    /// The state has no entry for the data_id, or no such revision of it.
+    NoEntry,
+    /// The value doesn't match the content hash of the entry.
+    IntegrityError,
+    /// The entry or the value can't be decoded.
+    Decode,
+    /// The value is compressed in a way the client can't undo.
+    Unsupported,
+}
+
+/// The storage key of the `Data` entry of `data_id`.
+pub fn data_key(data_id: &[u8]) -> Vec<u8> {
+    let mut key = twox_128(b"OffchainStorage").to_vec();
+    key.extend_from_slice(&twox_128(b"Data"));
+    key.extend_from_slice(&blake2_256(&data_id.encode()));
+    key
+}
+
+/// The storage key of the object `data_id` shares its value in, in the module's
+/// `SharedValues` map.
+pub fn shared_key(data_id: &[u8]) -> Vec<u8> {
+    let mut key = twox_128(b"OffchainStorage").to_vec();
+    key.extend_from_slice(&twox_128(b"SharedValues"));
+    key.extend_from_slice(&blake2_256(&data_id.encode()));
+    key
+}
+
+/// The storage key of revision `number` of `data_id` in the module's `Revisions` map.
+pub fn revision_key(data_id: &[u8], number: u32) -> Vec<u8> {
+    let mut key = twox_128(b"OffchainStorage").to_vec();
+    key.extend_from_slice(&twox_128(b"Revisions"));
+    key.extend_from_slice(&blake2_256(&data_id.encode()));
+    key.extend_from_slice(&blake2_256(&number.encode()));
+    key
+}
+
+impl<E> Error<E> {
+    /// The stable code of the error, the one the chain uses for the same failure.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::Transport(_) => ErrorCode::TRANSPORT,
+            Error::InvalidProof => ErrorCode::INVALID_PROOF,
+            // `NoneData` and `IntegrityError` of the module.
+            Error::NoEntry => ErrorCode::module(2),
+            Error::IntegrityError => ErrorCode::module(3),
+            Error::Decode => ErrorCode::DECODE,
+            // `UnsupportedCompression` of the module.
+            Error::Unsupported => ErrorCode::module(71),
+        }
+    }
+}
+
+/// Reads and verifies entries of a chain hashing with `H`.
+pub struct Client<T, H> {
+    transport: T,
+    _hashing: PhantomData<H>,
+}
+
+impl<T: Transport<H::Output>, H: Hash> Client<T, H> {
+    pub fn new(transport: T) -> Self {
+        Client {
+            transport,
+            _hashing: PhantomData,
+        }
+    }
+
+    /// The entry of `data_id` in the state of `header`.
+    pub fn entry<AccountId: Decode, Hdr: Header<Hash = H::Output>>(
+        &self,
+        header: &Hdr,
+        data_id: &[u8],
+    ) -> Result<Entry<AccountId, H::Output, Hdr::Number>, Error<T::Error>> {
+        self.prove(header, data_key(data_id))
+    }
+
+    /// Revision `number` of `data_id` in the state of `header`.
+    pub fn revision<AccountId: Decode, Hdr: Header<Hash = H::Output>>(
+        &self,
+        header: &Hdr,
+        data_id: &[u8],
+        number: u32,
+    ) -> Result<Revision<AccountId, H::Output, Hdr::Number>, Error<T::Error>> {
+        self.prove(header, revision_key(data_id, number))
+    }
+
+    /// The value of revision `number` of `data_id`, checked against the revision in the
+    /// state of `header`.
+    pub fn read_version<AccountId: Decode, Hdr: Header<Hash = H::Output>>(
+        &self,
+        header: &Hdr,
+        data_id: &[u8],
+        number: u32,
+    ) -> Result<Vec<u8>, Error<T::Error>> {
+        let revision = self.revision::<AccountId, _>(header, data_id, number)?;
+        let value = self.transport.fetch(&revision.key).map_err(Error::Transport)?;
+        if H::hash(&value) != revision.content_hash {
+            return Err(Error::IntegrityError);
+        }
+        Ok(value)
+    }
+
+    /// What changed from revision `a` of `data_id` to revision `b`, both checked against
+    /// the state of `header`.
+    pub fn diff<AccountId: Decode, Hdr: Header<Hash = H::Output>>(
+        &self,
+        header: &Hdr,
+        data_id: &[u8],
+        a: u32,
+        b: u32,
+    ) -> Result<diff::Delta<H::Output>, Error<T::Error>> {
+        let old = self.read_version::<AccountId, _>(header, data_id, a)?;
+        let new = self.read_version::<AccountId, _>(header, data_id, b)?;
+        Ok(diff::Delta::compute::<H>(&old, &new))
+    }
+
+    // the value under key in the state of header, decoded as a V.
+    fn prove<V: Decode, Hdr: Header<Hash = H::Output>>(
+        &self,
+        header: &Hdr,
+        key: Vec<u8>,
+    ) -> Result<V, Error<T::Error>> {
+        let proof = self
+            .transport
+            .read_proof(&header.hash(), vec![key.clone()])
+            .map_err(Error::Transport)?;
+
+        let mut db = MemoryDB::<H::Hasher>::default();
+        for node in &proof {
+            HashDBT::insert(&mut db, EMPTY_PREFIX, &node[..]);
+        }
+        let raw = read_trie_value::<Layout<H::Hasher>, _>(&db, header.state_root(), &key)
+            .map_err(|_| Error::InvalidProof)?
+            .ok_or(Error::NoEntry)?;
+        Decode::decode(&mut &raw[..]).map_err(|_| Error::Decode)
+    }
+
+    /// The value of `data_id`, checked against its entry in the state of `header`.
+    pub fn read<AccountId: Decode, Hdr: Header<Hash = H::Output>>(
+        &self,
+        header: &Hdr,
+        data_id: &[u8],
+    ) -> Result<Vec<u8>, Error<T::Error>> {
+        let entry = self.entry::<AccountId, _>(header, data_id)?;
+        let key = match self.prove::<Vec<u8>, _>(header, shared_key(data_id)) {
+            Ok(key) => key,
+            Err(Error::NoEntry) => data_id.to_vec(),
+            Err(e) => return Err(e),
+        };
+        let value = self.transport.fetch(&key).map_err(Error::Transport)?;
+        let value = match entry.compression {
+            Compression::None => value,
+            Compression::Snappy => snappy::decompress(&value).ok_or(Error::IntegrityError)?,
+            Compression::Zstd => return Err(Error::Unsupported),
+        };
+        if H::hash(&value) != entry.content_hash {
+            return Err(Error::IntegrityError);
+        }
+        Ok(value)
+    }
+
+    /// Like `read`, decoding the value as a `V`.
+    pub fn read_typed<AccountId: Decode, V: Decode, Hdr: Header<Hash = H::Output>>(
+        &self,
+        header: &Hdr,
+        data_id: &[u8],
+    ) -> Result<V, Error<T::Error>> {
+        let value = self.read::<AccountId, _>(header, data_id)?;
+        V::decode(&mut &value[..]).map_err(|_| Error::Decode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::H256;
+    use sp_runtime::{testing::Header as TestHeader, traits::BlakeTwo256};
+    use sp_trie::{
+        trie_types::{TrieDB, TrieDBMut},
+        Recorder, Trie, TrieMut,
+    };
+    use std::collections::BTreeMap;
+
+    type Hasher = <BlakeTwo256 as Hash>::Hasher;
+
+    struct Node {
+        db: MemoryDB<Hasher>,
+        root: H256,
+        values: BTreeMap<Vec<u8>, Vec<u8>>,
+    }
+
+    impl Transport<H256> for Node {
+        type Error = ();
+
+        fn read_proof(&self, _: &H256, keys: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>, ()> {
+            let trie = TrieDB::<Hasher>::new(&self.db, &self.root).map_err(|_| ())?;
+            let mut recorder = Recorder::new();
+            for key in keys {
+                trie.get_with(&key, &mut recorder).map_err(|_| ())?;
+            }
+            Ok(recorder.drain().into_iter().map(|r| r.data).collect())
+        }
+
+        fn fetch(&self, key: &[u8]) -> Result<Vec<u8>, ()> {
+            self.values.get(key).cloned().ok_or(())
+        }
+    }
+
+    fn node(data_id: &[u8], value: &[u8]) -> (Node, TestHeader) {
+        let entry = Entry {
+            author: 1u64,
+            access: Access::Read,
+            content_hash: BlakeTwo256::hash(value),
+            replication: 0,
+            providers: vec![],
+            size: value.len() as u32,
+            created_at: 1u64,
+            updated_at: 1u64,
+            encryption: None,
+            backend: 0,
+            compression: Compression::None,
+        };
+        let mut db = MemoryDB::<Hasher>::default();
+        let mut root = H256::default();
+        TrieDBMut::<Hasher>::new(&mut db, &mut root)
+            .insert(&data_key(data_id), &entry.encode())
+            .unwrap();
+        let header = TestHeader {
+            parent_hash: Default::default(),
+            number: 1,
+            state_root: root,
+            extrinsics_root: Default::default(),
+            digest: Default::default(),
+        };
+        let mut values = BTreeMap::new();
+        values.insert(data_id.to_vec(), value.to_vec());
+        (Node { db, root, values }, header)
+    }
+
+    #[test]
+    fn reads_are_verified() {
+        let value = 42u32.encode();
+        let (node, header) = node(b"key", &value);
+        let client = Client::<_, BlakeTwo256>::new(node);
+
+        assert_eq!(client.read::<u64, _>(&header, b"key"), Ok(value));
+        assert_eq!(client.read_typed::<u64, u32, _>(&header, b"key"), Ok(42));
+        assert_eq!(
+            client.read::<u64, _>(&header, b"missing"),
+            Err(Error::NoEntry)
+        );
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let (mut node, mut header) = node(b"key", b"value");
+        node.values.insert(b"key".to_vec(), b"evil".to_vec());
+        let client = Client::<_, BlakeTwo256>::new(node);
+        assert_eq!(
+            client.read::<u64, _>(&header, b"key"),
+            Err(Error::IntegrityError)
+        );
+
+        header.state_root = H256::repeat_byte(1);
+        assert_eq!(
+            client.read::<u64, _>(&header, b"key"),
+            Err(Error::InvalidProof)
+        );
+    }
+
+    #[test]
+    fn diffs_are_checked_against_the_revisions() {
+        let (old, new) = (b"port = 80\nhost = a".to_vec(), b"port = 8080\nhost = a".to_vec());
+        let mut db = MemoryDB::<Hasher>::default();
+        let mut root = H256::default();
+        let mut values = BTreeMap::new();
+        {
+            let mut trie = TrieDBMut::<Hasher>::new(&mut db, &mut root);
+            for (number, value) in vec![(1u32, &old), (2, &new)] {
+                let key = (&b"offchain-storage/version"[..], &b"key"[..], number).encode();
+                let revision = Revision {
+                    writer: 1u64,
+                    content_hash: BlakeTwo256::hash(value),
+                    key: key.clone(),
+                    message: vec![],
+                    at: 1u64,
+                };
+                trie.insert(&revision_key(b"key", number), &revision.encode()).unwrap();
+                values.insert(key, value.clone());
+            }
+        }
+        let header = TestHeader {
+            parent_hash: Default::default(),
+            number: 1,
+            state_root: root,
+            extrinsics_root: Default::default(),
+            digest: Default::default(),
+        };
+        let client = Client::<_, BlakeTwo256>::new(Node { db, root, values });
+
+        let delta = client.diff::<u64, _>(&header, b"key", 1, 2).unwrap();
+        assert_eq!(delta.ops[1], diff::DeltaOp::Insert(b"80".to_vec()));
+        assert_eq!(delta.apply::<BlakeTwo256>(&old), Some(new));
+        assert_eq!(delta.apply::<BlakeTwo256>(b"port = 81\nhost = a"), None);
+        assert_eq!(
+            client.diff::<u64, _>(&header, b"key", 1, 3),
+            Err(Error::NoEntry)
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! Write, read and delete a value through the upload pipeline of the offchain worker.
+//!
+//! ```text
+//! cargo run --example pipeline -- [fs|ipfs|rocksdb]
+//! ```
+//!
+//! The IPFS backend needs a daemon, see the ipfs module of the runtime.
+
+mod runtime;
+
+use offchain_storage::{routing::DEFAULT_BACKEND, Call, RawEvent};
+use runtime::{node::Node, Runtime, IPFS, ROCKSDB};
+
+fn main() {
+    let backend = match std::env::args().nth(1).as_ref().map(String::as_str) {
+        None | Some("fs") => DEFAULT_BACKEND,
+        Some("ipfs") => IPFS,
+        Some("rocksdb") => ROCKSDB,
+        Some(other) => {
+            eprintln!("unknown backend {}, expected fs, ipfs or rocksdb", other);
+            std::process::exit(1);
+        }
+    };
+    runtime::set_deferred_uploads(true);
+    let mut node = Node::default();
+    let (key, value) = (b"example".to_vec(), b"hello web3".to_vec());
+
+    let write = Call::<Runtime>::write_data_to(key.clone(), value.clone(), backend);
+    let outcomes = node.author_block(vec![(1, write.into())]);
+    println!("block 1: write_data_to {:?}, the worker uploads the value", outcomes[0]);
+
+    let confirmed = node.author_until(1, |e| *e == RawEvent::UploadConfirmed(key.clone()));
+    if confirmed.is_none() {
+        let failed = node.author_until(32, |e| match e {
+            RawEvent::WriteFailed(data_id, _) => *data_id == key,
+            _ => false,
+        });
+        println!("the upload failed: {:?}", failed);
+        std::process::exit(1);
+    }
+    println!("block 2: the upload is confirmed");
+
+    node.author_block(vec![(2, Call::<Runtime>::read_data(key.clone()).into())]);
+    let delivered = node.deliver(2, &key).map(|value| String::from_utf8_lossy(&value).into_owned());
+    println!("block 3: read_data, the RPC delivers {:?}", delivered);
+
+    let outcomes = node.author_block(vec![(1, Call::<Runtime>::delete_data(key.clone()).into())]);
+    println!("block 4: delete_data {:?}", outcomes[0]);
+    node.author_block(vec![]);
+    println!("block 5: the garbage collector drops the past versions");
+}
@@ -0,0 +1,110 @@
+//! An IPFS backend, keeping every value as a file of the mutable file system of a daemon.
+//!
+//! The daemon is reached over its HTTP API, at `OFFCHAIN_STORAGE_IPFS_API` or
+//! `127.0.0.1:5001`, e.g. one started with `docker run -p 5001:5001 ipfs/go-ipfs`. Files of
+//! the mutable file system are kept by the daemon without pinning them.
+
+use offchain_storage::{adapter::StorageClient, impl_external_storage, StorageError};
+use std::cell::RefCell;
+use std::io::{self, prelude::*};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// The directory of the mutable file system values are kept in.
+const ROOT: &str = "/offchain-storage";
+/// How long a call to the daemon may take.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+thread_local! {
+    static API: RefCell<String> = RefCell::new(
+        std::env::var("OFFCHAIN_STORAGE_IPFS_API").unwrap_or_else(|_| "127.0.0.1:5001".into())
+    );
+}
+
+/// Reach the daemon at `address` from this thread on.
+pub fn connect_to(address: &str) {
+    API.with(|a| *a.borrow_mut() = address.into());
+}
+
+pub struct Ipfs;
+
+impl StorageClient for Ipfs {
+    type Error = io::Error;
+
+    fn get(key: &[u8]) -> Result<Option<Vec<u8>>, io::Error> {
+        match call("files/read", key, None) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            value => value.map(Some),
+        }
+    }
+
+    fn set(key: &[u8], value: &[u8]) -> Result<(), io::Error> {
+        call("files/write", key, Some(value)).map(|_| ())
+    }
+
+    fn delete(key: &[u8]) -> Result<(), io::Error> {
+        match call("files/rm", key, None) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            outcome => outcome.map(|_| ()),
+        }
+    }
+
+    fn map_error(e: io::Error) -> StorageError {
+        super::reason(e)
+    }
+}
+
+impl_external_storage!(Ipfs);
+
+/// Whether the daemon answers at all.
+pub fn is_running() -> bool {
+    let address = API.with(|a| a.borrow().clone());
+    TcpStream::connect(address).is_ok()
+}
+
+// call the command of the API for the file of key, uploading value if there is one, and
+// return the body of the answer. A file that doesn't exist fails with NotFound.
+fn call(command: &str, key: &[u8], value: Option<&[u8]>) -> Result<Vec<u8>, io::Error> {
+    let address = API.with(|a| a.borrow().clone());
+    let mut path = format!("/api/v0/{}?arg={}/{}", command, ROOT, super::hex(key));
+    let mut body = Vec::new();
+    let boundary = "offchain-storage-value";
+    if let Some(value) = value {
+        path.push_str("&create=true&truncate=true&parents=true");
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        body.extend_from_slice(b"Content-Disposition: form-data; name=\"file\"\r\n");
+        body.extend_from_slice(b"Content-Type: application/octet-stream\r\n\r\n");
+        body.extend_from_slice(value);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    }
+    // HTTP/1.0, so the answer comes whole and not in chunks.
+    let head = format!(
+        "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: multipart/form-data; boundary={}\r\n\
+         Content-Length: {}\r\n\r\n",
+        path,
+        address,
+        boundary,
+        body.len()
+    );
+    let mut stream = TcpStream::connect(&address)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&body)?;
+    let mut answer = Vec::new();
+    stream.read_to_end(&mut answer)?;
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not an HTTP answer");
+    let end = answer.windows(4).position(|w| w == b"\r\n\r\n").ok_or_else(invalid)?;
+    let status = answer[..end].split(|b| *b == b' ').nth(1).ok_or_else(invalid)?;
+    let ok = status == b"200";
+    let body = answer.split_off(end + 4);
+    let message = String::from_utf8_lossy(&body).into_owned();
+    if ok {
+        Ok(body)
+    } else if message.contains("does not exist") {
+        Err(io::ErrorKind::NotFound.into())
+    } else {
+        Err(io::Error::new(io::ErrorKind::Other, message))
+    }
+}
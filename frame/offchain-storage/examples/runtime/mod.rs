@@ -0,0 +1,290 @@
+//! An example runtime wiring the offchain-storage module to real backends.
+//!
+//! Values are kept in files under a scratch directory by default, in IPFS as backend
+//! [`IPFS`] and in RocksDB as backend [`ROCKSDB`], see the routing module of the pallet.
+//! Writes are stored in place unless [`set_deferred_uploads`] turns on the upload pipeline
+//! of the offchain worker, which submits its confirmations to the [`TransactionPool`], signed
+//! by [`OPERATOR`]. The [`node`] executes blocks of this runtime the way a node does.
+//!
+//! Shared by the `pipeline` example and the end-to-end tests, which each use part of it.
+#![allow(dead_code)]
+
+pub mod ipfs;
+pub mod node;
+pub mod rocksdb;
+
+use frame_support::{
+    impl_outer_dispatch, impl_outer_event, impl_outer_origin, parameter_types, traits::Get,
+    weights::Weight,
+};
+use frame_system as system;
+use offchain_storage::{
+    routing::BackendId, upload::SubmitConfirmation, ExternalStorage, StorageError,
+};
+use sp_core::H256;
+use sp_runtime::{
+    testing::Header,
+    traits::{BlakeTwo256, IdentifyAccount, IdentityLookup, Lazy, Verify},
+    Perbill,
+};
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, prelude::*};
+use std::path::PathBuf;
+
+impl_outer_origin! {
+    pub enum Origin for Runtime {}
+}
+
+impl_outer_event! {
+    pub enum MetaEvent for Runtime {
+        offchain_storage<T>,
+    }
+}
+
+impl_outer_dispatch! {
+    pub enum Call for Runtime where origin: Origin {
+        offchain_storage::OffchainStorage,
+    }
+}
+
+/// The id of the IPFS backend.
+pub const IPFS: BackendId = 1;
+/// The id of the RocksDB backend.
+pub const ROCKSDB: BackendId = 2;
+/// The account the offchain worker of the node signs its confirmations with.
+pub const OPERATOR: u64 = 8;
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Runtime;
+parameter_types! {
+    pub const BlockHashCount: u64 = 250;
+    pub const MaximumBlockWeight: Weight = 1024;
+    pub const MaximumBlockLength: u32 = 2 * 1024;
+    pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+    pub const ChunkSize: u32 = 1024;
+    pub const Provider: u64 = 9;
+    pub const Operator: u64 = OPERATOR;
+    pub const ChallengePeriod: u64 = 10;
+    pub const GcKeysPerBlock: u32 = 16;
+    pub const ExpiriesPerBlock: u32 = 64;
+    pub const MaxRevisions: u32 = 4;
+    pub const MaxDataIdLength: u32 = 256;
+    pub const MaxValueLength: u32 = 1024 * 1024;
+    pub const MaxEntriesPerAccount: u32 = 1024;
+    pub const MaxBytesPerAccount: u64 = u64::max_value();
+    pub const MaxWritesPerBlock: u32 = u32::max_value();
+    pub const MaxUploadAttempts: u32 = 5;
+    pub const ChunkedUploadPeriod: u64 = 100;
+    pub const MaxChunkedUploads: u32 = 16;
+    pub const MaxEndpointLength: u32 = 256;
+    pub const MaxReviewers: u32 = 16;
+}
+impl system::Trait for Runtime {
+    type Origin = Origin;
+    type Call = Call;
+    type Index = u64;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = MetaEvent;
+    type BlockHashCount = BlockHashCount;
+    type MaximumBlockWeight = MaximumBlockWeight;
+    type MaximumBlockLength = MaximumBlockLength;
+    type AvailableBlockRatio = AvailableBlockRatio;
+    type Version = ();
+    type ModuleToIndex = ();
+}
+impl pallet_balances::Trait for Runtime {
+    type Balance = u64;
+    type OnFreeBalanceZero = ();
+    type OnNewAccount = ();
+    type Event = ();
+    type TransferPayment = ();
+    type DustRemoval = ();
+    type ExistentialDeposit = ();
+    type TransferFee = ();
+    type CreationFee = ();
+}
+impl pallet_randomness_collective_flip::Trait for Runtime {}
+impl offchain_storage::Trait for Runtime {
+    type Event = MetaEvent;
+    type Storage = FsStorage;
+    type AdminOrigin = frame_system::EnsureRoot<u64>;
+    type RemoteHeaders = ();
+    type SovereignAccount = offchain_storage::bridge::HashedSovereignAccount;
+    type ChunkSize = ChunkSize;
+    type Providers = Provider;
+    type Randomness = pallet_randomness_collective_flip::Module<Runtime>;
+    type ChallengePeriod = ChallengePeriod;
+    type Replicas = ();
+    type ErasureCoding = ();
+    type Currency = pallet_balances::Module<Runtime>;
+    type DepositBase = ();
+    type DepositPerByte = ();
+    type RentPerByte = ();
+    type RentPayment = ();
+    type RentGracePeriod = ();
+    type CurrentSession = ();
+    type ProtocolFee = ();
+    type ProtocolFeeDestination = ();
+    type Operators = Operator;
+    type UnbondingDelay = ();
+    type SlashFraction = ();
+    type Slash = ();
+    type Attestation = ();
+    type BundleSignature = TestSignature;
+    type BundleSigner = TestSigner;
+    type GcKeysPerBlock = GcKeysPerBlock;
+    type GcWeightPerKey = ();
+    type ExpiriesPerBlock = ExpiriesPerBlock;
+    type ExpiryWeight = ();
+    type TrashPeriod = ();
+    type MaxMessageLen = ();
+    type MaxRevisions = MaxRevisions;
+    type SnapshotPeriod = ();
+    type WeightInfo = ();
+    type IdempotencyWindow = ();
+    type StatusRetention = ();
+    type RequestDeposit = ();
+    type MaxRequestAttempts = ();
+    type MaxDataIdLength = MaxDataIdLength;
+    type MaxValueLength = MaxValueLength;
+    type MaxEntriesPerAccount = MaxEntriesPerAccount;
+    type MaxBytesPerAccount = MaxBytesPerAccount;
+    type MaxWritesPerBlock = MaxWritesPerBlock;
+    type DeferredUploads = DeferredUploads;
+    type Confirmations = TransactionPool;
+    type MaxUploadAttempts = MaxUploadAttempts;
+    type OnAccessGranted = ();
+    type NamespaceDeposit = ();
+    type Backends = Backends;
+    type TypedDataIds = ();
+    type OnDataWritten = ();
+    type OnDataDeleted = ();
+    type Compressor = ();
+    type DefaultCompression = ();
+    type Deduplication = ();
+    type PinDeposit = ();
+    type MaxAuditRecords = ();
+    type ChunkedUploadPeriod = ChunkedUploadPeriod;
+    type MaxChunkedUploads = MaxChunkedUploads;
+    type MaxEndpointLength = MaxEndpointLength;
+    type ProviderDeposit = ();
+    type MaxReviewers = MaxReviewers;
+}
+
+/// The backends besides the filesystem, [`IPFS`] and [`ROCKSDB`].
+pub type Backends = (ipfs::Ipfs, rocksdb::RocksDb);
+
+pub type System = system::Module<Runtime>;
+pub type OffchainStorage = offchain_storage::Module<Runtime>;
+
+// A signature of account .0, valid for the message .1.
+#[derive(codec::Encode, codec::Decode, Clone, PartialEq, Eq, Debug)]
+pub struct TestSignature(pub u64, pub Vec<u8>);
+
+pub struct TestSigner(pub u64);
+
+impl IdentifyAccount for TestSigner {
+    type AccountId = u64;
+    fn into_account(self) -> u64 {
+        self.0
+    }
+}
+
+impl Verify for TestSignature {
+    type Signer = TestSigner;
+    fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &u64) -> bool {
+        self.0 == *signer && self.1[..] == *msg.get()
+    }
+}
+
+thread_local! {
+    static DEFERRED: RefCell<bool> = RefCell::new(false);
+    static POOL: RefCell<Vec<Call>> = RefCell::new(vec![]);
+}
+
+/// Leave storing the values of writes to the offchain worker, or not, on this thread.
+pub fn set_deferred_uploads(deferred: bool) {
+    DEFERRED.with(|d| *d.borrow_mut() = deferred);
+}
+
+/// Whether writes are uploaded by the offchain worker, see [`set_deferred_uploads`].
+pub struct DeferredUploads;
+
+impl Get<bool> for DeferredUploads {
+    fn get() -> bool {
+        DEFERRED.with(|d| *d.borrow())
+    }
+}
+
+/// The calls the offchain worker submitted, to be signed by [`OPERATOR`] and included in
+/// the next block.
+pub struct TransactionPool;
+
+impl TransactionPool {
+    /// Take the calls submitted since the last block.
+    pub fn drain() -> Vec<Call> {
+        POOL.with(|p| p.borrow_mut().drain(..).collect())
+    }
+}
+
+impl SubmitConfirmation<Runtime> for TransactionPool {
+    fn submit(call: offchain_storage::Call<Runtime>) -> Result<(), ()> {
+        POOL.with(|p| p.borrow_mut().push(Call::OffchainStorage(call)));
+        Ok(())
+    }
+}
+
+/// A filesystem backend: every key is a file under a scratch directory.
+pub struct FsStorage;
+
+impl FsStorage {
+    /// The file the value under `key` is kept in.
+    pub fn path(key: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join("offchain-storage-end-to-end");
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(hex(key))
+    }
+}
+
+impl ExternalStorage for FsStorage {
+    fn get(key: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        let mut value = Vec::new();
+        fs::File::open(Self::path(&key))
+            .and_then(|mut file| file.read_to_end(&mut value))
+            .map_err(reason)?;
+        Ok(value)
+    }
+
+    fn set(key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        fs::File::create(Self::path(&key))
+            .and_then(|mut file| file.write_all(&value))
+            .map_err(reason)
+    }
+
+    fn delete(key: Vec<u8>) -> Result<(), StorageError> {
+        match fs::remove_file(Self::path(&key)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(reason(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Why an I/O operation of a backend failed, as the module reports it.
+pub fn reason(e: io::Error) -> StorageError {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => StorageError::Rejected,
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => StorageError::Timeout,
+        _ => StorageError::Unavailable,
+    }
+}
+
+// key in hex, a name every backend takes.
+fn hex(key: &[u8]) -> String {
+    key.iter().map(|b| format!("{:02x}", b)).collect()
+}
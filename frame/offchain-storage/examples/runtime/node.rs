@@ -0,0 +1,115 @@
+//! Executing blocks of the example runtime the way a node does.
+//!
+//! A [`Node`] keeps the state of the chain and authors one block at a time: it initializes
+//! the block, applies the calls the offchain worker submitted since the last one, signed by
+//! [`OPERATOR`](super::OPERATOR), then the extrinsics given, finalizes the block and runs
+//! the offchain worker on it, with a local storage of its own. The runtime is executed
+//! natively, there is no networking, transaction pool or wasm executor in between.
+
+use super::{
+    Backends, Call, FsStorage, MetaEvent, OffchainStorage, Origin, Runtime, System, OPERATOR,
+};
+use offchain_storage::{
+    routing::{StorageBackends, DEFAULT_BACKEND},
+    ExternalStorage, RawEvent,
+};
+use sp_core::{
+    offchain::{testing::TestOffchainExt, OffchainExt},
+    H256,
+};
+use sp_runtime::{
+    traits::{
+        BlakeTwo256, Dispatchable, Hash, Header as _, OffchainWorker, OnFinalize, OnInitialize,
+    },
+    DispatchResult,
+};
+
+/// An event of the module, as the runtime deposits it.
+pub type Event = RawEvent<u64, u64, H256>;
+
+pub struct Node {
+    state: sp_io::TestExternalities,
+    parent_hash: H256,
+}
+
+/// A node at the genesis of the chain.
+impl Default for Node {
+    fn default() -> Self {
+        let mut state: sp_io::TestExternalities = frame_system::GenesisConfig::default()
+            .build_storage::<Runtime>()
+            .unwrap()
+            .into();
+        let (offchain, _) = TestOffchainExt::new();
+        state.register_extension(OffchainExt::new(offchain));
+        Node { state, parent_hash: H256::default() }
+    }
+}
+
+impl Node {
+    /// Run `f` against the state at the last block.
+    pub fn execute_with<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        self.state.execute_with(f)
+    }
+
+    /// Author the next block with `extrinsics`, after the calls of the offchain worker, and
+    /// run the offchain worker on it. Returns the outcomes of `extrinsics`.
+    pub fn author_block(&mut self, extrinsics: Vec<(u64, Call)>) -> Vec<DispatchResult> {
+        let parent_hash = self.parent_hash;
+        let (outcomes, hash) = self.state.execute_with(|| {
+            let n = System::block_number() + 1;
+            System::initialize(&n, &parent_hash, &Default::default(), &Default::default());
+            OffchainStorage::on_initialize(n);
+            for call in super::TransactionPool::drain() {
+                // a confirmation the chain moved past is refused, as it would be in a block.
+                let _ = call.dispatch(Origin::signed(OPERATOR));
+            }
+            let outcomes = extrinsics
+                .into_iter()
+                .map(|(who, call)| call.dispatch(Origin::signed(who)))
+                .collect();
+            OffchainStorage::on_finalize(n);
+            let header = System::finalize();
+            OffchainStorage::offchain_worker(n);
+            (outcomes, header.hash())
+        });
+        self.parent_hash = hash;
+        outcomes
+    }
+
+    /// Author empty blocks until one deposits an event `matches`, at most `max` of them.
+    /// Returns the event.
+    pub fn author_until(&mut self, max: u32, matches: impl Fn(&Event) -> bool) -> Option<Event> {
+        for _ in 0..max {
+            self.author_block(vec![]);
+            if let Some(event) = self.events().into_iter().find(|event| matches(event)) {
+                return Some(event);
+            }
+        }
+        None
+    }
+
+    /// The events of the module in the last block.
+    pub fn events(&mut self) -> Vec<Event> {
+        self.execute_with(|| {
+            System::events()
+                .into_iter()
+                .filter_map(|record| match record.event {
+                    MetaEvent::offchain_storage(event) => Some(event),
+                    _ => None,
+                })
+                .collect()
+        })
+    }
+
+    /// Deliver the value `reader` paid to read of `data_id`, as the `offchainStorage_read`
+    /// RPC does: fetch it where the runtime locates it and check it against its hash.
+    pub fn deliver(&mut self, reader: u64, data_id: &[u8]) -> Option<Vec<u8>> {
+        let location = self.execute_with(|| OffchainStorage::locate(&reader, data_id))?;
+        let value = match location.backend {
+            DEFAULT_BACKEND => FsStorage::get(location.key),
+            backend => <Backends as StorageBackends>::get(backend, location.key),
+        }
+        .ok()?;
+        Some(value).filter(|value| BlakeTwo256::hash(value) == location.content_hash)
+    }
+}
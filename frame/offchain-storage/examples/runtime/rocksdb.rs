@@ -0,0 +1,52 @@
+//! A RocksDB backend, keeping every value under its key in one column of a database.
+//!
+//! The database is opened at `OFFCHAIN_STORAGE_ROCKSDB_PATH`, or in a scratch directory,
+//! the first time a value is read or written. RocksDB runs in the process of the node, so
+//! unlike IPFS it needs no service of its own.
+
+use kvdb_rocksdb::{Database, DatabaseConfig};
+use lazy_static::lazy_static;
+use offchain_storage::{adapter::StorageClient, impl_external_storage, StorageError};
+use std::io;
+
+/// The column values are kept in.
+const COLUMN: u32 = 0;
+
+lazy_static! {
+    static ref DB: Database = {
+        let path = std::env::var("OFFCHAIN_STORAGE_ROCKSDB_PATH").unwrap_or_else(|_| {
+            let dir = std::env::temp_dir().join("offchain-storage-rocksdb");
+            dir.to_string_lossy().into_owned()
+        });
+        Database::open(&DatabaseConfig::with_columns(1), &path)
+            .expect("the database of the RocksDB backend opens")
+    };
+}
+
+pub struct RocksDb;
+
+impl StorageClient for RocksDb {
+    type Error = io::Error;
+
+    fn get(key: &[u8]) -> Result<Option<Vec<u8>>, io::Error> {
+        DB.get(COLUMN, key).map(|value| value.map(|value| value.to_vec()))
+    }
+
+    fn set(key: &[u8], value: &[u8]) -> Result<(), io::Error> {
+        let mut transaction = DB.transaction();
+        transaction.put(COLUMN, key, value);
+        DB.write(transaction)
+    }
+
+    fn delete(key: &[u8]) -> Result<(), io::Error> {
+        let mut transaction = DB.transaction();
+        transaction.delete(COLUMN, key);
+        DB.write(transaction)
+    }
+
+    fn map_error(e: io::Error) -> StorageError {
+        super::reason(e)
+    }
+}
+
+impl_external_storage!(RocksDb);
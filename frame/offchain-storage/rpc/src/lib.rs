@@ -0,0 +1,266 @@
+//! RPC delivering values stored through the offchain-storage module.
+//!
+//! The read calls of the module only record that the reader paid to read a value, and emit
+//! its hash. `offchainStorage_read` delivers the value to a reader that proves it holds the
+//! key of its account: the node asks the `locate` runtime API where the value the account
+//! paid for is kept at the given block, fetches it from the backend of the node, undoes its
+//! compression and returns it once it matches the content hash the chain committed to.
+//!
+//! The reader signs `(b"offchain-storage/read", data_id, at)`, so a signed read can't be
+//! replayed against another entry or block. Values the runtime can't locate, e.g. those
+//! stored as erasure coded shards, and values kept in a backend other than the one of the
+//! node aren't delivered.
+//!
+//! `offchainStorage_readRange` returns a slice of the value, with the same signature. The
+//! backend serves a slice of an uncompressed value through `ExternalStorage::get_range`,
+//! which can't be checked against the content hash: readers needing that read the whole
+//! value.
+//!
+//! `offchainStorage_canAccess` tells whether an account may read or write an entry, through
+//! the `can_access` runtime API, so dapps check a call is allowed before submitting it.
+
+use codec::{Codec, Encode};
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use offchain_storage::{Access, Compression, ExternalStorage, ValueLocation};
+use offchain_storage_runtime_api::compression::snappy;
+pub use offchain_storage_runtime_api::OffchainStorageApi as OffchainStorageRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_runtime::{
+    generic::BlockId,
+    traits::{Block as BlockT, Hash, Header as HeaderT, IdentifyAccount, ProvideRuntimeApi, Verify},
+};
+use std::{marker::PhantomData, sync::Arc};
+
+pub use self::gen_client::Client as OffchainStorageClient;
+
+/// What a reader signs to read `data_id` at the block `at`.
+pub fn read_payload<BlockHash: Encode>(data_id: &[u8], at: &BlockHash) -> Vec<u8> {
+    (&b"offchain-storage/read"[..], data_id, at).encode()
+}
+
+#[rpc]
+pub trait OffchainStorageApi<BlockHash, AccountId, Signature> {
+    /// The value of `data_id`, if `account` may read it at the block `at`. `signature` is
+    /// the signature of `account` on [`read_payload`].
+    #[rpc(name = "offchainStorage_read")]
+    fn read(
+        &self,
+        data_id: Bytes,
+        account: AccountId,
+        signature: Signature,
+        at: Option<BlockHash>,
+    ) -> Result<Bytes>;
+
+    /// The `len` bytes of the value of `data_id` from `offset` on, if `account` may read it
+    /// at the block `at`. `signature` is the signature of `account` on [`read_payload`].
+    #[rpc(name = "offchainStorage_readRange")]
+    fn read_range(
+        &self,
+        data_id: Bytes,
+        offset: u32,
+        len: u32,
+        account: AccountId,
+        signature: Signature,
+        at: Option<BlockHash>,
+    ) -> Result<Bytes>;
+
+    /// Whether `account` has `level` of access to `data_id` at the block `at`.
+    #[rpc(name = "offchainStorage_canAccess")]
+    fn can_access(
+        &self,
+        data_id: Bytes,
+        account: AccountId,
+        level: Access,
+        at: Option<BlockHash>,
+    ) -> Result<bool>;
+}
+
+/// A struct that implements the [`OffchainStorageApi`], reading values from the backend
+/// `S`.
+pub struct OffchainStorage<C, S, P> {
+    client: Arc<C>,
+    _marker: PhantomData<(S, P)>,
+}
+
+impl<C, S, P> OffchainStorage<C, S, P> {
+    /// Create new `OffchainStorage` with the given reference to the client.
+    pub fn new(client: Arc<C>) -> Self {
+        OffchainStorage { client, _marker: Default::default() }
+    }
+}
+
+/// Error type of this RPC api.
+pub enum Error {
+    /// The call to runtime failed.
+    RuntimeError,
+    /// The signature isn't the reader's on the read payload.
+    BadSignature,
+    /// The reader didn't pay to read the entry, may not read it anymore, or it doesn't
+    /// exist.
+    PermissionDenied,
+    /// The backend failed to return the value.
+    Backend,
+    /// The value doesn't match the content hash of the entry.
+    IntegrityError,
+    /// The range reaches past the end of the value.
+    BadRange,
+    /// The value is kept in another backend, or compressed in a way the node can't undo.
+    Unsupported,
+}
+
+impl From<Error> for i64 {
+    fn from(e: Error) -> i64 {
+        match e {
+            Error::RuntimeError => 1,
+            Error::BadSignature => 2,
+            Error::PermissionDenied => 3,
+            Error::Backend => 4,
+            Error::IntegrityError => 5,
+            Error::BadRange => 6,
+            Error::Unsupported => 7,
+        }
+    }
+}
+
+fn error(e: Error, message: &str, data: Option<String>) -> RpcError {
+    RpcError {
+        code: ErrorCode::ServerError(e.into()),
+        message: message.into(),
+        data: data.map(Into::into),
+    }
+}
+
+fn backend_error(e: offchain_storage::StorageError) -> RpcError {
+    error(Error::Backend, "Unable to fetch the value.", Some(format!("{:?}", e)))
+}
+
+impl<C, S, Block, AccountId, Balance, Signature>
+    OffchainStorageApi<<Block as BlockT>::Hash, AccountId, Signature>
+    for OffchainStorage<C, S, (Block, Balance)>
+where
+    Block: BlockT,
+    C: Send + Sync + 'static,
+    C: ProvideRuntimeApi,
+    C: HeaderBackend<Block>,
+    C::Api: OffchainStorageRuntimeApi<Block, AccountId, <Block as BlockT>::Hash, Balance>,
+    S: ExternalStorage + Send + Sync + 'static,
+    AccountId: Codec + Send + Sync + 'static,
+    Balance: Codec + Send + Sync + 'static,
+    Signature: Verify + Send + Sync + 'static,
+    Signature::Signer: IdentifyAccount<AccountId = AccountId>,
+{
+    fn read(
+        &self,
+        data_id: Bytes,
+        account: AccountId,
+        signature: Signature,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<Bytes> {
+        let location = self.authorize(&data_id, account, signature, at)?;
+        Ok(fetch::<S, Block>(location)?.into())
+    }
+
+    fn read_range(
+        &self,
+        data_id: Bytes,
+        offset: u32,
+        len: u32,
+        account: AccountId,
+        signature: Signature,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<Bytes> {
+        let location = self.authorize(&data_id, account, signature, at)?;
+        let bad_range = || error(Error::BadRange, "The range is out of bounds.", None);
+        let end = offset.checked_add(len).ok_or_else(bad_range)? as usize;
+        if location.compression == Compression::None {
+            let slice = S::get_range(location.key, offset, len).map_err(backend_error)?;
+            return if slice.len() == len as usize { Ok(slice.into()) } else { Err(bad_range()) };
+        }
+        let value = fetch::<S, Block>(location)?;
+        let slice = value.get(offset as usize..end).ok_or_else(bad_range)?;
+        Ok(slice.to_vec().into())
+    }
+
+    fn can_access(
+        &self,
+        data_id: Bytes,
+        account: AccountId,
+        level: Access,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<bool> {
+        let api = self.client.runtime_api();
+        let at = BlockId::hash(at.unwrap_or_else(||
+            // If the block hash is not supplied assume the best block.
+            self.client.info().best_hash));
+        api.can_access(&at, account, data_id.to_vec(), level).map_err(|e| {
+            error(Error::RuntimeError, "Unable to check access.", Some(format!("{:?}", e)))
+        })
+    }
+}
+
+impl<C, S, Block, Balance> OffchainStorage<C, S, (Block, Balance)>
+where
+    Block: BlockT,
+    C: ProvideRuntimeApi,
+    C: HeaderBackend<Block>,
+{
+    // where the value of data_id account paid to read is kept, once the signature shows
+    // account asks to read it and the runtime says it may at the block at.
+    fn authorize<AccountId, Signature>(
+        &self,
+        data_id: &[u8],
+        account: AccountId,
+        signature: Signature,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<ValueLocation<<Block as BlockT>::Hash>>
+    where
+        C::Api: OffchainStorageRuntimeApi<Block, AccountId, <Block as BlockT>::Hash, Balance>,
+        AccountId: Codec,
+        Balance: Codec,
+        Signature: Verify,
+        Signature::Signer: IdentifyAccount<AccountId = AccountId>,
+    {
+        let api = self.client.runtime_api();
+        let at = at.unwrap_or_else(||
+            // If the block hash is not supplied assume the best block.
+            self.client.info().best_hash);
+
+        if !signature.verify(&read_payload(data_id, &at)[..], &account) {
+            return Err(error(Error::BadSignature, "Bad signature.", None));
+        }
+        let location = api.locate(&BlockId::hash(at), account, data_id.to_vec()).map_err(|e| {
+            error(Error::RuntimeError, "Unable to check access.", Some(format!("{:?}", e)))
+        })?;
+        let location =
+            location.ok_or_else(|| error(Error::PermissionDenied, "Permission denied.", None))?;
+        if location.backend != offchain_storage::routing::DEFAULT_BACKEND {
+            return Err(error(Error::Unsupported, "The value is in another backend.", None));
+        }
+        Ok(location)
+    }
+}
+
+// the value kept at location, fetched from the backend S and checked against its hash.
+fn fetch<S: ExternalStorage, Block: BlockT>(
+    location: ValueLocation<<Block as BlockT>::Hash>,
+) -> Result<Vec<u8>> {
+    let stored = S::get(location.key).map_err(backend_error)?;
+    let value = match location.compression {
+        Compression::None => Some(stored),
+        Compression::Snappy => snappy::decompress(&stored),
+        Compression::Zstd => {
+            return Err(error(Error::Unsupported, "The value is compressed with zstd.", None))
+        }
+    };
+    match value {
+        Some(value)
+            if <<Block as BlockT>::Header as HeaderT>::Hashing::hash(&value)
+                == location.content_hash =>
+        {
+            Ok(value)
+        }
+        _ => Err(error(Error::IntegrityError, "The value doesn't match.", None)),
+    }
+}
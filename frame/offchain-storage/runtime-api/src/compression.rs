@@ -0,0 +1,188 @@
+//! Compression of values kept in the backends.
+//!
+//! An entry records the `Compression` its value is stored with, so entries compressed in
+//! different ways, or not at all, stay readable side by side. Snappy is implemented here,
+//! in the raw format of the reference implementation, so runtimes and clients can both use
+//! it; zstd needs an implementation given by the runtime.
+
+use codec::{Decode, Encode};
+use sp_runtime::RuntimeDebug;
+#[cfg(feature = "std")]
+use sp_runtime::{Deserialize, Serialize};
+
+/// How a value is compressed in its backend.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum Compression {
+    /// Stored as it is.
+    None,
+    Snappy,
+    Zstd,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression::None
+    }
+}
+
+/// The snappy raw format: the length of the value as a varint, then literals and copies
+/// of earlier bytes.
+pub mod snappy {
+    use sp_std::vec::Vec;
+
+    const HASH_BITS: u32 = 14;
+    // the shortest match worth a copy.
+    const MIN_MATCH: usize = 4;
+    // copies are written with at most two bytes of offset.
+    const MAX_OFFSET: usize = 65535;
+
+    /// `value` compressed.
+    pub fn compress(value: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(value.len() / 2 + 8);
+        put_varint(&mut out, value.len() as u32);
+        // the last position, plus one, each hash of four bytes was seen at.
+        let mut table = sp_std::vec![0u32; 1 << HASH_BITS];
+        let (mut i, mut literal) = (0, 0);
+        while i + MIN_MATCH <= value.len() {
+            let h = hash(&value[i..i + MIN_MATCH]);
+            let candidate = table[h] as usize;
+            table[h] = i as u32 + 1;
+            if candidate > 0 {
+                let c = candidate - 1;
+                if i - c <= MAX_OFFSET && value[c..c + MIN_MATCH] == value[i..i + MIN_MATCH] {
+                    let mut len = MIN_MATCH;
+                    while i + len < value.len() && value[c + len] == value[i + len] {
+                        len += 1;
+                    }
+                    put_literal(&mut out, &value[literal..i]);
+                    put_copy(&mut out, i - c, len);
+                    i += len;
+                    literal = i;
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        put_literal(&mut out, &value[literal..]);
+        out
+    }
+
+    /// The value `bytes` is the compression of, `None` if they are malformed.
+    pub fn decompress(bytes: &[u8]) -> Option<Vec<u8>> {
+        let (len, mut rest) = get_varint(bytes)?;
+        let len = len as usize;
+        let mut out = Vec::new();
+        while let Some((&tag, tail)) = rest.split_first() {
+            rest = tail;
+            if tag & 3 == 0 {
+                let n = match (tag >> 2) as usize {
+                    n if n < 60 => n,
+                    n => {
+                        let width = n - 59;
+                        let n = le(rest, width)?;
+                        rest = &rest[width..];
+                        n
+                    }
+                } + 1;
+                if rest.len() < n {
+                    return None;
+                }
+                out.extend_from_slice(&rest[..n]);
+                rest = &rest[n..];
+            } else {
+                let (n, offset, width) = match tag & 3 {
+                    1 => {
+                        let low = *rest.first()? as usize;
+                        (((tag >> 2) & 7) as usize + 4, (tag as usize >> 5) << 8 | low, 1)
+                    }
+                    2 => ((tag >> 2) as usize + 1, le(rest, 2)?, 2),
+                    _ => ((tag >> 2) as usize + 1, le(rest, 4)?, 4),
+                };
+                rest = &rest[width..];
+                if offset == 0 || offset > out.len() {
+                    return None;
+                }
+                // copies may overlap the bytes they add.
+                let start = out.len() - offset;
+                for j in start..start + n {
+                    let byte = out[j];
+                    out.push(byte);
+                }
+            }
+            if out.len() > len {
+                return None;
+            }
+        }
+        Some(out).filter(|out| out.len() == len)
+    }
+
+    fn hash(bytes: &[u8]) -> usize {
+        let word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        (word.wrapping_mul(0x1e35_a7bd) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn put_varint(out: &mut Vec<u8>, mut n: u32) {
+        while n >= 0x80 {
+            out.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        out.push(n as u8);
+    }
+
+    // the varint bytes start with and the bytes after it.
+    fn get_varint(bytes: &[u8]) -> Option<(u32, &[u8])> {
+        let mut n = 0u64;
+        for (i, byte) in bytes.iter().take(5).enumerate() {
+            n |= ((byte & 0x7f) as u64) << (7 * i);
+            if byte & 0x80 == 0 {
+                let fits = n <= u32::max_value() as u64;
+                return Some((n as u32, &bytes[i + 1..])).filter(|_| fits);
+            }
+        }
+        None
+    }
+
+    // the little-endian number in the first width bytes of bytes.
+    fn le(bytes: &[u8], width: usize) -> Option<usize> {
+        let bytes = bytes.get(..width)?;
+        Some(bytes.iter().rev().fold(0, |n, byte| n << 8 | *byte as usize))
+    }
+
+    fn put_literal(out: &mut Vec<u8>, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let n = bytes.len() - 1;
+        if n < 60 {
+            out.push((n << 2) as u8);
+        } else {
+            let width = if n < 1 << 8 {
+                1
+            } else if n < 1 << 16 {
+                2
+            } else if n < 1 << 24 {
+                3
+            } else {
+                4
+            };
+            out.push(((59 + width) << 2) as u8);
+            out.extend_from_slice(&(n as u32).to_le_bytes()[..width]);
+        }
+        out.extend_from_slice(bytes);
+    }
+
+    fn put_copy(out: &mut Vec<u8>, offset: usize, mut len: usize) {
+        while len > 0 {
+            let n = len.min(64);
+            if n >= 4 && n < 12 && offset < 2048 {
+                out.push(((offset >> 8) << 5 | (n - 4) << 2 | 1) as u8);
+                out.push(offset as u8);
+            } else {
+                out.push(((n - 1) << 2 | 2) as u8);
+                out.extend_from_slice(&(offset as u16).to_le_bytes());
+            }
+            len -= n;
+        }
+    }
+}
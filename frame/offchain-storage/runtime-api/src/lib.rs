@@ -0,0 +1,424 @@
+//! Runtime API of the offchain-storage module.
+//!
+//! Lets clients ask what a call would do before paying fees to submit it, and gateways
+//! look up what the chain committed to. Called at a past block, the API answers from the
+//! state of that block.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Codec, Decode, Encode};
+use sp_runtime::RuntimeDebug;
+#[cfg(feature = "std")]
+use sp_runtime::{
+    generic::BlockId,
+    traits::{Block as BlockT, ProvideRuntimeApi},
+    Deserialize, Serialize,
+};
+use sp_std::vec::Vec;
+
+pub mod compression;
+pub mod uri;
+
+/// Access is that the access of UserData.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum Access {
+    // Avoid means that no one can read or write this data except author.
+    Avoid,
+    // Read means that this data just can be read.
+    Read,
+    // Write means that every one can read and write this data.
+    Write,
+}
+
+impl Default for Access {
+    fn default() -> Self {
+        Access::Read
+    }
+}
+
+/// What a write would do to the entry.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum WriteKind {
+    /// The entry doesn't exist yet and is created.
+    Create,
+    /// The entry exists and its value is replaced.
+    Update,
+    /// The same content is already stored, nothing is written.
+    Duplicate,
+}
+
+/// What a write would do, if it is allowed.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct FeeEstimate<Balance> {
+    /// Whether the entry is created, updated or left as it is.
+    pub kind: WriteKind,
+    /// How many chunks the value is committed to, 0 if it is too short to get a chunk root.
+    pub chunks: u32,
+    /// How much more the author has to reserve, nothing if the value shrinks.
+    pub deposit: Balance,
+}
+
+/// Why a write would fail.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum ValidationError {
+    /// The account may not write the entry.
+    PermissionDenied,
+    /// The entry is mirrored from another chain.
+    ReadOnlyMirror,
+    /// The account used up the writes delegated to it.
+    DelegationExhausted,
+    /// The author can't reserve the deposit for the value.
+    InsufficientBalance,
+    /// The entry expired for want of rent and is frozen.
+    Expired,
+    /// The entry is in a protected namespace and the account has no accepted attestation.
+    Unattested,
+    /// The entry was deleted and its author can still restore it.
+    Trashed,
+    /// The data_id is longer than the runtime allows.
+    KeyTooLong,
+    /// The value is longer than the runtime allows.
+    ValueTooLong,
+    /// The author has as many entries as the runtime allows.
+    TooManyEntries,
+    /// The value would take the author past the bytes the runtime allows it.
+    ByteQuotaExceeded,
+    /// The account made as many writes in the block as the runtime allows.
+    RateLimited,
+    /// The entry would be created in a namespace the account may not create entries in.
+    NamespaceReserved,
+    /// The data_id isn't the key of a well-formed `DataId`, which the runtime requires.
+    BadDataId,
+    /// The entry was renamed and its values are still kept under the data_id.
+    CopyPending,
+}
+
+impl ValidationError {
+    /// The code of the error, that of the module error a write fails with.
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::module(match self {
+            ValidationError::PermissionDenied => 0,
+            ValidationError::ReadOnlyMirror => 4,
+            ValidationError::DelegationExhausted => 11,
+            ValidationError::InsufficientBalance => 17,
+            ValidationError::Expired => 18,
+            ValidationError::Unattested => 27,
+            ValidationError::Trashed => 36,
+            ValidationError::KeyTooLong => 45,
+            ValidationError::ValueTooLong => 46,
+            ValidationError::TooManyEntries => 47,
+            ValidationError::ByteQuotaExceeded => 48,
+            ValidationError::RateLimited => 49,
+            ValidationError::NamespaceReserved => 63,
+            ValidationError::BadDataId => 67,
+            ValidationError::CopyPending => 75,
+        })
+    }
+}
+
+/// A stable code of a failure, the same whether an extrinsic, the runtime API, the RPC or
+/// a gateway reports it, so clients handle errors one way. Codes are never renumbered:
+///
+/// - `1000 + i` is error `i` of the module, its index in `DispatchError::Module`. New
+///   errors of the module are only ever appended.
+/// - `2000..3000` are failures of the storage backends.
+/// - `3000..4000` are failures of gateways and clients reading through them.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, RuntimeDebug)]
+pub struct ErrorCode(pub u16);
+
+impl ErrorCode {
+    /// The transport to the node or gateway failed.
+    pub const TRANSPORT: ErrorCode = ErrorCode(3000);
+    /// A storage proof doesn't match the state root.
+    pub const INVALID_PROOF: ErrorCode = ErrorCode(3001);
+    /// A value or proof can't be decoded.
+    pub const DECODE: ErrorCode = ErrorCode(3002);
+
+    /// The code of error `index` of the module.
+    pub fn module(index: u8) -> Self {
+        ErrorCode(1000 + index as u16)
+    }
+
+    /// The index of the module error, if the code is one.
+    pub fn module_index(self) -> Option<u8> {
+        self.0.checked_sub(1000).filter(|i| *i < 256).map(|i| i as u8)
+    }
+
+    /// The name of the code, for the codes that aren't module errors. Module errors are
+    /// named in the runtime metadata and in `DispatchError::Module`.
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            ErrorCode::TRANSPORT => Some("Transport"),
+            ErrorCode::INVALID_PROOF => Some("InvalidProof"),
+            ErrorCode::DECODE => Some("Decode"),
+            ErrorCode(2000) => Some("Unavailable"),
+            ErrorCode(2001) => Some("Timeout"),
+            ErrorCode(2002) => Some("OutOfSpace"),
+            ErrorCode(2003) => Some("Rejected"),
+            _ => None,
+        }
+    }
+}
+
+/// Why a storage backend failed an operation.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum StorageError {
+    /// The backend can't be reached.
+    Unavailable,
+    /// The backend didn't answer in time.
+    Timeout,
+    /// The backend has no room for the value.
+    OutOfSpace,
+    /// The backend refused the operation, e.g. for want of rights.
+    Rejected,
+}
+
+impl StorageError {
+    /// The code of the failure, in the range of backend failures.
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode(match self {
+            StorageError::Unavailable => 2000,
+            StorageError::Timeout => 2001,
+            StorageError::OutOfSpace => 2002,
+            StorageError::Rejected => 2003,
+        })
+    }
+}
+
+/// How far an operation the chain carries out over several blocks got.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum RequestStatus<Hash> {
+    /// Waiting to be carried out, e.g. for the offchain worker to take it up.
+    Queued,
+    /// A worker is carrying it out.
+    Executing,
+    /// Done, with the content hash of the value stored.
+    Committed(Hash),
+    /// Given up on.
+    Failed(ErrorCode),
+}
+
+impl<Hash> RequestStatus<Hash> {
+    /// Whether the operation is over, for better or worse.
+    pub fn is_final(&self) -> bool {
+        match self {
+            RequestStatus::Committed(_) | RequestStatus::Failed(_) => true,
+            RequestStatus::Queued | RequestStatus::Executing => false,
+        }
+    }
+}
+
+/// A page of the entries an account authored, see `owned_page`.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Page {
+    /// The data_ids on the page, in the order they are listed.
+    pub entries: Vec<Vec<u8>>,
+    /// The cursor the next page starts at, `None` on the last page.
+    pub next: Option<u64>,
+}
+
+/// What the chain keeps in the backends, in all.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Statistics {
+    /// The entries, live or in the trash, except mirrors.
+    pub entries: u64,
+    /// The bytes the values of those entries take.
+    pub bytes: u64,
+}
+
+/// Where a value a reader paid to read is kept, see `locate`.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct ValueLocation<Hash> {
+    /// The backend keeping it.
+    pub backend: u8,
+    /// The key it is kept under there.
+    pub key: Vec<u8>,
+    /// How it is compressed there.
+    pub compression: compression::Compression,
+    /// The hash of the value, uncompressed.
+    pub content_hash: Hash,
+}
+
+/// The longest field of an [`EncryptionInfo`].
+pub const MAX_ENCRYPTION_FIELD_LENGTH: usize = 64;
+
+/// How a value is encrypted, for readers to decrypt it. The chain doesn't check it.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct EncryptionInfo {
+    /// The cipher, e.g. `b"xchacha20poly1305"`.
+    pub scheme: Vec<u8>,
+    /// Names the key the value is encrypted with, e.g. a keyring of the module.
+    pub key_id: Vec<u8>,
+    pub nonce: Vec<u8>,
+}
+
+impl EncryptionInfo {
+    /// Whether no field is longer than [`MAX_ENCRYPTION_FIELD_LENGTH`].
+    pub fn is_bounded(&self) -> bool {
+        [&self.scheme, &self.key_id, &self.nonce]
+            .iter()
+            .all(|field| field.len() <= MAX_ENCRYPTION_FIELD_LENGTH)
+    }
+}
+
+/// A typed data_id. With typed data_ids on, an entry is kept under the SCALE encoding of
+/// one, see [`DataId::key`], so the backend and readers learn what the id is: IPFS backends
+/// get the CID of the value and other backends an opaque key.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum DataId {
+    /// Opaque bytes.
+    Raw(Vec<u8>),
+    /// A CID in binary form: a bare multihash for CIDv0, or the version, content codec and
+    /// multihash, each but the digest an unsigned varint, for CIDv1.
+    Cid(Vec<u8>),
+    /// A UUID.
+    Uuid([u8; 16]),
+}
+
+impl DataId {
+    /// The data_id the entry of the id is kept under.
+    pub fn key(&self) -> Vec<u8> {
+        self.encode()
+    }
+
+    /// The id `key` is the data_id of, if it is the key of a well-formed one.
+    pub fn from_key(key: &[u8]) -> Option<Self> {
+        let input = &mut &key[..];
+        let id = Self::decode(input).ok().filter(|_| input.is_empty())?;
+        Some(id).filter(DataId::is_well_formed)
+    }
+
+    /// Whether the id is well formed, i.e. a CID is a multihash whose digest is as long as
+    /// it says, after the version and codec for CIDv1. Raw ids and UUIDs always are.
+    pub fn is_well_formed(&self) -> bool {
+        match self {
+            DataId::Cid(cid) => {
+                let input = &mut &cid[..];
+                match cid.first() {
+                    // no hash has the code 1, a leading 1 is the version of a CIDv1.
+                    Some(1) => {
+                        *input = &input[1..];
+                        read_varint(input).is_some() && is_multihash(input)
+                    }
+                    Some(_) => is_multihash(input),
+                    None => false,
+                }
+            }
+            DataId::Raw(_) | DataId::Uuid(_) => true,
+        }
+    }
+}
+
+// read an unsigned varint of at most 9 bytes, as multiformats limit them, off input.
+fn read_varint(input: &mut &[u8]) -> Option<u64> {
+    let mut n = 0u64;
+    for (i, byte) in input.iter().enumerate().take(9) {
+        n |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *input = &input[i + 1..];
+            return Some(n);
+        }
+    }
+    None
+}
+
+// whether input is a multihash: the code of the hash, the length of the digest, the digest.
+fn is_multihash(input: &mut &[u8]) -> bool {
+    read_varint(input).is_some()
+        && read_varint(input).map_or(false, |len| len > 0 && len == input.len() as u64)
+}
+
+sp_api::decl_runtime_apis! {
+    pub trait OffchainStorageApi<AccountId, Hash, Balance> where
+        AccountId: Codec,
+        Hash: Codec,
+        Balance: Codec,
+    {
+        /// Check whether `account` may write a value of `len` bytes to `data_id`,
+        /// without writing it.
+        fn validate_write(
+            account: AccountId,
+            data_id: Vec<u8>,
+            len: u32,
+        ) -> Result<FeeEstimate<Balance>, ValidationError>;
+
+        /// Like `validate_write`, for a content addressed write of a value of `len` bytes
+        /// hashing to `content_hash`.
+        fn validate_content_addressed(
+            account: AccountId,
+            content_hash: Hash,
+            len: u32,
+        ) -> Result<FeeEstimate<Balance>, ValidationError>;
+
+        /// The content hash of revision `version` of `data_id`, if it is still kept. Gateways
+        /// serving diffs between revisions name these hashes so reviewers can check them.
+        fn version_hash(data_id: Vec<u8>, version: u32) -> Option<Hash>;
+
+        /// The SCALE encoded `MetadataSnapshot` of the entries and the grants on them.
+        fn metadata_snapshot() -> Vec<u8>;
+
+        /// The SCALE encoded `AccessReport`s of those of `data_ids` that exist, with only the
+        /// grants of `account` if one is given. Called at a block, it tells who could access
+        /// the entries then.
+        fn access_report(data_ids: Vec<Vec<u8>>, account: Option<AccountId>) -> Vec<u8>;
+
+        /// Whether `account` may read `data_id`, through its access level or any grant.
+        fn can_read(account: AccountId, data_id: Vec<u8>) -> bool;
+
+        /// The status of the deferred write `id`, given by `UploadQueued`, kept for a while
+        /// after it is over.
+        fn upload_status(id: u64) -> Option<RequestStatus<Hash>>;
+
+        /// The author, access, length of the value and content hash of `data_id`, if it
+        /// exists.
+        fn get_metadata(data_id: Vec<u8>) -> Option<(AccountId, Access, u32, Hash)>;
+
+        /// The data_ids of the live entries `account` authored.
+        fn owned_by(account: AccountId) -> Vec<Vec<u8>>;
+
+        /// Up to `limit` of the data_ids `owned_by` returns, from the cursor `cursor` on,
+        /// which is 0 for the first page. Entries created or deleted between two pages
+        /// don't move the others, a page picks up where the one before stopped.
+        fn owned_page(account: AccountId, cursor: u64, limit: u32) -> Page;
+
+        /// How many entries the chain counts and how many bytes their values take.
+        fn statistics() -> Statistics;
+
+        /// How the value of `data_id` is encrypted, if it exists and its writer said so.
+        fn encryption(data_id: Vec<u8>) -> Option<EncryptionInfo>;
+
+        /// The canonical URI of the value of `data_id`, if it exists. See the uri module.
+        fn uri(data_id: Vec<u8>) -> Option<Vec<u8>>;
+
+        /// Where the value `account` last paid to read of `data_id` with a read call is
+        /// kept, if `account` may still read `data_id` and the value is kept whole under
+        /// one key. The RPC delivering values fetches it from there.
+        fn locate(account: AccountId, data_id: Vec<u8>) -> Option<ValueLocation<Hash>>;
+
+        /// Whether `account` has `level` of access to `data_id`: may read it, may write it,
+        /// or, for `Access::Avoid`, is its author.
+        fn can_access(account: AccountId, data_id: Vec<u8>, level: Access) -> bool;
+    }
+}
+
+/// Whether `account` had `level` of access to `data_id` at the block `block_hash`,
+/// evaluated against the state of that block. Needs a client keeping that state, an
+/// archive node for old blocks.
+#[cfg(feature = "std")]
+pub fn can_access_at<Block, C, AccountId, Balance>(
+    client: &C,
+    account: AccountId,
+    data_id: Vec<u8>,
+    level: Access,
+    block_hash: Block::Hash,
+) -> Result<bool, sp_api::ApiErrorFor<C, Block>>
+where
+    Block: BlockT,
+    C: ProvideRuntimeApi,
+    C::Api: OffchainStorageApi<Block, AccountId, Block::Hash, Balance>,
+    AccountId: Codec,
+    Balance: Codec,
+{
+    client.runtime_api().can_access(&BlockId::Hash(block_hash), account, data_id, level)
+}
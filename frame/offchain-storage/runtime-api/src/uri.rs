@@ -0,0 +1,124 @@
+//! Canonical URIs of stored values.
+//!
+//! `offchain://<provider>/<data_id>?hash=<hash>` references the value of `data_id` kept in
+//! the backend named `provider`, and, with the query, only while its content hash is
+//! `hash`. The provider and the data_id are percent-encoded, every byte but the unreserved
+//! characters of RFC 3986 as `%XX` with uppercase digits; the hash is in lowercase hex.
+//! Only this canonical form parses, so every reference has exactly one URI and URIs can be
+//! compared as bytes.
+
+use codec::{Decode, Encode};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// The scheme and separator every URI starts with.
+pub const PREFIX: &[u8] = b"offchain://";
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+const UPPER_HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+/// What a URI references.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Reference {
+    /// Names the backend the value is kept in.
+    pub provider: Vec<u8>,
+    pub data_id: Vec<u8>,
+    /// The content hash the value must have, any value of the entry does if `None`.
+    pub hash: Option<Vec<u8>>,
+}
+
+impl Reference {
+    /// The canonical URI of the reference.
+    pub fn to_uri(&self) -> Vec<u8> {
+        let mut uri = PREFIX.to_vec();
+        escape(&self.provider, &mut uri);
+        uri.push(b'/');
+        escape(&self.data_id, &mut uri);
+        if let Some(hash) = &self.hash {
+            uri.extend_from_slice(b"?hash=");
+            for byte in hash {
+                uri.push(HEX[(byte >> 4) as usize]);
+                uri.push(HEX[(byte & 0xf) as usize]);
+            }
+        }
+        uri
+    }
+
+    /// The reference `uri` is the canonical URI of, if it is one with a provider and, if it
+    /// has the query, a hash.
+    pub fn parse(uri: &[u8]) -> Option<Self> {
+        if !uri.starts_with(PREFIX) {
+            return None;
+        }
+        let rest = &uri[PREFIX.len()..];
+        let (path, hash) = match rest.iter().position(|b| *b == b'?') {
+            Some(i) => {
+                let query = &rest[i + 1..];
+                if !query.starts_with(b"hash=") {
+                    return None;
+                }
+                let hash = unhex(&query[b"hash=".len()..]).filter(|hash| !hash.is_empty())?;
+                (&rest[..i], Some(hash))
+            }
+            None => (rest, None),
+        };
+        let slash = path.iter().position(|b| *b == b'/')?;
+        let reference = Reference {
+            provider: unescape(&path[..slash]).filter(|provider| !provider.is_empty())?,
+            data_id: unescape(&path[slash + 1..])?,
+            hash,
+        };
+        // anything decoded from another form, e.g. with lowercase escapes, isn't canonical.
+        Some(reference).filter(|reference| reference.to_uri() == uri)
+    }
+}
+
+// append bytes to out, percent-encoded.
+fn escape(bytes: &[u8], out: &mut Vec<u8>) {
+    for byte in bytes {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(*byte),
+            _ => {
+                out.push(b'%');
+                out.push(UPPER_HEX[(byte >> 4) as usize]);
+                out.push(UPPER_HEX[(byte & 0xf) as usize]);
+            }
+        }
+    }
+}
+
+// the bytes escaped percent-encodes, if it is well formed.
+fn unescape(escaped: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped;
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            if tail.len() < 2 {
+                return None;
+            }
+            bytes.push(digit(tail[0])? << 4 | digit(tail[1])?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Some(bytes)
+}
+
+// the bytes of the hex string hex, if it is one.
+fn unhex(hex: &[u8]) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.chunks(2).map(|pair| Some(digit(pair[0])? << 4 | digit(pair[1])?)).collect()
+}
+
+fn digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
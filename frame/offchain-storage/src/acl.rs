@@ -0,0 +1,182 @@
+//! Reports of who may access entries.
+//!
+//! For audits, `access_report` lists everything that decides who may read or write each
+//! of a set of entries: the author and access of the entry, what operators get, the read
+//! quorum, when the entry ends, and every grant with its limits. Called through the
+//! `access_report` runtime API at a block, it answers who could read an entry then.
+//! Entries are kept under hashed keys, so the auditor names the entries of a namespace;
+//! the report can be narrowed to the grants of one account.
+//!
+//! Grantees can't be listed from the grant maps, whose keys are hashed too, so every
+//! account that ever got a grant on an entry is kept in `Grantees` until the entry is
+//! removed or its grants are revoked.
+//!
+//! `can_read` answers for a single account and entry, and `can_access` for any level of
+//! access. Through the `can_read` and `can_access` runtime APIs, dapps check a call is
+//! allowed before submitting it and RPC servers reading backends directly enforce access.
+//! With `can_access_at` in the runtime API crate, they settle disputes about who could
+//! access an entry at a past block from the state of that block.
+//!
+//! Every grant of access to a single account, bought reads, session grants and delegated
+//! writes, is passed to `T::OnAccessGranted` as it is made. A proxy re-encryption service
+//! can e.g. queue the grantee for its offchain worker to produce a re-encryption key for.
+
+use super::{
+    audit::AuditOp, Access, Data, Grantees, Module, ReadGrants, SessionGrants, Trait,
+    WriteDelegations,
+};
+use codec::{Decode, Encode};
+use frame_support::{StorageDoubleMap, StorageMap};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// Told about grants of access as they are made.
+pub trait OnAccessGranted<AccountId> {
+    /// `grantee` got `access` to `data_id`. Called while the grant is made, the work of
+    /// answering it should be left to an offchain worker.
+    fn on_access_granted(data_id: &[u8], grantee: &AccountId, access: Access);
+}
+
+impl<AccountId> OnAccessGranted<AccountId> for () {
+    fn on_access_granted(_: &[u8], _: &AccountId, _: Access) {}
+}
+
+/// A grant on an entry.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum Grant<AccountId> {
+    /// Read access the account bought.
+    Read(AccountId),
+    /// Access for the session with the given index only.
+    Session(AccountId, u32, Access),
+    /// Writes delegated to the account, with how many are left.
+    Delegate(AccountId, u32),
+    /// The key of the entry is shared with the account through its keyring.
+    KeyShare(AccountId),
+    /// The account reviews staged values of the entry.
+    Reviewer(AccountId),
+}
+
+impl<AccountId> Grant<AccountId> {
+    /// The account the grant is for.
+    pub fn account(&self) -> &AccountId {
+        match self {
+            Grant::Read(who)
+            | Grant::Session(who, ..)
+            | Grant::Delegate(who, _)
+            | Grant::KeyShare(who)
+            | Grant::Reviewer(who) => who,
+        }
+    }
+}
+
+/// Everything deciding who may access an entry.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct AccessReport<AccountId, BlockNumber> {
+    pub data_id: Vec<u8>,
+    pub author: AccountId,
+    /// What everyone else may do.
+    pub access: Access,
+    /// What whoever is in `T::Operators` may do.
+    pub operator_access: Option<Access>,
+    /// How many readers must sign a read.
+    pub read_quorum: Option<u32>,
+    /// The block the time to live of the entry ends.
+    pub expires_at: Option<BlockNumber>,
+    pub grants: Vec<Grant<AccountId>>,
+}
+
+impl<T: Trait> Module<T> {
+    /// The access reports of those of `data_ids` that exist, listing only the grants of
+    /// `account` if one is given. Backs the `access_report` runtime API.
+    pub fn access_report(
+        data_ids: Vec<Vec<u8>>,
+        account: Option<T::AccountId>,
+    ) -> Vec<AccessReport<T::AccountId, T::BlockNumber>> {
+        data_ids
+            .into_iter()
+            .filter(|data_id| <Data<T>>::exists(data_id))
+            .map(|data_id| {
+                let data = Self::get_data(&data_id);
+                let grants = Self::grants_on(&data_id)
+                    .into_iter()
+                    .filter(|grant| account.as_ref().map_or(true, |a| grant.account() == a))
+                    .collect();
+                AccessReport {
+                    author: data.author,
+                    access: data.access,
+                    operator_access: Self::operator_access(&data_id),
+                    read_quorum: Self::read_quorum(&data_id),
+                    expires_at: Self::ttl_of(&data_id),
+                    grants,
+                    data_id,
+                }
+            })
+            .collect()
+    }
+
+    /// Whether `account` may read `data_id` through its access level or any grant. Backs
+    /// the `can_read` runtime API.
+    pub fn can_read(account: &T::AccountId, data_id: &[u8]) -> bool {
+        <Data<T>>::exists(data_id)
+            && !Self::ttl_passed(data_id)
+            && Self::may_read(account, data_id, Self::get_data(data_id))
+    }
+
+    /// Whether `account` has `level` of access to `data_id`: may read it, may write it,
+    /// through its access level, a grant or a delegated write left, or, for
+    /// `Access::Avoid`, is its author. Backs the `can_access` runtime API.
+    pub fn can_access(account: &T::AccountId, data_id: &[u8], level: Access) -> bool {
+        if !<Data<T>>::exists(data_id) || Self::ttl_passed(data_id) {
+            return false;
+        }
+        let data = Self::get_data(data_id);
+        match level {
+            Access::Avoid => data.author == *account,
+            Access::Read => Self::may_read(account, data_id, data),
+            Access::Write => {
+                Self::may_write(account, data_id, data)
+                    || Self::write_delegations(data_id, account) > 0
+            }
+        }
+    }
+
+    // every grant in force on data_id.
+    fn grants_on(data_id: &[u8]) -> Vec<Grant<T::AccountId>> {
+        let mut grants = Vec::new();
+        for who in Self::grantees_of(data_id) {
+            if <ReadGrants<T>>::exists(data_id, &who) {
+                grants.push(Grant::Read(who.clone()));
+            }
+            if let Some((session, access)) = <SessionGrants<T>>::get(data_id, &who) {
+                grants.push(Grant::Session(who.clone(), session, access));
+            }
+            if <WriteDelegations<T>>::exists(data_id, &who) {
+                grants.push(Grant::Delegate(who.clone(), Self::write_delegations(data_id, &who)));
+            }
+        }
+        let keyring = Self::keyring_of(data_id).and_then(|id| Self::keyring(id));
+        for (member, _) in keyring.map(|k| k.members).unwrap_or_default() {
+            grants.push(Grant::KeyShare(member));
+        }
+        for reviewer in Self::reviewers_of(data_id) {
+            grants.push(Grant::Reviewer(reviewer));
+        }
+        grants
+    }
+
+    // remember that grantee got access to data_id and tell T::OnAccessGranted.
+    pub(crate) fn note_access_granted(data_id: &[u8], grantee: &T::AccountId, access: Access) {
+        Self::note_grantee(data_id, grantee);
+        Self::audit(data_id, grantee, AuditOp::Granted(access.clone()));
+        T::OnAccessGranted::on_access_granted(data_id, grantee, access);
+    }
+
+    // remember that grantee got a grant on data_id.
+    pub(crate) fn note_grantee(data_id: &[u8], grantee: &T::AccountId) {
+        <Grantees<T>>::mutate(data_id, |grantees| {
+            if !grantees.contains(grantee) {
+                grantees.push(grantee.clone());
+            }
+        });
+    }
+}
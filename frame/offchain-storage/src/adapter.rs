@@ -0,0 +1,155 @@
+//! Implementing `ExternalStorage` from a plain client.
+//!
+//! A backend only has to say how to get, set and delete a value with its client, as a
+//! [`StorageClient`], and [`impl_external_storage!`](crate::impl_external_storage) or
+//! [`Adapter`] make an `ExternalStorage` of it. The adapter checks keys and values against
+//! the limits of the client before calling it, so the service never sees what it would
+//! refuse, turns the errors of the client into a `StorageError` and reports every call to
+//! a [`BackendMetrics`]:
+//!
+//! ```ignore
+//! pub struct Ipfs;
+//!
+//! impl StorageClient for Ipfs {
+//!     type Error = ipfs::Error;
+//!     const MAX_VALUE_LENGTH: usize = 1 << 20;
+//!
+//!     fn get(key: &[u8]) -> Result<Option<Vec<u8>>, ipfs::Error> { ipfs::cat(key) }
+//!     fn set(key: &[u8], value: &[u8]) -> Result<(), ipfs::Error> { ipfs::add(key, value) }
+//!     fn delete(key: &[u8]) -> Result<(), ipfs::Error> { ipfs::unpin(key) }
+//! }
+//!
+//! impl_external_storage!(Ipfs);
+//! ```
+//!
+//! A value the client doesn't have reads as empty, as from any other backend.
+
+use super::{ExternalStorage, StorageError};
+use sp_runtime::RuntimeDebug;
+use sp_std::{marker::PhantomData, vec::Vec};
+
+/// The bytes of a key or value, for the macro to name without `sp_std` in scope.
+pub type Bytes = Vec<u8>;
+
+/// A client of a storage service, with the calls it answers and the limits it has.
+pub trait StorageClient {
+    type Error;
+    /// The longest key the service takes.
+    const MAX_KEY_LENGTH: usize = 1024;
+    /// The longest value the service takes.
+    const MAX_VALUE_LENGTH: usize = usize::max_value();
+
+    /// The value under `key`, `None` if there is none.
+    fn get(key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error>;
+    fn set(key: &[u8], value: &[u8]) -> Result<(), Self::Error>;
+    fn delete(key: &[u8]) -> Result<(), Self::Error>;
+
+    /// Why a call failed with `error`. By default the service is taken as unavailable.
+    fn map_error(_error: Self::Error) -> StorageError {
+        StorageError::Unavailable
+    }
+}
+
+/// An operation of a backend, as reported to [`BackendMetrics`].
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Operation {
+    Get,
+    Set,
+    Delete,
+}
+
+/// Counts the calls made to a backend, e.g. in the metrics of the node.
+pub trait BackendMetrics {
+    /// `op` moved `bytes` bytes of value and ended with `outcome`.
+    fn record(op: Operation, bytes: usize, outcome: Result<(), StorageError>);
+}
+
+impl BackendMetrics for () {
+    fn record(_: Operation, _: usize, _: Result<(), StorageError>) {}
+}
+
+/// The `ExternalStorage` of the client `C`, reporting its calls to `M`.
+pub struct Adapter<C, M = ()>(PhantomData<(C, M)>);
+
+impl<C: StorageClient, M: BackendMetrics> ExternalStorage for Adapter<C, M> {
+    fn get(key: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        get::<C, M>(key)
+    }
+
+    fn set(key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        set::<C, M>(key, value)
+    }
+
+    fn delete(key: Vec<u8>) -> Result<(), StorageError> {
+        delete::<C, M>(key)
+    }
+}
+
+// a key the client takes: not empty and within its limit.
+fn check_key<C: StorageClient>(key: &[u8]) -> Result<(), StorageError> {
+    if key.is_empty() || key.len() > C::MAX_KEY_LENGTH {
+        return Err(StorageError::Rejected);
+    }
+    Ok(())
+}
+
+/// `ExternalStorage::get` through the client `C`.
+pub fn get<C: StorageClient, M: BackendMetrics>(key: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+    let value = check_key::<C>(&key)
+        .and_then(|_| C::get(&key).map_err(C::map_error))
+        .map(Option::unwrap_or_default);
+    let bytes = value.as_ref().map_or(0, |value| value.len());
+    M::record(Operation::Get, bytes, value.as_ref().map(|_| ()).map_err(|e| *e));
+    value
+}
+
+/// `ExternalStorage::set` through the client `C`.
+pub fn set<C: StorageClient, M: BackendMetrics>(
+    key: Vec<u8>,
+    value: Vec<u8>,
+) -> Result<(), StorageError> {
+    let outcome = check_key::<C>(&key).and_then(|_| {
+        if value.len() > C::MAX_VALUE_LENGTH {
+            return Err(StorageError::OutOfSpace);
+        }
+        C::set(&key, &value).map_err(C::map_error)
+    });
+    M::record(Operation::Set, value.len(), outcome);
+    outcome
+}
+
+/// `ExternalStorage::delete` through the client `C`.
+pub fn delete<C: StorageClient, M: BackendMetrics>(key: Vec<u8>) -> Result<(), StorageError> {
+    let outcome = check_key::<C>(&key).and_then(|_| C::delete(&key).map_err(C::map_error));
+    M::record(Operation::Delete, 0, outcome);
+    outcome
+}
+
+/// Implement `ExternalStorage` for a [`StorageClient`](crate::adapter::StorageClient), reporting
+/// its calls to the `BackendMetrics` given second, if any. See the adapter module.
+#[macro_export]
+macro_rules! impl_external_storage {
+    ($client:ty) => {
+        $crate::impl_external_storage!($client, ());
+    };
+    ($client:ty, $metrics:ty) => {
+        impl $crate::ExternalStorage for $client {
+            fn get(
+                key: $crate::adapter::Bytes,
+            ) -> Result<$crate::adapter::Bytes, $crate::StorageError> {
+                $crate::adapter::get::<$client, $metrics>(key)
+            }
+
+            fn set(
+                key: $crate::adapter::Bytes,
+                value: $crate::adapter::Bytes,
+            ) -> Result<(), $crate::StorageError> {
+                $crate::adapter::set::<$client, $metrics>(key, value)
+            }
+
+            fn delete(key: $crate::adapter::Bytes) -> Result<(), $crate::StorageError> {
+                $crate::adapter::delete::<$client, $metrics>(key)
+            }
+        }
+    };
+}
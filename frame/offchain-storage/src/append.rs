@@ -0,0 +1,73 @@
+//! Appends.
+//!
+//! `append_data` adds bytes to the end of a value, e.g. to records of a log written as they
+//! come. The entry is written as by `write_data` with the whole new value, which is checked
+//! and paid for as any write, and gets a new content hash and revision: the value before is
+//! read from the backend, and must be intact, to compute them. The entry stays encrypted as
+//! it was, the bytes appended are expected to be encrypted the same way, and compressed as
+//! it was.
+//!
+//! Only the backend write differs. A value stored whole under its data_id gets the bytes
+//! through `ExternalStorage::append`, which backends able to append in place implement; the
+//! default reads the value and sets it again. Values stored as shards, compressed or
+//! shared, not uploaded yet or written with `T::DeferredUploads` or `T::Deduplication` on
+//! are stored again in full. Replicas always are.
+
+use super::{
+    replication::ReplicaStorage, Compression, Data, Error, Module, PendingUploads,
+    Shards, SharedValues, Trait, Upload, UserData,
+};
+use frame_support::{dispatch::DispatchResult, traits::Get, StorageMap};
+use sp_std::vec::Vec;
+
+impl<T: Trait> Module<T> {
+    // append bytes to the value of data_id for user, writing them if there is none.
+    pub(crate) fn append_entry(
+        user: T::AccountId,
+        data_id: Vec<u8>,
+        bytes: Vec<u8>,
+    ) -> DispatchResult {
+        // the value stays compressed as it is.
+        let (mut value, encryption, compression) =
+            if <Data<T>>::exists(&data_id) && !Self::ttl_passed(&data_id) {
+                let data = Self::get_data(&data_id);
+                let value = Self::get_external_storage(&data_id, &data)?;
+                (value, data.encryption, Some(data.compression))
+            } else {
+                (Vec::new(), None, None)
+            };
+        let added = bytes.len() as u32;
+        value.extend(bytes);
+        let upload = match Self::upload_mode() {
+            Upload::Now => Upload::Append(added),
+            deferred => deferred,
+        };
+        Self::write_entry(user, data_id, value, Vec::new(), upload, encryption, None, compression)
+    }
+
+    // whether the value of data_id is stored whole and uncompressed in its backend and new
+    // values are stored whole too.
+    pub(crate) fn appendable(data_id: &[u8]) -> bool {
+        T::ErasureCoding::get().is_none()
+            && !<Shards<T>>::exists(data_id)
+            && !<PendingUploads<T>>::exists(data_id)
+            && Self::get_data(data_id).compression == Compression::None
+            && !T::Deduplication::get()
+            && !SharedValues::exists(data_id)
+    }
+
+    // store value, which ends with the added bytes appended to the value stored before.
+    pub(crate) fn append_external_storage(
+        data_id: &[u8],
+        value: &[u8],
+        added: u32,
+        data: &UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) -> Result<(), Error<T>> {
+        let tail = value[value.len() - added as usize..].to_vec();
+        Self::backend(data_id, Self::routed_append(data.backend, data_id.to_vec(), tail))?;
+        for p in &data.providers {
+            T::Replicas::set(p, data_id.to_vec(), value.to_vec());
+        }
+        Ok(())
+    }
+}
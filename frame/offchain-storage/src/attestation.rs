@@ -0,0 +1,34 @@
+//! Attested writing keys.
+//!
+//! `T::AdminOrigin` can protect namespaces, data_id prefixes whose entries may only be
+//! written by accounts with a registered attestation, e.g. an HSM or secure enclave quote
+//! showing where the account's key lives. `T::Attestation` checks the attestation when it
+//! is registered and again on every write, so revoked attestations stop working at once.
+
+use super::{Attestations, Module, ProtectedNamespaces, Trait};
+use frame_support::{StorageMap, StorageValue};
+
+/// Checks attestations of account keys.
+pub trait AttestationVerifier<AccountId> {
+    /// Whether `attestation` shows the key of `who` is kept as required.
+    fn verify(who: &AccountId, attestation: &[u8]) -> bool;
+}
+
+/// No attestation is accepted, protected namespaces can't be written.
+impl<AccountId> AttestationVerifier<AccountId> for () {
+    fn verify(_: &AccountId, _: &[u8]) -> bool {
+        false
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Whether `data_id` is in a protected namespace.
+    pub fn is_protected(data_id: &[u8]) -> bool {
+        ProtectedNamespaces::get().iter().any(|prefix| data_id.starts_with(prefix))
+    }
+
+    // whether who has a registered attestation T::Attestation still accepts.
+    pub(crate) fn is_attested(who: &T::AccountId) -> bool {
+        <Attestations<T>>::get(who).map_or(false, |a| T::Attestation::verify(who, &a))
+    }
+}
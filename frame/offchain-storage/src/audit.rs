@@ -0,0 +1,75 @@
+//! Audit trails of entries.
+//!
+//! The author of an entry may turn its audit trail on with `set_audit`. Every read, write,
+//! grant and revocation of access, pin, deletion, restoration, rename and transfer of the
+//! entry is then recorded in `AuditTrail` with the account behind it and the block, so a
+//! deployment that must prove who accessed what can show it from the chain alone, without
+//! indexing the events. Only the last `T::MaxAuditRecords` records are kept. Turning the
+//! trail off stops recording but keeps the records made so far. They move with the entry
+//! when it is renamed and stay with it in the trash, so deleting and restoring an entry
+//! doesn't clear them; they only go when the entry is removed for good.
+
+use super::{Access, AuditTrail, Audited, Module, Trait};
+use codec::{Decode, Encode};
+use frame_support::{traits::Get, StorageMap};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// What was done to an audited entry.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum AuditOp {
+    /// The value was read.
+    Read,
+    /// A value was written.
+    Write,
+    /// The account was granted the access.
+    Granted(Access),
+    /// The account pinned the value.
+    Pinned,
+    /// The account dropped its pin.
+    Unpinned,
+    /// The account deleted the entry.
+    Deleted,
+    /// The account took the entry out of the trash.
+    Restored,
+    /// The grants of the account were revoked.
+    Revoked,
+    /// The account renamed the entry from the data_id `.0`.
+    Renamed(Vec<u8>),
+    /// The entry was transferred to the account.
+    Transferred,
+}
+
+/// A record of an audit trail.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct AuditRecord<AccountId, BlockNumber> {
+    /// The account that acted on the entry, or was granted access, lost it or got the
+    /// entry.
+    pub who: AccountId,
+    pub op: AuditOp,
+    /// The block it happened in.
+    pub at: BlockNumber,
+}
+
+impl<T: Trait> Module<T> {
+    // record op by who in the audit trail of data_id if it is on, dropping the oldest
+    // record once T::MaxAuditRecords are kept.
+    pub(crate) fn audit(data_id: &[u8], who: &T::AccountId, op: AuditOp) {
+        if !Audited::get(data_id) {
+            return;
+        }
+        let at = <frame_system::Module<T>>::block_number();
+        let max = T::MaxAuditRecords::get() as usize;
+        <AuditTrail<T>>::mutate(data_id, |trail| {
+            trail.push(AuditRecord { who: who.clone(), op, at });
+            let excess = trail.len().saturating_sub(max);
+            trail.drain(..excess);
+        });
+    }
+
+    // drop the audit trail of data_id, which is removed for good.
+    pub(crate) fn drop_audit(data_id: &[u8]) {
+        Audited::remove(data_id);
+        <AuditTrail<T>>::remove(data_id);
+    }
+}
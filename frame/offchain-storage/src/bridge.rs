@@ -0,0 +1,219 @@
+//! Interaction with other chains running this module.
+//!
+//! ## Mirroring remote data
+//!
+//! A bridged chain commits to its `Data` map in its state root. Given one of its
+//! headers that `T::RemoteHeaders` trusts (e.g. because a light client of that
+//! chain finalized it) and a storage proof of the entry, we can learn the remote
+//! author, access and content hash without trusting whoever relays them.
+//!
+//! Both chains are expected to use this module under the same name and with the
+//! same `AccountId` and `Hash` types, so the remote entry lives at the same storage
+//! key and decodes to the same `UserData` as a local one would.
+//!
+//! ## Remote requests
+//!
+//! A bridge or XCM transport that has authenticated a message of another chain hands
+//! it to `HandleRemoteRequest`. The request is applied on behalf of the local
+//! sovereign account of the remote sender, with the same checks a local write or
+//! delete of that account goes through. This lets several chains keep their data
+//! on one hub chain.
+//!
+//! The author of an entry can also let an account of yet another chain read it, or hand
+//! the entry over to one, by its sovereign account here, so data registered through one
+//! chain can be consumed from another. The new author reserves the deposit of the entry
+//! and takes it on in its quotas. With the `xcm` feature, the xcm module delivers requests
+//! sent as XCM messages.
+
+use super::{
+    audit::AuditOp, Access, Data, Error, GrantedEntries, Mirrors, Module, RawEvent, ReadGrants,
+    Trait, UserData,
+};
+use codec::{Decode, Encode};
+use frame_support::{
+    dispatch::DispatchResult,
+    ensure,
+    traits::{Get, ReservableCurrency},
+    StorageDoubleMap, StorageMap,
+};
+use sp_runtime::{
+    traits::{Hash as HashT, Header as HeaderT, TrailingZeroInput},
+    RuntimeDebug,
+};
+use sp_std::vec::Vec;
+use sp_trie::{read_trie_value, trie_types::Layout, MemoryDB, EMPTY_PREFIX};
+
+type HasherOf<T> = <<T as frame_system::Trait>::Hashing as HashT>::Hasher;
+
+/// Tells which headers of bridged chains can be trusted.
+pub trait RemoteHeaders<Header> {
+    /// Whether `header` is a finalized header of the chain `chain_id`.
+    fn is_trusted(chain_id: &[u8], header: &Header) -> bool;
+}
+
+impl<Header> RemoteHeaders<Header> for () {
+    fn is_trusted(_: &[u8], _: &Header) -> bool {
+        false
+    }
+}
+
+/// Read the `Data` entry of `data_id` out of a storage proof against `state_root`.
+///
+/// Returns `None` if the proof is incomplete or doesn't contain the entry.
+pub fn check_proof<T: Trait>(
+    state_root: &T::Hash,
+    data_id: &[u8],
+    proof: &[Vec<u8>],
+) -> Option<UserData<T::AccountId, T::Hash, T::BlockNumber>> {
+    use sp_trie::HashDBT;
+
+    let mut db = MemoryDB::<HasherOf<T>>::default();
+    for node in proof {
+        HashDBT::insert(&mut db, EMPTY_PREFIX, &node[..]);
+    }
+    let key = <Data<T>>::hashed_key_for(data_id);
+    let raw = read_trie_value::<Layout<HasherOf<T>>, _>(&db, state_root, &key).ok()??;
+    Decode::decode(&mut &raw[..]).ok()
+}
+
+/// Check that `header` is trusted for `chain_id` and read `data_id` from its state.
+pub fn verify<T: Trait>(
+    chain_id: &[u8],
+    header: &T::Header,
+    data_id: &[u8],
+    proof: &[Vec<u8>],
+) -> Option<UserData<T::AccountId, T::Hash, T::BlockNumber>> {
+    if !T::RemoteHeaders::is_trusted(chain_id, header) {
+        return None;
+    }
+    check_proof::<T>(header.state_root(), data_id, proof)
+}
+
+/// A change requested by an account of another chain.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum RemoteRequest {
+    Write { data_id: Vec<u8>, value: Vec<u8> },
+    Delete { data_id: Vec<u8> },
+    /// Let `account` of the chain `chain_id` read `data_id`.
+    GrantRead { data_id: Vec<u8>, chain_id: Vec<u8>, account: Vec<u8> },
+    /// Make `account` of the chain `chain_id` the author of `data_id`.
+    Transfer { data_id: Vec<u8>, chain_id: Vec<u8>, account: Vec<u8> },
+}
+
+/// Maps an account of a bridged chain to the local account acting for it.
+pub trait SovereignAccount<AccountId> {
+    fn sovereign_account(chain_id: &[u8], sender: &[u8]) -> AccountId;
+}
+
+/// Derives the sovereign account from a hash of the chain id and the remote sender.
+pub struct HashedSovereignAccount;
+
+impl<AccountId: Decode> SovereignAccount<AccountId> for HashedSovereignAccount {
+    fn sovereign_account(chain_id: &[u8], sender: &[u8]) -> AccountId {
+        let entropy = (&b"offchain-storage/remote"[..], chain_id, sender)
+            .using_encoded(sp_io::hashing::blake2_256);
+        AccountId::decode(&mut TrailingZeroInput::new(&entropy))
+            .expect("infinite length input; no invalid inputs for type; qed")
+    }
+}
+
+/// Entry point for transports delivering authenticated requests of other chains.
+pub trait HandleRemoteRequest {
+    /// Apply `request` sent by `sender` on the chain `chain_id`.
+    fn handle_remote_request(
+        chain_id: &[u8],
+        sender: &[u8],
+        request: RemoteRequest,
+    ) -> DispatchResult;
+}
+
+impl<T: Trait> HandleRemoteRequest for Module<T> {
+    fn handle_remote_request(
+        chain_id: &[u8],
+        sender: &[u8],
+        request: RemoteRequest,
+    ) -> DispatchResult {
+        let who = T::SovereignAccount::sovereign_account(chain_id, sender);
+        let data_id = match request {
+            RemoteRequest::Write { data_id, value } => {
+                Self::do_write(who, data_id.clone(), value)?;
+                data_id
+            }
+            RemoteRequest::Delete { data_id } => {
+                Self::do_delete(who, data_id.clone())?;
+                data_id
+            }
+            RemoteRequest::GrantRead { data_id, chain_id: to, account } => {
+                let grantee = T::SovereignAccount::sovereign_account(&to, &account);
+                Self::grant_remote_read(&who, &data_id, grantee)?;
+                data_id
+            }
+            RemoteRequest::Transfer { data_id, chain_id: to, account } => {
+                let owner = T::SovereignAccount::sovereign_account(&to, &account);
+                Self::transfer_entry(&who, &data_id, owner)?;
+                data_id
+            }
+        };
+        Self::deposit_event(RawEvent::RemoteRequestApplied(chain_id.to_vec(), data_id));
+        Ok(())
+    }
+}
+
+impl<T: Trait> Module<T> {
+    // check that who is the author of data_id and may hand it on.
+    fn ensure_author(who: &T::AccountId, data_id: &[u8]) -> Result<(), Error<T>> {
+        ensure!(<Data<T>>::exists(data_id), Error::<T>::NoneData);
+        ensure!(!<Mirrors<T>>::exists(data_id), Error::<T>::ReadOnlyMirror);
+        ensure!(Self::get_data(data_id).author == *who, Error::<T>::PermissionDenied);
+        ensure!(!Self::is_expired(data_id), Error::<T>::Expired);
+        Ok(())
+    }
+
+    // let grantee read data_id, whose author who must be.
+    fn grant_remote_read(
+        who: &T::AccountId,
+        data_id: &[u8],
+        grantee: T::AccountId,
+    ) -> DispatchResult {
+        Self::ensure_author(who, data_id)?;
+        <ReadGrants<T>>::insert(data_id, &grantee, true);
+        Self::note_grant(who, data_id);
+        Self::note_access_granted(data_id, &grantee, Access::Read);
+        Ok(())
+    }
+
+    // make owner the author of data_id in place of who, along with its deposit and the
+    // bytes it takes.
+    fn transfer_entry(who: &T::AccountId, data_id: &[u8], owner: T::AccountId) -> DispatchResult {
+        Self::ensure_author(who, data_id)?;
+        ensure!(
+            Self::owned_count(&owner) < T::MaxEntriesPerAccount::get(),
+            Error::<T>::TooManyEntries
+        );
+        let size = Self::size_of(data_id);
+        ensure!(
+            Self::used_bytes(&owner).saturating_add(u64::from(size))
+                <= T::MaxBytesPerAccount::get(),
+            Error::<T>::ByteQuotaExceeded
+        );
+        let deposit = Self::deposit_of(data_id);
+        ensure!(T::Currency::can_reserve(&owner, deposit), Error::<T>::InsufficientBalance);
+        T::Currency::reserve(&owner, deposit)?;
+        let _ = T::Currency::unreserve(who, deposit);
+        Self::uncount_entry(who);
+        Self::unlist_owned(who, data_id);
+        Self::release_bytes(who, size);
+        Self::count_entry(&owner);
+        Self::list_owned(&owner, data_id);
+        Self::use_bytes(&owner, size);
+        // the grants on the entry stay, the new author revokes them.
+        if Self::granted_entries(who).iter().any(|id| id[..] == *data_id) {
+            <GrantedEntries<T>>::mutate(who, |ids| ids.retain(|id| id[..] != *data_id));
+            Self::note_grant(&owner, data_id);
+        }
+        <Data<T>>::mutate(data_id, |data| data.author = owner.clone());
+        Self::audit(data_id, &owner, AuditOp::Transferred);
+        Self::deposit_event(RawEvent::OwnershipTransferred(data_id.to_vec(), who.clone(), owner));
+        Ok(())
+    }
+}
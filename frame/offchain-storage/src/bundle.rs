@@ -0,0 +1,75 @@
+//! Offline write bundles.
+//!
+//! An author on a disconnected machine puts writes in a [`WriteBundle`], signs its
+//! [`signing_payload`](WriteBundle::signing_payload) with the key of their account, and
+//! carries bundle and signature out. Anyone may then submit them with `submit_bundle`,
+//! which applies the writes as if the author made them. Every bundle of an author needs a
+//! higher nonce than the last one submitted, so a bundle can't be replayed, and is refused
+//! after `expires_at`.
+//!
+//! The writes of a bundle are applied as the writes of a transaction: checked together
+//! against the quotas, rate limit and balance of the author first, then recorded on chain
+//! and uploaded by the offchain worker. Either all of them take effect or none does, and
+//! the nonce of the author only moves once they have.
+//!
+//! Relayers submit many bundles with `submit_bundles`. Their signatures are all checked
+//! in one pass by `verify_bundles`, before any write, which is where batch verification
+//! of sr25519 signatures belongs once the runtime interface offers it.
+
+use super::{transaction::Op, BundleNonces, Error, Module, RawEvent, Trait};
+use codec::{Decode, Encode};
+use frame_support::{dispatch::DispatchResult, ensure, StorageMap};
+use sp_runtime::{traits::Verify, RuntimeDebug};
+use sp_std::vec::Vec;
+
+/// Writes signed by their author, to be submitted by anyone.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct WriteBundle<AccountId, BlockNumber> {
+    /// The account writing, which signed the bundle.
+    pub author: AccountId,
+    /// Must be higher than the nonce of the last bundle of the author.
+    pub nonce: u64,
+    /// The last block the bundle may be submitted in.
+    pub expires_at: BlockNumber,
+    /// The writes, as `(data_id, value)`.
+    pub writes: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl<AccountId: Encode, BlockNumber: Encode> WriteBundle<AccountId, BlockNumber> {
+    /// What the author signs.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        (&b"offchain-storage/bundle"[..], self).encode()
+    }
+}
+
+impl<T: Trait> Module<T> {
+    // whether every bundle is signed by its author.
+    pub(crate) fn verify_bundles(
+        bundles: &[(WriteBundle<T::AccountId, T::BlockNumber>, T::BundleSignature)],
+    ) -> bool {
+        bundles.iter().all(|(bundle, signature)| {
+            signature.verify(&bundle.signing_payload()[..], &bundle.author)
+        })
+    }
+
+    // apply the writes of bundle, whose signature was verified, all of them or none.
+    pub(crate) fn apply_bundle(
+        bundle: WriteBundle<T::AccountId, T::BlockNumber>,
+    ) -> DispatchResult {
+        ensure!(bundle.nonce > Self::bundle_nonce(&bundle.author), Error::<T>::StaleBundle);
+        ensure!(
+            <frame_system::Module<T>>::block_number() <= bundle.expires_at,
+            Error::<T>::BundleExpired
+        );
+        let mut ids: Vec<&Vec<u8>> = bundle.writes.iter().map(|(id, _)| id).collect();
+        ids.sort();
+        ids.dedup();
+        ensure!(ids.len() == bundle.writes.len(), Error::<T>::BadBundle);
+        let count = bundle.writes.len() as u32;
+        let ops = bundle.writes.into_iter().map(|(data_id, value)| Op::Write(data_id, value));
+        Self::apply_ops(bundle.author.clone(), ops.collect())?;
+        <BundleNonces<T>>::insert(&bundle.author, bundle.nonce);
+        Self::deposit_event(RawEvent::BundleApplied(bundle.nonce, count));
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+//! Proof-of-storage challenges.
+//!
+//! Anyone may challenge a stored entry. The challenge picks a random chunk of the
+//! value (see the merkle module), and a storage provider has `T::ChallengePeriod`
+//! blocks to answer with that chunk and its merkle proof. Values short enough to
+//! have no chunk root are answered with the whole value. Wrong answers and
+//! challenges nobody answers are counted so slashing logic can act on them.
+
+use super::{merkle, Module, Trait};
+use codec::{Decode, Encode};
+use frame_support::traits::Randomness;
+use sp_runtime::{traits::Hash, RuntimeDebug};
+use sp_std::vec::Vec;
+
+/// An open challenge of an entry.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Challenge<BlockNumber> {
+    /// Identifies the challenge, answers must repeat it.
+    pub nonce: u64,
+    /// The chunk that has to be shown.
+    pub chunk_index: u32,
+    /// The last block the challenge can be answered in.
+    pub deadline: BlockNumber,
+}
+
+/// An answer to a challenge.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct StorageProof<Hash> {
+    /// The challenged chunk, or the whole value if it has no chunk root.
+    pub chunk: Vec<u8>,
+    /// The merkle proof of the chunk, empty if the whole value is given.
+    pub path: Vec<Hash>,
+}
+
+impl<T: Trait> Module<T> {
+    // pick the chunk a new challenge of data_id asks for.
+    pub(crate) fn challenged_chunk(data_id: &[u8], nonce: u64) -> u32 {
+        let count = match Self::chunk_root(data_id) {
+            Some((_, count)) => count,
+            None => return 0,
+        };
+        let subject = (&b"offchain-storage/challenge"[..], data_id, nonce).encode();
+        let seed = T::Randomness::random(&subject);
+        u32::decode(&mut seed.as_ref()).unwrap_or_default() % count
+    }
+
+    // whether proof shows the challenged chunk of data_id.
+    pub(crate) fn check_storage_proof(
+        data_id: &[u8],
+        challenge: &Challenge<T::BlockNumber>,
+        proof: &StorageProof<T::Hash>,
+    ) -> bool {
+        match Self::chunk_root(data_id) {
+            Some((root, count)) => merkle::verify::<T::Hashing>(
+                &root,
+                count as usize,
+                challenge.chunk_index as usize,
+                T::Hashing::hash(&proof.chunk),
+                &proof.path,
+            ),
+            None => T::Hashing::hash(&proof.chunk) == Self::get_data(data_id).content_hash,
+        }
+    }
+}
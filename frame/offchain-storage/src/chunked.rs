@@ -0,0 +1,182 @@
+//! Chunked uploads.
+//!
+//! A value too large for one extrinsic is uploaded over several blocks. `begin_upload`
+//! declares how many chunks it has and the merkle root over their hashes, see the merkle
+//! module. `upload_chunk` puts each chunk in `T::Storage` under [`chunk_key`] and keeps its
+//! hash on chain. Every chunk is `T::ChunkSize` bytes long but the last, which may be
+//! shorter. Once all chunks are in, `commit_upload` checks them against the root and
+//! writes the value as `write_data` would, which still bounds it by `T::MaxValueLength`.
+//!
+//! An upload belongs to the account that began it, so uploads of several accounts to the
+//! same data_id don't get in each other's way. Beginning an upload again drops the chunks
+//! uploaded so far.
+//!
+//! The uploader reserves the deposit of an entry as long as all the chunks together until
+//! the upload is committed, and may have at most `T::MaxChunkedUploads` open. An upload not
+//! committed within `T::ChunkedUploadPeriod` blocks is dropped: `on_initialize` releases the
+//! deposit and hands the chunks to the garbage collector.
+
+use super::{
+    expiry::Expiry, gc::GcItem, merkle, BalanceOf, ChunkedUploadCount, ChunkedUploadDeadlines,
+    ChunkedUploads, Error, ExternalStorage, Module, RawEvent, Trait,
+};
+use codec::{Decode, Encode};
+use frame_support::{
+    dispatch::DispatchResult,
+    ensure,
+    traits::{Get, ReservableCurrency},
+    StorageDoubleMap, StorageMap,
+};
+use sp_runtime::{
+    traits::{Hash, Saturating, Zero},
+    RuntimeDebug,
+};
+use sp_std::vec::Vec;
+
+/// A value being uploaded chunk by chunk.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct ChunkedUpload<Hash, Balance, BlockNumber> {
+    /// The merkle root over the hashes of the chunks.
+    pub root: Hash,
+    /// The hashes of the chunks uploaded, by index.
+    pub chunks: Vec<Option<Hash>>,
+    /// The deposit the uploader reserved.
+    pub deposit: Balance,
+    /// The block the upload is dropped in unless it is committed before.
+    pub expires_at: BlockNumber,
+}
+
+/// The backend key chunk `index` of the upload of `who` to `data_id` is kept under.
+pub fn chunk_key<AccountId: Encode>(who: &AccountId, data_id: &[u8], index: u32) -> Vec<u8> {
+    (&b"offchain-storage/chunk"[..], who, data_id, index).encode()
+}
+
+impl<T: Trait> Module<T> {
+    // start the upload of a value of total chunks to data_id for who.
+    pub(crate) fn begin_chunked_upload(
+        who: T::AccountId,
+        data_id: Vec<u8>,
+        total: u32,
+        root: T::Hash,
+    ) -> DispatchResult {
+        ensure!(total > 0, Error::<T>::BadChunk);
+        let shortest = (total - 1).saturating_mul(T::ChunkSize::get()).saturating_add(1);
+        ensure!(shortest <= T::MaxValueLength::get(), Error::<T>::ValueTooLong);
+        // fail early if the value couldn't be written anyway.
+        Self::validate_write(&who, &data_id, 0).map_err(Error::<T>::from)?;
+        let replaced = Self::chunked_upload(&who, &data_id).map(|upload| upload.deposit);
+        ensure!(
+            replaced.is_some() || Self::chunked_upload_count(&who) < T::MaxChunkedUploads::get(),
+            Error::<T>::TooManyUploads
+        );
+        let longest = total.saturating_mul(T::ChunkSize::get()).min(T::MaxValueLength::get());
+        let deposit = Self::deposit_for(longest);
+        let freed = replaced.unwrap_or_else(Zero::zero);
+        ensure!(
+            T::Currency::can_reserve(&who, deposit.saturating_sub(freed)),
+            Error::<T>::InsufficientBalance
+        );
+        Self::drop_chunks(&who, &data_id);
+        T::Currency::reserve(&who, deposit).map_err(|_| Error::<T>::InsufficientBalance)?;
+        let now = <frame_system::Module<T>>::block_number();
+        let expires_at = now.saturating_add(T::ChunkedUploadPeriod::get());
+        let chunks = sp_std::vec![None; total as usize];
+        let upload = ChunkedUpload { root, chunks, deposit, expires_at };
+        <ChunkedUploads<T>>::insert(&who, &data_id, upload);
+        <ChunkedUploadDeadlines<T>>::insert(expires_at, (&who, &data_id), (&who, &data_id));
+        <ChunkedUploadCount<T>>::mutate(&who, |count| *count = count.saturating_add(1));
+        Ok(())
+    }
+
+    // store chunk index of the upload of who to data_id.
+    pub(crate) fn store_chunk(
+        who: T::AccountId,
+        data_id: Vec<u8>,
+        index: u32,
+        bytes: Vec<u8>,
+    ) -> DispatchResult {
+        let mut upload = Self::open_upload(&who, &data_id)?;
+        let total = upload.chunks.len() as u32;
+        let chunk_size = T::ChunkSize::get() as usize;
+        ensure!(index < total, Error::<T>::BadChunk);
+        let fits = if index + 1 < total {
+            bytes.len() == chunk_size
+        } else {
+            !bytes.is_empty() && bytes.len() <= chunk_size
+        };
+        ensure!(fits, Error::<T>::BadChunk);
+        upload.chunks[index as usize] = Some(T::Hashing::hash(&bytes));
+        Self::backend(&data_id, T::Storage::set(chunk_key(&who, &data_id, index), bytes))?;
+        <ChunkedUploads<T>>::insert(&who, &data_id, upload);
+        Ok(())
+    }
+
+    // write the value who uploaded to data_id, once all its chunks are in and match.
+    pub(crate) fn commit_chunks(who: T::AccountId, data_id: Vec<u8>) -> DispatchResult {
+        let upload = Self::open_upload(&who, &data_id)?;
+        let leaves = upload.chunks.iter().cloned().collect::<Option<Vec<_>>>();
+        let leaves = leaves.ok_or(Error::<T>::IncompleteUpload)?;
+        ensure!(merkle::root::<T::Hashing>(&leaves) == upload.root, Error::<T>::IntegrityError);
+        let mut value = Vec::new();
+        for (index, hash) in leaves.iter().enumerate() {
+            let key = chunk_key(&who, &data_id, index as u32);
+            value.extend(Self::get_intact(&data_id, key, hash)?);
+        }
+        Self::do_write(who.clone(), data_id.clone(), value)?;
+        Self::drop_chunks(&who, &data_id);
+        Ok(())
+    }
+
+    // the upload of who to data_id, unless its time ran out. One that did may wait to be
+    // dropped, see the expiry module.
+    fn open_upload(
+        who: &T::AccountId,
+        data_id: &[u8],
+    ) -> Result<ChunkedUpload<T::Hash, BalanceOf<T>, T::BlockNumber>, Error<T>> {
+        let now = <frame_system::Module<T>>::block_number();
+        Self::chunked_upload(who, data_id)
+            .filter(|upload| now < upload.expires_at)
+            .ok_or(Error::<T>::NoChunkedUpload)
+    }
+
+    // drop the upload of who to data_id and its chunks, if there is one.
+    fn drop_chunks(who: &T::AccountId, data_id: &[u8]) {
+        if let Some(upload) = Self::close_upload(who, data_id) {
+            for index in 0..upload.chunks.len() as u32 {
+                // a chunk left behind is replaced by the next upload.
+                let _ = T::Storage::delete(chunk_key(who, data_id, index));
+            }
+        }
+    }
+
+    // drop the uploads not committed by block n, as many as the block takes, and leave
+    // their chunks to the garbage collector.
+    pub(crate) fn expire_chunked_uploads(n: T::BlockNumber) {
+        let ending = Self::take_expired::<ChunkedUploadDeadlines<T>, _>(Expiry::ChunkedUpload, n);
+        for (_, (who, data_id)) in ending {
+            let upload = match Self::close_upload(&who, &data_id) {
+                Some(upload) => upload,
+                None => continue,
+            };
+            let stored = upload.chunks.iter().enumerate().filter(|(_, hash)| hash.is_some());
+            let items = stored
+                .map(|(index, _)| GcItem::Chunk(who.clone(), data_id.clone(), index as u32))
+                .collect();
+            Self::queue_items(items);
+            Self::deposit_event(RawEvent::ChunkedUploadExpired(who, data_id));
+        }
+    }
+
+    // remove the upload of who to data_id from the chain and release its deposit, leaving
+    // the chunks.
+    fn close_upload(
+        who: &T::AccountId,
+        data_id: &[u8],
+    ) -> Option<ChunkedUpload<T::Hash, BalanceOf<T>, T::BlockNumber>> {
+        let upload = <ChunkedUploads<T>>::take(who, data_id)?;
+        let _ = T::Currency::unreserve(who, upload.deposit);
+        <ChunkedUploadDeadlines<T>>::remove(upload.expires_at, (who, data_id));
+        <ChunkedUploadCount<T>>::mutate(who, |count| *count = count.saturating_sub(1));
+        Some(upload)
+    }
+}
@@ -0,0 +1,60 @@
+//! Transparent compression of values.
+//!
+//! A write stores its value in the backend compressed with the `Compression` it asks for
+//! through `write_data_compressed`, or with `T::DefaultCompression` if it doesn't, and
+//! records it in the entry. Reads undo the compression the entry records, so values written
+//! with different settings stay readable. Values that don't compress, e.g. encrypted ones,
+//! are better written with `Compression::None`.
+//!
+//! Only the copy in the backend is compressed. The content hash, chunk roots, revisions and
+//! replicas are of the value as written. Appends to a compressed value write it in full, and
+//! ranged reads of it read the whole value.
+
+use super::{Compression, Error, Module, Trait};
+use frame_support::{ensure, traits::Get};
+use offchain_storage_runtime_api::compression::snappy;
+use sp_std::vec::Vec;
+
+/// Compresses values with the algorithms it supports.
+pub trait Compressor {
+    /// Whether `compress` and `decompress` take `algorithm`.
+    fn supports(algorithm: Compression) -> bool;
+    /// `value` compressed with `algorithm`, which is supported.
+    fn compress(algorithm: Compression, value: Vec<u8>) -> Vec<u8>;
+    /// The value `bytes` are the compression of with `algorithm`, `None` if they aren't one.
+    fn decompress(algorithm: Compression, bytes: Vec<u8>) -> Option<Vec<u8>>;
+}
+
+/// Supports snappy. A runtime with a zstd implementation, e.g. behind a host function,
+/// handles `Compression::Zstd` itself and leaves the others to this one.
+impl Compressor for () {
+    fn supports(algorithm: Compression) -> bool {
+        algorithm != Compression::Zstd
+    }
+
+    fn compress(algorithm: Compression, value: Vec<u8>) -> Vec<u8> {
+        match algorithm {
+            Compression::Snappy => snappy::compress(&value),
+            _ => value,
+        }
+    }
+
+    fn decompress(algorithm: Compression, bytes: Vec<u8>) -> Option<Vec<u8>> {
+        match algorithm {
+            Compression::None => Some(bytes),
+            Compression::Snappy => snappy::decompress(&bytes),
+            Compression::Zstd => None,
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    // the compression of a write asking for requested, T::DefaultCompression if None.
+    pub(crate) fn compression_for(
+        requested: Option<Compression>,
+    ) -> Result<Compression, Error<T>> {
+        let compression = requested.unwrap_or_else(T::DefaultCompression::get);
+        ensure!(T::Compressor::supports(compression), Error::<T>::UnsupportedCompression);
+        Ok(compression)
+    }
+}
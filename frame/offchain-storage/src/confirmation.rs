@@ -0,0 +1,137 @@
+//! Unsigned confirmations of providers.
+//!
+//! Providers confirm that they keep a replica, or that they served a read, without paying
+//! fees: they sign a [`ProviderConfirmation`] with the key of their account, as authors
+//! sign bundles, and submit it with `confirm_unsigned` as an unsigned extrinsic. The
+//! transaction pool only accepts it from a registered provider with a valid signature and
+//! a nonce above the last one used, so an account without funds can't be made to spam the
+//! chain, and a confirmation can't be replayed. What it confirms is checked there too: a
+//! replica must be one the provider keeps, of the value the entry has, and a read one the
+//! reader paid for, holding a receipt in `ReadReceipts`. Confirmations that don't hold are
+//! rejected by the pool rather than included for free.
+
+use super::{
+    Call, ConfirmationNonces, Data, Error, Module, RawEvent, ReadReceipts, ReplicaConfirmations,
+    Trait,
+};
+use codec::{Decode, Encode};
+use frame_support::{ensure, StorageDoubleMap, StorageMap};
+use sp_runtime::{
+    traits::{SaturatedConversion, Verify},
+    transaction_validity::{
+        InvalidTransaction, TransactionPriority, TransactionValidity, ValidTransaction,
+    },
+    RuntimeDebug,
+};
+use sp_std::vec::Vec;
+
+/// What a provider confirms.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum Confirmed<AccountId, Hash> {
+    /// It keeps a replica of `.0` with the value hashing to `.1`.
+    Stored(Vec<u8>, Hash),
+    /// It served the value of `.0` to the reader `.1`.
+    Retrieved(Vec<u8>, AccountId),
+}
+
+/// A confirmation signed by a provider, to be submitted unsigned.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct ProviderConfirmation<AccountId, Hash, BlockNumber> {
+    /// The provider confirming, which signed.
+    pub provider: AccountId,
+    /// Must be higher than the nonce of the last confirmation of the provider.
+    pub nonce: u64,
+    /// The last block the confirmation may be submitted in.
+    pub expires_at: BlockNumber,
+    pub confirmed: Confirmed<AccountId, Hash>,
+}
+
+impl<AccountId: Encode, Hash: Encode, BlockNumber: Encode>
+    ProviderConfirmation<AccountId, Hash, BlockNumber>
+{
+    /// What the provider signs.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        (&b"offchain-storage/confirmation"[..], self).encode()
+    }
+}
+
+impl<T: Trait> Module<T> {
+    // check that confirmation is signed by its provider, holds and may be applied now.
+    pub(crate) fn check_confirmation(
+        confirmation: &ProviderConfirmation<T::AccountId, T::Hash, T::BlockNumber>,
+        signature: &T::BundleSignature,
+    ) -> Result<(), Error<T>> {
+        ensure!(Self::is_provider(&confirmation.provider), Error::<T>::NotProvider);
+        ensure!(
+            confirmation.nonce > Self::confirmation_nonce(&confirmation.provider),
+            Error::<T>::StaleConfirmation
+        );
+        ensure!(
+            <frame_system::Module<T>>::block_number() <= confirmation.expires_at,
+            Error::<T>::StaleConfirmation
+        );
+        ensure!(
+            signature.verify(&confirmation.signing_payload()[..], &confirmation.provider),
+            Error::<T>::BadConfirmation
+        );
+        match &confirmation.confirmed {
+            Confirmed::Stored(data_id, content_hash) => {
+                ensure!(<Data<T>>::exists(data_id), Error::<T>::NoneData);
+                let data = Self::get_data(data_id);
+                ensure!(data.providers.contains(&confirmation.provider), Error::<T>::NotReplica);
+                ensure!(data.content_hash == *content_hash, Error::<T>::IntegrityError);
+            }
+            Confirmed::Retrieved(data_id, reader) => {
+                ensure!(<Data<T>>::exists(data_id), Error::<T>::NoneData);
+                ensure!(
+                    <ReadReceipts<T>>::exists(data_id, reader),
+                    Error::<T>::NoReadReceipt
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // apply confirmation, which check_confirmation accepted.
+    pub(crate) fn apply_confirmation(
+        confirmation: ProviderConfirmation<T::AccountId, T::Hash, T::BlockNumber>,
+    ) {
+        let provider = confirmation.provider;
+        <ConfirmationNonces<T>>::insert(&provider, confirmation.nonce);
+        match confirmation.confirmed {
+            Confirmed::Stored(data_id, _) => {
+                let now = <frame_system::Module<T>>::block_number();
+                <ReplicaConfirmations<T>>::insert(&data_id, &provider, now);
+                Self::deposit_event(RawEvent::ReplicaConfirmed(data_id, provider));
+            }
+            Confirmed::Retrieved(data_id, reader) => {
+                Self::note_metric(&provider, |m| m.served_reads += 1);
+                Self::deposit_event(RawEvent::ReadServed(data_id, provider, reader));
+            }
+        }
+    }
+}
+
+#[allow(deprecated)]
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+    type Call = Call<T>;
+
+    fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+        if let Call::confirm_unsigned(confirmation, signature) = call {
+            if Self::check_confirmation(confirmation, signature).is_err() {
+                return InvalidTransaction::BadProof.into();
+            }
+            let now = <frame_system::Module<T>>::block_number();
+            let longevity = (confirmation.expires_at - now).saturated_into::<u64>();
+            Ok(ValidTransaction {
+                priority: TransactionPriority::max_value() / 2,
+                requires: Vec::new(),
+                provides: sp_std::vec![(&confirmation.provider, confirmation.nonce).encode()],
+                longevity: longevity.max(1),
+                propagate: true,
+            })
+        } else {
+            InvalidTransaction::Call.into()
+        }
+    }
+}
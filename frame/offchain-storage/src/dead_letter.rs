@@ -0,0 +1,70 @@
+//! Dead letters of storage requests.
+//!
+//! A match of a storage request falls through if the value can't be copied to the
+//! provider. When a backend failed, the request stays open and is tried again with the
+//! next match, at most `T::MaxRequestAttempts` times in all. A request out of attempts, or
+//! one that failed for any other reason, is taken off the open requests and kept as a
+//! [`DeadLetter`] with the code of the failure, and `OperationDeadLettered` is emitted. Its
+//! deposit stays reserved. The author, or whoever is in `T::Operators`, puts it back in the
+//! queue with `retry_dead_letter` or drops it and releases the deposit with
+//! `purge_dead_letter`.
+
+use super::{
+    market::StorageRequest, BalanceOf, DeadLetters, Error, ErrorCode, Module, RawEvent,
+    StorageRequests, Trait,
+};
+use codec::{Decode, Encode};
+use frame_support::{
+    ensure,
+    traits::{Contains, Get},
+    StorageMap,
+};
+use sp_runtime::RuntimeDebug;
+
+/// A storage request given up on.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct DeadLetter<AccountId, Balance, BlockNumber> {
+    pub request: StorageRequest<AccountId, Balance, BlockNumber>,
+    /// The code of the last failure.
+    pub reason: ErrorCode,
+    /// The block the request was given up on.
+    pub at: BlockNumber,
+}
+
+impl<T: Trait> Module<T> {
+    // note that a match of the open storage request id failed with error. Returns whether
+    // the request stays open.
+    pub(crate) fn fail_attempt(
+        id: u64,
+        mut request: StorageRequest<T::AccountId, BalanceOf<T>, T::BlockNumber>,
+        error: Error<T>,
+    ) -> bool {
+        request.attempts = request.attempts.saturating_add(1);
+        let transient = match error {
+            Error::<T>::ExternalError => true,
+            _ => false,
+        };
+        if transient && request.attempts < T::MaxRequestAttempts::get() {
+            <StorageRequests<T>>::insert(id, request);
+            return true;
+        }
+        let reason = error.code();
+        <StorageRequests<T>>::remove(id);
+        let at = <frame_system::Module<T>>::block_number();
+        <DeadLetters<T>>::insert(id, DeadLetter { request, reason, at });
+        Self::deposit_event(RawEvent::OperationDeadLettered(id, reason));
+        false
+    }
+
+    // check that who may retry or purge letter.
+    pub(crate) fn ensure_may_handle(
+        who: &T::AccountId,
+        letter: &DeadLetter<T::AccountId, BalanceOf<T>, T::BlockNumber>,
+    ) -> Result<(), Error<T>> {
+        ensure!(
+            letter.request.author == *who || T::Operators::contains(who),
+            Error::<T>::PermissionDenied
+        );
+        Ok(())
+    }
+}
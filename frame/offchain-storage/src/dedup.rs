@@ -0,0 +1,92 @@
+//! Deduplication of values by their content hash.
+//!
+//! With `T::Deduplication` on, a write stores its value in the backend of the entry under
+//! the [`object_key`] of its content hash rather than under its data_id, and entries with
+//! the same value in the same backend, compressed the same way, share that object.
+//! `SharedValues` says which object each entry reads and `ObjectRefs` counts the entries
+//! sharing each one. Writing a value whose object exists only counts one more reference;
+//! the object is deleted once the last entry sharing it is deleted or written again with
+//! another value, or handed to the garbage collector if that entry is.
+//!
+//! Values stored as erasure coded shards or written with `T::DeferredUploads` on are kept
+//! under their data_id as before, as are the values of entries written before
+//! deduplication was turned on, until their next write. Shared values aren't appended to in
+//! place.
+
+use super::{
+    gc::GcItem, routing::BackendId, Compression, Data, Error, Module, ObjectRefs,
+    SharedValues, Trait, UserData,
+};
+use codec::Encode;
+use frame_support::{StorageDoubleMap, StorageMap};
+use sp_std::vec::Vec;
+
+/// The backend key of the object holding the values hashing to `content_hash`, compressed
+/// with `compression`.
+pub fn object_key<Hash: Encode>(content_hash: &Hash, compression: Compression) -> Vec<u8> {
+    (&b"offchain-storage/object"[..], content_hash, compression).encode()
+}
+
+impl<T: Trait> Module<T> {
+    /// The backend key the whole value of `data_id` is kept under.
+    pub fn value_key(data_id: &[u8]) -> Vec<u8> {
+        Self::shared_value(data_id).unwrap_or_else(|| data_id.to_vec())
+    }
+
+    // store stored, the value of data_id compressed as data says, as the object of its
+    // content and let data_id share it.
+    pub(crate) fn store_shared(
+        data_id: &[u8],
+        stored: Vec<u8>,
+        data: &UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) -> Result<(), Error<T>> {
+        let (backend, key) = (data.backend, object_key(&data.content_hash, data.compression));
+        if Self::shared_value(data_id).as_ref() == Some(&key) {
+            return Ok(());
+        }
+        if Self::object_refs(backend, &key) == 0 {
+            Self::backend(data_id, Self::routed_set(backend, key.clone(), stored))?;
+        }
+        if SharedValues::exists(data_id) {
+            Self::release_shared(data_id, backend)?;
+        } else if <Data<T>>::exists(data_id) {
+            // the value written before deduplication was on.
+            Self::backend(data_id, Self::routed_delete(backend, data_id.to_vec()))?;
+        }
+        ObjectRefs::mutate(backend, &key, |refs| *refs += 1);
+        SharedValues::insert(data_id, key);
+        Ok(())
+    }
+
+    // stop data_id from sharing an object, deleting the object from backend if no other
+    // entry shares it.
+    pub(crate) fn release_shared(data_id: &[u8], backend: BackendId) -> Result<(), Error<T>> {
+        if let Some(key) = Self::shared_value(data_id) {
+            if Self::object_refs(backend, &key) <= 1 {
+                Self::backend(data_id, Self::routed_delete(backend, key))?;
+            }
+            Self::unshare(data_id, backend);
+        }
+        Ok(())
+    }
+
+    // like release_shared, leaving the object to the garbage collector.
+    pub(crate) fn queue_release(data_id: &[u8], backend: BackendId) {
+        if let Some(key) = Self::unshare(data_id, backend) {
+            Self::queue_items(sp_std::vec![GcItem::Object(backend, key)]);
+        }
+    }
+
+    // drop the reference of data_id to its object, returns the object if it was the last.
+    fn unshare(data_id: &[u8], backend: BackendId) -> Option<Vec<u8>> {
+        let key = SharedValues::take(data_id)?;
+        let refs = Self::object_refs(backend, &key).saturating_sub(1);
+        if refs == 0 {
+            ObjectRefs::remove(backend, &key);
+            Some(key)
+        } else {
+            ObjectRefs::insert(backend, &key, refs);
+            None
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Delivering values off chain.
+//!
+//! The read calls don't put the value they read in a block, where every node would keep it
+//! and anyone could see it. `read_data`, `read_version` and `read_data_range` check that the
+//! caller may read the entry, have it pay for the read, and record a receipt in
+//! `ReadReceipts` naming the content hash of the value the caller is served: the live
+//! value, the staged one for readers in the canary share, or the revision asked for. The
+//! event of the read carries that hash, so the read can be audited without the value.
+//!
+//! The value itself is delivered by the `offchainStorage_read` RPC. It asks the `locate`
+//! runtime API where the value of the receipt of the reader is kept, fetches it from the
+//! backend of the node and checks it against the hash before returning it. A receipt
+//! stands until the reader reads the entry again, loses its access, or the value goes:
+//! the entry is written again and its last revisions dropped, renamed or deleted.
+//!
+//! Values stored as erasure coded shards, or waiting for their upload, aren't kept whole
+//! under one key and can't be located. Other modules read values in the runtime with
+//! `read_for`, which fetches and checks them itself.
+
+use super::{
+    audit::AuditOp, staging, Data, Error, Module, PendingUploads, RawEvent, ReadReceipts,
+    Shards, Staged, Trait, ValueLocation, DEFAULT_BACKEND,
+};
+use frame_support::{ensure, traits::Get, StorageDoubleMap, StorageMap};
+use sp_std::vec::Vec;
+
+impl<T: Trait> Module<T> {
+    /// Where the value `reader` has a receipt for of `data_id` is kept, if `reader` may
+    /// still read `data_id` and the value is kept whole under one key. Backs the `locate`
+    /// runtime API.
+    pub fn locate(reader: &T::AccountId, data_id: &[u8]) -> Option<ValueLocation<T::Hash>> {
+        let content_hash = Self::read_receipt(data_id, reader)?;
+        if !Self::can_read(reader, data_id) {
+            return None;
+        }
+        let data = Self::get_data(data_id);
+        let location = |backend, key, compression| ValueLocation {
+            backend,
+            key,
+            compression,
+            content_hash,
+        };
+        match Self::staged_of(data_id) {
+            Some(staged) if staged.content_hash == content_hash => {
+                let key = staging::staged_key(&Self::staged_source(data_id, &content_hash));
+                return Some(location(DEFAULT_BACKEND, key, Default::default()));
+            }
+            _ => {}
+        }
+        let source = Self::value_source(data_id, &content_hash);
+        let whole = !<Shards<T>>::exists(&source) && !<PendingUploads<T>>::exists(data_id);
+        if data.content_hash == content_hash && whole {
+            return Some(location(data.backend, Self::value_key(&source), data.compression));
+        }
+        let count = Self::revision_count(data_id);
+        (count.saturating_sub(T::MaxRevisions::get()) + 1..=count)
+            .rev()
+            .filter_map(|number| Self::revision(data_id, number))
+            .find(|revision| revision.content_hash == content_hash)
+            .map(|revision| location(DEFAULT_BACKEND, revision.key, Default::default()))
+    }
+
+    /// The value `reader` reads of `data_id`, fetched from its backend and checked against
+    /// the chain, for other modules. Entries with a read quorum are only read through
+    /// `read_data`.
+    pub fn read_for(reader: &T::AccountId, data_id: &[u8]) -> Result<Vec<u8>, Error<T>> {
+        ensure!(
+            <Data<T>>::exists(data_id) && !Self::ttl_passed(data_id),
+            Error::<T>::NoneData
+        );
+        let data = Self::get_data(data_id);
+        ensure!(
+            Self::may_read(reader, data_id, data.clone())
+                && Self::read_quorum(data_id).is_none(),
+            Error::<T>::PermissionDenied
+        );
+        let value = Self::read_value(data_id, reader, &data)?;
+        Self::audit(data_id, reader, AuditOp::Read);
+        Self::deposit_event(RawEvent::DataRead(reader.clone(), data_id.to_vec()));
+        Ok(value)
+    }
+
+    // the content hash of the value reader is served of data_id, the staged one if reader
+    // is in the canary share. The value of an entry the chain started with is hashed first.
+    pub(crate) fn served_hash(
+        data_id: &[u8],
+        reader: &T::AccountId,
+    ) -> Result<T::Hash, Error<T>> {
+        if Self::get_data(data_id).content_hash == T::Hash::default() {
+            Self::adopt_legacy_value(data_id)?;
+        }
+        match Self::staged_of(data_id) {
+            Some(staged) if Self::reads_staged(data_id, reader) => {
+                <Staged<T>>::mutate(data_id, |staged| {
+                    if let Some(staged) = staged {
+                        staged.canary_reads = staged.canary_reads.saturating_add(1);
+                    }
+                });
+                Ok(staged.content_hash)
+            }
+            _ => Ok(Self::get_data(data_id).content_hash),
+        }
+    }
+
+    // record that reader read the value of data_id hashing to content_hash, for the RPC to
+    // deliver it.
+    pub(crate) fn note_read(data_id: &[u8], reader: &T::AccountId, content_hash: T::Hash) {
+        <ReadReceipts<T>>::insert(data_id, reader, content_hash);
+        Self::audit(data_id, reader, AuditOp::Read);
+        Self::deposit_event(RawEvent::DataRead(reader.clone(), data_id.to_vec()));
+    }
+}
+
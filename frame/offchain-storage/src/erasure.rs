@@ -0,0 +1,178 @@
+//! Erasure coding of values, so they survive the loss of some of their shards.
+//!
+//! A value is padded and split into `k` data shards, and `n - k` parity shards are added
+//! with a systematic Reed-Solomon code over GF(2^8), built from a Cauchy matrix. Any `k`
+//! of the `n` shards give the value back. The module keeps the hash of every shard, so
+//! shards the backend corrupted count as missing.
+//!
+//! Every write stores its shards under the keys of a new version, and the entry only
+//! switches to them once all are stored. A write the backend fails halfway leaves the
+//! shards of the value before whole, the old shards are left to the garbage collector.
+
+use codec::{Decode, Encode};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// How a value was split into shards.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct ShardInfo<Hash> {
+    /// How many shards are needed to rebuild the value.
+    pub k: u8,
+    /// Length of the value, without the padding of the last data shard.
+    pub len: u32,
+    /// Hash of every shard, `n` of them.
+    pub hashes: Vec<Hash>,
+    /// The version of the shards, which their keys carry.
+    pub version: u32,
+}
+
+impl<Hash> ShardInfo<Hash> {
+    /// The backend key shard `index` of `data_id` is stored under.
+    pub fn key(&self, data_id: &[u8], index: u8) -> Vec<u8> {
+        shard_key(data_id, self.version, index)
+    }
+}
+
+/// The backend key shard `index` of the version `version` of the shards of `data_id` is
+/// stored under. Shards stored before they had versions are version 0, under keys without.
+pub fn shard_key(data_id: &[u8], version: u32, index: u8) -> Vec<u8> {
+    match version {
+        0 => (&b"offchain-storage/shard"[..], data_id, index).encode(),
+        _ => (&b"offchain-storage/shard"[..], data_id, version, index).encode(),
+    }
+}
+
+/// Split `value` into `n` shards, any `k` of which rebuild it. Needs `1 <= k <= n`.
+pub fn encode(value: &[u8], k: u8, n: u8) -> Vec<Vec<u8>> {
+    let field = Field::new();
+    let (k, n) = (k as usize, n as usize);
+    let size = (value.len() + k - 1) / k;
+    let mut shards: Vec<Vec<u8>> = (0..k)
+        .map(|i| {
+            let mut shard: Vec<u8> = value.iter().skip(i * size).take(size).cloned().collect();
+            shard.resize(size, 0);
+            shard
+        })
+        .collect();
+    for p in k..n {
+        let mut parity = sp_std::vec![0u8; size];
+        for (d, c) in row(&field, k, p).into_iter().enumerate() {
+            for (b, x) in parity.iter_mut().zip(&shards[d]) {
+                *b ^= field.mul(c, *x);
+            }
+        }
+        shards.push(parity);
+    }
+    shards
+}
+
+/// Rebuild a value of `len` bytes from its shards, `None` standing for a missing one.
+/// Returns `None` if fewer than `k` shards are present.
+pub fn decode(shards: &[Option<Vec<u8>>], k: u8, len: usize) -> Option<Vec<u8>> {
+    let field = Field::new();
+    let k = k as usize;
+    let present: Vec<(usize, &Vec<u8>)> = shards
+        .iter()
+        .enumerate()
+        .filter_map(|(i, s)| s.as_ref().map(|s| (i, s)))
+        .take(k)
+        .collect();
+    if k == 0 || present.len() < k {
+        return None;
+    }
+    let size = present[0].1.len();
+    if present.iter().any(|(_, s)| s.len() != size) || k * size < len {
+        return None;
+    }
+
+    // invert the coding rows of the present shards by Gauss-Jordan elimination.
+    let mut m: Vec<Vec<u8>> = present.iter().map(|(i, _)| row(&field, k, *i)).collect();
+    let mut inv: Vec<Vec<u8>> = (0..k).map(|i| row(&field, k, i)).collect();
+    for col in 0..k {
+        let pivot = (col..k).find(|&r| m[r][col] != 0)?;
+        m.swap(col, pivot);
+        inv.swap(col, pivot);
+        let scale = field.inv(m[col][col]);
+        for x in 0..k {
+            m[col][x] = field.mul(m[col][x], scale);
+            inv[col][x] = field.mul(inv[col][x], scale);
+        }
+        for r in 0..k {
+            let factor = m[r][col];
+            if r == col || factor == 0 {
+                continue;
+            }
+            for x in 0..k {
+                let (a, b) = (field.mul(factor, m[col][x]), field.mul(factor, inv[col][x]));
+                m[r][x] ^= a;
+                inv[r][x] ^= b;
+            }
+        }
+    }
+
+    let mut value = Vec::with_capacity(k * size);
+    for coefs in &inv {
+        for b in 0..size {
+            let byte = coefs
+                .iter()
+                .zip(&present)
+                .fold(0u8, |acc, (c, (_, s))| acc ^ field.mul(*c, s[b]));
+            value.push(byte);
+        }
+    }
+    value.truncate(len);
+    Some(value)
+}
+
+// the coefficients shard is computed with from the k data shards:
+// a unit row for data shards, a Cauchy matrix row for parity shards.
+fn row(field: &Field, k: usize, shard: usize) -> Vec<u8> {
+    (0..k)
+        .map(|d| {
+            if shard < k {
+                (d == shard) as u8
+            } else {
+                field.inv((shard ^ d) as u8)
+            }
+        })
+        .collect()
+}
+
+// GF(2^8) with the polynomial x^8 + x^4 + x^3 + x^2 + 1.
+struct Field {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+impl Field {
+    fn new() -> Self {
+        let mut exp = [0u8; 512];
+        let mut log = [0u8; 256];
+        let mut x: u16 = 1;
+        for (i, e) in exp.iter_mut().take(255).enumerate() {
+            *e = x as u8;
+            log[x as usize] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= 0x11d;
+            }
+        }
+        // doubled so mul can add two logs without reducing them.
+        let (low, high) = exp.split_at_mut(255);
+        high[..255].copy_from_slice(low);
+        Field { exp, log }
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            0
+        } else {
+            self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+        }
+    }
+
+    // a must not be 0.
+    fn inv(&self, a: u8) -> u8 {
+        self.exp[255 - self.log[a as usize] as usize]
+    }
+}
@@ -0,0 +1,74 @@
+//! Bounded expiry passes.
+//!
+//! Entries whose time to live ends, trashed entries, chunked uploads and challenges are
+//! queued under the block they expire in. `on_initialize` works through each queue from an
+//! [`Expiry`] cursor, the oldest block it hasn't emptied yet, and handles at most
+//! `T::ExpiriesPerBlock` keys and blocks of it per block, adding `T::ExpiryWeight` to the
+//! block for every key handled. Keys left over stay queued under their block and are
+//! handled in the next blocks, before the keys of later blocks. Until then an entry whose
+//! time to live ended already reads as if it didn't exist, and a challenge past its
+//! deadline can't be answered.
+
+use super::{ExpiryCursors, Module, Trait};
+use codec::{Decode, Encode, FullCodec};
+use frame_support::{traits::Get, StorageDoubleMap, StorageMap};
+use sp_runtime::{
+    traits::{One, Saturating, Zero},
+    RuntimeDebug,
+};
+use sp_std::vec::Vec;
+
+/// A queue of keys by the block they expire in.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum Expiry {
+    /// `TtlQueue`, entries whose time to live ends.
+    Ttl,
+    /// `TrashQueue`, trashed entries to purge.
+    Trash,
+    /// `ChunkedUploadDeadlines`, chunked uploads not committed in time.
+    ChunkedUpload,
+    /// `ChallengeDeadlines`, challenges nobody answered.
+    Challenge,
+}
+
+impl<T: Trait> Module<T> {
+    // take the keys of Q due by block n that this block handles, with the block each was
+    // queued under, oldest block first.
+    pub(crate) fn take_expired<Q, K>(
+        expiry: Expiry,
+        n: T::BlockNumber,
+    ) -> Vec<(T::BlockNumber, K)>
+    where
+        Q: StorageDoubleMap<T::BlockNumber, K, K>,
+        K: FullCodec,
+    {
+        let limit = T::ExpiriesPerBlock::get() as usize;
+        let mut block = Self::expiry_cursor(expiry);
+        // passes before the cursor was kept emptied every block up to this one.
+        if block.is_zero() {
+            block = n;
+        }
+        let mut expired = Vec::new();
+        let mut walked = 0;
+        while block <= n && expired.len() < limit && walked < limit {
+            let left = limit - expired.len();
+            let mut keys: Vec<K> = Q::iter_prefix(block).take(left + 1).collect();
+            let emptied = keys.len() <= left;
+            keys.truncate(left);
+            for key in keys {
+                Q::remove(block, &key);
+                expired.push((block, key));
+            }
+            if !emptied {
+                break;
+            }
+            block = block.saturating_add(One::one());
+            walked += 1;
+        }
+        <ExpiryCursors<T>>::insert(expiry, block);
+        <frame_system::Module<T>>::register_extra_weight_unchecked(
+            T::ExpiryWeight::get().saturating_mul(expired.len() as u32),
+        );
+        expired
+    }
+}
@@ -0,0 +1,198 @@
+//! Garbage collection of backend keys.
+//!
+//! Entries removed without a caller to pay for it, when their time to live ends or their
+//! rent grace period is over, only lose their on-chain state at once. The backend keys of
+//! their value, shards, replicas and past versions are queued, and `on_initialize` deletes
+//! at most `T::GcKeysPerBlock` of them per block, adding the weight of what it did to the
+//! block. The chunks of uploads not committed in time are queued the same way.
+//! The shards a write replaces and the keys of writes the backend failed are queued too.
+//! Keys a new entry of the same data_id uses again by then, or one waiting in the trash,
+//! are left alone, unless it keeps its value in another backend, as are objects entries
+//! share again, and shards of the version the entry keeps. When the backend fails a
+//! deletion, collection stops for the block and the key is tried again in the next.
+
+use super::{
+    chunked,
+    erasure::{self, ShardInfo},
+    history,
+    replication::ReplicaStorage,
+    routing::{BackendId, DEFAULT_BACKEND},
+    staging, ExternalStorage, GcBounds, GcQueue, Module, ObjectRefs, Shards, SharedValues, Staged,
+    StorageError, Trait, UserData,
+};
+use codec::{Decode, Encode};
+use frame_support::{traits::Get, StorageDoubleMap, StorageMap, StorageValue};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// A backend key waiting for deletion, with the data_id it belonged to.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum GcItem<AccountId> {
+    /// The whole value, kept in `T::Storage` under the data_id.
+    Value(Vec<u8>),
+    /// A shard of the value, by version and index.
+    Shard(Vec<u8>, u32, u8),
+    /// The replica a provider keeps.
+    Replica(Vec<u8>, AccountId),
+    /// A past version of the value, by revision number.
+    Version(Vec<u8>, u32),
+    /// The whole value, or a shard of it by version and index, kept in the backend `.0` of
+    /// `T::Backends`.
+    Routed(BackendId, Vec<u8>, Option<(u32, u8)>),
+    /// An object entries shared in the backend `.0`, by key, see the dedup module.
+    Object(BackendId, Vec<u8>),
+    /// The staged value, kept in `T::Storage`, see the staging module.
+    Staged(Vec<u8>),
+    /// A chunk of the upload of account `.0`, by index, kept in `T::Storage`, see the
+    /// chunked module.
+    Chunk(AccountId, Vec<u8>, u32),
+}
+
+impl<AccountId> GcItem<AccountId> {
+    /// The item of the value of `data_id` kept in `backend`, or of its shard `shard`, by
+    /// version and index.
+    pub fn stored(backend: BackendId, data_id: &[u8], shard: Option<(u32, u8)>) -> Self {
+        match (backend, shard) {
+            (DEFAULT_BACKEND, None) => GcItem::Value(data_id.to_vec()),
+            (DEFAULT_BACKEND, Some((version, index))) => {
+                GcItem::Shard(data_id.to_vec(), version, index)
+            }
+            _ => GcItem::Routed(backend, data_id.to_vec(), shard),
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    // queue every backend key of data_id, kept as data says, for deletion.
+    pub(crate) fn queue_deletion(
+        data_id: &[u8],
+        data: &UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) {
+        let mut items: Vec<GcItem<T::AccountId>> = data
+            .providers
+            .iter()
+            .map(|p| GcItem::Replica(data_id.to_vec(), p.clone()))
+            .collect();
+        match <Shards<T>>::take(data_id) {
+            Some(info) => Self::queue_shards(data_id, data.backend, &info),
+            None if SharedValues::exists(data_id) => Self::queue_release(data_id, data.backend),
+            None => items.push(GcItem::stored(data.backend, data_id, None)),
+        }
+        Self::queue_items(items);
+    }
+
+    // queue the shards of data_id info tells of, kept in backend, for deletion.
+    pub(crate) fn queue_shards(data_id: &[u8], backend: BackendId, info: &ShardInfo<T::Hash>) {
+        let shards = 0..info.hashes.len() as u8;
+        let items = shards.map(|i| GcItem::stored(backend, data_id, Some((info.version, i))));
+        Self::queue_items(items.collect());
+    }
+
+    // queue items for deletion.
+    pub(crate) fn queue_items(items: Vec<GcItem<T::AccountId>>) {
+        let (head, mut tail) = Self::gc_bounds();
+        for item in items {
+            <GcQueue<T>>::insert(tail, item);
+            tail += 1;
+        }
+        GcBounds::put((head, tail));
+    }
+
+    // delete up to T::GcKeysPerBlock queued keys and account for their weight.
+    pub(crate) fn collect_garbage() {
+        let (mut head, tail) = Self::gc_bounds();
+        let end = tail.min(head.saturating_add(T::GcKeysPerBlock::get() as u64));
+        if head == end {
+            return;
+        }
+        let swept = (end - head) as u32;
+        while head < end {
+            if let Some(item) = <GcQueue<T>>::get(head) {
+                if Self::delete_orphaned(item).is_err() {
+                    break;
+                }
+                <GcQueue<T>>::remove(head);
+            }
+            head += 1;
+        }
+        GcBounds::put((head, tail));
+        <frame_system::Module<T>>::register_extra_weight_unchecked(
+            T::GcWeightPerKey::get().saturating_mul(swept),
+        );
+    }
+
+    // delete the key of item, unless an entry written since uses it again.
+    fn delete_orphaned(item: GcItem<T::AccountId>) -> Result<(), StorageError> {
+        match item {
+            GcItem::Value(data_id) => {
+                let reused = Self::kept_data(&data_id).map_or(false, |data| {
+                    data.backend == DEFAULT_BACKEND
+                        && !<Shards<T>>::exists(&data_id)
+                        && !SharedValues::exists(&data_id)
+                });
+                if !reused {
+                    T::Storage::delete(data_id)?;
+                }
+            }
+            GcItem::Shard(data_id, version, index) => {
+                let reused = Self::kept_data(&data_id)
+                    .map_or(false, |data| data.backend == DEFAULT_BACKEND)
+                    && Self::shards_of(&data_id).map_or(false, |info| {
+                        info.version == version && (index as usize) < info.hashes.len()
+                    });
+                if !reused {
+                    T::Storage::delete(erasure::shard_key(&data_id, version, index))?;
+                }
+            }
+            GcItem::Replica(data_id, provider) => {
+                let kept = Self::kept_data(&data_id)
+                    .map_or(false, |data| data.providers.contains(&provider));
+                if !kept {
+                    T::Replicas::delete(&provider, data_id);
+                }
+            }
+            GcItem::Version(data_id, number) => {
+                if Self::revision(&data_id, number).is_none() {
+                    T::Storage::delete(history::version_key(&data_id, number))?;
+                }
+            }
+            GcItem::Routed(backend, data_id, shard) => {
+                let reused = Self::kept_data(&data_id).filter(|data| data.backend == backend);
+                let reused = reused.is_some()
+                    && match (shard, Self::shards_of(&data_id)) {
+                        (None, info) => info.is_none() && !SharedValues::exists(&data_id),
+                        (Some((version, index)), info) => info.map_or(false, |info| {
+                            info.version == version && (index as usize) < info.hashes.len()
+                        }),
+                    };
+                if !reused {
+                    let key = shard.map_or(data_id.clone(), |(version, index)| {
+                        erasure::shard_key(&data_id, version, index)
+                    });
+                    Self::routed_delete(backend, key)?;
+                }
+            }
+            GcItem::Object(backend, key) => {
+                // written again since.
+                if !ObjectRefs::exists(backend, &key) {
+                    Self::routed_delete(backend, key)?;
+                }
+            }
+            GcItem::Staged(data_id) => {
+                if !<Staged<T>>::exists(&data_id) {
+                    T::Storage::delete(staging::staged_key(&data_id))?;
+                }
+            }
+            GcItem::Chunk(who, data_id, index) => {
+                // an upload begun again since may have stored the chunk anew.
+                let reused = Self::chunked_upload(&who, &data_id).map_or(false, |upload| {
+                    upload.chunks.get(index as usize).map_or(false, |hash| hash.is_some())
+                });
+                if !reused {
+                    T::Storage::delete(chunked::chunk_key(&who, &data_id, index))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! Revision history of entries.
+//!
+//! Every write of an entry is recorded as a numbered revision: who wrote it, the hash of
+//! the value and the block. Writers may attach a short message with `write_data_with_message`
+//! saying why they changed the entry, which is kept in the revision and emitted in
+//! `DataUpdated`, so audits see the intent of a change and not just its hash.
+//!
+//! Each value written is also kept in `T::Storage` under the backend key of its revision, so
+//! an overwrite doesn't lose the old value: `read_version` reads it back and `rollback`
+//! writes it again as a new revision. Only the last `T::MaxRevisions` revisions and their
+//! values are kept, and they go when the entry is deleted.
+
+use super::{gc::GcItem, Error, ExternalStorage, Module, RawEvent, RevisionCount, Revisions, Trait};
+use codec::{Decode, Encode};
+use frame_support::{ensure, traits::Get, StorageDoubleMap, StorageMap};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// A write of an entry.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Revision<AccountId, Hash, BlockNumber> {
+    /// The account that wrote the value, the author or a delegate.
+    pub writer: AccountId,
+    /// Hash of the value written.
+    pub content_hash: Hash,
+    /// The backend key the value is kept under.
+    pub key: Vec<u8>,
+    /// Why the writer made the change, empty if they didn't say.
+    pub message: Vec<u8>,
+    /// The block the value was written in.
+    pub at: BlockNumber,
+}
+
+/// The backend key the value of revision `number` of `data_id` is kept under.
+pub fn version_key(data_id: &[u8], number: u32) -> Vec<u8> {
+    (&b"offchain-storage/version"[..], data_id, number).encode()
+}
+
+impl<T: Trait> Module<T> {
+    /// The value of revision `number` of `data_id`, if it is still kept and the backend
+    /// returns it intact.
+    pub fn version_value(data_id: &[u8], number: u32) -> Option<Vec<u8>> {
+        Self::get_version(data_id, number).ok()
+    }
+
+    // the value of revision number of data_id, which must still be kept and returned intact.
+    pub(crate) fn get_version(data_id: &[u8], number: u32) -> Result<Vec<u8>, Error<T>> {
+        let revision = Self::revision(data_id, number).ok_or(Error::<T>::NoSuchVersion)?;
+        Self::get_intact(data_id, revision.key, &revision.content_hash)
+    }
+
+    /// The content hash of revision `number` of `data_id`, if it is still kept. Backs the
+    /// `version_hash` runtime API.
+    pub fn version_hash(data_id: &[u8], number: u32) -> Option<T::Hash> {
+        Self::revision(data_id, number).map(|revision| revision.content_hash)
+    }
+
+    // check that message fits in a revision.
+    pub(crate) fn ensure_message(message: &[u8]) -> Result<(), Error<T>> {
+        ensure!(message.len() as u32 <= T::MaxMessageLen::get(), Error::<T>::MessageTooLong);
+        Ok(())
+    }
+
+    // keep value in the backend as the next revision of data_id.
+    pub(crate) fn store_version(data_id: &[u8], value: Vec<u8>) -> Result<(), Error<T>> {
+        let key = version_key(data_id, Self::revision_count(data_id) + 1);
+        Self::backend(data_id, T::Storage::set(key, value))
+    }
+
+    // leave the value store_version kept for a write that then failed to the garbage
+    // collector. A later write keeps its own value under the same key, which stays.
+    pub(crate) fn drop_version(data_id: &[u8]) {
+        let number = Self::revision_count(data_id) + 1;
+        Self::queue_items(sp_std::vec![GcItem::Version(data_id.to_vec(), number)]);
+    }
+
+    // record the write stored by store_version as the next revision of data_id, leaving
+    // the oldest one kept to the garbage collector.
+    pub(crate) fn note_revision(
+        data_id: &[u8],
+        writer: T::AccountId,
+        content_hash: T::Hash,
+        message: Vec<u8>,
+    ) {
+        let number = Self::revision_count(data_id) + 1;
+        RevisionCount::insert(data_id, number);
+        if let Some(dropped) = number.checked_sub(T::MaxRevisions::get()) {
+            if <Revisions<T>>::take(data_id, dropped).is_some() {
+                Self::queue_items(sp_std::vec![GcItem::Version(data_id.to_vec(), dropped)]);
+            }
+        }
+        let key = version_key(data_id, number);
+        let at = <frame_system::Module<T>>::block_number();
+        let revision = Revision { writer, content_hash, key, message: message.clone(), at };
+        <Revisions<T>>::insert(data_id, number, revision);
+        Self::deposit_event(RawEvent::DataUpdated(data_id.to_vec(), number, message));
+    }
+
+    // drop the history of data_id, leaving the values to the garbage collector.
+    pub(crate) fn forget_revisions(data_id: &[u8]) {
+        let last = RevisionCount::take(data_id);
+        let first = last.saturating_sub(T::MaxRevisions::get());
+        let items = (first..=last)
+            .filter(|number| <Revisions<T>>::exists(data_id, number))
+            .map(|number| GcItem::Version(data_id.to_vec(), number))
+            .collect();
+        <Revisions<T>>::remove_prefix(data_id);
+        Self::queue_items(items);
+    }
+}
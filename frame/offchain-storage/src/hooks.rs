@@ -0,0 +1,48 @@
+//! Hooks for other modules.
+//!
+//! `T::OnDataWritten` and `T::OnDataDeleted` are told about every entry written or deleted,
+//! once the change is made, with the author of the entry and the size of its value. A
+//! billing, indexing or reputation module can follow the activity of the storage through
+//! them instead of through the events or its storage items.
+//!
+//! Writes of any kind count, appends, copies and committed chunks included, as does an
+//! entry restored from the trash. An entry is deleted when it leaves the `Data` map: moved
+//! to the trash, removed, evicted or expired. Purging it from the trash later isn't
+//! reported again. A runtime with several listeners sets a tuple of them.
+
+/// Told about entries as they are written.
+pub trait OnDataWritten<AccountId> {
+    /// `data_id`, authored by `author`, now holds a value of `size` bytes.
+    fn on_data_written(author: &AccountId, data_id: &[u8], size: u32);
+}
+
+/// Told about entries as they are deleted.
+pub trait OnDataDeleted<AccountId> {
+    /// `data_id`, authored by `author` and holding a value of `size` bytes, is gone.
+    fn on_data_deleted(author: &AccountId, data_id: &[u8], size: u32);
+}
+
+macro_rules! impl_hooks {
+    ($($hook:ident),*) => {
+        impl<AccountId, $($hook: OnDataWritten<AccountId>),*> OnDataWritten<AccountId>
+            for ($($hook,)*)
+        {
+            fn on_data_written(_author: &AccountId, _data_id: &[u8], _size: u32) {
+                $($hook::on_data_written(_author, _data_id, _size);)*
+            }
+        }
+
+        impl<AccountId, $($hook: OnDataDeleted<AccountId>),*> OnDataDeleted<AccountId>
+            for ($($hook,)*)
+        {
+            fn on_data_deleted(_author: &AccountId, _data_id: &[u8], _size: u32) {
+                $($hook::on_data_deleted(_author, _data_id, _size);)*
+            }
+        }
+    };
+}
+
+impl_hooks!();
+impl_hooks!(A);
+impl_hooks!(A, B);
+impl_hooks!(A, B, C);
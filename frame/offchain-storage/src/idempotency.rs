@@ -0,0 +1,79 @@
+//! Idempotent writes.
+//!
+//! Clients on flaky connections can't tell whether a write they submitted made it, and
+//! submitting it again would write it twice. `write_data_once` carries an idempotency key
+//! chosen by the client: for `T::IdempotencyWindow` blocks the key stands for the first
+//! write made with it, and submitting the same write again writes nothing and reports the
+//! revision of the first in `WriteRepeated`. Gateways relaying writes pass the key along.
+
+use super::{Error, IdempotencyQueue, IdempotentWrites, Module, RawEvent, Trait};
+use codec::{Decode, Encode};
+use frame_support::{ensure, traits::Get, StorageDoubleMap};
+use sp_runtime::{
+    traits::{Hash, Saturating, Zero},
+    RuntimeDebug,
+};
+use sp_std::vec::Vec;
+
+/// A key a client chooses to name a write, e.g. a random UUID.
+pub type IdempotencyKey = [u8; 16];
+
+/// The first write made with an idempotency key.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct IdempotentWrite<Hash> {
+    pub data_id: Vec<u8>,
+    /// Hash of the value written.
+    pub content_hash: Hash,
+    /// The revision the write made.
+    pub revision: u32,
+}
+
+impl<T: Trait> Module<T> {
+    // whether who already wrote value to data_id with key, which must not stand for another
+    // write. Emits WriteRepeated if so.
+    pub(crate) fn repeated_write(
+        who: &T::AccountId,
+        key: &IdempotencyKey,
+        data_id: &[u8],
+        value: &[u8],
+    ) -> Result<bool, Error<T>> {
+        match Self::idempotent_write(who, key) {
+            Some(first) => {
+                ensure!(
+                    first.data_id[..] == *data_id && first.content_hash == T::Hashing::hash(value),
+                    Error::<T>::IdempotencyKeyReused
+                );
+                Self::deposit_event(RawEvent::WriteRepeated(first.data_id, first.revision));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // remember that who wrote value to data_id with key, for the idempotency window.
+    pub(crate) fn note_idempotent_write(
+        who: T::AccountId,
+        key: IdempotencyKey,
+        data_id: Vec<u8>,
+        value: &[u8],
+    ) {
+        let window = T::IdempotencyWindow::get();
+        if window.is_zero() {
+            return;
+        }
+        let expires_at = <frame_system::Module<T>>::block_number().saturating_add(window);
+        let revision = Self::revision_count(&data_id);
+        let content_hash = T::Hashing::hash(value);
+        let first = IdempotentWrite { data_id, content_hash, revision };
+        <IdempotentWrites<T>>::insert(&who, key, first);
+        <IdempotencyQueue<T>>::insert(expires_at, (who.clone(), key), (who, key));
+    }
+
+    // forget the idempotency keys whose window ends at block n.
+    pub(crate) fn forget_idempotency_keys(n: T::BlockNumber) {
+        for (who, key) in <IdempotencyQueue<T>>::iter_prefix(n) {
+            <IdempotentWrites<T>>::remove(&who, key);
+        }
+        <IdempotencyQueue<T>>::remove_prefix(n);
+    }
+}
@@ -0,0 +1,90 @@
+//! Group keyrings for sharing encrypted values.
+//!
+//! Values are encrypted off-chain with a per-entry key. Instead of handing that key to
+//! every reader, the author attaches a keyring to the entry and publishes the entry key
+//! wrapped (encrypted) to each member's public key. Adding or removing a member bumps
+//! the keyring's epoch, which makes all wrapped keys of the previous epoch stale. The
+//! author re-wraps lazily, e.g. the next time the value is re-encrypted, and until then
+//! members get no key, so removed members can't read values written after removal.
+//!
+//! Single grantees get the key without a keyring: every account registers the public key
+//! it takes wrapped keys for in `Keys`, and the author of an entry publishes the entry key
+//! wrapped to the registered key of each account it grants access to. A wrapped key is
+//! kept with the public key it was wrapped to, and stops being handed out once the grantee
+//! registers another key or can't read the entry anymore.
+
+use super::{
+    Data, DataKeyrings, Error, Keyrings, Keys, Module, RawEvent, SharedKeys, Trait,
+    WrappedKeys,
+};
+use codec::{Decode, Encode};
+use frame_support::{dispatch::DispatchResult, ensure, StorageDoubleMap, StorageMap};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// The public key a member receives wrapped keys for, e.g. an x25519 key.
+pub type PublicKey = [u8; 32];
+
+/// A group of accounts sharing encrypted values.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Keyring<AccountId> {
+    /// The account managing the members.
+    pub owner: AccountId,
+    /// Bumped on every change of the members.
+    pub epoch: u32,
+    /// The members and their public keys.
+    pub members: Vec<(AccountId, PublicKey)>,
+}
+
+impl<AccountId: PartialEq> Keyring<AccountId> {
+    /// The public key of `who`, if it is a member.
+    pub fn public_key(&self, who: &AccountId) -> Option<&PublicKey> {
+        self.members.iter().find(|(m, _)| m == who).map(|(_, key)| key)
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// The key of `data_id` wrapped for `member`, if it was wrapped in the current epoch
+    /// of the entry's keyring and `member` is still in it.
+    pub fn wrapped_key_for(data_id: &[u8], member: &T::AccountId) -> Option<Vec<u8>> {
+        let keyring = <Keyrings<T>>::get(DataKeyrings::get(data_id)?)?;
+        keyring.public_key(member)?;
+        <WrappedKeys<T>>::get(data_id, member)
+            .filter(|(epoch, _)| *epoch == keyring.epoch)
+            .map(|(_, key)| key)
+    }
+
+    /// The key of `data_id` wrapped for `grantee`, if it was wrapped to the key `grantee`
+    /// has registered and `grantee` may still read the entry.
+    pub fn shared_key_for(data_id: &[u8], grantee: &T::AccountId) -> Option<Vec<u8>> {
+        let (public_key, wrapped) = <SharedKeys<T>>::get(data_id, grantee)?;
+        let readable = Self::may_read(grantee, data_id, Self::get_data(data_id));
+        if readable && Self::encryption_key(grantee) == Some(public_key) {
+            Some(wrapped)
+        } else {
+            None
+        }
+    }
+
+    // publish the key of data_id wrapped to public_key for grantee, on behalf of user.
+    pub(crate) fn share_wrapped_key(
+        user: T::AccountId,
+        data_id: Vec<u8>,
+        grantee: T::AccountId,
+        public_key: PublicKey,
+        wrapped: Vec<u8>,
+    ) -> DispatchResult {
+        ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+        let data = Self::get_data(&data_id);
+        ensure!(data.author == user, Error::<T>::PermissionDenied);
+        ensure!(Self::may_read(&grantee, &data_id, data), Error::<T>::PermissionDenied);
+        let registered = Self::encryption_key(&grantee).ok_or(Error::<T>::NoEncryptionKey)?;
+        // the grantee may have registered another key since the author wrapped it.
+        ensure!(registered == public_key, Error::<T>::StaleEncryptionKey);
+        <SharedKeys<T>>::insert(&data_id, &grantee, (public_key, wrapped));
+        Self::note_grant(&user, &data_id);
+        Self::note_grantee(&data_id, &grantee);
+        Self::deposit_event(RawEvent::KeyShared(data_id, grantee));
+        Ok(())
+    }
+}
@@ -1,46 +1,448 @@
 use codec::{Decode, Encode};
-use frame_support::{decl_error, decl_event, decl_module, decl_storage, dispatch::DispatchResult};
-use frame_system::{self as system, ensure_signed};
+use frame_support::{
+    decl_error, decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure,
+    traits::{
+        Contains, Currency, ExistenceRequirement, Get, OnUnbalanced, Randomness,
+        ReservableCurrency, WithdrawReason,
+    },
+    storage::StoragePrefixedMap,
+    weights::Weight,
+    Parameter,
+};
+use frame_system::{self as system, ensure_none, ensure_signed};
+use sp_runtime::{
+    traits::{EnsureOrigin, Hash, Header as HeaderT, IdentifyAccount, Saturating, Verify, Zero},
+    MultiSignature, MultiSigner, Perbill, RuntimeDebug,
+};
 use sp_std::{default::Default, vec::Vec};
 
+pub use offchain_storage_runtime_api::{
+    Access, DataId, EncryptionInfo, ErrorCode, FeeEstimate, Page, RequestStatus, Statistics,
+    StorageError, ValidationError, ValueLocation, WriteKind, MAX_ENCRYPTION_FIELD_LENGTH,
+};
+pub use offchain_storage_runtime_api::compression::Compression;
+pub use offchain_storage_runtime_api::uri::{self, Reference};
+
+pub mod acl;
+pub mod adapter;
+pub mod append;
+pub mod attestation;
+pub mod audit;
+pub mod bridge;
+pub mod bundle;
+pub mod challenge;
+pub mod chunked;
+pub mod compression;
+pub mod confirmation;
+pub mod dead_letter;
+pub mod dedup;
+pub mod delivery;
+pub mod erasure;
+pub mod expiry;
+pub mod gc;
+pub mod history;
+pub mod hooks;
+pub mod idempotency;
+pub mod keyring;
+pub mod listing;
+pub mod market;
+pub mod merkle;
+pub mod metrics;
+pub mod migration;
+pub mod namespace;
+pub mod pin;
+#[cfg(feature = "std")]
+pub mod replay;
+pub mod registry;
+pub mod rename;
+pub mod rent;
+pub mod replication;
+pub mod reputation;
+pub mod routing;
+pub mod snapshot;
+pub mod staging;
+pub mod staking;
+pub mod status;
+pub mod transaction;
+pub mod trash;
+pub mod ttl;
+pub mod typed;
+pub mod upload;
+pub mod weights;
+#[cfg(feature = "xcm")]
+pub mod xcm;
+
+use acl::OnAccessGranted;
+use hooks::{OnDataDeleted, OnDataWritten};
+use attestation::AttestationVerifier;
+use audit::{AuditOp, AuditRecord};
+use bundle::WriteBundle;
+use replication::ReplicaStorage;
+// imported by name so the metadata carries plain type names, see types.json.
+use challenge::{Challenge, StorageProof};
+use chunked::ChunkedUpload;
+use compression::Compressor;
+use confirmation::ProviderConfirmation;
+use dead_letter::DeadLetter;
+use erasure::ShardInfo;
+use expiry::Expiry;
+use gc::GcItem;
+use upload::{PendingUpload, SubmitConfirmation};
+use history::Revision;
+use idempotency::{IdempotencyKey, IdempotentWrite};
+use keyring::{Keyring, PublicKey};
+use market::{Agreement, StorageOffer, StorageRequest};
+use namespace::Namespace;
+use registry::ProviderInfo;
+use rename::PendingCopy;
+use rent::RentInfo;
+use reputation::ProviderMetrics;
+use routing::{BackendId, StorageBackends, DEFAULT_BACKEND};
+use staging::StagedWrite;
+use staking::ProviderStake;
+use transaction::Op;
+use trash::TrashedEntry;
+use weights::{
+    ByBundleLen, ByKeyLen, ByOpsLen, ByProofLen, ByReviewers, ByValueLen, ByWrappedKeys,
+    UpToValueLen, WeightInfo,
+};
+
+type BalanceOf<T> =
+    <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> =
+    <<T as Trait>::Currency as Currency<<T as frame_system::Trait>::AccountId>>::NegativeImbalance;
+
 // ExternalStorage is for developers to implement specific storage
 // such as ipfs, mysql, mongodb, neo4j and so on.
+// A backend that fails returns why instead of panicking, calls then fail with
+// ExternalError and the reason is emitted in ExternalStorageFailed.
 pub trait ExternalStorage {
-    fn get(key: Vec<u8>) -> Vec<u8>;
-    fn set(key: Vec<u8>, value: Vec<u8>);
-    fn delete(key: Vec<u8>);
+    fn get(key: Vec<u8>) -> Result<Vec<u8>, StorageError>;
+    fn set(key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError>;
+    fn delete(key: Vec<u8>) -> Result<(), StorageError>;
+
+    // the len bytes of the value under key from offset on, fewer if the value ends before.
+    // backends able to serve a slice without fetching the whole value should override it.
+    fn get_range(key: Vec<u8>, offset: u32, len: u32) -> Result<Vec<u8>, StorageError> {
+        let value = Self::get(key)?;
+        let start = (offset as usize).min(value.len());
+        let end = start.saturating_add(len as usize).min(value.len());
+        Ok(value[start..end].to_vec())
+    }
+
+    // append bytes to the value under key, or store them if there is none. backends able to
+    // append in place should override it.
+    fn append(key: Vec<u8>, bytes: Vec<u8>) -> Result<(), StorageError> {
+        let mut value = Self::get(key.clone())?;
+        value.extend(bytes);
+        Self::set(key, value)
+    }
+
+    // keep the value under key until it is unpinned. backends dropping values nobody pins,
+    // e.g. IPFS, should override it, the others keep every value anyway.
+    fn pin(_key: Vec<u8>) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    // let the backend drop the value under key again.
+    fn unpin(_key: Vec<u8>) -> Result<(), StorageError> {
+        Ok(())
+    }
 }
 
 pub trait Trait: frame_system::Trait {
     /// The overarching event type.
-    type Event: From<Event> + Into<<Self as frame_system::Trait>::Event>;
+    type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
     /// External storage service.
     type Storage: ExternalStorage;
+    /// The origin of maintenance calls, e.g. root or council: forcibly deleting data or
+    /// changing its access, removing providers and protecting namespaces.
+    type AdminOrigin: EnsureOrigin<Self::Origin>;
+    /// Headers of bridged chains that data may be mirrored from.
+    type RemoteHeaders: bridge::RemoteHeaders<Self::Header>;
+    /// The local account acting for an account of a bridged chain.
+    type SovereignAccount: bridge::SovereignAccount<Self::AccountId>;
+    /// Values longer than this are committed to chunk by chunk in a merkle tree. Must not be 0.
+    type ChunkSize: Get<u32>;
+    /// The accounts storing data, which answer proof-of-storage challenges. More can
+    /// join through the registry, see the registry module.
+    type Providers: Contains<Self::AccountId>;
+    /// Source of the chunk picked by a challenge.
+    type Randomness: Randomness<Self::Hash>;
+    /// How many blocks providers have to answer a challenge.
+    type ChallengePeriod: Get<Self::BlockNumber>;
+    /// The backends of the providers keeping replicas.
+    type Replicas: replication::ReplicaStorage<Self::AccountId>;
+    /// If set to `(k, n)`, values are stored in `T::Storage` as `n` erasure coded shards,
+    /// any `k` of which rebuild them. Needs `1 <= k <= n`.
+    type ErasureCoding: Get<Option<(u8, u8)>>;
+    /// The currency deposits are reserved in.
+    type Currency: ReservableCurrency<Self::AccountId>;
+    /// The deposit the author reserves for every entry.
+    type DepositBase: Get<BalanceOf<Self>>;
+    /// The deposit the author reserves for every byte of a value.
+    type DepositPerByte: Get<BalanceOf<Self>>;
+    /// The rent for storing one byte for one block.
+    type RentPerByte: Get<BalanceOf<Self>>;
+    /// Where the rent goes.
+    type RentPayment: OnUnbalanced<NegativeImbalanceOf<Self>>;
+    /// How long expired data is kept before anyone may remove it.
+    type RentGracePeriod: Get<Self::BlockNumber>;
+    /// Index of the current session, session grants lapse when it changes.
+    type CurrentSession: Get<u32>;
+    /// The share of rent and read payments taken as protocol fee.
+    type ProtocolFee: Get<Perbill>;
+    /// Where the protocol fee goes, e.g. the treasury.
+    type ProtocolFeeDestination: OnUnbalanced<NegativeImbalanceOf<Self>>;
+    /// Accounts operating the chain, e.g. the current validators or a collective. Entries
+    /// can grant them access as a group, checked whenever they access the entry.
+    type Operators: Contains<Self::AccountId>;
+    /// How long unbonded provider stake stays slashable before it can be withdrawn.
+    type UnbondingDelay: Get<Self::BlockNumber>;
+    /// The share of a provider's stake slashed for every failed challenge.
+    type SlashFraction: Get<Perbill>;
+    /// Where slashed stake goes.
+    type Slash: OnUnbalanced<NegativeImbalanceOf<Self>>;
+    /// Checks the attestations needed to write to protected namespaces.
+    type Attestation: attestation::AttestationVerifier<Self::AccountId>;
+    /// The signature offline write bundles are signed with, see the bundle module.
+    type BundleSignature: Parameter + Verify<Signer = Self::BundleSigner>;
+    /// The key behind a bundle signature, identifying the account that signed.
+    type BundleSigner: IdentifyAccount<AccountId = Self::AccountId>;
+    /// How many backend keys the garbage collector deletes per block at most.
+    type GcKeysPerBlock: Get<u32>;
+    /// The weight of deleting one backend key.
+    type GcWeightPerKey: Get<Weight>;
+    /// How many keys of each expiry queue `on_initialize` handles per block at most, and
+    /// how many blocks of it it moves past. At least one, see the expiry module.
+    type ExpiriesPerBlock: Get<u32>;
+    /// The weight of handling one key of an expiry queue, e.g. removing an entry whose time
+    /// to live ended.
+    type ExpiryWeight: Get<Weight>;
+    /// How many blocks deleted entries stay in the trash, where their author can restore
+    /// them. Zero deletes them at once.
+    type TrashPeriod: Get<Self::BlockNumber>;
+    /// The longest message a write may carry.
+    type MaxMessageLen: Get<u32>;
+    /// How many revisions of an entry are kept.
+    type MaxRevisions: Get<u32>;
+    /// Every how many blocks the offchain worker stores a snapshot of the metadata. Zero
+    /// never does.
+    type SnapshotPeriod: Get<Self::BlockNumber>;
+    /// The weights of the calls, see [`weights`].
+    type WeightInfo: WeightInfo;
+    /// For how many blocks an idempotency key stands for the first write made with it.
+    /// Zero doesn't keep the keys.
+    type IdempotencyWindow: Get<Self::BlockNumber>;
+    /// For how many blocks the status of a finished deferred write is kept.
+    type StatusRetention: Get<Self::BlockNumber>;
+    /// Reserved with a storage request until it is matched or cancelled.
+    type RequestDeposit: Get<BalanceOf<Self>>;
+    /// How many times a storage request is matched before it is dead-lettered, if the
+    /// matches fall through for backend failures.
+    type MaxRequestAttempts: Get<u32>;
+    /// The longest data_id an entry may have.
+    type MaxDataIdLength: Get<u32>;
+    /// The longest value an entry may hold.
+    type MaxValueLength: Get<u32>;
+    /// How many entries an account may author, those in the trash included.
+    type MaxEntriesPerAccount: Get<u32>;
+    /// How many bytes the values of the entries of an account may take in all.
+    type MaxBytesPerAccount: Get<u64>;
+    /// How many writes an account may make in a block.
+    type MaxWritesPerBlock: Get<u32>;
+    /// Whether writes leave storing the value to the offchain worker, see the upload module.
+    type DeferredUploads: Get<bool>;
+    /// Submits the confirmations of values the offchain worker uploaded.
+    type Confirmations: SubmitConfirmation<Self>;
+    /// How many times the offchain worker tries an upload before reporting it failed.
+    type MaxUploadAttempts: Get<u32>;
+    /// Told about grants of access as they are made, see the acl module.
+    type OnAccessGranted: OnAccessGranted<Self::AccountId>;
+    /// Reserved with a namespace until it is removed.
+    type NamespaceDeposit: Get<BalanceOf<Self>>;
+    /// The storage backends besides `Storage`, see the routing module.
+    type Backends: StorageBackends;
+    /// Whether entries are created only under the keys of well-formed typed data_ids, see
+    /// `DataId`.
+    type TypedDataIds: Get<bool>;
+    /// Told about entries as they are written, see the hooks module.
+    type OnDataWritten: OnDataWritten<Self::AccountId>;
+    /// Told about entries as they are deleted, see the hooks module.
+    type OnDataDeleted: OnDataDeleted<Self::AccountId>;
+    /// Compresses and decompresses values, see the compression module.
+    type Compressor: Compressor;
+    /// How writes that don't say otherwise compress their value in the backend.
+    type DefaultCompression: Get<Compression>;
+    /// Whether entries with the same value share one object in their backend, see the dedup
+    /// module.
+    type Deduplication: Get<bool>;
+    /// Reserved with every pin until it is dropped, see the pin module.
+    type PinDeposit: Get<BalanceOf<Self>>;
+    /// How many records the audit trail of an entry keeps, see the audit module.
+    type MaxAuditRecords: Get<u32>;
+    /// How many blocks a chunked upload may stay open before it is dropped, see the chunked
+    /// module.
+    type ChunkedUploadPeriod: Get<Self::BlockNumber>;
+    /// How many chunked uploads an account may have open at once.
+    type MaxChunkedUploads: Get<u32>;
+    /// The longest endpoint a provider may register.
+    type MaxEndpointLength: Get<u32>;
+    /// Reserved with a provider registration until it is removed, see the registry module.
+    type ProviderDeposit: Get<BalanceOf<Self>>;
+    /// How many reviewers an entry may require to approve its staged values.
+    type MaxReviewers: Get<u32>;
 }
 
-#[derive(Encode, Decode, Clone, Default, PartialEq)]
-pub struct UserData<AccountId> {
+/// An entry of the `Data` map. Other modules and genesis builders build one with `new` and
+/// the `with_` methods and read it through the accessors.
+#[derive(Encode, Decode, Clone, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct UserData<AccountId, Hash, BlockNumber> {
     // the author means this data was created by this person.
     // author has the Write access.
     author: AccountId,
 
     access: Access,
+
+    // hash of the value last written to external storage.
+    // the external storage is untrusted, so every read is checked against it.
+    content_hash: Hash,
+
+    // how many providers the author wants to keep a replica.
+    replication: u32,
+
+    // the providers keeping a replica, at most replication of them.
+    providers: Vec<AccountId>,
+
+    // length of the value last written.
+    size: u32,
+
+    // the block the entry was created in.
+    created_at: BlockNumber,
+
+    // the block the value was last written in.
+    updated_at: BlockNumber,
+
+    // how the value last written is encrypted, if its writer said so.
+    encryption: Option<EncryptionInfo>,
+
+    // the backend the value is kept in, see the routing module.
+    backend: BackendId,
+
+    // how the value is compressed in the backend, see the compression module.
+    compression: Compression,
 }
 
-/// Access is that the access of UserData.
-#[derive(Encode, Decode, Clone, PartialEq)]
-pub enum Access {
-    // Avoid means that no one can read or write this data except author.
-    Avoid,
-    // Read means that this data just can be read.
-    Read,
-    // Write means that every one can read and write this data.
-    Write,
+impl<AccountId, Hash: Default, BlockNumber: Default> UserData<AccountId, Hash, BlockNumber> {
+    /// An entry of `author` with the default access and nothing written yet.
+    pub fn new(author: AccountId) -> Self {
+        UserData {
+            author,
+            access: Default::default(),
+            content_hash: Default::default(),
+            replication: 0,
+            providers: Vec::new(),
+            size: 0,
+            created_at: Default::default(),
+            updated_at: Default::default(),
+            encryption: None,
+            backend: DEFAULT_BACKEND,
+            compression: Compression::None,
+        }
+    }
 }
 
-impl Default for Access {
-    fn default() -> Self {
-        Access::Read
+impl<AccountId, Hash, BlockNumber> UserData<AccountId, Hash, BlockNumber> {
+    pub fn with_access(mut self, access: Access) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// The entry with a value of `size` bytes hashing to `content_hash`.
+    pub fn with_content(mut self, content_hash: Hash, size: u32) -> Self {
+        self.content_hash = content_hash;
+        self.size = size;
+        self
+    }
+
+    pub fn with_replication(mut self, replication: u32) -> Self {
+        self.replication = replication;
+        self
+    }
+
+    pub fn with_providers(mut self, providers: Vec<AccountId>) -> Self {
+        self.providers = providers;
+        self
+    }
+
+    /// The entry created in block `created_at` and last written in `updated_at`.
+    pub fn with_blocks(mut self, created_at: BlockNumber, updated_at: BlockNumber) -> Self {
+        self.created_at = created_at;
+        self.updated_at = updated_at;
+        self
+    }
+
+    pub fn with_encryption(mut self, encryption: Option<EncryptionInfo>) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    pub fn with_backend(mut self, backend: BackendId) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn author(&self) -> &AccountId {
+        &self.author
+    }
+
+    pub fn access(&self) -> &Access {
+        &self.access
+    }
+
+    /// The hash of the value last written.
+    pub fn content_hash(&self) -> &Hash {
+        &self.content_hash
+    }
+
+    /// How many providers the author wants to keep a replica.
+    pub fn replication(&self) -> u32 {
+        self.replication
+    }
+
+    /// The providers keeping a replica.
+    pub fn providers(&self) -> &[AccountId] {
+        &self.providers
+    }
+
+    /// The length of the value last written.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn created_at(&self) -> &BlockNumber {
+        &self.created_at
+    }
+
+    pub fn updated_at(&self) -> &BlockNumber {
+        &self.updated_at
+    }
+
+    pub fn encryption(&self) -> Option<&EncryptionInfo> {
+        self.encryption.as_ref()
+    }
+
+    pub fn backend(&self) -> BackendId {
+        self.backend
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
     }
 }
 
@@ -53,10 +455,159 @@ fn access_value(ac: Access) -> u8 {
     }
 }
 
+// events are encoded by their position, so new ones go at the end.
 decl_event! {
-    pub enum Event
+    pub enum Event<T> where
+        AccountId = <T as frame_system::Trait>::AccountId,
+        BlockNumber = <T as frame_system::Trait>::BlockNumber,
+        Hash = <T as frame_system::Trait>::Hash,
     {
-        GetData(Vec<u8>),
+        // a value was read, the RPC delivers it. [content hash]
+        GetData(Hash),
+        // data was removed by the force origin. [data_id]
+        DataForceDeleted(Vec<u8>),
+        // data access was changed by the force origin. [data_id, access]
+        AccessForceSet(Vec<u8>, Access),
+        // a value was stored under its content hash. [data_id]
+        ContentWritten(Vec<u8>),
+        // data of another chain was mirrored. [chain_id, data_id]
+        DataMirrored(Vec<u8>, Vec<u8>),
+        // a request of another chain was applied. [chain_id, data_id]
+        RemoteRequestApplied(Vec<u8>, Vec<u8>),
+        // data was challenged to prove it is stored. [data_id, nonce, chunk_index, deadline]
+        ChallengeIssued(Vec<u8>, u64, u32, BlockNumber),
+        // a provider answered a challenge. [data_id, nonce, provider]
+        StorageProved(Vec<u8>, u64, AccountId),
+        // a provider answered a challenge wrongly. [data_id, nonce]
+        StorageProofFailed(Vec<u8>, u64),
+        // nobody answered a challenge in time. [data_id, nonce]
+        ChallengeExpired(Vec<u8>, u64),
+        // the author changed how many replicas to keep. [data_id, replication]
+        ReplicationSet(Vec<u8>, u32),
+        // a provider took a replica. [data_id, provider]
+        ReplicaAssigned(Vec<u8>, AccountId),
+        // a keyring was created. [keyring]
+        KeyringCreated(u64),
+        // the members of a keyring changed, keys wrapped before are stale. [keyring, epoch]
+        KeyringEpochBumped(u64, u32),
+        // the key of data was wrapped for the members of its keyring. [data_id, epoch]
+        KeysWrapped(Vec<u8>, u32),
+        // all grants on the entries of an author were revoked. [entries]
+        EmergencyRevocation(u32),
+        // the author couldn't pay the rent of data, which is frozen now. [data_id]
+        DataExpired(Vec<u8>),
+        // expired data was removed after its grace period. [data_id]
+        ExpiredDataRemoved(Vec<u8>),
+        // the author set or removed the read price of data. [data_id]
+        ReadPriceSet(Vec<u8>),
+        // read access to data was bought from its author. [data_id, buyer]
+        ReadAccessPurchased(Vec<u8>, AccountId),
+        // access to data was granted until the session ends. [data_id, grantee, session]
+        SessionAccessGranted(Vec<u8>, AccountId, u32),
+        // the access of operators to data changed. [data_id, access]
+        OperatorAccessSet(Vec<u8>, Access),
+        // a provider's stake was slashed for failing the challenge of data. [data_id]
+        ProviderSlashed(Vec<u8>),
+        // the author changed how many readers must co-sign a read of data. [data_id, quorum]
+        ReadQuorumSet(Vec<u8>, u32),
+        // a read of data still lacks co-signers. [data_id, signatures so far]
+        ReadCoSigned(Vec<u8>, u32),
+        // a namespace was protected, or no longer is. [prefix, protected]
+        NamespaceProtected(Vec<u8>, bool),
+        // a provider registered, or changed what it offers. [endpoint]
+        ProviderRegistered(Vec<u8>),
+        // a provider left the registry. [endpoint]
+        ProviderDeregistered(Vec<u8>),
+        // an offline bundle was submitted. [nonce, writes]
+        BundleApplied(u64, u32),
+        // a bundle submitted with others couldn't be applied and was skipped. [nonce]
+        BundleRejected(u64),
+        // an author asked for data to be stored. [request_id, data_id]
+        StorageRequested(u64, Vec<u8>),
+        // a storage request was matched with an offer. [request_id, data_id, provider, until]
+        StorageAgreed(u64, Vec<u8>, AccountId, BlockNumber),
+        // a storage request was withdrawn before it matched. [request_id]
+        StorageRequestCancelled(u64),
+        // the time to live of data ended and it was removed. [data_id]
+        TtlPassed(Vec<u8>),
+        // a value was put in the staging slot of data. [data_id]
+        DataStaged(Vec<u8>),
+        // the staged value of data was written live. [data_id]
+        StagedPromoted(Vec<u8>),
+        // the staged value of data was dropped. [data_id]
+        StagedDiscarded(Vec<u8>),
+        // the share of readers served the staged value of data changed. [data_id]
+        CanarySet(Vec<u8>),
+        // data was taken out of the trash. [data_id]
+        DataRestored(Vec<u8>),
+        // data in the trash was deleted for good. [data_id]
+        DataPurged(Vec<u8>),
+        // data was written. [data_id, revision, message]
+        DataUpdated(Vec<u8>, u32, Vec<u8>),
+        // a provider handed its replica of data to another. [data_id, from, to]
+        ReplicaMigrated(Vec<u8>, AccountId, AccountId),
+        // the author changed who reviews staged values of data. [data_id, reviewers]
+        ReviewersSet(Vec<u8>, u32),
+        // a reviewer signed the staged value of data off. [data_id, reviewer]
+        StagedApproved(Vec<u8>, AccountId),
+        // data was moved to a new data_id. [old_id, new_id]
+        DataRenamed(Vec<u8>, Vec<u8>),
+        // data was copied to a new entry of the caller. [src_id, dst_id]
+        DataCopied(Vec<u8>, Vec<u8>),
+        // a write was submitted again with its idempotency key and not repeated.
+        // [data_id, revision of the first write]
+        WriteRepeated(Vec<u8>, u32),
+        // data was written. [who, data_id, size]
+        DataWritten(AccountId, Vec<u8>, u32),
+        // data was deleted. [who, data_id]
+        DataDeleted(AccountId, Vec<u8>),
+        // data was read. [who, data_id]
+        DataRead(AccountId, Vec<u8>),
+        // the storage backend failed an operation on data. [data_id, reason]
+        ExternalStorageFailed(Vec<u8>, StorageError),
+        // a storage request was given up on. [request id, error code]
+        OperationDeadLettered(u64, ErrorCode),
+        // a dead-lettered storage request was queued again. [request id]
+        DeadLetterRetried(u64),
+        // a dead-lettered storage request was dropped. [request id]
+        DeadLetterPurged(u64),
+        // the value of data was confirmed stored in the backend. [data_id]
+        UploadConfirmed(Vec<u8>),
+        // a provider confirmed it keeps a replica. [data_id, provider]
+        ReplicaConfirmed(Vec<u8>, AccountId),
+        // a provider confirmed it served a read. [data_id, provider, reader]
+        ReadServed(Vec<u8>, AccountId, AccountId),
+        // the value of data could not be uploaded and was dropped. [data_id, reason]
+        WriteFailed(Vec<u8>, StorageError),
+        // the ops of a transaction were applied. [account, ops]
+        Transacted(AccountId, u32),
+        // a slice of a value was read, see read_data_range. [offset, len]
+        GetDataRange(u32, u32),
+        // an account registered the public key data keys are wrapped to. [account]
+        EncryptionKeyRegistered(AccountId),
+        // the key of data was wrapped for a grantee. [data_id, grantee]
+        KeyShared(Vec<u8>, AccountId),
+        // a namespace was reserved. [prefix, owner]
+        NamespaceCreated(Vec<u8>, AccountId),
+        // a namespace was released. [prefix]
+        NamespaceRemoved(Vec<u8>),
+        // an entry got a new author. [data_id, old author, new author]
+        OwnershipTransferred(Vec<u8>, AccountId, AccountId),
+        // an account pinned the value of an entry. [data_id, who]
+        DataPinned(Vec<u8>, AccountId),
+        // an account dropped its pin on the value of an entry. [data_id, who]
+        DataUnpinned(Vec<u8>, AccountId),
+        // the author turned the audit trail of data on or off. [data_id, on]
+        AuditSet(Vec<u8>, bool),
+        // the values of an entry renamed in a transaction were copied to it. [data_id]
+        ValuesCopied(Vec<u8>),
+        // a chunked upload was not committed in time and dropped. [uploader, data_id]
+        ChunkedUploadExpired(AccountId, Vec<u8>),
+        // a registered provider was approved. [provider]
+        ProviderApproved(AccountId),
+        // a deferred write was queued for the offchain worker, see the status module.
+        // [data_id, id of the write]
+        UploadQueued(Vec<u8>, u64),
     }
 }
 
@@ -69,9 +620,207 @@ decl_error! {
         // external storage has no data
         // Perhaps the data has never been uploaded
         NoneData,
+        // the value returned by external storage does not match the hash on chain
+        IntegrityError,
+        // the data was mirrored from another chain and can't be changed here
+        ReadOnlyMirror,
+        // data_id is already taken by local data
+        DataExists,
+        // the header is not trusted or the proof doesn't contain the data
+        InvalidRemoteProof,
+        // the signature is not valid for the value and signer
+        BadSignature,
+        // the caller is not a storage provider
+        NotProvider,
+        // the data is already challenged
+        AlreadyChallenged,
+        // there is no open challenge with this nonce
+        NoChallenge,
+        // the delegate has used up all the writes granted by the author
+        DelegationExhausted,
+        // the data already has as many replicas as the author wants
+        ReplicationFull,
+        // the provider already keeps a replica of the data
+        AlreadyReplica,
+        // there is no keyring with this id, or none attached to the data
+        NoKeyring,
+        // the keys were wrapped for an older epoch of the keyring
+        StaleEpoch,
+        // the account is not a member of the keyring
+        NotMember,
+        // the author can't reserve the deposit for the value
+        InsufficientBalance,
+        // the data expired for want of rent, it can only be read or deleted
+        Expired,
+        // the data is not expired, or its grace period isn't over
+        NotExpired,
+        // the author doesn't sell read access to the data
+        NotForSale,
+        // the account can already read the data
+        AlreadyReadable,
+        // the provider has less stake bonded than it tried to unbond
+        InsufficientBond,
+        // the provider has no unbonded stake, or its unbonding delay isn't over
+        NotUnbonded,
+        // the data doesn't require a read quorum
+        NoQuorum,
+        // there is no pending read of the data by this reader
+        NoPendingRead,
+        // the account already signed this read
+        AlreadySigned,
+        // the attestation is not accepted, or the writer has none for a protected namespace
+        Unattested,
+        // the account is not in the provider registry
+        NotRegistered,
+        // the bundle signature is invalid, or it writes a data_id twice
+        BadBundle,
+        // the bundle nonce is not higher than that of the author's last bundle
+        StaleBundle,
+        // the bundle can no longer be submitted
+        BundleExpired,
+        // there is no open storage request with this id
+        NoStorageRequest,
+        // the time to live must end after the current block
+        InvalidTtl,
+        // the data has no staged value
+        NothingStaged,
+        // the data is not in the trash
+        NotInTrash,
+        // the data was deleted and can still be restored
+        Trashed,
+        // the message is longer than MaxMessageLen
+        MessageTooLong,
+        // the data has no such revision, or no longer keeps it
+        NoSuchVersion,
+        // the caller keeps no replica of the data
+        NotReplica,
+        // the caller is not a reviewer of the data
+        NotReviewer,
+        // the staged value is not the one the reviewer names
+        StagedChanged,
+        // a reviewer has not approved the staged value yet
+        Unapproved,
+        // the idempotency key was used for another write
+        IdempotencyKeyReused,
+        // there is no dead-lettered storage request with this id
+        NoDeadLetter,
+        // the data_id is longer than MaxDataIdLength
+        KeyTooLong,
+        // the value is longer than MaxValueLength
+        ValueTooLong,
+        // the author already has MaxEntriesPerAccount entries
+        TooManyEntries,
+        // the write would take the author past MaxBytesPerAccount
+        ByteQuotaExceeded,
+        // the account made MaxWritesPerBlock writes in this block already
+        RateLimited,
+        // no upload of the value is pending
+        NoPendingUpload,
+        // the value is not in the backend until its upload is confirmed
+        UploadPending,
+        // the confirmation has expired or its nonce was used
+        StaleConfirmation,
+        // the confirmation isn't signed by its provider
+        BadConfirmation,
+        // more than one op of the transaction touches the same data_id
+        ConflictingOps,
+        // the account has no chunked upload to the data_id going
+        NoChunkedUpload,
+        // the chunk is out of the range of the upload or of the wrong length
+        BadChunk,
+        // not every chunk of the upload is in yet
+        IncompleteUpload,
+        // the range reaches past the end of the value
+        BadRange,
+        // a field of the encryption info is too long
+        BadEncryptionInfo,
+        // the account registered no encryption key
+        NoEncryptionKey,
+        // the key was wrapped to a public key the account no longer has
+        StaleEncryptionKey,
+        // the namespace exists already
+        NamespaceTaken,
+        // the data_id is in a namespace the account may not create entries in
+        NamespaceReserved,
+        // there is no such namespace
+        NoNamespace,
+        // the runtime has no backend with this id
+        NoBackend,
+        // the entry is kept in another backend
+        BackendMismatch,
+        // the data_id isn't the key of a well-formed DataId
+        BadDataId,
+        // the name is a number or names another backend
+        BadBackendName,
+        // the message doesn't decode to a request
+        BadRemoteMessage,
+        // the value doesn't decode as the type asked for
+        UndecodableValue,
+        // the compression asked for isn't supported by T::Compressor
+        UnsupportedCompression,
+        // the caller already pins the value
+        AlreadyPinned,
+        // the caller doesn't pin the value
+        NotPinned,
+        // the reader has no receipt for a read of the entry
+        NoReadReceipt,
+        // the values of a rename are still kept under the old data_id, see the rename module
+        CopyPending,
+        // the account has MaxChunkedUploads chunked uploads open already
+        TooManyUploads,
+        // the value was overwritten or deleted before it was uploaded
+        UploadSuperseded,
+        // the endpoint is longer than MaxEndpointLength
+        EndpointTooLong,
+        // more reviewers than MaxReviewers
+        TooManyReviewers,
+    }
+}
+
+// how write_entry gets a value into the backends.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Upload {
+    // store the value while the block executes.
+    Now,
+    // leave the value to the offchain worker, see the upload module.
+    Deferred,
+    // like Now, appending the last .0 bytes of the value to the one stored before if it can.
+    Append(u32),
+}
+
+impl<T: Trait> Error<T> {
+    /// The stable code of the error, see [`ErrorCode`].
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::module(self.as_u8())
+    }
+}
+
+impl<T: Trait> From<ValidationError> for Error<T> {
+    fn from(e: ValidationError) -> Self {
+        match e {
+            ValidationError::PermissionDenied => Error::<T>::PermissionDenied,
+            ValidationError::ReadOnlyMirror => Error::<T>::ReadOnlyMirror,
+            ValidationError::DelegationExhausted => Error::<T>::DelegationExhausted,
+            ValidationError::InsufficientBalance => Error::<T>::InsufficientBalance,
+            ValidationError::Expired => Error::<T>::Expired,
+            ValidationError::Unattested => Error::<T>::Unattested,
+            ValidationError::Trashed => Error::<T>::Trashed,
+            ValidationError::KeyTooLong => Error::<T>::KeyTooLong,
+            ValidationError::ValueTooLong => Error::<T>::ValueTooLong,
+            ValidationError::TooManyEntries => Error::<T>::TooManyEntries,
+            ValidationError::ByteQuotaExceeded => Error::<T>::ByteQuotaExceeded,
+            ValidationError::RateLimited => Error::<T>::RateLimited,
+            ValidationError::NamespaceReserved => Error::<T>::NamespaceReserved,
+            ValidationError::BadDataId => Error::<T>::BadDataId,
+            ValidationError::CopyPending => Error::<T>::CopyPending,
+        }
     }
 }
 
+// Calls on one data_id only touch keys of that data_id, its author and its providers, so
+// calls on unrelated entries can run in parallel. Values shared by all entries are only
+// changed by rarely used calls, never by reads, writes or challenges. The totals of entries
+// and bytes are the exception, every write and delete bumps them.
 decl_storage! {
     trait Store for Module<T: Trait> as OffchainStorage{
 
@@ -79,7 +828,279 @@ decl_storage! {
         // the data_id represants where data locate in external storage.
         // In KVDB, it would be a key. In IPFS, it would be a hash.
         // In some other RDBMS, it would be a more complex structure.
-        Data get(fn get_data): map Vec<u8> => UserData<T::AccountId>;
+        Data get(fn get_data): map Vec<u8> => UserData<T::AccountId, T::Hash, T::BlockNumber>;
+
+        /// double_map: data_id, delegate => remaining writes
+        // the author of data_id allows the delegate to write it this many more times.
+        WriteDelegations get(fn write_delegations):
+            double_map hasher(blake2_256) Vec<u8>, blake2_256(T::AccountId) => u32;
+
+        /// map: data_id => (chain_id, remote block hash)
+        // data_id is a read-only copy of data on another chain, as of that block.
+        Mirrors get(fn mirror_of): map Vec<u8> => Option<(Vec<u8>, T::Hash)>;
+
+        /// map: data_id => (merkle root, number of chunks)
+        // only kept for values longer than ChunkSize, see the merkle module.
+        ChunkRoots get(fn chunk_root): map Vec<u8> => Option<(T::Hash, u32)>;
+
+        /// map: data_id => (signer, signature of the value)
+        // lets anyone fetching the value straight from the backend check who produced it.
+        ContentSignatures get(fn content_signature):
+            map Vec<u8> => Option<(MultiSigner, MultiSignature)>;
+
+        /// map: data_id => open challenge
+        Challenges get(fn challenge_of):
+            map Vec<u8> => Option<Challenge<T::BlockNumber>>;
+        /// double_map: deadline, data_id => data_id, for the challenges ending then
+        ChallengeDeadlines:
+            double_map hasher(blake2_256) T::BlockNumber, blake2_256(Vec<u8>) => Vec<u8>;
+        /// map: data_id => nonce of its last challenge
+        ChallengeNonces: map Vec<u8> => u64;
+        /// map: provider => wrong answers to challenges
+        FailedProofs get(fn failed_proofs): map T::AccountId => u32;
+        /// map: data_id => challenges nobody answered
+        MissedChallenges get(fn missed_challenges): map Vec<u8> => u32;
+
+        /// map: data_id => how its value is split into shards
+        // only kept for values stored while T::ErasureCoding was set.
+        Shards get(fn shards_of): map Vec<u8> => Option<ShardInfo<T::Hash>>;
+
+        /// map: data_id => deposit the author reserved for it
+        Deposits get(fn deposit_of): map Vec<u8> => BalanceOf<T>;
+
+        /// map: author => data_ids the author granted read or write access to, or shared
+        /// with a keyring
+        GrantedEntries get(fn granted_entries): map T::AccountId => Vec<Vec<u8>>;
+        /// map: author => account that may revoke the author's grants in an emergency
+        Guardians get(fn guardian_of): map T::AccountId => Option<T::AccountId>;
+
+        /// map: data_id => rent account, see the rent module.
+        Rent get(fn rent_of): map Vec<u8> => Option<RentInfo<T::BlockNumber>>;
+
+        /// map: data_id => price of read access, paid to the author
+        ReadPrices get(fn read_price): map Vec<u8> => Option<BalanceOf<T>>;
+        /// double_map: data_id, reader => whether reader bought read access
+        ReadGrants get(fn has_read_grant):
+            double_map hasher(blake2_256) Vec<u8>, blake2_256(T::AccountId) => bool;
+        /// double_map: data_id, grantee => (session, access granted for that session)
+        SessionGrants: double_map hasher(blake2_256) Vec<u8>, blake2_256(T::AccountId)
+            => Option<(u32, Access)>;
+        /// map: data_id => access whoever is in T::Operators has
+        OperatorAccess get(fn operator_access): map Vec<u8> => Option<Access>;
+        /// map: data_id => accounts granted access to it, see the acl module
+        Grantees get(fn grantees_of): map Vec<u8> => Vec<T::AccountId>;
+        /// map: data_id => how many readers must sign a read before the value is released
+        ReadQuorums get(fn read_quorum): map Vec<u8> => Option<u32>;
+        /// double_map: data_id, reader => accounts that signed the reader's read so far
+        PendingReads get(fn pending_read):
+            double_map hasher(blake2_256) Vec<u8>, blake2_256(T::AccountId) => Vec<T::AccountId>;
+
+        /// map: keyring id => keyring, see the keyring module.
+        Keyrings get(fn keyring): map u64 => Option<Keyring<T::AccountId>>;
+        /// Id of the next keyring.
+        NextKeyringId: u64;
+        /// map: data_id => keyring its key is shared with
+        DataKeyrings get(fn keyring_of): map Vec<u8> => Option<u64>;
+        /// double_map: data_id, member => (epoch, key of data_id wrapped for member)
+        WrappedKeys: double_map hasher(blake2_256) Vec<u8>, blake2_256(T::AccountId)
+            => Option<(u32, Vec<u8>)>;
+
+        /// map: provider => stake it bonded, see the staking module.
+        Stakes get(fn stake_of):
+            map T::AccountId => Option<ProviderStake<BalanceOf<T>, T::BlockNumber>>;
+
+        /// data_id prefixes only attested accounts may write, see the attestation module.
+        ProtectedNamespaces get(fn protected_namespaces): Vec<Vec<u8>>;
+        /// map: account => attestation of its key
+        Attestations get(fn attestation_of): map T::AccountId => Option<Vec<u8>>;
+
+        /// map: provider => what it offers, see the registry module.
+        Providers get(fn provider_info): map T::AccountId => Option<ProviderInfo<BalanceOf<T>>>;
+        /// map: provider => its track record, see the reputation module.
+        Metrics get(fn metrics_of): map T::AccountId => ProviderMetrics;
+
+        /// map: author => nonce of the last bundle submitted, see the bundle module.
+        BundleNonces get(fn bundle_nonce): map T::AccountId => u64;
+
+        /// map: request id => open storage request, see the market module.
+        StorageRequests get(fn storage_request):
+            map u64 => Option<StorageRequest<T::AccountId, BalanceOf<T>, T::BlockNumber>>;
+        /// Ids of the open storage requests, oldest first.
+        OpenStorageRequests: Vec<u64>;
+        /// Id of the next storage request.
+        NextStorageRequestId: u64;
+        /// map: id of a deferred write => how far its upload got, see the status module.
+        UploadStatuses get(fn upload_status): map u64 => Option<RequestStatus<T::Hash>>;
+        /// double_map: block, id of a deferred write => its id, for the statuses dropped then
+        StatusQueue: double_map hasher(blake2_256) T::BlockNumber, blake2_256(u64) => u64;
+        /// map: request id => storage request given up on, see the dead_letter module.
+        DeadLetters get(fn dead_letter):
+            map u64 => Option<DeadLetter<T::AccountId, BalanceOf<T>, T::BlockNumber>>;
+        /// The storage offers of providers, cheapest first.
+        StorageOffers get(fn storage_offers): Vec<StorageOffer<T::AccountId, BalanceOf<T>>>;
+        /// double_map: data_id, provider => what the provider agreed to store
+        Agreements get(fn agreement):
+            double_map hasher(blake2_256) Vec<u8>, blake2_256(T::AccountId)
+            => Option<Agreement<BalanceOf<T>, T::BlockNumber>>;
+
+        /// map: data_id => block its time to live ends, see the ttl module.
+        Ttls get(fn ttl_of): map Vec<u8> => Option<T::BlockNumber>;
+        /// double_map: block, data_id => data_id, for the entries whose time to live ends then
+        TtlQueue: double_map hasher(blake2_256) T::BlockNumber, blake2_256(Vec<u8>) => Vec<u8>;
+
+        /// map: data_id => deleted entry, see the trash module.
+        Trash get(fn trash_of): map Vec<u8>
+            => Option<TrashedEntry<T::AccountId, T::Hash, BalanceOf<T>, T::BlockNumber>>;
+        /// double_map: block, data_id => data_id, for the entries deleted for good then
+        TrashQueue: double_map hasher(blake2_256) T::BlockNumber, blake2_256(Vec<u8>) => Vec<u8>;
+
+        /// map: data_id => number of its last revision, see the history module.
+        RevisionCount get(fn revision_count): map Vec<u8> => u32;
+        /// double_map: data_id, number => revision, only the last MaxRevisions are kept
+        Revisions get(fn revision):
+            double_map hasher(blake2_256) Vec<u8>, blake2_256(u32)
+            => Option<Revision<T::AccountId, T::Hash, T::BlockNumber>>;
+
+        /// map: position => backend key waiting for deletion, see the gc module.
+        GcQueue: map u64 => Option<GcItem<T::AccountId>>;
+        /// map: expiry queue => oldest block it still keeps keys of, see the expiry module.
+        ExpiryCursors get(fn expiry_cursor): map Expiry => T::BlockNumber;
+        /// Position of the next key to delete, and of the next key to queue.
+        GcBounds get(fn gc_bounds): (u64, u64);
+
+        /// map: data_id => value in its staging slot, see the staging module.
+        Staged get(fn staged_of):
+            map Vec<u8> => Option<StagedWrite<T::AccountId, T::Hash, BalanceOf<T>>>;
+        /// map: data_id => accounts that must approve staged values before promotion
+        Reviewers get(fn reviewers_of): map Vec<u8> => Vec<T::AccountId>;
+
+        /// double_map: writer, idempotency key => first write made with it, see the
+        /// idempotency module.
+        IdempotentWrites get(fn idempotent_write):
+            double_map hasher(blake2_256) T::AccountId, blake2_256(IdempotencyKey)
+            => Option<IdempotentWrite<T::Hash>>;
+        /// double_map: block, (writer, key) => (writer, key), for the keys forgotten then
+        IdempotencyQueue: double_map hasher(blake2_256) T::BlockNumber,
+            blake2_256((T::AccountId, IdempotencyKey)) => (T::AccountId, IdempotencyKey);
+
+        /// The layout the storage is in, see the migration module.
+        StorageVersion get(fn storage_version): u32;
+
+        /// map: author => how many entries it has, live or in the trash
+        OwnedCount get(fn owned_count): map T::AccountId => u32;
+
+        /// double_map: author, position => data_id of a live entry, see the listing module
+        OwnedEntries get(fn owned_entry): double_map hasher(blake2_256) T::AccountId,
+            blake2_256(u64) => Option<Vec<u8>>;
+        /// map: author => the position its next entry is listed at
+        NextOwnedPosition get(fn next_owned_position): map T::AccountId => u64;
+        /// map: data_id => the position of the live entry in the listing of its author
+        OwnedPositions get(fn owned_position): map Vec<u8> => Option<u64>;
+
+        /// map: author => bytes the values of its entries take, live or in the trash
+        UsedBytes get(fn used_bytes): map T::AccountId => u64;
+
+        /// The entries of all authors, live or in the trash, mirrors aren't counted
+        TotalEntries get(fn total_entries): u64;
+        /// The bytes the values of all entries take, live or in the trash
+        TotalBytesStored get(fn total_bytes_stored): u64;
+
+        /// double_map: block, account => writes the account made in the block, kept until
+        /// the block is finalized
+        WritesInBlock get(fn writes_in_block):
+            double_map hasher(blake2_256) T::BlockNumber, blake2_256(T::AccountId) => u32;
+
+        /// map: data_id => its value, until the offchain worker confirms it uploaded
+        PendingUploads get(fn pending_upload):
+            map Vec<u8> => Option<PendingUpload<T::Hash, T::BlockNumber>>;
+        /// Id of the last deferred write
+        NextUploadId: u64;
+
+        /// map: provider => nonce of its last unsigned confirmation
+        ConfirmationNonces get(fn confirmation_nonce): map T::AccountId => u64;
+
+        /// double_map: data_id, provider => the block it last confirmed keeping a replica in
+        ReplicaConfirmations get(fn replica_confirmed_at):
+            double_map hasher(blake2_256) Vec<u8>, blake2_256(T::AccountId)
+            => Option<T::BlockNumber>;
+
+        /// double_map: uploader, data_id => chunked upload, see the chunked module
+        ChunkedUploads get(fn chunked_upload): double_map hasher(blake2_256) T::AccountId,
+            blake2_256(Vec<u8>) => Option<ChunkedUpload<T::Hash, BalanceOf<T>, T::BlockNumber>>;
+        /// double_map: block, (uploader, data_id) => (uploader, data_id), for the chunked
+        /// uploads dropped then
+        ChunkedUploadDeadlines: double_map hasher(blake2_256) T::BlockNumber,
+            blake2_256((T::AccountId, Vec<u8>)) => (T::AccountId, Vec<u8>);
+        /// map: account => how many chunked uploads it has open
+        ChunkedUploadCount get(fn chunked_upload_count): map T::AccountId => u32;
+
+        /// map: account => public key data keys are wrapped to for it, see the keyring module
+        Keys get(fn encryption_key): map T::AccountId => Option<PublicKey>;
+        /// double_map: data_id, grantee => (public key, key of data_id wrapped to it)
+        SharedKeys: double_map hasher(blake2_256) Vec<u8>, blake2_256(T::AccountId)
+            => Option<(PublicKey, Vec<u8>)>;
+
+        /// map: prefix => namespace reserving it, see the namespace module
+        Namespaces get(fn namespace):
+            map Vec<u8> => Option<Namespace<T::AccountId, BalanceOf<T>>>;
+        /// double_map: prefix, account => whether the account may create entries under it
+        NamespaceGrants: double_map hasher(blake2_256) Vec<u8>, blake2_256(T::AccountId)
+            => bool;
+        /// map: backend => the name URIs give it as the provider, its id if empty
+        BackendNames get(fn backend_name): map BackendId => Vec<u8>;
+        /// map: data_id => backend key of the object its value is shared in, see the dedup
+        /// module
+        SharedValues get(fn shared_value): map Vec<u8> => Option<Vec<u8>>;
+        /// double_map: backend, object key => how many entries share the object
+        ObjectRefs get(fn object_refs):
+            double_map hasher(blake2_256) BackendId, blake2_256(Vec<u8>) => u32;
+        /// map: data_id => accounts pinning its value and the deposits they reserved
+        Pins get(fn pins_of): map Vec<u8> => Vec<(T::AccountId, BalanceOf<T>)>;
+        /// map: data_id => whether its audit trail is on, see the audit module
+        Audited get(fn is_audited): map Vec<u8> => bool;
+        /// map: data_id => the last MaxAuditRecords records of its audit trail
+        AuditTrail get(fn audit_trail):
+            map Vec<u8> => Vec<AuditRecord<T::AccountId, T::BlockNumber>>;
+
+        /// double_map: data_id, reader => hash of the value the reader last paid to read, see
+        /// the delivery module
+        ReadReceipts get(fn read_receipt):
+            double_map hasher(blake2_256) Vec<u8>, blake2_256(T::AccountId) => Option<T::Hash>;
+
+        /// how many values are in PendingUploads, for the metrics
+        PendingUploadCount get(fn pending_upload_count): u32;
+
+        /// map: data_id renamed to in a transaction => where its values are kept until the
+        /// offchain worker copied them, see the rename module
+        PendingCopies get(fn pending_copy): map Vec<u8> => Option<PendingCopy<T::Hash>>;
+        /// map: data_id renamed from in a transaction => the data_id its values are copied to
+        CopySources get(fn copy_source): map Vec<u8> => Option<Vec<u8>>;
+    }
+    add_extra_genesis {
+        // entries the chain starts with: data_id, author and access.
+        config(entries): Vec<(Vec<u8>, T::AccountId, Access)>;
+        // values of genesis entries, pushed to T::Storage by the node building the genesis.
+        // entries without one are hashed on their first read, see the migration module.
+        config(values): Vec<(Vec<u8>, Vec<u8>)>;
+        build(|config: &GenesisConfig<T>| {
+            for (data_id, value) in &config.values {
+                T::Storage::set(data_id.clone(), value.clone())
+                    .expect("the backend takes the genesis values");
+            }
+            for (data_id, author, access) in &config.entries {
+                let value = config.values.iter().find(|(id, _)| id == data_id);
+                let content_hash =
+                    value.map_or_else(Default::default, |(_, value)| T::Hashing::hash(value));
+                let size = value.map_or(0, |(_, value)| value.len() as u32);
+                let data = UserData::new(author.clone())
+                    .with_access(access.clone())
+                    .with_content(content_hash, size);
+                <Data<T>>::insert(data_id, data);
+                <Module<T>>::count_entry(author);
+                <Module<T>>::list_owned(author, data_id);
+            }
+            StorageVersion::put(migration::CURRENT_VERSION);
+        });
     }
 }
 
@@ -89,15 +1110,46 @@ decl_module! {
 
         fn deposit_event() = default;
 
+        // calls are encoded by their position, so new ones go at the end
+        // and none are removed, to keep the indices clients rely on.
+
+        fn on_initialize(n: T::BlockNumber) {
+            Self::ensure_storage_upgraded();
+            Self::expire_challenges(n);
+            Self::remove_ttl_passed(n);
+            Self::purge_trash(n);
+            Self::expire_chunked_uploads(n);
+            Self::forget_idempotency_keys(n);
+            Self::forget_upload_statuses(n);
+            Self::collect_garbage();
+        }
+
+        fn on_finalize(n: T::BlockNumber) {
+            <WritesInBlock<T>>::remove_prefix(n);
+        }
+
+        fn offchain_worker(n: T::BlockNumber) {
+            Self::store_snapshot(n);
+            Self::upload_pending(n);
+            Self::copy_pending();
+            Self::record_queues();
+        }
+
+        /// Pay to read the value of `data_id`, which the `offchainStorage_read` RPC then
+        /// delivers. See the delivery module.
+        #[weight = ByKeyLen(T::WeightInfo::read_data)]
         fn read_data(origin, data_id: Vec<u8>) -> DispatchResult{
             let user = ensure_signed(origin)?;
-            if <Data<T>>::exists(&data_id){
+            if <Data<T>>::exists(&data_id) && !Self::ttl_passed(&data_id){
                 let data = Self::get_data(&data_id);
-                if !Self::check_op_access(user, data, Access::Read){
+                if !Self::may_read(&user, &data_id, data.clone()){
                     Err(Error::<T>::PermissionDenied)?
+                }else if let Some(quorum) = Self::read_quorum(&data_id){
+                    Self::co_sign_read(&data_id, &user, user.clone(), quorum)
                 }else{
-                    let data = Self::get_external_storage(data_id);
-                    Self::deposit_event(Event::GetData(data));
+                    let content_hash = Self::served_hash(&data_id, &user)?;
+                    Self::note_read(&data_id, &user, content_hash);
+                    Self::deposit_event(RawEvent::GetData(content_hash));
                     Ok(())
                 }
             }else{
@@ -106,156 +1158,2055 @@ decl_module! {
 
         }
 
+        #[weight = ByValueLen(T::WeightInfo::write_data)]
         fn write_data(origin, data_id: Vec<u8>, write_data: Vec<u8>) -> DispatchResult{
             let user = ensure_signed(origin)?;
-            let data = Self::get_data(&data_id);
-            if !Self::check_op_access(user, data.clone(), Access::Read){
-                Err(Error::<T>::PermissionDenied)?
-            }else{
-                Self::set_external_storage(data_id.clone(), write_data);
-                <Data<T>>::insert(data_id, data);
-                Ok(())
+            Self::do_write(user, data_id, write_data)
+        }
+
+        /// Write a value together with an sr25519 or ed25519 signature of it, which is kept
+        /// until the value is next overwritten.
+        #[weight = ByValueLen(T::WeightInfo::write_data)]
+        fn write_signed_data(
+            origin,
+            data_id: Vec<u8>,
+            write_data: Vec<u8>,
+            signer: MultiSigner,
+            signature: MultiSignature
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(
+                signature.verify(&write_data[..], &signer.clone().into_account()),
+                Error::<T>::BadSignature
+            );
+            Self::do_write(user, data_id.clone(), write_data)?;
+            <ContentSignatures<T>>::insert(data_id, (signer, signature));
+            Ok(())
+        }
+
+        /// Write a value under the id `T::Hashing::hash(value)`, like IPFS does.
+        /// Writing content that is already stored leaves it untouched, see
+        /// `validate_content_addressed`.
+        #[weight = ByValueLen(T::WeightInfo::write_data)]
+        fn write_content_addressed(origin, write_data: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            let data_id = T::Hashing::hash(&write_data).encode();
+            if !<Data<T>>::exists(&data_id){
+                Self::do_write(user, data_id.clone(), write_data)?;
             }
+            Self::deposit_event(RawEvent::ContentWritten(data_id));
+            Ok(())
         }
 
+        #[weight = ByKeyLen(T::WeightInfo::delete_data)]
         fn delete_data(origin, data_id: Vec<u8>) -> DispatchResult{
             let user = ensure_signed(origin)?;
-            if <Data<T>>::exists(&data_id){
-                let data = Self::get_data(&data_id);
-                if !Self::check_op_access(user, data, Access::Read){
-                    Err(Error::<T>::PermissionDenied)?
-                }else{
-                    Self::delete_external_storage(data_id.clone());
-                    <Data<T>>::remove(data_id);
-                    Ok(())
+            Self::do_delete(user, data_id)
+        }
+
+        /// Let `delegate` write `data_id` on behalf of the author `max_writes` more times.
+        /// Passing `max_writes = 0` revokes the delegation.
+        fn delegate_write(origin, data_id: Vec<u8>, delegate: T::AccountId, max_writes: u32) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            if max_writes == 0 {
+                if <WriteDelegations<T>>::exists(&data_id, &delegate) {
+                    <WriteDelegations<T>>::remove(&data_id, &delegate);
+                    Self::audit(&data_id, &delegate, AuditOp::Revoked);
                 }
             }else{
-                Err(Error::<T>::NoneData)?
+                <WriteDelegations<T>>::insert(&data_id, &delegate, max_writes);
+                Self::note_grant(&user, &data_id);
+                Self::note_access_granted(&data_id, &delegate, Access::Write);
+            }
+            Ok(())
+        }
+
+        /// Remove data regardless of its author. Must be called by `T::AdminOrigin`.
+        fn force_delete_data(origin, data_id: Vec<u8>) -> DispatchResult{
+            T::AdminOrigin::ensure_origin(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            Self::remove_entry(&data_id)?;
+            Self::deposit_event(RawEvent::DataForceDeleted(data_id));
+            Ok(())
+        }
+
+        /// Change the access of data regardless of its author. Must be called by `T::AdminOrigin`.
+        fn force_set_access(origin, data_id: Vec<u8>, access: Access) -> DispatchResult{
+            T::AdminOrigin::ensure_origin(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            <Data<T>>::mutate(&data_id, |data| data.access = access.clone());
+            Self::deposit_event(RawEvent::AccessForceSet(data_id, access));
+            Ok(())
+        }
+
+        /// Challenge the providers to prove they still hold `data_id`.
+        fn challenge_storage(origin, data_id: Vec<u8>) -> DispatchResult{
+            ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(!<Challenges<T>>::exists(&data_id), Error::<T>::AlreadyChallenged);
+            let nonce = ChallengeNonces::mutate(&data_id, |n| { *n += 1; *n });
+            let chunk_index = Self::challenged_chunk(&data_id, nonce);
+            let deadline = <system::Module<T>>::block_number() + T::ChallengePeriod::get();
+            let open = Challenge { nonce, chunk_index, deadline };
+            <Challenges<T>>::insert(&data_id, open);
+            <ChallengeDeadlines<T>>::insert(deadline, &data_id, &data_id);
+            Self::deposit_event(RawEvent::ChallengeIssued(data_id, nonce, chunk_index, deadline));
+            Ok(())
+        }
+
+        /// Answer the open challenge `nonce` of `data_id`. Wrong answers are counted
+        /// against the provider and leave the challenge open.
+        #[weight = ByProofLen(T::WeightInfo::submit_storage_proof)]
+        fn submit_storage_proof(
+            origin,
+            data_id: Vec<u8>,
+            nonce: u64,
+            proof: StorageProof<T::Hash>
+        ) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(Self::is_provider(&who), Error::<T>::NotProvider);
+            let now = <frame_system::Module<T>>::block_number();
+            let open = Self::challenge_of(&data_id)
+                .filter(|c| c.nonce == nonce && now < c.deadline)
+                .ok_or(Error::<T>::NoChallenge)?;
+            if Self::check_storage_proof(&data_id, &open, &proof) {
+                <Challenges<T>>::remove(&data_id);
+                <ChallengeDeadlines<T>>::remove(open.deadline, &data_id);
+                Self::note_metric(&who, |m| m.proved += 1);
+                Self::deposit_event(RawEvent::StorageProved(data_id, nonce, who));
+            } else {
+                <FailedProofs<T>>::mutate(&who, |n| *n += 1);
+                Self::note_metric(&who, |m| m.failed += 1);
+                Self::slash_provider(&who, &data_id);
+                Self::deposit_event(RawEvent::StorageProofFailed(data_id, nonce));
+            }
+            Ok(())
+        }
+
+        /// Mirror `data_id` of the bridged chain `chain_id` as a read-only local entry.
+        /// `proof` must prove the remote `Data` entry against the state root of `header`,
+        /// which `T::RemoteHeaders` must trust. Mirroring again refreshes the entry.
+        #[weight = ByProofLen(T::WeightInfo::mirror_remote_data)]
+        fn mirror_remote_data(
+            origin,
+            chain_id: Vec<u8>,
+            header: T::Header,
+            data_id: Vec<u8>,
+            proof: Vec<Vec<u8>>
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(Self::fits_data_id(&data_id), Error::<T>::KeyTooLong);
+            ensure!(
+                (!<Data<T>>::exists(&data_id) && !<Trash<T>>::exists(&data_id))
+                    || <Mirrors<T>>::exists(&data_id),
+                Error::<T>::DataExists
+            );
+            ensure!(
+                <Mirrors<T>>::exists(&data_id) || Self::may_create(&user, &data_id),
+                Error::<T>::NamespaceReserved
+            );
+            let mut data = bridge::verify::<T>(&chain_id, &header, &data_id, &proof)
+                .ok_or(Error::<T>::InvalidRemoteProof)?;
+            // the backend ids of the remote chain mean nothing here.
+            data.backend = DEFAULT_BACKEND;
+            <Data<T>>::insert(&data_id, data);
+            <Mirrors<T>>::insert(&data_id, (chain_id.clone(), header.hash()));
+            Self::deposit_event(RawEvent::DataMirrored(chain_id, data_id));
+            Ok(())
+        }
+
+        /// Keep up to `replication` replicas of `data_id`. Lowering it drops the replicas
+        /// of the last assigned providers. Only the author may call this.
+        fn set_replication(origin, data_id: Vec<u8>, replication: u32) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            let mut data = Self::get_data(&data_id);
+            ensure!(data.author == user, Error::<T>::PermissionDenied);
+            if data.providers.len() > replication as usize {
+                let dropped = data.providers.split_off(replication as usize);
+                for p in &dropped {
+                    T::Replicas::delete(p, data_id.clone());
+                    <ReplicaConfirmations<T>>::remove(&data_id, p);
+                }
+            }
+            data.replication = replication;
+            <Data<T>>::insert(&data_id, data);
+            Self::deposit_event(RawEvent::ReplicationSet(data_id, replication));
+            Ok(())
+        }
+
+        /// Take a replica of `data_id`, copied from a backend that still has it intact.
+        /// The caller must be a provider.
+        #[weight = UpToValueLen(T::WeightInfo::accept_replica, T::MaxValueLength::get())]
+        fn accept_replica(origin, data_id: Vec<u8>) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(Self::is_provider(&who), Error::<T>::NotProvider);
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(!<Mirrors<T>>::exists(&data_id), Error::<T>::ReadOnlyMirror);
+            let mut data = Self::get_data(&data_id);
+            ensure!(!data.providers.contains(&who), Error::<T>::AlreadyReplica);
+            ensure!(
+                (data.providers.len() as u32) < data.replication,
+                Error::<T>::ReplicationFull
+            );
+            let value = Self::get_external_storage(&data_id, &data)?;
+            T::Replicas::set(&who, data_id.clone(), value);
+            data.providers.push(who.clone());
+            <Data<T>>::insert(&data_id, data);
+            Self::deposit_event(RawEvent::ReplicaAssigned(data_id, who));
+            Ok(())
+        }
+
+        /// Create an empty keyring owned by the caller.
+        fn create_keyring(origin) -> DispatchResult{
+            let owner = ensure_signed(origin)?;
+            let id = NextKeyringId::mutate(|id| { *id += 1; *id - 1 });
+            <Keyrings<T>>::insert(id, Keyring { owner, epoch: 0, members: Vec::new() });
+            Self::deposit_event(RawEvent::KeyringCreated(id));
+            Ok(())
+        }
+
+        /// Add `member` to `keyring`, or change its public key. Only the owner may call this.
+        fn add_keyring_member(
+            origin,
+            keyring: u64,
+            member: T::AccountId,
+            public_key: PublicKey
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::change_members(user, keyring, |members| {
+                members.retain(|(m, _)| *m != member);
+                members.push((member, public_key));
+            })
+        }
+
+        /// Remove `member` from `keyring`. Only the owner may call this.
+        fn remove_keyring_member(origin, keyring: u64, member: T::AccountId) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::change_members(user, keyring, |members| members.retain(|(m, _)| *m != member))
+        }
+
+        /// Share the key of `data_id` with the members of `keyring`, or stop sharing it
+        /// with `None`. Only the author may call this.
+        fn attach_keyring(origin, data_id: Vec<u8>, keyring: Option<u64>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            ensure!(keyring.map_or(true, <Keyrings<T>>::exists), Error::<T>::NoKeyring);
+            <WrappedKeys<T>>::remove_prefix(&data_id);
+            match keyring {
+                Some(id) => {
+                    DataKeyrings::insert(&data_id, id);
+                    Self::note_grant(&user, &data_id);
+                }
+                None => DataKeyrings::remove(&data_id),
+            }
+            Ok(())
+        }
+
+        /// Publish the key of `data_id` wrapped for members of its keyring, as of `epoch`.
+        /// Only the author may call this.
+        #[weight = ByWrappedKeys(T::WeightInfo::wrap_keys)]
+        fn wrap_keys(
+            origin,
+            data_id: Vec<u8>,
+            epoch: u32,
+            wrapped: Vec<(T::AccountId, Vec<u8>)>
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            let keyring = Self::keyring_of(&data_id)
+                .and_then(Self::keyring)
+                .ok_or(Error::<T>::NoKeyring)?;
+            ensure!(epoch == keyring.epoch, Error::<T>::StaleEpoch);
+            ensure!(
+                wrapped.iter().all(|(m, _)| keyring.public_key(m).is_some()),
+                Error::<T>::NotMember
+            );
+            for (member, key) in wrapped {
+                <WrappedKeys<T>>::insert(&data_id, &member, (epoch, key));
+            }
+            Self::deposit_event(RawEvent::KeysWrapped(data_id, epoch));
+            Ok(())
+        }
+
+        /// Let `guardian` revoke all grants of the caller, e.g. when the caller's key is
+        /// compromised. `None` removes the guardian.
+        fn set_guardian(origin, guardian: Option<T::AccountId>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            match guardian {
+                Some(g) => <Guardians<T>>::insert(&user, g),
+                None => <Guardians<T>>::remove(&user),
+            }
+            Ok(())
+        }
+
+        /// Revoke every read grant, write delegation and keyring share on the entries of
+        /// `author` at once. Must be called by the author or their guardian.
+        fn revoke_all_grants(origin, author: T::AccountId) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(
+                user == author || Self::guardian_of(&author) == Some(user),
+                Error::<T>::PermissionDenied
+            );
+            let entries = <GrantedEntries<T>>::take(&author);
+            for data_id in &entries {
+                for grantee in Self::grantees_of(data_id) {
+                    Self::audit(data_id, &grantee, AuditOp::Revoked);
+                }
+                <WriteDelegations<T>>::remove_prefix(data_id);
+                <ReadGrants<T>>::remove_prefix(data_id);
+                <SessionGrants<T>>::remove_prefix(data_id);
+                <Grantees<T>>::remove(data_id);
+                <PendingReads<T>>::remove_prefix(data_id);
+                DataKeyrings::remove(data_id);
+                <WrappedKeys<T>>::remove_prefix(data_id);
+                <SharedKeys<T>>::remove_prefix(data_id);
+            }
+            Self::deposit_event(RawEvent::EmergencyRevocation(entries.len() as u32));
+            Ok(())
+        }
+
+        /// Charge the rent due for `data_id`, expiring it if the author can't pay.
+        fn collect_rent(origin, data_id: Vec<u8>) -> DispatchResult{
+            ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            Self::charge_rent(&data_id);
+            Ok(())
+        }
+
+        /// Remove `data_id` once `T::RentGracePeriod` has passed since it expired.
+        fn remove_expired(origin, data_id: Vec<u8>) -> DispatchResult{
+            ensure_signed(origin)?;
+            let expired_at = Self::rent_of(&data_id)
+                .and_then(|rent| rent.expired_at)
+                .ok_or(Error::<T>::NotExpired)?;
+            let now = <system::Module<T>>::block_number();
+            ensure!(now >= expired_at + T::RentGracePeriod::get(), Error::<T>::NotExpired);
+            Self::evict_entry(&data_id);
+            Self::deposit_event(RawEvent::ExpiredDataRemoved(data_id));
+            Ok(())
+        }
+
+        /// Sell read access to `data_id` for `price`, or stop selling it with `None`.
+        /// Accounts that already bought access keep it. Only the author may call this.
+        fn set_read_price(origin, data_id: Vec<u8>, price: Option<BalanceOf<T>>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            match price {
+                Some(price) => <ReadPrices<T>>::insert(&data_id, price),
+                None => <ReadPrices<T>>::remove(&data_id),
+            }
+            Self::deposit_event(RawEvent::ReadPriceSet(data_id));
+            Ok(())
+        }
+
+        /// Pay the read price of `data_id` for the right to read it. The author gets the
+        /// price less the protocol fee.
+        fn purchase_read_access(origin, data_id: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            let price = Self::read_price(&data_id).ok_or(Error::<T>::NotForSale)?;
+            let data = Self::get_data(&data_id);
+            ensure!(
+                !Self::has_read_grant(&data_id, &user)
+                    && !Self::check_op_access(user.clone(), data.clone(), Access::Read),
+                Error::<T>::AlreadyReadable
+            );
+            ensure!(
+                T::Currency::free_balance(&user) >= price,
+                Error::<T>::InsufficientBalance
+            );
+            let fee = T::ProtocolFee::get() * price;
+            T::Currency::transfer(
+                &user,
+                &data.author,
+                price - fee,
+                ExistenceRequirement::KeepAlive,
+            )?;
+            if !fee.is_zero() {
+                let paid = T::Currency::withdraw(
+                    &user,
+                    fee,
+                    WithdrawReason::Transfer.into(),
+                    ExistenceRequirement::KeepAlive,
+                )?;
+                T::ProtocolFeeDestination::on_unbalanced(paid);
+            }
+            <ReadGrants<T>>::insert(&data_id, &user, true);
+            Self::note_grant(&data.author, &data_id);
+            Self::note_access_granted(&data_id, &user, Access::Read);
+            Self::deposit_event(RawEvent::ReadAccessPurchased(data_id, user));
+            Ok(())
+        }
+
+        /// Let `grantee` read, or read and write, `data_id` until the current session ends.
+        /// `Access::Avoid` removes the grant. Only the author may call this.
+        fn grant_session_access(
+            origin,
+            data_id: Vec<u8>,
+            grantee: T::AccountId,
+            access: Access
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            if access == Access::Avoid {
+                if <SessionGrants<T>>::exists(&data_id, &grantee) {
+                    <SessionGrants<T>>::remove(&data_id, &grantee);
+                    Self::audit(&data_id, &grantee, AuditOp::Revoked);
+                }
+                return Ok(());
+            }
+            let session = T::CurrentSession::get();
+            <SessionGrants<T>>::insert(&data_id, &grantee, (session, access.clone()));
+            Self::note_grant(&user, &data_id);
+            Self::note_access_granted(&data_id, &grantee, access);
+            Self::deposit_event(RawEvent::SessionAccessGranted(data_id, grantee, session));
+            Ok(())
+        }
+
+        /// Let whoever is in `T::Operators` at the time read, or read and write, `data_id`.
+        /// `Access::Avoid` removes their access. Only the author may call this.
+        fn set_operator_access(origin, data_id: Vec<u8>, access: Access) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            if access == Access::Avoid {
+                OperatorAccess::remove(&data_id);
+            } else {
+                OperatorAccess::insert(&data_id, access.clone());
+            }
+            Self::deposit_event(RawEvent::OperatorAccessSet(data_id, access));
+            Ok(())
+        }
+
+        /// Bond `amount` of the caller's balance as provider stake, which is slashed when
+        /// the provider fails challenges.
+        fn bond_provider(origin, amount: BalanceOf<T>) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(Self::is_provider(&who), Error::<T>::NotProvider);
+            ensure!(T::Currency::can_reserve(&who, amount), Error::<T>::InsufficientBalance);
+            T::Currency::reserve(&who, amount)?;
+            <Stakes<T>>::mutate(&who, |stake| {
+                let stake = stake.get_or_insert_with(Default::default);
+                stake.bonded = stake.bonded.saturating_add(amount);
+            });
+            Ok(())
+        }
+
+        /// Start unbonding `amount` of the caller's stake. It can be withdrawn after
+        /// `T::UnbondingDelay` blocks, counted again from now if some is already unbonding.
+        fn unbond_provider(origin, amount: BalanceOf<T>) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            let mut stake = Self::stake_of(&who)
+                .filter(|s| s.bonded >= amount)
+                .ok_or(Error::<T>::InsufficientBond)?;
+            stake.bonded -= amount;
+            stake.unbonding = stake.unbonding.saturating_add(amount);
+            stake.unlock_at = <system::Module<T>>::block_number() + T::UnbondingDelay::get();
+            <Stakes<T>>::insert(&who, stake);
+            Ok(())
+        }
+
+        /// Withdraw the caller's unbonded stake once its unbonding delay is over.
+        fn withdraw_unbonded(origin) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            let mut stake = Self::stake_of(&who)
+                .filter(|s| !s.unbonding.is_zero())
+                .filter(|s| s.unlock_at <= <system::Module<T>>::block_number())
+                .ok_or(Error::<T>::NotUnbonded)?;
+            T::Currency::unreserve(&who, stake.unbonding);
+            stake.unbonding = Zero::zero();
+            if stake.bonded.is_zero() {
+                <Stakes<T>>::remove(&who);
+            } else {
+                <Stakes<T>>::insert(&who, stake);
+            }
+            Ok(())
+        }
+
+        /// Require `quorum` distinct readers, the reader included, to sign every read of
+        /// `data_id` before its value is released. A quorum of 0 or 1 removes the
+        /// requirement. Only the author may call this.
+        fn set_read_quorum(origin, data_id: Vec<u8>, quorum: u32) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            if quorum > 1 {
+                ReadQuorums::insert(&data_id, quorum);
+            } else {
+                ReadQuorums::remove(&data_id);
+                <PendingReads<T>>::remove_prefix(&data_id);
+            }
+            Self::deposit_event(RawEvent::ReadQuorumSet(data_id, quorum));
+            Ok(())
+        }
+
+        /// Co-sign the pending read of `data_id` by `reader`. The caller must be able to read
+        /// `data_id` too. The last signature the quorum needs releases the value.
+        fn approve_read(origin, data_id: Vec<u8>, reader: T::AccountId) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(
+                <Data<T>>::exists(&data_id) && !Self::ttl_passed(&data_id),
+                Error::<T>::NoneData
+            );
+            let quorum = Self::read_quorum(&data_id).ok_or(Error::<T>::NoQuorum)?;
+            let data = Self::get_data(&data_id);
+            ensure!(Self::may_read(&user, &data_id, data.clone()), Error::<T>::PermissionDenied);
+            ensure!(<PendingReads<T>>::exists(&data_id, &reader), Error::<T>::NoPendingRead);
+            Self::co_sign_read(&data_id, &reader, user, quorum)
+        }
+
+        /// Register an attestation of the caller's key, which `T::Attestation` must accept.
+        /// Needed to write to protected namespaces. `None` removes it.
+        fn register_attestation(origin, attestation: Option<Vec<u8>>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            match attestation {
+                Some(a) => {
+                    ensure!(T::Attestation::verify(&user, &a), Error::<T>::Unattested);
+                    <Attestations<T>>::insert(&user, a);
+                }
+                None => <Attestations<T>>::remove(&user),
             }
+            Ok(())
+        }
+
+        /// Protect the namespace `prefix`, so only attested accounts may write entries whose
+        /// data_id starts with it, or lift the protection.
+        fn set_protected_namespace(origin, prefix: Vec<u8>, protected: bool) -> DispatchResult{
+            T::AdminOrigin::ensure_origin(origin)?;
+            ProtectedNamespaces::mutate(|prefixes| {
+                prefixes.retain(|p| *p != prefix);
+                if protected {
+                    prefixes.push(prefix.clone());
+                }
+            });
+            Self::deposit_event(RawEvent::NamespaceProtected(prefix, protected));
+            Ok(())
+        }
+
+        /// Register the caller as a storage provider reachable at `endpoint`, offering to
+        /// store `capacity` bytes for `price_per_byte`, reserving `T::ProviderDeposit` until
+        /// it is removed. Registering again updates the offer. The caller is only a provider
+        /// once approved, see the registry module.
+        #[weight = ByKeyLen(T::WeightInfo::register_provider)]
+        fn register_provider(
+            origin,
+            endpoint: Vec<u8>,
+            capacity: u64,
+            price_per_byte: BalanceOf<T>
+        ) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(
+                endpoint.len() as u32 <= T::MaxEndpointLength::get(),
+                Error::<T>::EndpointTooLong
+            );
+            let old = Self::provider_info(&who);
+            // an update keeps the deposit, topped up if T::ProviderDeposit went up.
+            let held = old.as_ref().map_or_else(Zero::zero, |info| info.deposit);
+            let deposit = T::ProviderDeposit::get().max(held);
+            ensure!(
+                T::Currency::can_reserve(&who, deposit - held),
+                Error::<T>::InsufficientBalance
+            );
+            T::Currency::reserve(&who, deposit - held)?;
+            let info = ProviderInfo {
+                endpoint: endpoint.clone(),
+                capacity,
+                price_per_byte,
+                // an update keeps the approval.
+                approved: old.map_or(false, |info| info.approved),
+                deposit,
+            };
+            <Providers<T>>::insert(&who, info);
+            Self::deposit_event(RawEvent::ProviderRegistered(endpoint));
+            Ok(())
+        }
+
+        /// Remove the caller from the provider registry, returning its deposit. Replicas it
+        /// keeps and stake it bonded are left as they are.
+        fn deregister_provider(origin) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            let info = <Providers<T>>::take(&who).ok_or(Error::<T>::NotRegistered)?;
+            let _ = T::Currency::unreserve(&who, info.deposit);
+            Self::deposit_event(RawEvent::ProviderDeregistered(info.endpoint));
+            Ok(())
+        }
+
+        /// Remove `provider` from the provider registry, e.g. when its endpoint is gone, and
+        /// return its deposit. Replicas it keeps and stake it bonded are left as they are.
+        /// Must be called by `T::AdminOrigin`.
+        fn force_deregister_provider(origin, provider: T::AccountId) -> DispatchResult{
+            T::AdminOrigin::ensure_origin(origin)?;
+            let info = <Providers<T>>::take(&provider).ok_or(Error::<T>::NotRegistered)?;
+            let _ = T::Currency::unreserve(&provider, info.deposit);
+            Self::deposit_event(RawEvent::ProviderDeregistered(info.endpoint));
+            Ok(())
+        }
+
+        /// Report that `provider` took `latency` milliseconds to answer. Meant for the
+        /// offchain workers of `T::Operators`, the only accounts that may call this.
+        fn report_latency(origin, provider: T::AccountId, latency: u32) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(T::Operators::contains(&who), Error::<T>::PermissionDenied);
+            ensure!(Self::is_provider(&provider), Error::<T>::NotProvider);
+            Self::note_metric(&provider, |m| {
+                m.latency_reports = m.latency_reports.saturating_add(1);
+                m.total_latency = m.total_latency.saturating_add(latency as u64);
+            });
+            Ok(())
+        }
+
+        /// Apply the writes of a bundle its author signed offline. Anyone may submit it.
+        /// Either all writes are applied or none, see the bundle module.
+        #[weight = ByBundleLen(T::WeightInfo::submit_bundles)]
+        fn submit_bundle(
+            origin,
+            bundle: WriteBundle<T::AccountId, T::BlockNumber>,
+            signature: T::BundleSignature
+        ) -> DispatchResult{
+            ensure_signed(origin)?;
+            let bundles = sp_std::vec![(bundle, signature)];
+            ensure!(Self::verify_bundles(&bundles), Error::<T>::BadBundle);
+            for (bundle, _) in bundles {
+                Self::apply_bundle(bundle)?;
+            }
+            Ok(())
+        }
+
+        /// Submit many bundles at once, e.g. as a relayer. All signatures are checked
+        /// before any bundle is applied, and the call fails if one is invalid. Bundles that
+        /// can't be applied, e.g. because they expired, are skipped.
+        #[weight = ByBundleLen(T::WeightInfo::submit_bundles)]
+        fn submit_bundles(
+            origin,
+            bundles: Vec<(WriteBundle<T::AccountId, T::BlockNumber>, T::BundleSignature)>
+        ) -> DispatchResult{
+            ensure_signed(origin)?;
+            ensure!(Self::verify_bundles(&bundles), Error::<T>::BadBundle);
+            for (bundle, _) in bundles {
+                let nonce = bundle.nonce;
+                if Self::apply_bundle(bundle).is_err() {
+                    Self::deposit_event(RawEvent::BundleRejected(nonce));
+                }
+            }
+            Ok(())
+        }
+
+        /// Ask for `size` bytes of `data_id` to be stored for `duration` blocks, paying at
+        /// most `max_price` per byte and block. Only the author may call this, and reserves
+        /// `T::RequestDeposit` until the request is matched or cancelled.
+        fn post_storage_request(
+            origin,
+            data_id: Vec<u8>,
+            size: u32,
+            duration: T::BlockNumber,
+            max_price: BalanceOf<T>
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            let deposit = T::RequestDeposit::get();
+            ensure!(T::Currency::can_reserve(&user, deposit), Error::<T>::InsufficientBalance);
+            T::Currency::reserve(&user, deposit)?;
+            let id = NextStorageRequestId::mutate(|n| { *n += 1; *n });
+            let request = StorageRequest {
+                author: user,
+                data_id: data_id.clone(),
+                size,
+                duration,
+                max_price,
+                deposit,
+                attempts: 0,
+            };
+            <StorageRequests<T>>::insert(id, request);
+            OpenStorageRequests::mutate(|ids| ids.push(id));
+            Self::deposit_event(RawEvent::StorageRequested(id, data_id));
+            Self::match_storage_requests();
+            Ok(())
+        }
+
+        /// Withdraw the open storage request `id` before an offer matches it, releasing its
+        /// deposit. Only its author may call this.
+        fn cancel_storage_request(origin, id: u64) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            let request = Self::storage_request(id).ok_or(Error::<T>::NoStorageRequest)?;
+            ensure!(request.author == user, Error::<T>::PermissionDenied);
+            let _ = T::Currency::unreserve(&request.author, request.deposit);
+            <StorageRequests<T>>::remove(id);
+            OpenStorageRequests::mutate(|ids| ids.retain(|i| *i != id));
+            Self::deposit_event(RawEvent::StorageRequestCancelled(id));
+            Ok(())
+        }
+
+        /// Put the dead-lettered storage request `id` back in the queue, with all its
+        /// attempts. Only its author or whoever is in `T::Operators` may call this.
+        fn retry_dead_letter(origin, id: u64) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            let letter = Self::dead_letter(id).ok_or(Error::<T>::NoDeadLetter)?;
+            Self::ensure_may_handle(&who, &letter)?;
+            <DeadLetters<T>>::remove(id);
+            let request = StorageRequest { attempts: 0, ..letter.request };
+            <StorageRequests<T>>::insert(id, request);
+            OpenStorageRequests::mutate(|ids| ids.push(id));
+            Self::deposit_event(RawEvent::DeadLetterRetried(id));
+            Self::match_storage_requests();
+            Ok(())
+        }
 
+        /// Drop the dead-lettered storage request `id`, releasing its deposit. Only its
+        /// author or whoever is in `T::Operators` may call this.
+        fn purge_dead_letter(origin, id: u64) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            let letter = Self::dead_letter(id).ok_or(Error::<T>::NoDeadLetter)?;
+            Self::ensure_may_handle(&who, &letter)?;
+            let _ = T::Currency::unreserve(&letter.request.author, letter.request.deposit);
+            <DeadLetters<T>>::remove(id);
+            Self::deposit_event(RawEvent::DeadLetterPurged(id));
+            Ok(())
+        }
+
+        /// Offer to store up to `capacity` more bytes for `price` per byte and block,
+        /// replacing the caller's previous offer. A capacity of 0 withdraws the offer. The
+        /// caller must be a provider.
+        fn post_storage_offer(origin, capacity: u64, price: BalanceOf<T>) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(Self::is_provider(&who), Error::<T>::NotProvider);
+            <StorageOffers<T>>::mutate(|offers| {
+                offers.retain(|o| o.provider != who);
+                if capacity > 0 {
+                    let at = offers.iter().position(|o| o.price > price).unwrap_or(offers.len());
+                    offers.insert(at, StorageOffer { provider: who, capacity, price });
+                }
+            });
+            Self::match_storage_requests();
+            Ok(())
+        }
+
+        /// Write like `write_data`, letting the entry live until the block `expires_at`, or
+        /// forever with `None`. Only the author may change the time to live of an entry.
+        #[weight = ByValueLen(T::WeightInfo::write_data)]
+        fn write_data_until(
+            origin,
+            data_id: Vec<u8>,
+            write_data: Vec<u8>,
+            expires_at: Option<T::BlockNumber>
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(
+                !<Data<T>>::exists(&data_id) || Self::get_data(&data_id).author == user,
+                Error::<T>::PermissionDenied
+            );
+            if let Some(at) = expires_at {
+                ensure!(at > <system::Module<T>>::block_number(), Error::<T>::InvalidTtl);
+            }
+            Self::do_write(user, data_id.clone(), write_data)?;
+            Self::set_ttl(&data_id, expires_at);
+            Ok(())
+        }
+
+        /// Put `write_data` in the staging slot of `data_id`, replacing what was staged. The
+        /// entry reads as before until the value is promoted. The caller must be allowed to
+        /// write `data_id`, and reserves the deposit for the staged value.
+        #[weight = ByValueLen(T::WeightInfo::write_data)]
+        fn write_staged(origin, data_id: Vec<u8>, write_data: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            Self::validate_write(&user, &data_id, write_data.len() as u32)
+                .map_err(Error::<T>::from)?;
+            let deposit = Self::deposit_for(write_data.len() as u32);
+            ensure!(T::Currency::can_reserve(&user, deposit), Error::<T>::InsufficientBalance);
+            let content_hash = T::Hashing::hash(&write_data);
+            Self::backend(&data_id, T::Storage::set(staging::staged_key(&data_id), write_data))?;
+            Self::forget_staged(&data_id);
+            T::Currency::reserve(&user, deposit)?;
+            let staged = StagedWrite {
+                writer: user,
+                content_hash,
+                deposit,
+                canary: Perbill::zero(),
+                canary_reads: 0,
+                approvals: Vec::new(),
+            };
+            <Staged<T>>::insert(&data_id, staged);
+            Self::deposit_event(RawEvent::DataStaged(data_id));
+            Ok(())
+        }
+
+        /// Write the staged value of `data_id` live, as the account that staged it. Only the
+        /// author or that account may call this.
+        #[weight = UpToValueLen(T::WeightInfo::promote_staged, T::MaxValueLength::get())]
+        fn promote_staged(origin, data_id: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            let staged = Self::staged_of(&data_id).ok_or(Error::<T>::NothingStaged)?;
+            ensure!(
+                user == staged.writer || user == Self::get_data(&data_id).author,
+                Error::<T>::PermissionDenied
+            );
+            Self::ensure_approved(&data_id, &staged)?;
+            let value = Self::get_staged(&data_id, &staged)?;
+            // the staging deposit is released first, the write may need it.
+            let _ = T::Currency::unreserve(&staged.writer, staged.deposit);
+            if let Err(e) = Self::do_write(staged.writer.clone(), data_id.clone(), value) {
+                T::Currency::reserve(&staged.writer, staged.deposit)?;
+                return Err(e);
+            }
+            <Staged<T>>::remove(&data_id);
+            // a staged value left behind is replaced by the next one.
+            let _ = T::Storage::delete(staging::staged_key(&data_id));
+            Self::deposit_event(RawEvent::StagedPromoted(data_id));
+            Ok(())
+        }
+
+        /// Drop the staged value of `data_id`. Only the author or the account that staged it
+        /// may call this.
+        fn discard_staged(origin, data_id: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            let staged = Self::staged_of(&data_id).ok_or(Error::<T>::NothingStaged)?;
+            ensure!(
+                user == staged.writer || user == Self::get_data(&data_id).author,
+                Error::<T>::PermissionDenied
+            );
+            Self::drop_staged(&data_id);
+            Self::deposit_event(RawEvent::StagedDiscarded(data_id));
+            Ok(())
+        }
+
+        /// Serve the staged value of `data_id` to a `canary` share of the readers, picked by
+        /// the hash of their account. Only the author may call this.
+        fn set_canary(origin, data_id: Vec<u8>, canary: Perbill) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Staged<T>>::exists(&data_id), Error::<T>::NothingStaged);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            <Staged<T>>::mutate(&data_id, |staged| {
+                if let Some(staged) = staged {
+                    staged.canary = canary;
+                }
+            });
+            Self::deposit_event(RawEvent::CanarySet(data_id));
+            Ok(())
+        }
+
+        /// Take `data_id` out of the trash, as it was when deleted. Only the author may call
+        /// this.
+        fn restore_data(origin, data_id: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            let now = <frame_system::Module<T>>::block_number();
+            // an entry past its time in the trash waits to be purged, see the expiry module.
+            let entry = Self::trash_of(&data_id)
+                .filter(|entry| now < entry.purge_at)
+                .ok_or(Error::<T>::NotInTrash)?;
+            ensure!(entry.data.author == user, Error::<T>::PermissionDenied);
+            Self::restore_entry(&data_id);
+            Self::audit(&data_id, &user, AuditOp::Restored);
+            Self::deposit_event(RawEvent::DataRestored(data_id));
+            Ok(())
+        }
+
+        /// Like `write_data`, with a message saying why, kept in the revision history.
+        #[weight = ByValueLen(T::WeightInfo::write_data)]
+        fn write_data_with_message(
+            origin,
+            data_id: Vec<u8>,
+            write_data: Vec<u8>,
+            message: Vec<u8>
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::ensure_message(&message)?;
+            Self::do_write_with_message(user, data_id, write_data, message)
+        }
+
+        /// Pay to read the value of revision `version` of `data_id`, if it is still kept.
+        /// Entries with a read quorum only serve their current value.
+        fn read_version(origin, data_id: Vec<u8>, version: u32) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(
+                <Data<T>>::exists(&data_id) && !Self::ttl_passed(&data_id),
+                Error::<T>::NoneData
+            );
+            let data = Self::get_data(&data_id);
+            ensure!(
+                Self::may_read(&user, &data_id, data) && Self::read_quorum(&data_id).is_none(),
+                Error::<T>::PermissionDenied
+            );
+            let content_hash =
+                Self::version_hash(&data_id, version).ok_or(Error::<T>::NoSuchVersion)?;
+            Self::note_read(&data_id, &user, content_hash);
+            Self::deposit_event(RawEvent::GetData(content_hash));
+            Ok(())
+        }
+
+        /// Write the value of revision `version` of `data_id` again, as a new revision. The
+        /// caller must be allowed to write `data_id`.
+        #[weight = UpToValueLen(T::WeightInfo::rollback, T::MaxValueLength::get())]
+        fn rollback(origin, data_id: Vec<u8>, version: u32) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Revisions<T>>::exists(&data_id, version), Error::<T>::NoSuchVersion);
+            let value = Self::get_version(&data_id, version)?;
+            Self::do_write(user, data_id, value)
+        }
+
+        /// Hand the caller's replica of `data_id` to `target_provider`, which takes the
+        /// caller's place among the providers of the entry. The value is read from the
+        /// caller's backend, or from any other that has it intact.
+        #[weight = UpToValueLen(T::WeightInfo::migrate_data, T::MaxValueLength::get())]
+        fn migrate_data(origin, data_id: Vec<u8>, target_provider: T::AccountId) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            let mut data = Self::get_data(&data_id);
+            let index = data.providers.iter().position(|p| *p == who)
+                .ok_or(Error::<T>::NotReplica)?;
+            ensure!(Self::is_provider(&target_provider), Error::<T>::NotProvider);
+            ensure!(!data.providers.contains(&target_provider), Error::<T>::AlreadyReplica);
+            let kept = T::Replicas::get(&who, data_id.clone());
+            let value = if T::Hashing::hash(&kept) == data.content_hash {
+                kept
+            } else {
+                Self::get_external_storage(&data_id, &data)?
+            };
+            T::Replicas::set(&target_provider, data_id.clone(), value);
+            T::Replicas::delete(&who, data_id.clone());
+            data.providers[index] = target_provider.clone();
+            <Data<T>>::insert(&data_id, data);
+            // what the caller agreed to store ends with its replica.
+            <Agreements<T>>::remove(&data_id, &who);
+            <ReplicaConfirmations<T>>::remove(&data_id, &who);
+            Self::deposit_event(RawEvent::ReplicaMigrated(data_id, who, target_provider));
+            Ok(())
+        }
+
+        /// Require every account in `reviewers` to approve staged values of `data_id` before
+        /// they are promoted, at most `T::MaxReviewers` of them. Only the author may call this.
+        #[weight = ByReviewers(T::WeightInfo::set_reviewers)]
+        fn set_reviewers(origin, data_id: Vec<u8>, reviewers: Vec<T::AccountId>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            let count = reviewers.len() as u32;
+            ensure!(count <= T::MaxReviewers::get(), Error::<T>::TooManyReviewers);
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            if reviewers.is_empty() {
+                <Reviewers<T>>::remove(&data_id);
+            }else{
+                <Reviewers<T>>::insert(&data_id, reviewers);
+            }
+            Self::deposit_event(RawEvent::ReviewersSet(data_id, count));
+            Ok(())
+        }
+
+        /// Sign off the staged value of `data_id`, which must hash to `content_hash`. The
+        /// caller must be a reviewer of `data_id`.
+        fn approve_staged(origin, data_id: Vec<u8>, content_hash: T::Hash) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            let mut staged = Self::staged_of(&data_id).ok_or(Error::<T>::NothingStaged)?;
+            ensure!(Self::reviewers_of(&data_id).contains(&user), Error::<T>::NotReviewer);
+            ensure!(staged.content_hash == content_hash, Error::<T>::StagedChanged);
+            ensure!(!staged.approvals.contains(&user), Error::<T>::AlreadySigned);
+            staged.approvals.push(user.clone());
+            <Staged<T>>::insert(&data_id, staged);
+            Self::deposit_event(RawEvent::StagedApproved(data_id, user));
+            Ok(())
+        }
+
+        /// Move `old_id` to `new_id`, which must be unused: the value in the backends and
+        /// everything kept about the entry on chain. Only the author may call this.
+        fn rename_data(origin, old_id: Vec<u8>, new_id: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::ensure_may_rename(&user, &old_id, &new_id)?;
+            Self::move_entry(&old_id, &new_id)?;
+            Self::audit(&new_id, &user, AuditOp::Renamed(old_id.clone()));
+            Self::deposit_event(RawEvent::DataRenamed(old_id, new_id));
+            Ok(())
+        }
+
+        /// Copy the value of `src_id`, which the caller must be able to read, to the unused
+        /// `dst_id`. The copy is the caller's, with the default access and no grants, and is
+        /// encrypted as the value copied.
+        #[weight = UpToValueLen(T::WeightInfo::copy_data, T::MaxValueLength::get())]
+        fn copy_data(origin, src_id: Vec<u8>, dst_id: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(
+                <Data<T>>::exists(&src_id) && !Self::ttl_passed(&src_id),
+                Error::<T>::NoneData
+            );
+            // values behind a read quorum are only read with the readers' consent.
+            ensure!(
+                Self::can_read(&user, &src_id) && Self::read_quorum(&src_id).is_none(),
+                Error::<T>::PermissionDenied
+            );
+            ensure!(!<Data<T>>::exists(&dst_id), Error::<T>::DataExists);
+            let src = Self::get_data(&src_id);
+            let value = Self::get_external_storage(&src_id, &src)?;
+            // the copy is kept in the backend of the value copied, compressed as it is.
+            let (upload, backend) = (Self::upload_mode(), Some(src.backend));
+            let (id, encryption) = (dst_id.clone(), src.encryption);
+            Self::write_entry(
+                user,
+                id,
+                value,
+                Vec::new(),
+                upload,
+                encryption,
+                backend,
+                Some(src.compression),
+            )?;
+            Self::deposit_event(RawEvent::DataCopied(src_id, dst_id));
+            Ok(())
+        }
+
+        /// Like `write_data`, unless the caller made the same write with `idempotency_key`
+        /// within `T::IdempotencyWindow` blocks, in which case nothing is written.
+        #[weight = ByValueLen(T::WeightInfo::write_data)]
+        fn write_data_once(
+            origin,
+            data_id: Vec<u8>,
+            write_data: Vec<u8>,
+            idempotency_key: IdempotencyKey
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            if Self::repeated_write(&user, &idempotency_key, &data_id, &write_data)? {
+                return Ok(());
+            }
+            Self::do_write(user.clone(), data_id.clone(), write_data.clone())?;
+            Self::note_idempotent_write(user, idempotency_key, data_id, &write_data);
+            Ok(())
+        }
+
+        /// Confirm that the value of `data_id` hashing to `content_hash` is uploaded. Meant
+        /// for the offchain workers of `T::Operators`, the only accounts that may call this.
+        fn confirm_upload(origin, data_id: Vec<u8>, content_hash: T::Hash) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(T::Operators::contains(&who), Error::<T>::PermissionDenied);
+            let upload = Self::pending_upload(&data_id).ok_or(Error::<T>::NoPendingUpload)?;
+            // a value written since is confirmed on its own.
+            ensure!(upload.content_hash == content_hash, Error::<T>::NoPendingUpload);
+            Self::finish_upload(&data_id, RequestStatus::Committed(content_hash));
+            Self::deposit_event(RawEvent::UploadConfirmed(data_id));
+            Ok(())
+        }
+
+        /// Apply a confirmation a provider signed, see the confirmation module. Submitted
+        /// as an unsigned extrinsic, so the provider pays no fees.
+        fn confirm_unsigned(
+            origin,
+            confirmation: ProviderConfirmation<T::AccountId, T::Hash, T::BlockNumber>,
+            signature: T::BundleSignature
+        ) -> DispatchResult{
+            ensure_none(origin)?;
+            Self::check_confirmation(&confirmation, &signature)?;
+            Self::apply_confirmation(confirmation);
+            Ok(())
+        }
+
+        /// Report that the value of `data_id` hashing to `content_hash` could not be
+        /// uploaded, dropping it. Meant for the offchain workers of `T::Operators`, the only
+        /// accounts that may call this.
+        fn report_upload_failed(
+            origin,
+            data_id: Vec<u8>,
+            content_hash: T::Hash,
+            reason: StorageError
+        ) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(T::Operators::contains(&who), Error::<T>::PermissionDenied);
+            let upload = Self::pending_upload(&data_id).ok_or(Error::<T>::NoPendingUpload)?;
+            ensure!(upload.content_hash == content_hash, Error::<T>::NoPendingUpload);
+            Self::finish_upload(&data_id, RequestStatus::Failed(reason.code()));
+            Self::deposit_event(RawEvent::WriteFailed(data_id, reason));
+            Ok(())
+        }
+
+        /// Apply `ops` as one: either all of them take effect or none does. See the
+        /// transaction module.
+        #[weight = ByOpsLen(T::WeightInfo::transact)]
+        fn transact(origin, ops: Vec<Op>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            let count = ops.len() as u32;
+            Self::apply_ops(user.clone(), ops)?;
+            Self::deposit_event(RawEvent::Transacted(user, count));
+            Ok(())
+        }
+
+        /// Start uploading a value of `total_chunks` chunks to `data_id`, `root_hash` being
+        /// the merkle root over their hashes. See the chunked module.
+        fn begin_upload(
+            origin,
+            data_id: Vec<u8>,
+            total_chunks: u32,
+            root_hash: T::Hash
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::begin_chunked_upload(user, data_id, total_chunks, root_hash)
+        }
+
+        /// Upload chunk `index` of the value begun with `begin_upload`.
+        #[weight = ByValueLen(T::WeightInfo::upload_chunk)]
+        fn upload_chunk(origin, data_id: Vec<u8>, index: u32, bytes: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::store_chunk(user, data_id, index, bytes)
+        }
+
+        /// Write the value uploaded in chunks to `data_id`, once every chunk is in and they
+        /// match the root given to `begin_upload`.
+        #[weight = UpToValueLen(T::WeightInfo::commit_upload, T::MaxValueLength::get())]
+        fn commit_upload(origin, data_id: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::commit_chunks(user, data_id)
+        }
+
+        /// Pay to read the `len` bytes of the value of `data_id` from `offset` on, e.g. to
+        /// seek in a large value, which the `offchainStorage_readRange` RPC then delivers.
+        #[weight = UpToValueLen(T::WeightInfo::read_data_range, T::MaxValueLength::get())]
+        fn read_data_range(origin, data_id: Vec<u8>, offset: u32, len: u32) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(
+                <Data<T>>::exists(&data_id) && !Self::ttl_passed(&data_id),
+                Error::<T>::NoneData
+            );
+            let data = Self::get_data(&data_id);
+            // values behind a read quorum are only read with the readers' consent.
+            ensure!(
+                Self::may_read(&user, &data_id, data.clone())
+                    && Self::read_quorum(&data_id).is_none(),
+                Error::<T>::PermissionDenied
+            );
+            let end = offset.checked_add(len).ok_or(Error::<T>::BadRange)?;
+            // the sizes of staged values and of values the chain started with are only known
+            // to the RPC.
+            let sized = data.content_hash != T::Hash::default()
+                && !Self::reads_staged(&data_id, &user);
+            ensure!(!sized || end <= data.size, Error::<T>::BadRange);
+            let content_hash = Self::served_hash(&data_id, &user)?;
+            Self::note_read(&data_id, &user, content_hash);
+            Self::deposit_event(RawEvent::GetDataRange(offset, len));
+            Ok(())
+        }
+
+        /// Append `bytes` to the value of `data_id`, or write them if it doesn't exist, as
+        /// `write_data` would write the whole value. See the append module.
+        #[weight = UpToValueLen(T::WeightInfo::append_data, T::MaxValueLength::get())]
+        fn append_data(origin, data_id: Vec<u8>, bytes: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::append_entry(user, data_id, bytes)
+        }
+
+        /// Write `write_data`, encrypted as `encryption` says, to `data_id` as `write_data`
+        /// would. Writes without it leave the value marked as unencrypted.
+        #[weight = ByValueLen(T::WeightInfo::write_data)]
+        fn write_encrypted(
+            origin,
+            data_id: Vec<u8>,
+            write_data: Vec<u8>,
+            encryption: EncryptionInfo,
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(encryption.is_bounded(), Error::<T>::BadEncryptionInfo);
+            let upload = Self::upload_mode();
+            let encryption = Some(encryption);
+            // encrypted values don't compress.
+            Self::write_entry(
+                user,
+                data_id,
+                write_data,
+                Vec::new(),
+                upload,
+                encryption,
+                None,
+                Some(Compression::None),
+            )
+        }
+
+        /// Register the public key the data keys of entries are wrapped to for the caller,
+        /// replacing the one registered before. See the keyring module.
+        fn register_encryption_key(origin, public_key: PublicKey) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            <Keys<T>>::insert(&user, public_key);
+            Self::deposit_event(RawEvent::EncryptionKeyRegistered(user));
+            Ok(())
+        }
+
+        /// Publish the key of `data_id` wrapped to `public_key`, the key `grantee` registered,
+        /// for `grantee` to decrypt the value with. `grantee` must be able to read the entry.
+        /// Only the author may call this.
+        fn share_key(
+            origin,
+            data_id: Vec<u8>,
+            grantee: T::AccountId,
+            public_key: PublicKey,
+            wrapped: Vec<u8>
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::share_wrapped_key(user, data_id, grantee, public_key, wrapped)
+        }
+
+        /// Reserve the data_id prefix `name` for the caller, see the namespace module.
+        fn create_namespace(origin, name: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::reserve_namespace(user.clone(), name.clone())?;
+            Self::deposit_event(RawEvent::NamespaceCreated(name, user));
+            Ok(())
+        }
+
+        /// Let `grantee` create entries in the namespace `name`, or stop it with `false`.
+        /// Only the owner may call this.
+        fn set_namespace_grant(
+            origin,
+            name: Vec<u8>,
+            grantee: T::AccountId,
+            allowed: bool
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            let ns = Self::namespace(&name).ok_or(Error::<T>::NoNamespace)?;
+            ensure!(ns.owner == user, Error::<T>::PermissionDenied);
+            if allowed {
+                <NamespaceGrants<T>>::insert(&name, &grantee, true);
+            } else {
+                <NamespaceGrants<T>>::remove(&name, &grantee);
+            }
+            Ok(())
+        }
+
+        /// Release the namespace `name` and its deposit. Only the owner may call this.
+        fn remove_namespace(origin, name: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::release_namespace(&user, &name)?;
+            Self::deposit_event(RawEvent::NamespaceRemoved(name));
+            Ok(())
+        }
+
+        /// Write `write_data` to `data_id` as `write_data` would, creating the entry in the
+        /// backend `backend` of `T::Backends`, or `T::Storage` for 0. An entry that exists
+        /// must already be kept there. See the routing module.
+        #[weight = ByValueLen(T::WeightInfo::write_data)]
+        fn write_data_to(
+            origin,
+            data_id: Vec<u8>,
+            write_data: Vec<u8>,
+            backend: BackendId,
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(Self::has_backend(backend), Error::<T>::NoBackend);
+            ensure!(
+                !<Data<T>>::exists(&data_id) || Self::get_data(&data_id).backend == backend,
+                Error::<T>::BackendMismatch
+            );
+            let (upload, backend) = (Self::upload_mode(), Some(backend));
+            Self::write_entry(
+                user,
+                data_id,
+                write_data,
+                Vec::new(),
+                upload,
+                None,
+                backend,
+                None,
+            )
+        }
+
+        /// Name the backend `backend` `name` in URIs, or by its id again if `name` is empty.
+        /// Must be called by `T::AdminOrigin`. URIs given out before stop resolving.
+        fn set_backend_name(origin, backend: BackendId, name: Vec<u8>) -> DispatchResult{
+            T::AdminOrigin::ensure_origin(origin)?;
+            ensure!(Self::has_backend(backend), Error::<T>::NoBackend);
+            if name.is_empty() {
+                BackendNames::remove(backend);
+                return Ok(());
+            }
+            ensure!(Self::fits_data_id(&name), Error::<T>::KeyTooLong);
+            // a number would read as the id of a backend.
+            ensure!(!name.iter().all(u8::is_ascii_digit), Error::<T>::BadBackendName);
+            ensure!(
+                Self::backend_name(backend) == name || !BackendNames::iter().any(|n| n == name),
+                Error::<T>::BadBackendName
+            );
+            BackendNames::insert(backend, name);
+            Ok(())
+        }
+
+        /// Write `write_data` to `data_id` as `write_data` would, storing it compressed with
+        /// `compression` instead of `T::DefaultCompression`, e.g. `Compression::None` for
+        /// values that don't compress. See the compression module.
+        #[weight = ByValueLen(T::WeightInfo::write_data)]
+        fn write_data_compressed(
+            origin,
+            data_id: Vec<u8>,
+            write_data: Vec<u8>,
+            compression: Compression,
+        ) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            let (upload, compression) = (Self::upload_mode(), Some(compression));
+            Self::write_entry(
+                user,
+                data_id,
+                write_data,
+                Vec::new(),
+                upload,
+                None,
+                None,
+                compression,
+            )
+        }
+
+        /// Keep the value of `data_id`, which the caller must be able to read, pinned in its
+        /// backend, reserving `T::PinDeposit` until `unpin_data`. See the pin module.
+        fn pin_data(origin, data_id: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::pin_entry(user, data_id)
+        }
+
+        /// Drop the caller's pin on `data_id`, unpinning its value if no one else pins it.
+        fn unpin_data(origin, data_id: Vec<u8>) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            Self::unpin_entry(user, data_id)
+        }
+
+        /// Turn the audit trail of `data_id` on or off. Only the author may call this.
+        fn set_audit(origin, data_id: Vec<u8>, on: bool) -> DispatchResult{
+            let user = ensure_signed(origin)?;
+            ensure!(<Data<T>>::exists(&data_id), Error::<T>::NoneData);
+            ensure!(Self::get_data(&data_id).author == user, Error::<T>::PermissionDenied);
+            if on {
+                Audited::insert(&data_id, true);
+            } else {
+                Audited::remove(&data_id);
+            }
+            Self::deposit_event(RawEvent::AuditSet(data_id, on));
+            Ok(())
+        }
+
+        /// Confirm that the values of `data_id`, renamed in a transaction, are copied from
+        /// under its old data_id. Meant for the offchain workers of `T::Operators`, the only
+        /// accounts that may call this.
+        fn confirm_copy(origin, data_id: Vec<u8>) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(T::Operators::contains(&who), Error::<T>::PermissionDenied);
+            let data = Self::kept_data(&data_id).ok_or(Error::<T>::NoneData)?;
+            ensure!(Self::finish_copy(&data_id, &data), Error::<T>::NoPendingUpload);
+            Self::deposit_event(RawEvent::ValuesCopied(data_id));
+            Ok(())
+        }
+
+        /// Let the registered `provider` answer challenges, keep replicas and bond stake. Must
+        /// be called by `T::AdminOrigin`. Deregistering drops the approval.
+        fn approve_provider(origin, provider: T::AccountId) -> DispatchResult{
+            T::AdminOrigin::ensure_origin(origin)?;
+            let mut info = Self::provider_info(&provider).ok_or(Error::<T>::NotRegistered)?;
+            info.approved = true;
+            <Providers<T>>::insert(&provider, info);
+            Self::deposit_event(RawEvent::ProviderApproved(provider));
+            Ok(())
+        }
+
+        /// Note that the offchain worker started uploading the value of `data_id` hashing to
+        /// `content_hash`, see the status module. Meant for the offchain workers of
+        /// `T::Operators`, the only accounts that may call this.
+        fn start_upload(origin, data_id: Vec<u8>, content_hash: T::Hash) -> DispatchResult{
+            let who = ensure_signed(origin)?;
+            ensure!(T::Operators::contains(&who), Error::<T>::PermissionDenied);
+            let upload = Self::pending_upload(&data_id).ok_or(Error::<T>::NoPendingUpload)?;
+            ensure!(upload.content_hash == content_hash, Error::<T>::NoPendingUpload);
+            if Self::upload_status(upload.id) == Some(RequestStatus::Queued) {
+                Self::set_upload_status(upload.id, RequestStatus::Executing);
+            }
+            Ok(())
         }
     }
 }
 
 impl<T: Trait> Module<T> {
+    /// Check a chunk of a large value, fetched off-chain, against the merkle root on chain.
+    /// `proof` is what `merkle::proof` returns for the chunk.
+    pub fn verify_chunk(data_id: &[u8], index: u32, chunk: &[u8], proof: &[T::Hash]) -> bool {
+        match Self::chunk_root(data_id) {
+            Some((root, count)) => merkle::verify::<T::Hashing>(
+                &root,
+                count as usize,
+                index as usize,
+                T::Hashing::hash(chunk),
+                proof,
+            ),
+            None => false,
+        }
+    }
+
+    // close the challenges ended by block n that nobody answered, as many as the block takes.
+    fn expire_challenges(n: T::BlockNumber) {
+        let ending = Self::take_expired::<ChallengeDeadlines<T>, _>(Expiry::Challenge, n);
+        for (deadline, data_id) in ending {
+            let open = Self::challenge_of(&data_id).filter(|c| c.deadline == deadline);
+            if let Some(open) = open {
+                <Challenges<T>>::remove(&data_id);
+                MissedChallenges::mutate(&data_id, |m| *m += 1);
+                for provider in Self::get_data(&data_id).providers {
+                    Self::note_metric(&provider, |m| m.failed += 1);
+                    Self::slash_provider(&provider, &data_id);
+                }
+                Self::deposit_event(RawEvent::ChallengeExpired(data_id, open.nonce));
+            }
+        }
+    }
+
     // check user's operation access
-    fn check_op_access(user: T::AccountId, data: UserData<T::AccountId>, op: Access) -> bool {
+    fn check_op_access(
+        user: T::AccountId,
+        data: UserData<T::AccountId, T::Hash, T::BlockNumber>,
+        op: Access,
+    ) -> bool {
         // User must have a higher access level than the data has.
         // Or the user is author itself.
         access_value(data.access) >= access_value(op) || user == data.author
     }
 
-    fn get_external_storage(data_id: Vec<u8>) -> Vec<u8> {
-        T::Storage::get(data_id)
+    /// Check whether `user` may write a value of `len` bytes to `data_id`, without writing.
+    /// Backs the `validate_write` runtime API, `write_data` fails exactly when this does.
+    pub fn validate_write(
+        user: &T::AccountId,
+        data_id: &[u8],
+        len: u32,
+    ) -> Result<FeeEstimate<BalanceOf<T>>, ValidationError> {
+        ensure!(Self::fits_data_id(data_id), ValidationError::KeyTooLong);
+        ensure!(
+            <Data<T>>::exists(data_id) || Self::is_typed(data_id),
+            ValidationError::BadDataId
+        );
+        ensure!(len <= T::MaxValueLength::get(), ValidationError::ValueTooLong);
+        ensure!(!<Mirrors<T>>::exists(data_id), ValidationError::ReadOnlyMirror);
+        ensure!(!<Trash<T>>::exists(data_id), ValidationError::Trashed);
+        ensure!(!CopySources::exists(data_id), ValidationError::CopyPending);
+        ensure!(!Self::is_expired(data_id), ValidationError::Expired);
+        ensure!(
+            !Self::is_protected(data_id) || Self::is_attested(user),
+            ValidationError::Unattested
+        );
+        let now = <system::Module<T>>::block_number();
+        ensure!(
+            Self::writes_in_block(now, user) < T::MaxWritesPerBlock::get(),
+            ValidationError::RateLimited
+        );
+        let (kind, author) = if <Data<T>>::exists(data_id) {
+            (WriteKind::Update, Self::get_data(data_id).author)
+        } else {
+            (WriteKind::Create, user.clone())
+        };
+        ensure!(
+            kind == WriteKind::Update || Self::owned_count(user) < T::MaxEntriesPerAccount::get(),
+            ValidationError::TooManyEntries
+        );
+        ensure!(
+            kind == WriteKind::Update || Self::may_create(user, data_id),
+            ValidationError::NamespaceReserved
+        );
+        if kind == WriteKind::Update && !Self::may_write(user, data_id, Self::get_data(data_id)) {
+            ensure!(
+                <WriteDelegations<T>>::exists(data_id, user),
+                ValidationError::PermissionDenied
+            );
+            ensure!(
+                Self::write_delegations(data_id, user) > 0,
+                ValidationError::DelegationExhausted
+            );
+        }
+        let chunk_size = T::ChunkSize::get();
+        let chunks = if len > chunk_size { (len + chunk_size - 1) / chunk_size } else { 0 };
+        let (new, old) = (Self::deposit_for(len), Self::deposit_of(data_id));
+        let deposit = if new > old { new - old } else { Zero::zero() };
+        ensure!(
+            T::Currency::can_reserve(&author, deposit),
+            ValidationError::InsufficientBalance
+        );
+        let used = Self::used_bytes(&author).saturating_sub(u64::from(Self::size_of(data_id)));
+        ensure!(
+            used.saturating_add(u64::from(len)) <= T::MaxBytesPerAccount::get(),
+            ValidationError::ByteQuotaExceeded
+        );
+        Ok(FeeEstimate { kind, chunks, deposit })
     }
 
-    fn set_external_storage(data_id: Vec<u8>, data: Vec<u8>) {
-        T::Storage::set(data_id, data)
+    /// Whether `data_id` is no longer than `T::MaxDataIdLength`. Entries are only created
+    /// through calls that check this, so other calls never find one with a longer data_id.
+    pub fn fits_data_id(data_id: &[u8]) -> bool {
+        data_id.len() as u32 <= T::MaxDataIdLength::get()
     }
 
-    fn delete_external_storage(data_id: Vec<u8>) {
-        T::Storage::delete(data_id)
+    /// Whether `data_id` may name a new entry as `T::TypedDataIds` says: any does with it
+    /// off, only the key of a well-formed `DataId` with it on.
+    pub fn is_typed(data_id: &[u8]) -> bool {
+        !T::TypedDataIds::get() || DataId::from_key(data_id).is_some()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    use frame_support::{assert_ok, impl_outer_origin, parameter_types, weights::Weight};
-    use sp_core::H256;
-    use sp_runtime::{
-        testing::Header,
-        traits::{BlakeTwo256, IdentityLookup},
-        Perbill,
-    };
-    use sp_std::str;
-
-    impl_outer_origin! {
-        pub enum Origin for Test {}
-    }
-
-    // For testing the module, we construct most of a mock runtime. This means
-    // first constructing a configuration type (`Test`) which `impl`s each of the
-    // configuration traits of modules we want to use.
-    #[derive(Clone, Eq, PartialEq)]
-    pub struct Test;
-    parameter_types! {
-        pub const BlockHashCount: u64 = 250;
-        pub const MaximumBlockWeight: Weight = 1024;
-        pub const MaximumBlockLength: u32 = 2 * 1024;
-        pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
-    }
-    impl system::Trait for Test {
-        type Origin = Origin;
-        type Call = ();
-        type Index = u64;
-        type BlockNumber = u64;
-        type Hash = H256;
-        type Hashing = BlakeTwo256;
-        type AccountId = u64;
-        type Lookup = IdentityLookup<Self::AccountId>;
-        type Header = Header;
-        type Event = ();
-        type BlockHashCount = BlockHashCount;
-        type MaximumBlockWeight = MaximumBlockWeight;
-        type MaximumBlockLength = MaximumBlockLength;
-        type AvailableBlockRatio = AvailableBlockRatio;
-        type Version = ();
-        type ModuleToIndex = ();
-    }
-    impl Trait for Test {
-        type Event = ();
-        type Storage = DB;
-    }
-    // Simulate a external database.
-    pub struct DB;
-
-    use std::fs;
-    use std::fs::File;
-    use std::io::prelude::*;
-
-    impl ExternalStorage for DB {
-        fn get(key: Vec<u8>) -> Vec<u8> {
-            let mut f = File::open(str::from_utf8(key.as_slice()).unwrap()).unwrap();
-            let ref mut value: Vec<u8> = Vec::new();
-            f.read_to_end(value).unwrap();
-            value.to_vec()
-        }
-
-        fn set(key: Vec<u8>, value: Vec<u8>) {
-            let mut f = File::create(str::from_utf8(key.as_slice()).unwrap()).unwrap();
-            f.write(value.as_slice()).unwrap();
-        }
-
-        fn delete(key: Vec<u8>) {
-            fs::remove_file(str::from_utf8(key.as_slice()).unwrap()).unwrap();
-        }
-    }
-
-    type OffchainStorage = Module<Test>;
-
-    // This function basically just builds a genesis storage key/value store according to
-    // our desired mockup.
-    fn new_test_ext() -> sp_io::TestExternalities {
-        frame_system::GenesisConfig::default()
-            .build_storage::<Test>()
-            .unwrap()
-            .into()
-    }
-
-    #[test]
-    fn do_external_storage() {
-        new_test_ext().execute_with(|| {
-            let key: Vec<u8> = b"key".to_vec();
-            let value: Vec<u8> = b"key".to_vec();
-            assert_ok!(OffchainStorage::write_data(
-                Origin::signed(1),
-                key.clone(),
-                value
-            ));
-            assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
-            assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key));
+    /// The length of the value of `data_id` as counted against the byte quota of its author,
+    /// that rent is charged for.
+    pub fn size_of(data_id: &[u8]) -> u32 {
+        Self::rent_of(data_id).map_or(0, |rent| rent.size)
+    }
+
+    /// The author, access, length of the value and content hash of `data_id`, if it exists.
+    /// Backs the `get_metadata` runtime API.
+    pub fn metadata(data_id: &[u8]) -> Option<(T::AccountId, Access, u32, T::Hash)> {
+        if !<Data<T>>::exists(data_id) {
+            return None;
+        }
+        let data = Self::get_data(data_id);
+        Some((data.author, data.access, Self::size_of(data_id), data.content_hash))
+    }
+
+    /// The deposit for an entry holding `len` bytes.
+    pub fn deposit_for(len: u32) -> BalanceOf<T> {
+        T::DepositBase::get() + T::DepositPerByte::get() * BalanceOf::<T>::from(len)
+    }
+
+    /// Like `validate_write`, for `write_content_addressed` of a value of `len` bytes
+    /// hashing to `content_hash`. Backs the `validate_content_addressed` runtime API.
+    pub fn validate_content_addressed(
+        user: &T::AccountId,
+        content_hash: &T::Hash,
+        len: u32,
+    ) -> Result<FeeEstimate<BalanceOf<T>>, ValidationError> {
+        let data_id = content_hash.encode();
+        if <Data<T>>::exists(&data_id) {
+            // write_content_addressed leaves stored content untouched.
+            return Ok(FeeEstimate {
+                kind: WriteKind::Duplicate,
+                chunks: 0,
+                deposit: Zero::zero(),
+            });
+        }
+        Self::validate_write(user, &data_id, len)
+    }
+
+    fn do_write(user: T::AccountId, data_id: Vec<u8>, write_data: Vec<u8>) -> DispatchResult {
+        Self::do_write_with_message(user, data_id, write_data, Vec::new())
+    }
+
+    fn do_write_with_message(
+        user: T::AccountId,
+        data_id: Vec<u8>,
+        write_data: Vec<u8>,
+        message: Vec<u8>,
+    ) -> DispatchResult {
+        let upload = Self::upload_mode();
+        Self::write_entry(user, data_id, write_data, message, upload, None, None, None)
+    }
+
+    // how writes store their value, see T::DeferredUploads.
+    fn upload_mode() -> Upload {
+        if T::DeferredUploads::get() {
+            Upload::Deferred
+        } else {
+            Upload::Now
+        }
+    }
+
+    // write the value of data_id, leaving the upload to the offchain worker if deferred. An
+    // entry created is kept in backend, the default one if None. The value is compressed as
+    // compression says, T::DefaultCompression if None.
+    pub(crate) fn write_entry(
+        user: T::AccountId,
+        data_id: Vec<u8>,
+        write_data: Vec<u8>,
+        message: Vec<u8>,
+        upload: Upload,
+        encryption: Option<EncryptionInfo>,
+        backend: Option<BackendId>,
+        compression: Option<Compression>,
+    ) -> DispatchResult {
+        let len = write_data.len() as u32;
+        let compression = Self::compression_for(compression)?;
+        // rent for the old value is due before it changes, validation fails if it expires.
+        Self::charge_rent(&data_id);
+        let estimate = Self::validate_write(&user, &data_id, len).map_err(Error::<T>::from)?;
+        let now = <system::Module<T>>::block_number();
+        let mut data = if estimate.kind == WriteKind::Create {
+            UserData {
+                author: user.clone(),
+                created_at: now,
+                backend: backend.unwrap_or(DEFAULT_BACKEND),
+                ..Default::default()
+            }
+        } else {
+            Self::get_data(&data_id)
+        };
+        data.content_hash = T::Hashing::hash(&write_data);
+        data.size = len;
+        data.updated_at = now;
+        data.encryption = encryption;
+        // appends only add to a value stored as it is.
+        let appendable = Self::appendable(&data_id) && compression == Compression::None;
+        data.compression = compression;
+        if upload == Upload::Deferred {
+            // the upload stores the value under data_id.
+            Self::queue_release(&data_id, data.backend);
+            Self::defer_upload(&data_id, write_data.clone());
+        } else {
+            // the backends are written first, nothing changes on chain if they fail.
+            Self::store_version(&data_id, write_data.clone())?;
+            let stored = match upload {
+                Upload::Append(added) if appendable => {
+                    Self::append_external_storage(&data_id, &write_data, added, &data)
+                }
+                _ => Self::set_external_storage(&data_id, write_data.clone(), &data),
+            };
+            if let Err(e) = stored {
+                Self::drop_version(&data_id);
+                return Err(e.into());
+            }
+        }
+        // the author pays for the space, even for writes of a delegate.
+        let (old, new) = (Self::deposit_of(&data_id), Self::deposit_for(len));
+        if new > old {
+            T::Currency::reserve(&data.author, new - old)?;
+        } else if old > new {
+            let _ = T::Currency::unreserve(&data.author, old - new);
+        }
+        <Deposits<T>>::insert(&data_id, new);
+        if !Self::may_write(&user, &data_id, data.clone()) {
+            Self::use_delegated_write(&data_id, &user)?;
+        }
+        let chunk_size = T::ChunkSize::get() as usize;
+        if write_data.len() > chunk_size {
+            let leaves = merkle::leaves::<T::Hashing>(&write_data, chunk_size);
+            let root = merkle::root::<T::Hashing>(&leaves);
+            <ChunkRoots<T>>::insert(&data_id, (root, leaves.len() as u32));
+        } else {
+            <ChunkRoots<T>>::remove(&data_id);
+        }
+        // a signature over the previous value doesn't vouch for this one.
+        <ContentSignatures<T>>::remove(&data_id);
+        Self::release_bytes(&data.author, Self::size_of(&data_id));
+        Self::use_bytes(&data.author, len);
+        <Rent<T>>::insert(&data_id, RentInfo { size: len, charged_until: now, expired_at: None });
+        Self::note_revision(&data_id, user.clone(), data.content_hash, message);
+        <Data<T>>::insert(&data_id, &data);
+        if estimate.kind == WriteKind::Create {
+            Self::count_entry(&user);
+            Self::list_owned(&user, &data_id);
+        }
+        <WritesInBlock<T>>::mutate(now, &user, |n| *n += 1);
+        Self::audit(&data_id, &user, AuditOp::Write);
+        T::OnDataWritten::on_data_written(&data.author, &data_id, len);
+        Self::deposit_event(RawEvent::DataWritten(user, data_id, len));
+        Ok(())
+    }
+
+    fn do_delete(user: T::AccountId, data_id: Vec<u8>) -> DispatchResult {
+        Self::ensure_may_delete(&user, &data_id)?;
+        Self::audit(&data_id, &user, AuditOp::Deleted);
+        let period = T::TrashPeriod::get();
+        if period.is_zero() {
+            Self::remove_entry(&data_id)?;
+        }else{
+            let now = <system::Module<T>>::block_number();
+            Self::trash_entry(&data_id, now.saturating_add(period));
+        }
+        Self::deposit_event(RawEvent::DataDeleted(user, data_id));
+        Ok(())
+    }
+
+    // check that user may delete data_id.
+    pub(crate) fn ensure_may_delete(user: &T::AccountId, data_id: &[u8]) -> Result<(), Error<T>> {
+        ensure!(!<Mirrors<T>>::exists(data_id), Error::<T>::ReadOnlyMirror);
+        ensure!(<Data<T>>::exists(data_id), Error::<T>::NoneData);
+        ensure!(
+            Self::check_op_access(user.clone(), Self::get_data(data_id), Access::Write),
+            Error::<T>::PermissionDenied
+        );
+        Ok(())
+    }
+
+    // remove data_id from the backends along with everything kept about it.
+    fn remove_entry(data_id: &[u8]) -> Result<(), Error<T>> {
+        Self::delete_external_storage(data_id, &Self::get_data(data_id))?;
+        Self::forget_entry(data_id);
+        Self::drop_audit(data_id);
+        Ok(())
+    }
+
+    // like remove_entry, leaving the backends to the garbage collector.
+    fn evict_entry(data_id: &[u8]) {
+        Self::queue_deletion(data_id, &Self::get_data(data_id));
+        Self::forget_entry(data_id);
+        Self::drop_audit(data_id);
+    }
+
+    // remove everything kept about data_id on chain but its audit trail, its value must be
+    // deleted already. a staged value is deleted here.
+    fn forget_entry(data_id: &[u8]) {
+        Self::drop_staged(data_id);
+        let data = Self::get_data(data_id);
+        Self::finish_copy(data_id, &data);
+        let deposit = <Deposits<T>>::take(data_id);
+        if !deposit.is_zero() {
+            let _ = T::Currency::unreserve(&data.author, deposit);
+        }
+        <WriteDelegations<T>>::remove_prefix(data_id);
+        Self::abandon_upload(data_id);
+        <ReplicaConfirmations<T>>::remove_prefix(data_id);
+        <ChunkRoots<T>>::remove(data_id);
+        <ContentSignatures<T>>::remove(data_id);
+        <Challenges<T>>::remove(data_id);
+        if let Some(rent) = <Rent<T>>::take(data_id) {
+            Self::release_bytes(&data.author, rent.size);
+        }
+        <ReadPrices<T>>::remove(data_id);
+        <ReadGrants<T>>::remove_prefix(data_id);
+        <SessionGrants<T>>::remove_prefix(data_id);
+        <Grantees<T>>::remove(data_id);
+        OperatorAccess::remove(data_id);
+        ReadQuorums::remove(data_id);
+        <PendingReads<T>>::remove_prefix(data_id);
+        <Agreements<T>>::remove_prefix(data_id);
+        Self::set_ttl(data_id, None);
+        Self::forget_revisions(data_id);
+        <Reviewers<T>>::remove(data_id);
+        Self::drop_pins(data_id);
+        <ReadReceipts<T>>::remove_prefix(data_id);
+        DataKeyrings::remove(data_id);
+        <WrappedKeys<T>>::remove_prefix(data_id);
+        <SharedKeys<T>>::remove_prefix(data_id);
+        if <GrantedEntries<T>>::exists(&data.author) {
+            <GrantedEntries<T>>::mutate(&data.author, |ids| ids.retain(|id| id[..] != *data_id));
+        }
+        // mirrors are authored on another chain and not counted here.
+        if <Mirrors<T>>::take(data_id).is_none() {
+            Self::uncount_entry(&data.author);
+            Self::unlist_owned(&data.author, data_id);
+        }
+        <Data<T>>::remove(data_id);
+        T::OnDataDeleted::on_data_deleted(&data.author, data_id, data.size);
+    }
+
+    // count a new entry of author, against its quota and in the totals.
+    pub(crate) fn count_entry(author: &T::AccountId) {
+        <OwnedCount<T>>::mutate(author, |n| *n += 1);
+        TotalEntries::mutate(|n| *n = n.saturating_add(1));
+    }
+
+    // stop counting an entry of author.
+    pub(crate) fn uncount_entry(author: &T::AccountId) {
+        <OwnedCount<T>>::mutate(author, |n| *n = n.saturating_sub(1));
+        TotalEntries::mutate(|n| *n = n.saturating_sub(1));
+    }
+
+    // count len bytes against the byte quota of author and in the totals.
+    fn use_bytes(author: &T::AccountId, len: u32) {
+        <UsedBytes<T>>::mutate(author, |n| *n = n.saturating_add(u64::from(len)));
+        TotalBytesStored::mutate(|n| *n = n.saturating_add(u64::from(len)));
+    }
+
+    // take len bytes off the byte quota used by author.
+    pub(crate) fn release_bytes(author: &T::AccountId, len: u32) {
+        <UsedBytes<T>>::mutate(author, |n| *n = n.saturating_sub(u64::from(len)));
+        TotalBytesStored::mutate(|n| *n = n.saturating_sub(u64::from(len)));
+    }
+
+    /// How many entries the chain counts and how many bytes their values take. Backs the
+    /// `statistics` runtime API.
+    pub fn statistics() -> Statistics {
+        Statistics { entries: Self::total_entries(), bytes: Self::total_bytes_stored() }
+    }
+
+    /// How the value of `data_id` is encrypted, if it exists and its writer said so. Backs
+    /// the `encryption` runtime API.
+    pub fn encryption_of(data_id: &[u8]) -> Option<EncryptionInfo> {
+        Self::get_data(data_id).encryption
+    }
+
+    /// The canonical URI of the value of `data_id`, with its content hash, if it exists.
+    /// Backs the `uri` runtime API.
+    pub fn uri_of(data_id: &[u8]) -> Option<Vec<u8>> {
+        if !<Data<T>>::exists(data_id) {
+            return None;
+        }
+        let data = Self::get_data(data_id);
+        let reference = Reference {
+            provider: Self::provider_name(data.backend),
+            data_id: data_id.to_vec(),
+            hash: Some(data.content_hash.as_ref().to_vec()),
+        };
+        Some(reference.to_uri())
+    }
+
+    /// The data_id `uri` references, if it is a canonical URI of an entry that exists, is
+    /// kept in the backend it names and, if it has a hash, still has that value.
+    pub fn resolve_uri(uri: &[u8]) -> Option<Vec<u8>> {
+        let reference = Reference::parse(uri)?;
+        if !<Data<T>>::exists(&reference.data_id) || Self::ttl_passed(&reference.data_id) {
+            return None;
+        }
+        let data = Self::get_data(&reference.data_id);
+        let current = reference.hash.as_ref().map_or(true, |hash| {
+            data.content_hash.as_ref() == &hash[..]
+        });
+        Some(reference.data_id)
+            .filter(|_| current && Self::provider_name(data.backend) == reference.provider)
+    }
+
+    // add signer to the pending read of data_id by reader, releasing the value once
+    // quorum accounts signed.
+    fn co_sign_read(
+        data_id: &[u8],
+        reader: &T::AccountId,
+        signer: T::AccountId,
+        quorum: u32,
+    ) -> DispatchResult {
+        let mut signers = Self::pending_read(data_id, reader);
+        ensure!(!signers.contains(&signer), Error::<T>::AlreadySigned);
+        signers.push(signer);
+        let signed = signers.len() as u32;
+        if signed < quorum {
+            <PendingReads<T>>::insert(data_id, reader, signers);
+            Self::deposit_event(RawEvent::ReadCoSigned(data_id.to_vec(), signed));
+            return Ok(());
+        }
+        let content_hash = Self::served_hash(data_id, reader)?;
+        <PendingReads<T>>::remove(data_id, reader);
+        Self::note_read(data_id, reader, content_hash);
+        Self::deposit_event(RawEvent::GetData(content_hash));
+        Ok(())
+    }
+
+    // remember that author granted something on data_id, for revoke_all_grants.
+    fn note_grant(author: &T::AccountId, data_id: &[u8]) {
+        <GrantedEntries<T>>::mutate(author, |ids| {
+            if !ids.iter().any(|id| id[..] == *data_id) {
+                ids.push(data_id.to_vec());
+            }
         });
     }
+
+    // apply change to the members of keyring and start a new epoch.
+    fn change_members(
+        user: T::AccountId,
+        keyring: u64,
+        change: impl FnOnce(&mut Vec<(T::AccountId, PublicKey)>),
+    ) -> DispatchResult {
+        let mut ring = Self::keyring(keyring).ok_or(Error::<T>::NoKeyring)?;
+        ensure!(ring.owner == user, Error::<T>::PermissionDenied);
+        change(&mut ring.members);
+        ring.epoch += 1;
+        let epoch = ring.epoch;
+        <Keyrings<T>>::insert(keyring, ring);
+        Self::deposit_event(RawEvent::KeyringEpochBumped(keyring, epoch));
+        Ok(())
+    }
+
+    // whether user may read data_id, through its access level or any grant.
+    fn may_read(
+        user: &T::AccountId,
+        data_id: &[u8],
+        data: UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) -> bool {
+        Self::check_op_access(user.clone(), data, Access::Read)
+            || <ReadGrants<T>>::exists(data_id, user)
+            || Self::session_access(data_id, user).is_some()
+            || Self::operator_access_of(data_id, user).is_some()
+    }
+
+    // whether user may write data_id without using up a delegated write.
+    fn may_write(
+        user: &T::AccountId,
+        data_id: &[u8],
+        data: UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) -> bool {
+        Self::check_op_access(user.clone(), data, Access::Write)
+            || Self::session_access(data_id, user) == Some(Access::Write)
+            || Self::operator_access_of(data_id, user) == Some(Access::Write)
+    }
+
+    // the access operators have to data_id, if user is one of them.
+    fn operator_access_of(data_id: &[u8], user: &T::AccountId) -> Option<Access> {
+        Self::operator_access(data_id).filter(|_| T::Operators::contains(user))
+    }
+
+    /// The access `user` was granted to `data_id` for the current session, if any.
+    pub fn session_access(data_id: &[u8], user: &T::AccountId) -> Option<Access> {
+        <SessionGrants<T>>::get(data_id, user)
+            .filter(|(session, _)| *session == T::CurrentSession::get())
+            .map(|(_, access)| access)
+    }
+
+    // consume one of the writes the author delegated to user.
+    fn use_delegated_write(data_id: &Vec<u8>, user: &T::AccountId) -> DispatchResult {
+        ensure!(
+            <WriteDelegations<T>>::exists(data_id, user),
+            Error::<T>::PermissionDenied
+        );
+        let remaining = Self::write_delegations(data_id, user);
+        ensure!(remaining > 0, Error::<T>::DelegationExhausted);
+        <WriteDelegations<T>>::insert(data_id, user, remaining - 1);
+        Ok(())
+    }
+
+    // the value of data_id from the first backend that returns it intact. Fails with
+    // ExternalError if none does and the backend of the entry failed.
+    fn get_external_storage(
+        data_id: &[u8],
+        data: &UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) -> Result<Vec<u8>, Error<T>> {
+        if data.content_hash == T::Hash::default() {
+            return Self::adopt_legacy_value(data_id);
+        }
+        if let Some(upload) = Self::pending_upload(data_id) {
+            return Ok(upload.value);
+        }
+        // a value renamed in a transaction is read from its old data_id until it is copied.
+        let source = Self::value_source(data_id, &data.content_hash);
+        let data_id = &source[..];
+        let intact = |value: &Vec<u8>| T::Hashing::hash(value) == data.content_hash;
+        let mut failure = None;
+        let mut get = |key: Vec<u8>| {
+            Self::routed_get(data.backend, key).map_err(|reason| failure = Some(reason)).ok()
+        };
+        let value = match Self::shards_of(data_id) {
+            Some(info) => {
+                // corrupted shards count as missing.
+                let shards: Vec<Option<Vec<u8>>> = info
+                    .hashes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, hash)| {
+                        get(info.key(data_id, i as u8))
+                            .filter(|shard| T::Hashing::hash(shard) == *hash)
+                    })
+                    .collect();
+                erasure::decode(&shards, info.k, info.len as usize).unwrap_or_default()
+            }
+            None => get(Self::value_key(data_id)).unwrap_or_default(),
+        };
+        // a value that doesn't decompress is as corrupted as one that doesn't hash right.
+        let value = T::Compressor::decompress(data.compression, value).unwrap_or_default();
+        if intact(&value) {
+            return Ok(value);
+        }
+        let replica = data
+            .providers
+            .iter()
+            .map(|p| (p, T::Replicas::get(p, data_id.to_vec())))
+            .find(|(_, value)| intact(value));
+        match (replica, failure) {
+            (Some((provider, value)), _) => {
+                Self::note_metric(provider, |m| m.served_reads += 1);
+                Ok(value)
+            }
+            (None, Some(reason)) => Self::backend(data_id, Err(reason)),
+            (None, None) => Err(Error::<T>::IntegrityError),
+        }
+    }
+
+    // the value under key in T::Storage, which must hash to content_hash.
+    fn get_intact(
+        data_id: &[u8],
+        key: Vec<u8>,
+        content_hash: &T::Hash,
+    ) -> Result<Vec<u8>, Error<T>> {
+        let value = Self::backend(data_id, T::Storage::get(key))?;
+        ensure!(T::Hashing::hash(&value) == *content_hash, Error::<T>::IntegrityError);
+        Ok(value)
+    }
+
+    // store value as the value of data_id, in the backend compressed as data says and as it
+    // is with the providers of data.
+    fn set_external_storage(
+        data_id: &[u8],
+        value: Vec<u8>,
+        data: &UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) -> Result<(), Error<T>> {
+        let backend = data.backend;
+        let stored = T::Compressor::compress(data.compression, value.clone());
+        match T::ErasureCoding::get() {
+            Some((k, n)) => {
+                let shards = erasure::encode(&stored, k, n);
+                let hashes = shards.iter().map(|shard| T::Hashing::hash(shard)).collect();
+                // the value before stays readable until every new shard is stored.
+                let old = Self::shards_of(data_id);
+                let version = old.as_ref().map_or(1, |info| info.version.saturating_add(1));
+                for (i, shard) in shards.into_iter().enumerate() {
+                    let key = erasure::shard_key(data_id, version, i as u8);
+                    if let Err(e) = Self::backend(data_id, Self::routed_set(backend, key, shard)) {
+                        let written = (0..=i as u8)
+                            .map(|j| GcItem::stored(backend, data_id, Some((version, j))));
+                        Self::queue_items(written.collect());
+                        return Err(e);
+                    }
+                }
+                let info = ShardInfo { k, len: stored.len() as u32, hashes, version };
+                <Shards<T>>::insert(data_id, info);
+                // the old shards, or the value stored whole, go to the garbage collector.
+                match old {
+                    Some(old) => Self::queue_shards(data_id, backend, &old),
+                    None if SharedValues::exists(data_id) => Self::queue_release(data_id, backend),
+                    None if Self::kept_data(data_id).is_some() => {
+                        Self::queue_items(sp_std::vec![GcItem::stored(backend, data_id, None)])
+                    }
+                    None => {}
+                }
+            }
+            None if T::Deduplication::get() => {
+                Self::store_shared(data_id, stored, data)?;
+                Self::delete_shards(data_id, backend)?;
+            }
+            None => {
+                let stored = Self::routed_set(backend, data_id.to_vec(), stored);
+                Self::backend(data_id, stored)?;
+                Self::release_shared(data_id, backend)?;
+                Self::delete_shards(data_id, backend)?;
+            }
+        }
+        if <Pins<T>>::exists(data_id) {
+            Self::backend(data_id, Self::pin_value(data_id, backend))?;
+        }
+        for p in &data.providers {
+            T::Replicas::set(p, data_id.to_vec(), value.clone());
+        }
+        Ok(())
+    }
+
+    // delete the value of data_id from the backend and the providers of data.
+    fn delete_external_storage(
+        data_id: &[u8],
+        data: &UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) -> Result<(), Error<T>> {
+        if SharedValues::exists(data_id) {
+            Self::release_shared(data_id, data.backend)?;
+        } else if !Self::delete_shards(data_id, data.backend)? {
+            Self::backend(data_id, Self::routed_delete(data.backend, data_id.to_vec()))?;
+        }
+        for p in &data.providers {
+            T::Replicas::delete(p, data_id.to_vec());
+        }
+        Ok(())
+    }
+
+    // delete the shards of data_id from backend, returns whether it had any.
+    fn delete_shards(data_id: &[u8], backend: BackendId) -> Result<bool, Error<T>> {
+        match Self::shards_of(data_id) {
+            Some(info) => {
+                for i in 0..info.hashes.len() {
+                    let key = info.key(data_id, i as u8);
+                    Self::backend(data_id, Self::routed_delete(backend, key))?;
+                }
+                <Shards<T>>::remove(data_id);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // turn a failure of T::Storage on data_id into ExternalError, emitting the reason.
+    fn backend<R>(data_id: &[u8], result: Result<R, StorageError>) -> Result<R, Error<T>> {
+        result.map_err(|reason| {
+            Self::deposit_event(RawEvent::ExternalStorageFailed(data_id.to_vec(), reason));
+            Error::<T>::ExternalError
+        })
+    }
 }
+
+#[cfg(test)]
+mod mock;
+
+#[cfg(test)]
+mod tests;
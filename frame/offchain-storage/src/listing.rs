@@ -0,0 +1,59 @@
+//! Listing the entries of an author.
+//!
+//! Every live entry is listed at a position in the listing of its author, counting up in
+//! the order the entries are created. A renamed entry keeps its position, a restored one is
+//! listed anew. Positions are never reused, so a [`Page`] picks up where the page before it
+//! stopped, whatever was created or deleted in between. Deleted entries leave gaps, which
+//! pages skip over.
+
+use super::{Module, NextOwnedPosition, OwnedEntries, OwnedPositions, Page, Trait};
+use frame_support::{StorageDoubleMap, StorageMap};
+use sp_std::vec::Vec;
+
+impl<T: Trait> Module<T> {
+    /// The data_ids of the live entries `account` authored, in the order they are listed.
+    pub fn owned_by(account: T::AccountId) -> Vec<Vec<u8>> {
+        (0..Self::next_owned_position(&account))
+            .filter_map(|position| Self::owned_entry(&account, position))
+            .collect()
+    }
+
+    /// Up to `limit` of the entries `owned_by` returns, from the position `cursor` on. Backs
+    /// the `owned_page` runtime API.
+    pub fn owned_page(account: T::AccountId, cursor: u64, limit: u32) -> Page {
+        let end = Self::next_owned_position(&account);
+        let mut entries = Vec::new();
+        let mut position = cursor;
+        while position < end && (entries.len() as u32) < limit {
+            entries.extend(Self::owned_entry(&account, position));
+            position += 1;
+        }
+        let next = if position < end { Some(position) } else { None };
+        Page { entries, next }
+    }
+
+    // list data_id, a new live entry of author, after its other entries.
+    pub(crate) fn list_owned(author: &T::AccountId, data_id: &[u8]) {
+        let position = <NextOwnedPosition<T>>::mutate(author, |next| {
+            *next += 1;
+            *next - 1
+        });
+        <OwnedEntries<T>>::insert(author, position, data_id.to_vec());
+        OwnedPositions::insert(data_id, position);
+    }
+
+    // take data_id off the listing of its author.
+    pub(crate) fn unlist_owned(author: &T::AccountId, data_id: &[u8]) {
+        if let Some(position) = OwnedPositions::take(data_id) {
+            <OwnedEntries<T>>::remove(author, position);
+        }
+    }
+
+    // list new where old, renamed to new, was listed.
+    pub(crate) fn relist_owned(author: &T::AccountId, old: &[u8], new: &[u8]) {
+        if let Some(position) = OwnedPositions::take(old) {
+            <OwnedEntries<T>>::insert(author, position, new.to_vec());
+            OwnedPositions::insert(new, position);
+        }
+    }
+}
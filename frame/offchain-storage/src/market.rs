@@ -0,0 +1,117 @@
+//! Storage market.
+//!
+//! Authors post requests to have an entry stored: how many bytes, for how many blocks and
+//! at most at what price per byte and block. Providers post offers: how many bytes they
+//! still take and at what price. Whenever either side posts, open requests are matched
+//! in the order they were posted with the cheapest offer that has room and fits the
+//! price. A match records an agreement, takes the space from the offer and makes the
+//! provider keep a replica of the entry. Authors reserve a deposit with each request,
+//! released when it is matched or when they cancel it while it is still open. Requests
+//! whose matches fall through end up in the dead letters, see the dead_letter module.
+
+use super::{
+    replication::ReplicaStorage, Agreements, Data, Error, Module, OpenStorageRequests, RawEvent,
+    StorageOffers, StorageRequests, Trait,
+};
+use codec::{Decode, Encode};
+use frame_support::{
+    ensure, traits::ReservableCurrency, StorageDoubleMap, StorageMap, StorageValue,
+};
+use sp_runtime::{traits::Saturating, RuntimeDebug};
+use sp_std::vec::Vec;
+
+/// What an author asks for.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct StorageRequest<AccountId, Balance, BlockNumber> {
+    /// The author of the entry, who posted the request.
+    pub author: AccountId,
+    /// The entry to store.
+    pub data_id: Vec<u8>,
+    /// How many bytes have to be stored.
+    pub size: u32,
+    /// For how many blocks from the match.
+    pub duration: BlockNumber,
+    /// The most the author pays per byte and block.
+    pub max_price: Balance,
+    /// What the author reserved until the request is matched or cancelled.
+    pub deposit: Balance,
+    /// How many matches fell through so far, see the dead_letter module.
+    pub attempts: u32,
+}
+
+/// What a provider offers.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct StorageOffer<AccountId, Balance> {
+    /// The provider storing the data.
+    pub provider: AccountId,
+    /// How many more bytes the provider takes.
+    pub capacity: u64,
+    /// What it asks per byte and block.
+    pub price: Balance,
+}
+
+/// A matched request.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Agreement<Balance, BlockNumber> {
+    /// How many bytes the provider stores.
+    pub size: u32,
+    /// The agreed price per byte and block, that of the offer.
+    pub price: Balance,
+    /// The last block the provider stores the entry for.
+    pub until: BlockNumber,
+}
+
+impl<T: Trait> Module<T> {
+    // match the open requests with the offers, see the module docs.
+    pub(crate) fn match_storage_requests() {
+        let mut offers = <StorageOffers<T>>::get();
+        let mut open = OpenStorageRequests::get();
+        open.retain(|id| {
+            let request = match Self::storage_request(id) {
+                Some(request) => request,
+                None => return false,
+            };
+            // offers are kept sorted by price, the first that fits is the cheapest.
+            let offer = match offers.iter_mut().find(|o| {
+                o.capacity >= request.size as u64
+                    && o.price <= request.max_price
+                    && !<Agreements<T>>::exists(&request.data_id, &o.provider)
+            }) {
+                Some(offer) => offer,
+                None => return true,
+            };
+            if let Err(e) = Self::keep_replica(&request.data_id, &offer.provider) {
+                // the offer keeps its space.
+                return Self::fail_attempt(*id, request, e);
+            }
+            offer.capacity -= request.size as u64;
+            let now = <frame_system::Module<T>>::block_number();
+            let until = now.saturating_add(request.duration);
+            let agreement = Agreement { size: request.size, price: offer.price, until };
+            <Agreements<T>>::insert(&request.data_id, &offer.provider, agreement);
+            let _ = T::Currency::unreserve(&request.author, request.deposit);
+            <StorageRequests<T>>::remove(id);
+            let provider = offer.provider.clone();
+            Self::deposit_event(RawEvent::StorageAgreed(*id, request.data_id, provider, until));
+            false
+        });
+        offers.retain(|o| o.capacity > 0);
+        <StorageOffers<T>>::put(offers);
+        OpenStorageRequests::put(open);
+    }
+
+    // make provider keep a replica of data_id, raising its replication if needed.
+    fn keep_replica(data_id: &[u8], provider: &T::AccountId) -> Result<(), Error<T>> {
+        ensure!(<Data<T>>::exists(data_id), Error::<T>::NoneData);
+        let mut data = Self::get_data(data_id);
+        if data.providers.contains(provider) {
+            return Ok(());
+        }
+        let value = Self::get_external_storage(data_id, &data)?;
+        T::Replicas::set(provider, data_id.to_vec(), value);
+        data.providers.push(provider.clone());
+        data.replication = data.replication.max(data.providers.len() as u32);
+        <Data<T>>::insert(data_id, data);
+        Ok(())
+    }
+}
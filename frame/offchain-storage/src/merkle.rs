@@ -0,0 +1,91 @@
+//! Binary merkle trees over the chunks of a value.
+//!
+//! Leaves are the hashes of the chunks, each parent is the hash of its two children
+//! concatenated, and the last node of a layer with odd width is carried up as is.
+//! Readers fetching a single chunk of a large value can check it against the root
+//! kept on chain with a proof of `log2(count)` hashes.
+
+use sp_runtime::traits::Hash;
+use sp_std::vec::Vec;
+
+fn hash_pair<H: Hash>(left: &H::Output, right: &H::Output) -> H::Output {
+    let mut both = left.as_ref().to_vec();
+    both.extend_from_slice(right.as_ref());
+    H::hash(&both)
+}
+
+fn next_layer<H: Hash>(layer: &[H::Output]) -> Vec<H::Output> {
+    layer
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_pair::<H>(left, right),
+            _ => pair[0],
+        })
+        .collect()
+}
+
+/// Hash every `chunk_size` bytes of `value`.
+pub fn leaves<H: Hash>(value: &[u8], chunk_size: usize) -> Vec<H::Output> {
+    value.chunks(chunk_size).map(H::hash).collect()
+}
+
+/// The root of the tree over `leaves`.
+pub fn root<H: Hash>(leaves: &[H::Output]) -> H::Output {
+    if leaves.is_empty() {
+        return H::hash(&[]);
+    }
+    let mut layer = leaves.to_vec();
+    while layer.len() > 1 {
+        layer = next_layer::<H>(&layer);
+    }
+    layer[0]
+}
+
+/// The sibling hashes proving the leaf at `index`, from the bottom up.
+pub fn proof<H: Hash>(leaves: &[H::Output], index: usize) -> Vec<H::Output> {
+    let mut proof = Vec::new();
+    let mut layer = leaves.to_vec();
+    let mut index = index;
+    while layer.len() > 1 {
+        if let Some(sibling) = layer.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        layer = next_layer::<H>(&layer);
+        index /= 2;
+    }
+    proof
+}
+
+/// Whether `proof` shows `leaf` is the leaf at `index` of a tree of `count` leaves
+/// with the given `root`.
+pub fn verify<H: Hash>(
+    root: &H::Output,
+    count: usize,
+    index: usize,
+    leaf: H::Output,
+    proof: &[H::Output],
+) -> bool {
+    if index >= count {
+        return false;
+    }
+    let mut proof = proof.iter();
+    let mut hash = leaf;
+    let mut index = index;
+    let mut width = count;
+    while width > 1 {
+        if index ^ 1 < width {
+            let sibling = match proof.next() {
+                Some(sibling) => sibling,
+                None => return false,
+            };
+            hash = if index % 2 == 0 {
+                hash_pair::<H>(&hash, sibling)
+            } else {
+                hash_pair::<H>(sibling, &hash)
+            };
+        }
+        index /= 2;
+        width = (width + 1) / 2;
+    }
+    proof.next().is_none() && hash == *root
+}
@@ -0,0 +1,92 @@
+//! Metrics of the storage backends.
+//!
+//! A runtime wraps a backend in [`Metered`] to have every call to it timed and its failures
+//! counted, e.g. `type Storage = Metered<Ipfs, IpfsName>`. The metrics go to the metrics
+//! server of the node (`--grafana-port`), where they are graphed next to its own:
+//!
+//! - `offchain_storage_<backend>_<call>_latency_ns`: how long each call took.
+//! - `offchain_storage_<backend>_<call>_failed`: 1 for each call that failed, 0 otherwise,
+//!   so their mean is the error rate and their count the call rate.
+//! - `offchain_storage_pending_uploads` and `offchain_storage_gc_queue`: how many uploads
+//!   and backend keys wait for the offchain worker and the garbage collector, recorded by
+//!   the offchain worker every block.
+//!
+//! Only calls made natively, in the offchain worker or in a native import of blocks, are
+//! measured; in wasm `Metered` only passes them on.
+
+use super::{ExternalStorage, Module, StorageError, Trait};
+use frame_support::traits::Get;
+use sp_std::{marker::PhantomData, vec::Vec};
+
+/// The backend `S`, named `N` in the metrics, with its calls measured.
+pub struct Metered<S, N>(PhantomData<(S, N)>);
+
+impl<S: ExternalStorage, N: Get<&'static str>> ExternalStorage for Metered<S, N> {
+    fn get(key: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        measure::<N, _>("get", || S::get(key))
+    }
+
+    fn set(key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        measure::<N, _>("set", || S::set(key, value))
+    }
+
+    fn delete(key: Vec<u8>) -> Result<(), StorageError> {
+        measure::<N, _>("delete", || S::delete(key))
+    }
+
+    fn get_range(key: Vec<u8>, offset: u32, len: u32) -> Result<Vec<u8>, StorageError> {
+        measure::<N, _>("get_range", || S::get_range(key, offset, len))
+    }
+
+    fn append(key: Vec<u8>, bytes: Vec<u8>) -> Result<(), StorageError> {
+        measure::<N, _>("append", || S::append(key, bytes))
+    }
+
+    fn pin(key: Vec<u8>) -> Result<(), StorageError> {
+        measure::<N, _>("pin", || S::pin(key))
+    }
+
+    fn unpin(key: Vec<u8>) -> Result<(), StorageError> {
+        measure::<N, _>("unpin", || S::unpin(key))
+    }
+}
+
+// make the call named call to the backend named N, recording how long it took and whether
+// it failed.
+#[cfg(feature = "std")]
+fn measure<N: Get<&'static str>, R>(
+    call: &str,
+    f: impl FnOnce() -> Result<R, StorageError>,
+) -> Result<R, StorageError> {
+    let start = std::time::Instant::now();
+    let result = f();
+    let metric = format!("offchain_storage_{}_{}", N::get(), call);
+    let _ = grafana_data_source::record_metrics!(
+        &format!("{}_latency_ns", metric) => start.elapsed().as_nanos(),
+        &format!("{}_failed", metric) => result.is_err() as u8,
+    );
+    result
+}
+
+#[cfg(not(feature = "std"))]
+fn measure<N: Get<&'static str>, R>(
+    _call: &str,
+    f: impl FnOnce() -> Result<R, StorageError>,
+) -> Result<R, StorageError> {
+    f()
+}
+
+impl<T: Trait> Module<T> {
+    // record how much work waits for the offchain worker and the garbage collector.
+    #[cfg(feature = "std")]
+    pub(crate) fn record_queues() {
+        let (head, tail) = Self::gc_bounds();
+        let _ = grafana_data_source::record_metrics!(
+            "offchain_storage_pending_uploads" => Self::pending_upload_count(),
+            "offchain_storage_gc_queue" => tail - head,
+        );
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn record_queues() {}
+}
@@ -0,0 +1,622 @@
+//! Migrations of the storage layout.
+//!
+//! `StorageVersion` says which layout the storage of the module is in. Before anything
+//! else, `on_initialize` brings it up to [`CURRENT_VERSION`] by running every migration
+//! from the stored version on, each bumping the version by one. Once the storage is current
+//! this costs a read per block. A change to the layout bumps `CURRENT_VERSION` and appends
+//! its migration to `ensure_storage_upgraded`.
+//!
+//! Version 1 keeps the content hash, replication and providers in `UserData`. Entries
+//! written before only had the author and access, and get the default content hash: their
+//! value is hashed on the first read, until then the backend is trusted as it was before.
+//!
+//! Version 2 keeps the size of the value and the blocks the entry was created and last
+//! written in. The storage keys don't tell which entry they belong to, so entries written
+//! before, live or in the trash, get a size and blocks of zero until their next write.
+//!
+//! Version 3 keeps how the value is encrypted in `UserData`. Entries written before are
+//! taken as unencrypted.
+//!
+//! Version 4 keeps the backend of the value in `UserData`. Entries written before are kept
+//! in `T::Storage`, the default backend.
+//!
+//! Version 5 keeps how the value is compressed in `UserData`. Entries written before are
+//! stored uncompressed.
+//!
+//! Version 6 keeps whether a registered provider is approved in `ProviderInfo`. Providers
+//! registered before are not, until `T::AdminOrigin` approves them.
+//!
+//! Version 7 keeps statuses of deferred writes rather than of storage requests, see the
+//! status module. The statuses of storage requests are dropped, values pending upload get
+//! an id and the `Queued` status.
+//!
+//! Version 8 counts the entries of every author in `OwnedCount` anew, from the live entries
+//! that aren't mirrors and the trashed ones, so entries written before the count began
+//! count against the quota like the others. Entries listed while the listing of an author
+//! was a single list are listed anew, after the entries listed by position. The storage
+//! keys don't tell which entry they belong to, so entries written before authors' entries
+//! were listed at all stay unlisted. `UsedBytes` is charged anew as well, with the size
+//! rent is charged for, which is what writes and deletes release. Entries whose size
+//! version 2 zeroed get it back from their rent. Entries without rent, written at genesis
+//! or before rent was kept, are charged nothing until their next write, like the genesis
+//! entries always were. `TotalEntries` and `TotalBytesStored` are set to the sums of the
+//! new counts and charges.
+//!
+//! Version 9 keeps the deposit a registered provider reserved in `ProviderInfo`. Providers
+//! registered before reserved none, until they register again.
+//!
+//! Version 10 keeps the version of the shards of an erasure-coded value in `ShardInfo`, and
+//! in the shards `GcQueue` holds, see the erasure module. Shards stored before are version
+//! 0, whose keys carry no version, so they stay where they are.
+
+use super::{
+    erasure::ShardInfo,
+    gc::GcItem,
+    registry::ProviderInfo,
+    rent::RentInfo,
+    routing::{BackendId, DEFAULT_BACKEND},
+    trash::TrashedEntry,
+    upload::PendingUpload,
+    Access, BalanceOf, Compression, Data, EncryptionInfo, Error, ExternalStorage, GcQueue,
+    Mirrors, Module, NextUploadId, OwnedCount, OwnedEntries, PendingUploads, Providers, Rent,
+    RequestStatus, Shards, StatusQueue, StorageVersion, TotalBytesStored, TotalEntries, Trait,
+    Trash, UsedBytes, UserData,
+};
+use codec::{Decode, Encode};
+use frame_support::{storage::StoragePrefixedMap, StorageMap, StorageValue};
+use sp_io::hashing::twox_128;
+use sp_runtime::traits::{Hash, Zero};
+use sp_std::vec::Vec;
+
+/// Indicator of a version of a storage layout.
+pub type VersionNumber = u32;
+
+/// The version of the storage layout this code expects.
+pub const CURRENT_VERSION: VersionNumber = 10;
+
+// UserData as version 0 stored it.
+#[derive(Decode)]
+struct UserDataV0<AccountId> {
+    author: AccountId,
+    access: Access,
+}
+
+// UserData as version 1 stored it.
+#[derive(Decode)]
+struct UserDataV1<AccountId, Hash> {
+    author: AccountId,
+    access: Access,
+    content_hash: Hash,
+    replication: u32,
+    providers: Vec<AccountId>,
+}
+
+// TrashedEntry as version 1 stored it.
+#[derive(Decode)]
+struct TrashedEntryV1<AccountId, Hash, Balance, BlockNumber> {
+    data: UserDataV1<AccountId, Hash>,
+    deposit: Balance,
+    rent: Option<RentInfo<BlockNumber>>,
+    purge_at: BlockNumber,
+}
+
+// UserData as version 2 stored it.
+#[derive(Decode)]
+struct UserDataV2<AccountId, Hash, BlockNumber> {
+    author: AccountId,
+    access: Access,
+    content_hash: Hash,
+    replication: u32,
+    providers: Vec<AccountId>,
+    size: u32,
+    created_at: BlockNumber,
+    updated_at: BlockNumber,
+}
+
+// TrashedEntry as version 2 stored it.
+#[derive(Decode)]
+struct TrashedEntryV2<AccountId, Hash, Balance, BlockNumber> {
+    data: UserDataV2<AccountId, Hash, BlockNumber>,
+    deposit: Balance,
+    rent: Option<RentInfo<BlockNumber>>,
+    purge_at: BlockNumber,
+}
+
+// UserData as version 3 stored it.
+#[derive(Decode)]
+struct UserDataV3<AccountId, Hash, BlockNumber> {
+    author: AccountId,
+    access: Access,
+    content_hash: Hash,
+    replication: u32,
+    providers: Vec<AccountId>,
+    size: u32,
+    created_at: BlockNumber,
+    updated_at: BlockNumber,
+    encryption: Option<EncryptionInfo>,
+}
+
+// TrashedEntry as version 3 stored it.
+#[derive(Decode)]
+struct TrashedEntryV3<AccountId, Hash, Balance, BlockNumber> {
+    data: UserDataV3<AccountId, Hash, BlockNumber>,
+    deposit: Balance,
+    rent: Option<RentInfo<BlockNumber>>,
+    purge_at: BlockNumber,
+}
+
+// UserData as version 4 stored it.
+#[derive(Decode)]
+struct UserDataV4<AccountId, Hash, BlockNumber> {
+    author: AccountId,
+    access: Access,
+    content_hash: Hash,
+    replication: u32,
+    providers: Vec<AccountId>,
+    size: u32,
+    created_at: BlockNumber,
+    updated_at: BlockNumber,
+    encryption: Option<EncryptionInfo>,
+    backend: BackendId,
+}
+
+// TrashedEntry as version 4 stored it.
+#[derive(Decode)]
+struct TrashedEntryV4<AccountId, Hash, Balance, BlockNumber> {
+    data: UserDataV4<AccountId, Hash, BlockNumber>,
+    deposit: Balance,
+    rent: Option<RentInfo<BlockNumber>>,
+    purge_at: BlockNumber,
+}
+
+// ProviderInfo as version 5 stored it.
+#[derive(Decode)]
+struct ProviderInfoV5<Balance> {
+    endpoint: Vec<u8>,
+    capacity: u64,
+    price_per_byte: Balance,
+}
+
+// ProviderInfo as version 8 stored it.
+#[derive(Decode)]
+struct ProviderInfoV8<Balance> {
+    endpoint: Vec<u8>,
+    capacity: u64,
+    price_per_byte: Balance,
+    approved: bool,
+}
+
+// ShardInfo as version 9 stored it.
+#[derive(Decode)]
+struct ShardInfoV9<Hash> {
+    k: u8,
+    len: u32,
+    hashes: Vec<Hash>,
+}
+
+// GcItem as version 9 stored it.
+#[derive(Decode)]
+enum GcItemV9<AccountId> {
+    Value(Vec<u8>),
+    Shard(Vec<u8>, u8),
+    Replica(Vec<u8>, AccountId),
+    Version(Vec<u8>, u32),
+    Routed(BackendId, Vec<u8>, Option<u8>),
+    Object(BackendId, Vec<u8>),
+    Staged(Vec<u8>),
+    Chunk(AccountId, Vec<u8>, u32),
+}
+
+// PendingUpload as version 6 stored it.
+#[derive(Decode)]
+struct PendingUploadV6<Hash, BlockNumber> {
+    data_id: Vec<u8>,
+    value: Vec<u8>,
+    content_hash: Hash,
+    revision: u32,
+    at: BlockNumber,
+}
+
+impl<AccountId, Hash, BlockNumber: Default> From<UserDataV1<AccountId, Hash>>
+    for UserData<AccountId, Hash, BlockNumber>
+{
+    fn from(old: UserDataV1<AccountId, Hash>) -> Self {
+        UserData {
+            author: old.author,
+            access: old.access,
+            content_hash: old.content_hash,
+            replication: old.replication,
+            providers: old.providers,
+            size: 0,
+            created_at: Default::default(),
+            updated_at: Default::default(),
+            encryption: None,
+            backend: DEFAULT_BACKEND,
+            compression: Compression::None,
+        }
+    }
+}
+
+impl<AccountId, Hash, BlockNumber> From<UserDataV2<AccountId, Hash, BlockNumber>>
+    for UserData<AccountId, Hash, BlockNumber>
+{
+    fn from(old: UserDataV2<AccountId, Hash, BlockNumber>) -> Self {
+        UserData {
+            author: old.author,
+            access: old.access,
+            content_hash: old.content_hash,
+            replication: old.replication,
+            providers: old.providers,
+            size: old.size,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+            encryption: None,
+            backend: DEFAULT_BACKEND,
+            compression: Compression::None,
+        }
+    }
+}
+
+impl<AccountId, Hash, BlockNumber> From<UserDataV3<AccountId, Hash, BlockNumber>>
+    for UserData<AccountId, Hash, BlockNumber>
+{
+    fn from(old: UserDataV3<AccountId, Hash, BlockNumber>) -> Self {
+        UserData {
+            author: old.author,
+            access: old.access,
+            content_hash: old.content_hash,
+            replication: old.replication,
+            providers: old.providers,
+            size: old.size,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+            encryption: old.encryption,
+            backend: DEFAULT_BACKEND,
+            compression: Compression::None,
+        }
+    }
+}
+
+impl<AccountId, Hash, BlockNumber> From<UserDataV4<AccountId, Hash, BlockNumber>>
+    for UserData<AccountId, Hash, BlockNumber>
+{
+    fn from(old: UserDataV4<AccountId, Hash, BlockNumber>) -> Self {
+        UserData {
+            author: old.author,
+            access: old.access,
+            content_hash: old.content_hash,
+            replication: old.replication,
+            providers: old.providers,
+            size: old.size,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+            encryption: old.encryption,
+            backend: old.backend,
+            compression: Compression::None,
+        }
+    }
+}
+
+// replace every value under prefix that decodes in full as an O, entries written in the new
+// layout don't, with its translation.
+fn translate_values<O: Decode, N: Encode>(prefix: [u8; 32], f: impl Fn(O) -> N) {
+    let mut key = prefix.to_vec();
+    while let Some(next) =
+        sp_io::storage::next_key(&key).filter(|next| next.starts_with(&prefix[..]))
+    {
+        if let Some(raw) = sp_io::storage::get(&next) {
+            let input = &mut &raw[..];
+            match O::decode(input) {
+                Ok(old) if input.is_empty() => sp_io::storage::set(&next, &f(old).encode()),
+                _ => {}
+            }
+        }
+        key = next;
+    }
+}
+
+// call f with every value under prefix and the part of its key after prefix.
+fn for_each_value(prefix: [u8; 32], mut f: impl FnMut(&[u8], &[u8])) {
+    let mut key = prefix.to_vec();
+    while let Some(next) =
+        sp_io::storage::next_key(&key).filter(|next| next.starts_with(&prefix[..]))
+    {
+        if let Some(raw) = sp_io::storage::get(&next) {
+            f(&next[prefix.len()..], &raw);
+        }
+        key = next;
+    }
+}
+
+impl<T: Trait> Module<T> {
+    // bring the storage up to CURRENT_VERSION.
+    pub(crate) fn ensure_storage_upgraded() {
+        StorageVersion::mutate(|version| {
+            if *version == CURRENT_VERSION {
+                return;
+            }
+            Self::migrate_to_v1(version);
+            Self::migrate_to_v2(version);
+            Self::migrate_to_v3(version);
+            Self::migrate_to_v4(version);
+            Self::migrate_to_v5(version);
+            Self::migrate_to_v6(version);
+            Self::migrate_to_v7(version);
+            Self::migrate_to_v8(version);
+            Self::migrate_to_v9(version);
+            Self::migrate_to_v10(version);
+        });
+    }
+
+    // take the value of data_id, an entry from before version 1, from T::Storage as it is
+    // and keep its hash.
+    pub(crate) fn adopt_legacy_value(data_id: &[u8]) -> Result<Vec<u8>, Error<T>> {
+        let value = Self::backend(data_id, T::Storage::get(data_id.to_vec()))?;
+        <Data<T>>::mutate(data_id, |data| data.content_hash = T::Hashing::hash(&value));
+        Ok(value)
+    }
+
+    fn migrate_to_v1(version: &mut VersionNumber) {
+        if *version != 0 {
+            return;
+        }
+        translate_values(<Data<T>>::final_prefix(), |old: UserDataV0<T::AccountId>| {
+            UserData::<T::AccountId, T::Hash, T::BlockNumber> {
+                author: old.author,
+                access: old.access,
+                ..Default::default()
+            }
+        });
+        *version = 1;
+    }
+
+    fn migrate_to_v2(version: &mut VersionNumber) {
+        if *version != 1 {
+            return;
+        }
+        translate_values(<Data<T>>::final_prefix(), |old: UserDataV1<T::AccountId, T::Hash>| {
+            UserData::<T::AccountId, T::Hash, T::BlockNumber>::from(old)
+        });
+        translate_values(
+            <Trash<T>>::final_prefix(),
+            |old: TrashedEntryV1<T::AccountId, T::Hash, BalanceOf<T>, T::BlockNumber>| {
+                TrashedEntry {
+                    data: UserData::<T::AccountId, T::Hash, T::BlockNumber>::from(old.data),
+                    deposit: old.deposit,
+                    rent: old.rent,
+                    purge_at: old.purge_at,
+                }
+            },
+        );
+        *version = 2;
+    }
+
+    fn migrate_to_v3(version: &mut VersionNumber) {
+        if *version != 2 {
+            return;
+        }
+        translate_values(
+            <Data<T>>::final_prefix(),
+            |old: UserDataV2<T::AccountId, T::Hash, T::BlockNumber>| {
+                UserData::<T::AccountId, T::Hash, T::BlockNumber>::from(old)
+            },
+        );
+        translate_values(
+            <Trash<T>>::final_prefix(),
+            |old: TrashedEntryV2<T::AccountId, T::Hash, BalanceOf<T>, T::BlockNumber>| {
+                TrashedEntry {
+                    data: UserData::<T::AccountId, T::Hash, T::BlockNumber>::from(old.data),
+                    deposit: old.deposit,
+                    rent: old.rent,
+                    purge_at: old.purge_at,
+                }
+            },
+        );
+        *version = 3;
+    }
+
+    fn migrate_to_v4(version: &mut VersionNumber) {
+        if *version != 3 {
+            return;
+        }
+        translate_values(
+            <Data<T>>::final_prefix(),
+            |old: UserDataV3<T::AccountId, T::Hash, T::BlockNumber>| {
+                UserData::<T::AccountId, T::Hash, T::BlockNumber>::from(old)
+            },
+        );
+        translate_values(
+            <Trash<T>>::final_prefix(),
+            |old: TrashedEntryV3<T::AccountId, T::Hash, BalanceOf<T>, T::BlockNumber>| {
+                TrashedEntry {
+                    data: UserData::<T::AccountId, T::Hash, T::BlockNumber>::from(old.data),
+                    deposit: old.deposit,
+                    rent: old.rent,
+                    purge_at: old.purge_at,
+                }
+            },
+        );
+        *version = 4;
+    }
+
+    fn migrate_to_v5(version: &mut VersionNumber) {
+        if *version != 4 {
+            return;
+        }
+        translate_values(
+            <Data<T>>::final_prefix(),
+            |old: UserDataV4<T::AccountId, T::Hash, T::BlockNumber>| {
+                UserData::<T::AccountId, T::Hash, T::BlockNumber>::from(old)
+            },
+        );
+        translate_values(
+            <Trash<T>>::final_prefix(),
+            |old: TrashedEntryV4<T::AccountId, T::Hash, BalanceOf<T>, T::BlockNumber>| {
+                TrashedEntry {
+                    data: UserData::<T::AccountId, T::Hash, T::BlockNumber>::from(old.data),
+                    deposit: old.deposit,
+                    rent: old.rent,
+                    purge_at: old.purge_at,
+                }
+            },
+        );
+        *version = 5;
+    }
+
+    fn migrate_to_v6(version: &mut VersionNumber) {
+        if *version != 5 {
+            return;
+        }
+        translate_values(<Providers<T>>::final_prefix(), |old: ProviderInfoV5<BalanceOf<T>>| {
+            ProviderInfo {
+                endpoint: old.endpoint,
+                capacity: old.capacity,
+                price_per_byte: old.price_per_byte,
+                approved: false,
+                deposit: Zero::zero(),
+            }
+        });
+        *version = 6;
+    }
+
+    fn migrate_to_v7(version: &mut VersionNumber) {
+        if *version != 6 {
+            return;
+        }
+        // RequestStatuses of version 6, keyed by the ids of storage requests.
+        let statuses = [twox_128(b"OffchainStorage"), twox_128(b"RequestStatuses")].concat();
+        sp_io::storage::clear_prefix(&statuses);
+        <StatusQueue<T>>::remove_all();
+        translate_values(
+            <PendingUploads<T>>::final_prefix(),
+            |old: PendingUploadV6<T::Hash, T::BlockNumber>| {
+                let id = NextUploadId::mutate(|n| {
+                    *n += 1;
+                    *n
+                });
+                Self::set_upload_status(id, RequestStatus::Queued);
+                PendingUpload {
+                    id,
+                    data_id: old.data_id,
+                    value: old.value,
+                    content_hash: old.content_hash,
+                    revision: old.revision,
+                    at: old.at,
+                }
+            },
+        );
+        *version = 7;
+    }
+
+    fn migrate_to_v8(version: &mut VersionNumber) {
+        if *version != 7 {
+            return;
+        }
+        <OwnedCount<T>>::remove_all();
+        <UsedBytes<T>>::remove_all();
+        let (entries, mirrors) = (<Data<T>>::final_prefix(), <Mirrors<T>>::final_prefix());
+        let rents = <Rent<T>>::final_prefix();
+        for_each_value(entries, |hashed_id, raw| {
+            // Mirrors and Rent are keyed like Data, an entry is under the same hash in each.
+            if sp_io::storage::get(&[&mirrors[..], hashed_id].concat()).is_some() {
+                return;
+            }
+            let data = UserData::<T::AccountId, T::Hash, T::BlockNumber>::decode(&mut &raw[..]);
+            if let Ok(mut data) = data {
+                let rent = sp_io::storage::get(&[&rents[..], hashed_id].concat())
+                    .and_then(|raw| RentInfo::<T::BlockNumber>::decode(&mut &raw[..]).ok());
+                if let Some(rent) = rent {
+                    Self::charge_migrated(&data.author, &mut data.size, &rent);
+                    sp_io::storage::set(&[&entries[..], hashed_id].concat(), &data.encode());
+                }
+                <OwnedCount<T>>::mutate(&data.author, |n| *n += 1);
+            }
+        });
+        let trash = <Trash<T>>::final_prefix();
+        for_each_value(trash, |hashed_id, raw| {
+            let entry =
+                TrashedEntry::<T::AccountId, T::Hash, BalanceOf<T>, T::BlockNumber>::decode(
+                    &mut &raw[..],
+                );
+            if let Ok(mut entry) = entry {
+                if let Some(rent) = &entry.rent {
+                    Self::charge_migrated(&entry.data.author, &mut entry.data.size, rent);
+                    sp_io::storage::set(&[&trash[..], hashed_id].concat(), &entry.encode());
+                }
+                <OwnedCount<T>>::mutate(&entry.data.author, |n| *n += 1);
+            }
+        });
+        // the totals are what the counts and charges of the authors add up to.
+        TotalEntries::put(<OwnedCount<T>>::iter().map(u64::from).sum::<u64>());
+        TotalBytesStored::put(<UsedBytes<T>>::iter().sum::<u64>());
+        // before entries were listed by position, OwnedEntries kept the data_ids of an author
+        // in one list, keyed by the author alone.
+        let listing = <OwnedEntries<T>>::final_prefix();
+        let mut lists = Vec::new();
+        for_each_value(listing, |hashed_key, raw| {
+            if hashed_key.len() == 32 {
+                let data_ids = Vec::<Vec<u8>>::decode(&mut &raw[..]).unwrap_or_default();
+                lists.push(([&listing[..], hashed_key].concat(), data_ids));
+            }
+        });
+        for (key, data_ids) in lists {
+            sp_io::storage::clear(&key);
+            for data_id in data_ids {
+                let live = <Data<T>>::exists(&data_id) && !<Mirrors<T>>::exists(&data_id);
+                if live && Self::owned_position(&data_id).is_none() {
+                    Self::list_owned(&Self::get_data(&data_id).author, &data_id);
+                }
+            }
+        }
+        *version = 8;
+    }
+
+    fn migrate_to_v9(version: &mut VersionNumber) {
+        if *version != 8 {
+            return;
+        }
+        translate_values(<Providers<T>>::final_prefix(), |old: ProviderInfoV8<BalanceOf<T>>| {
+            ProviderInfo {
+                endpoint: old.endpoint,
+                capacity: old.capacity,
+                price_per_byte: old.price_per_byte,
+                approved: old.approved,
+                deposit: Zero::zero(),
+            }
+        });
+        *version = 9;
+    }
+
+    fn migrate_to_v10(version: &mut VersionNumber) {
+        if *version != 9 {
+            return;
+        }
+        translate_values(<Shards<T>>::final_prefix(), |old: ShardInfoV9<T::Hash>| ShardInfo {
+            k: old.k,
+            len: old.len,
+            hashes: old.hashes,
+            version: 0,
+        });
+        translate_values(<GcQueue<T>>::final_prefix(), |old: GcItemV9<T::AccountId>| match old {
+            GcItemV9::Value(data_id) => GcItem::Value(data_id),
+            GcItemV9::Shard(data_id, index) => GcItem::Shard(data_id, 0, index),
+            GcItemV9::Replica(data_id, provider) => GcItem::Replica(data_id, provider),
+            GcItemV9::Version(data_id, number) => GcItem::Version(data_id, number),
+            GcItemV9::Routed(backend, data_id, shard) => {
+                GcItem::Routed(backend, data_id, shard.map(|index| (0, index)))
+            }
+            GcItemV9::Object(backend, key) => GcItem::Object(backend, key),
+            GcItemV9::Staged(data_id) => GcItem::Staged(data_id),
+            GcItemV9::Chunk(who, data_id, index) => GcItem::Chunk(who, data_id, index),
+        });
+        *version = 10;
+    }
+
+    // charge author for an entry of version 7 with rent, whose size version 2 may have
+    // zeroed.
+    fn charge_migrated(author: &T::AccountId, size: &mut u32, rent: &RentInfo<T::BlockNumber>) {
+        // writes and deletes release the bytes rent is charged for.
+        *size = rent.size;
+        <UsedBytes<T>>::mutate(author, |n| *n = n.saturating_add(u64::from(rent.size)));
+    }
+}
@@ -0,0 +1,501 @@
+//! Test utilities
+
+use super::*;
+
+use frame_support::{impl_outer_event, impl_outer_origin, parameter_types, weights::Weight};
+use sp_core::{
+    offchain::{testing::TestOffchainExt, OffchainExt},
+    H256,
+};
+use sp_runtime::{
+    testing::Header,
+    traits::{BlakeTwo256, IdentityLookup, Lazy},
+    Perbill,
+};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+impl_outer_origin! {
+    pub enum Origin for Test {}
+}
+
+mod offchain_storage {
+    pub use crate::Event;
+}
+
+impl_outer_event! {
+    pub enum TestEvent for Test {
+        offchain_storage<T>,
+    }
+}
+
+// For testing the module, we construct most of a mock runtime. This means
+// first constructing a configuration type (`Test`) which `impl`s each of the
+// configuration traits of modules we want to use.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Test;
+parameter_types! {
+    pub const BlockHashCount: u64 = 250;
+    pub const MaximumBlockWeight: Weight = 1024;
+    pub const MaximumBlockLength: u32 = 2 * 1024;
+    pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+    pub const ChunkSize: u32 = 4;
+    pub const Provider: u64 = 9;
+    pub const ChallengePeriod: u64 = 5;
+    pub const ExistentialDeposit: u64 = 0;
+    pub const TransferFee: u64 = 0;
+    pub const CreationFee: u64 = 0;
+    pub const DepositBase: u64 = 2;
+    pub const DepositPerByte: u64 = 1;
+    pub const RentPerByte: u64 = 1;
+    pub const RentGracePeriod: u64 = 3;
+    pub const ProtocolFee: Perbill = Perbill::from_percent(10);
+    pub const UnbondingDelay: u64 = 2;
+    pub const SlashFraction: Perbill = Perbill::from_percent(50);
+    pub const GcKeysPerBlock: u32 = 2;
+    pub const GcWeightPerKey: Weight = 10;
+    pub const ExpiriesPerBlock: u32 = 2;
+    pub const ExpiryWeight: Weight = 5;
+    pub const TrashPeriod: u64 = 2;
+    pub const MaxMessageLen: u32 = 8;
+    pub const MaxRevisions: u32 = 2;
+    pub const SnapshotPeriod: u64 = 10;
+    pub const IdempotencyWindow: u64 = 5;
+    pub const StatusRetention: u64 = 5;
+    pub const RequestDeposit: u64 = 5;
+    pub const MaxRequestAttempts: u32 = 2;
+    pub const MaxDataIdLength: u32 = 32;
+    pub const MaxValueLength: u32 = 256;
+    pub const MaxEntriesPerAccount: u32 = 16;
+    pub const MaxBytesPerAccount: u64 = 64;
+    pub const MaxWritesPerBlock: u32 = 32;
+    pub const MaxUploadAttempts: u32 = 3;
+    pub const NamespaceDeposit: u64 = 5;
+    pub const PinDeposit: u64 = 3;
+    pub const MaxAuditRecords: u32 = 3;
+    pub const ChunkedUploadPeriod: u64 = 3;
+    pub const MaxChunkedUploads: u32 = 2;
+    pub const MaxEndpointLength: u32 = 32;
+    pub const MaxReviewers: u32 = 3;
+    pub const ProviderDeposit: u64 = 4;
+    pub const DbName: &'static str = "db";
+}
+impl system::Trait for Test {
+    type Origin = Origin;
+    type Call = ();
+    type Index = u64;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = TestEvent;
+    type BlockHashCount = BlockHashCount;
+    type MaximumBlockWeight = MaximumBlockWeight;
+    type MaximumBlockLength = MaximumBlockLength;
+    type AvailableBlockRatio = AvailableBlockRatio;
+    type Version = ();
+    type ModuleToIndex = ();
+}
+impl pallet_balances::Trait for Test {
+    type Balance = u64;
+    type OnFreeBalanceZero = ();
+    type OnNewAccount = ();
+    type Event = ();
+    type TransferPayment = ();
+    type DustRemoval = ();
+    type ExistentialDeposit = ExistentialDeposit;
+    type TransferFee = TransferFee;
+    type CreationFee = CreationFee;
+}
+impl Trait for Test {
+    type Event = TestEvent;
+    type Storage = DB;
+    type AdminOrigin = frame_system::EnsureRoot<u64>;
+    type RemoteHeaders = TrustedHeaders;
+    type SovereignAccount = bridge::HashedSovereignAccount;
+    type ChunkSize = ChunkSize;
+    type Providers = Provider;
+    type Randomness = TestRandomness;
+    type ChallengePeriod = ChallengePeriod;
+    type Replicas = Replicas;
+    type ErasureCoding = Coding;
+    type Currency = Balances;
+    type DepositBase = DepositBase;
+    type DepositPerByte = DepositPerByte;
+    type RentPerByte = RentPerByte;
+    type RentPayment = ();
+    type RentGracePeriod = RentGracePeriod;
+    type CurrentSession = CurrentSession;
+    type ProtocolFee = ProtocolFee;
+    type ProtocolFeeDestination = Treasury;
+    type Operators = Operators;
+    type UnbondingDelay = UnbondingDelay;
+    type SlashFraction = SlashFraction;
+    type Slash = Treasury;
+    type Attestation = Enclave;
+    type BundleSignature = TestSignature;
+    type BundleSigner = TestSigner;
+    type GcKeysPerBlock = GcKeysPerBlock;
+    type GcWeightPerKey = GcWeightPerKey;
+    type ExpiriesPerBlock = ExpiriesPerBlock;
+    type ExpiryWeight = ExpiryWeight;
+    type TrashPeriod = TrashPeriod;
+    type MaxMessageLen = MaxMessageLen;
+    type MaxRevisions = MaxRevisions;
+    type SnapshotPeriod = SnapshotPeriod;
+    type WeightInfo = ();
+    type IdempotencyWindow = IdempotencyWindow;
+    type StatusRetention = StatusRetention;
+    type RequestDeposit = RequestDeposit;
+    type MaxRequestAttempts = MaxRequestAttempts;
+    type MaxDataIdLength = MaxDataIdLength;
+    type MaxValueLength = MaxValueLength;
+    type MaxEntriesPerAccount = MaxEntriesPerAccount;
+    type MaxBytesPerAccount = MaxBytesPerAccount;
+    type MaxWritesPerBlock = MaxWritesPerBlock;
+    type DeferredUploads = Deferred;
+    type Confirmations = Confirmations;
+    type MaxUploadAttempts = MaxUploadAttempts;
+    type OnAccessGranted = Grants;
+    type NamespaceDeposit = NamespaceDeposit;
+    type Backends = (Cold,);
+    type TypedDataIds = Typed;
+    type OnDataWritten = Activity;
+    type OnDataDeleted = Activity;
+    type Compressor = ();
+    type DefaultCompression = DefaultCompression;
+    type Deduplication = Dedup;
+    type PinDeposit = PinDeposit;
+    type MaxAuditRecords = MaxAuditRecords;
+    type ChunkedUploadPeriod = ChunkedUploadPeriod;
+    type MaxChunkedUploads = MaxChunkedUploads;
+    type MaxEndpointLength = MaxEndpointLength;
+    type ProviderDeposit = ProviderDeposit;
+    type MaxReviewers = MaxReviewers;
+}
+
+thread_local! {
+    pub static STORE: RefCell<BTreeMap<Vec<u8>, Vec<u8>>> = RefCell::new(BTreeMap::new());
+    pub static TRUSTED: RefCell<Vec<H256>> = RefCell::new(vec![]);
+    pub static CODING: RefCell<Option<(u8, u8)>> = RefCell::new(None);
+    pub static SESSION: RefCell<u32> = RefCell::new(0);
+    pub static OPERATORS: RefCell<Vec<u64>> = RefCell::new(vec![]);
+    pub static REPLICAS: RefCell<BTreeMap<(u64, Vec<u8>), Vec<u8>>> = RefCell::new(BTreeMap::new());
+    pub static OUTAGE: RefCell<Option<StorageError>> = RefCell::new(None);
+    pub static DEFERRED: RefCell<bool> = RefCell::new(false);
+    pub static CONFIRMATIONS: RefCell<Vec<Call<Test>>> = RefCell::new(vec![]);
+    pub static GRANTS: RefCell<Vec<(Vec<u8>, u64, Access)>> = RefCell::new(vec![]);
+    pub static COLD: RefCell<BTreeMap<Vec<u8>, Vec<u8>>> = RefCell::new(BTreeMap::new());
+    pub static COLD_OUTAGE: RefCell<Option<StorageError>> = RefCell::new(None);
+    pub static TYPED: RefCell<bool> = RefCell::new(false);
+    pub static COMPRESSION: RefCell<Compression> = RefCell::new(Compression::None);
+    pub static DEDUP: RefCell<bool> = RefCell::new(false);
+    pub static PINNED: RefCell<Vec<Vec<u8>>> = RefCell::new(vec![]);
+    pub static ACTIVITY: RefCell<Vec<(bool, u64, Vec<u8>, u32)>> = RefCell::new(vec![]);
+}
+
+// Trusts the headers of the chain "remote" whose hashes are in TRUSTED.
+pub struct TrustedHeaders;
+
+impl bridge::RemoteHeaders<Header> for TrustedHeaders {
+    fn is_trusted(chain_id: &[u8], header: &Header) -> bool {
+        chain_id == b"remote" && TRUSTED.with(|t| t.borrow().contains(&header.hash()))
+    }
+}
+
+pub struct Coding;
+
+impl Get<Option<(u8, u8)>> for Coding {
+    fn get() -> Option<(u8, u8)> {
+        CODING.with(|c| *c.borrow())
+    }
+}
+
+pub struct Deferred;
+
+impl Get<bool> for Deferred {
+    fn get() -> bool {
+        DEFERRED.with(|d| *d.borrow())
+    }
+}
+
+pub struct Typed;
+
+impl Get<bool> for Typed {
+    fn get() -> bool {
+        TYPED.with(|t| *t.borrow())
+    }
+}
+
+pub struct Dedup;
+
+impl Get<bool> for Dedup {
+    fn get() -> bool {
+        DEDUP.with(|d| *d.borrow())
+    }
+}
+
+pub struct DefaultCompression;
+
+impl Get<Compression> for DefaultCompression {
+    fn get() -> Compression {
+        COMPRESSION.with(|c| *c.borrow())
+    }
+}
+
+// Keeps the confirmations of the offchain worker in CONFIRMATIONS.
+pub struct Confirmations;
+
+impl upload::SubmitConfirmation<Test> for Confirmations {
+    fn submit(call: Call<Test>) -> Result<(), ()> {
+        CONFIRMATIONS.with(|c| c.borrow_mut().push(call));
+        Ok(())
+    }
+}
+
+pub struct CurrentSession;
+
+impl Get<u32> for CurrentSession {
+    fn get() -> u32 {
+        SESSION.with(|s| *s.borrow())
+    }
+}
+
+// The validator set, as in OPERATORS.
+pub struct Operators;
+
+impl Contains<u64> for Operators {
+    fn contains(who: &u64) -> bool {
+        OPERATORS.with(|o| o.borrow().contains(who))
+    }
+}
+
+// Accepts the attestation "enclave" for every account.
+pub struct Enclave;
+
+impl attestation::AttestationVerifier<u64> for Enclave {
+    fn verify(_: &u64, attestation: &[u8]) -> bool {
+        attestation == b"enclave"
+    }
+}
+
+// A signature of account .0, valid for the message .1.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct TestSignature(pub u64, pub Vec<u8>);
+
+pub struct TestSigner(pub u64);
+
+impl IdentifyAccount for TestSigner {
+    type AccountId = u64;
+    fn into_account(self) -> u64 {
+        self.0
+    }
+}
+
+impl Verify for TestSignature {
+    type Signer = TestSigner;
+    fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &u64) -> bool {
+        self.0 == *signer && self.1[..] == *msg.get()
+    }
+}
+
+// The account collecting protocol fees.
+pub const TREASURY: u64 = 99;
+
+pub struct Treasury;
+
+impl OnUnbalanced<pallet_balances::NegativeImbalance<Test>> for Treasury {
+    fn on_nonzero_unbalanced(amount: pallet_balances::NegativeImbalance<Test>) {
+        Balances::resolve_creating(&TREASURY, amount);
+    }
+}
+
+pub struct TestRandomness;
+
+impl Randomness<H256> for TestRandomness {
+    fn random(subject: &[u8]) -> H256 {
+        BlakeTwo256::hash(subject)
+    }
+}
+
+// Simulate a external database, which fails with OUTAGE while it is set.
+pub struct DB;
+
+impl DB {
+    fn available() -> Result<(), StorageError> {
+        OUTAGE.with(|o| o.borrow().map_or(Ok(()), Err))
+    }
+}
+
+impl ExternalStorage for DB {
+    fn get(key: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        Self::available()?;
+        Ok(STORE.with(|s| s.borrow().get(&key).cloned().unwrap_or_default()))
+    }
+
+    fn set(key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        Self::available()?;
+        STORE.with(|s| s.borrow_mut().insert(key, value));
+        Ok(())
+    }
+
+    fn delete(key: Vec<u8>) -> Result<(), StorageError> {
+        Self::available()?;
+        STORE.with(|s| s.borrow_mut().remove(&key));
+        Ok(())
+    }
+
+    fn pin(key: Vec<u8>) -> Result<(), StorageError> {
+        Self::available()?;
+        PINNED.with(|p| p.borrow_mut().push(key));
+        Ok(())
+    }
+
+    fn unpin(key: Vec<u8>) -> Result<(), StorageError> {
+        Self::available()?;
+        PINNED.with(|p| p.borrow_mut().retain(|k| *k != key));
+        Ok(())
+    }
+}
+
+// Simulate a second external database, the backend 1, which fails writes with COLD_OUTAGE
+// while it is set.
+pub struct Cold;
+
+impl ExternalStorage for Cold {
+    fn get(key: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        Ok(COLD.with(|s| s.borrow().get(&key).cloned().unwrap_or_default()))
+    }
+
+    fn set(key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        COLD_OUTAGE.with(|o| o.borrow().map_or(Ok(()), Err))?;
+        COLD.with(|s| s.borrow_mut().insert(key, value));
+        Ok(())
+    }
+
+    fn delete(key: Vec<u8>) -> Result<(), StorageError> {
+        COLD.with(|s| s.borrow_mut().remove(&key));
+        Ok(())
+    }
+}
+
+// make the external database fail with reason, or work again with None.
+pub fn set_outage(reason: Option<StorageError>) {
+    OUTAGE.with(|o| *o.borrow_mut() = reason);
+}
+
+// Simulate the databases of the providers.
+pub struct Replicas;
+
+impl replication::ReplicaStorage<u64> for Replicas {
+    fn get(provider: &u64, key: Vec<u8>) -> Vec<u8> {
+        REPLICAS.with(|s| s.borrow().get(&(*provider, key)).cloned().unwrap_or_default())
+    }
+
+    fn set(provider: &u64, key: Vec<u8>, value: Vec<u8>) {
+        REPLICAS.with(|s| s.borrow_mut().insert((*provider, key), value));
+    }
+
+    fn delete(provider: &u64, key: Vec<u8>) {
+        REPLICAS.with(|s| s.borrow_mut().remove(&(*provider, key)));
+    }
+}
+
+// Record the grants of access made.
+pub struct Grants;
+
+impl acl::OnAccessGranted<u64> for Grants {
+    fn on_access_granted(data_id: &[u8], grantee: &u64, access: Access) {
+        GRANTS.with(|g| g.borrow_mut().push((data_id.to_vec(), *grantee, access)));
+    }
+}
+
+// Record the entries written and deleted.
+pub struct Activity;
+
+impl hooks::OnDataWritten<u64> for Activity {
+    fn on_data_written(author: &u64, data_id: &[u8], size: u32) {
+        ACTIVITY.with(|a| a.borrow_mut().push((true, *author, data_id.to_vec(), size)));
+    }
+}
+
+impl hooks::OnDataDeleted<u64> for Activity {
+    fn on_data_deleted(author: &u64, data_id: &[u8], size: u32) {
+        ACTIVITY.with(|a| a.borrow_mut().push((false, *author, data_id.to_vec(), size)));
+    }
+}
+
+// the entries written (true) and deleted (false) so far: author, data_id and size.
+pub fn activity() -> Vec<(bool, u64, Vec<u8>, u32)> {
+    ACTIVITY.with(|a| a.borrow().clone())
+}
+
+// the grants of access made so far: data_id, grantee and access.
+pub fn granted() -> Vec<(Vec<u8>, u64, Access)> {
+    GRANTS.with(|g| g.borrow().clone())
+}
+
+// read the value a provider's database holds for key, if any.
+pub fn replica(provider: u64, key: &[u8]) -> Option<Vec<u8>> {
+    REPLICAS.with(|s| s.borrow().get(&(provider, key.to_vec())).cloned())
+}
+
+// the keys the external database keeps pinned.
+pub fn pinned() -> Vec<Vec<u8>> {
+    PINNED.with(|p| p.borrow().clone())
+}
+
+// read the value the external database holds for key, if any.
+pub fn stored(key: &[u8]) -> Option<Vec<u8>> {
+    STORE.with(|s| s.borrow().get(key).cloned())
+}
+
+// read the value the second external database holds for key, if any.
+pub fn cold(key: &[u8]) -> Option<Vec<u8>> {
+    COLD.with(|s| s.borrow().get(key).cloned())
+}
+
+pub type System = frame_system::Module<Test>;
+pub type Balances = pallet_balances::Module<Test>;
+pub type OffchainStorage = Module<Test>;
+
+// This function basically just builds a genesis storage key/value store according to
+// our desired mockup.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+    STORE.with(|s| s.borrow_mut().clear());
+    REPLICAS.with(|s| s.borrow_mut().clear());
+    OUTAGE.with(|o| *o.borrow_mut() = None);
+    CODING.with(|c| *c.borrow_mut() = None);
+    SESSION.with(|s| *s.borrow_mut() = 0);
+    OPERATORS.with(|o| o.borrow_mut().clear());
+    DEFERRED.with(|d| *d.borrow_mut() = false);
+    CONFIRMATIONS.with(|c| c.borrow_mut().clear());
+    GRANTS.with(|g| g.borrow_mut().clear());
+    ACTIVITY.with(|a| a.borrow_mut().clear());
+    COLD.with(|s| s.borrow_mut().clear());
+    COLD_OUTAGE.with(|o| *o.borrow_mut() = None);
+    TYPED.with(|t| *t.borrow_mut() = false);
+    COMPRESSION.with(|c| *c.borrow_mut() = Compression::None);
+    DEDUP.with(|d| *d.borrow_mut() = false);
+    PINNED.with(|p| p.borrow_mut().clear());
+    let mut t = frame_system::GenesisConfig::default()
+        .build_storage::<Test>()
+        .unwrap();
+    pallet_balances::GenesisConfig::<Test> {
+        balances: vec![(1, 100), (2, 100), (3, 100), (7, 100), (9, 100)],
+        vesting: vec![],
+    }
+    .assimilate_storage(&mut t)
+    .unwrap();
+    t.into()
+}
+
+// Like new_test_ext, with the local storage offchain workers use.
+pub fn new_offchain_test_ext() -> sp_io::TestExternalities {
+    let mut ext = new_test_ext();
+    let (offchain, _) = TestOffchainExt::new();
+    ext.register_extension(OffchainExt::new(offchain));
+    ext
+}
@@ -0,0 +1,77 @@
+//! Namespaces.
+//!
+//! An account reserves a data_id prefix with `create_namespace`, reserving
+//! `T::NamespaceDeposit` until it removes the namespace again. Entries can then only be
+//! created under the prefix by the owner and the accounts it grants, so unrelated dapps
+//! can't squat on each other's data_ids. Entries existing when the namespace is created
+//! keep their author and access, only creating entries is restricted. A pallet owns a
+//! namespace through its account, reserving it with [`Module::reserve_namespace`].
+//!
+//! Namespaces may be nested. A data_id belongs to the longest namespace that is a prefix
+//! of it, and creating a namespace inside another takes the right to create entries in
+//! it, so an outer namespace created later doesn't take over an inner one.
+
+use super::{Error, Module, NamespaceGrants, Namespaces, Trait};
+use codec::{Decode, Encode};
+use frame_support::{
+    dispatch::DispatchResult,
+    ensure,
+    traits::{Get, ReservableCurrency},
+    StorageDoubleMap, StorageMap,
+};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// A reserved data_id prefix.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct Namespace<AccountId, Balance> {
+    pub owner: AccountId,
+    /// Reserved from the owner until the namespace is removed.
+    pub deposit: Balance,
+}
+
+impl<T: Trait> Module<T> {
+    /// The longest namespace `data_id` is in, if any.
+    pub fn namespace_of(data_id: &[u8]) -> Option<Vec<u8>> {
+        (1..=data_id.len())
+            .rev()
+            .map(|len| &data_id[..len])
+            .find(|prefix| <Namespaces<T>>::exists(prefix))
+            .map(|prefix| prefix.to_vec())
+    }
+
+    /// Whether `who` may create entries with the data_id `data_id`.
+    pub fn may_create(who: &T::AccountId, data_id: &[u8]) -> bool {
+        match Self::namespace_of(data_id) {
+            Some(name) => {
+                Self::namespace(&name).map_or(false, |ns| ns.owner == *who)
+                    || <NamespaceGrants<T>>::exists(&name, who)
+            }
+            None => true,
+        }
+    }
+
+    /// Reserve the namespace `name` for `owner`, e.g. for the account of a pallet.
+    pub fn reserve_namespace(owner: T::AccountId, name: Vec<u8>) -> DispatchResult {
+        // the empty prefix would take every data_id.
+        ensure!(!name.is_empty(), Error::<T>::NamespaceReserved);
+        ensure!(Self::fits_data_id(&name), Error::<T>::KeyTooLong);
+        ensure!(!<Namespaces<T>>::exists(&name), Error::<T>::NamespaceTaken);
+        ensure!(Self::may_create(&owner, &name), Error::<T>::NamespaceReserved);
+        let deposit = T::NamespaceDeposit::get();
+        ensure!(T::Currency::can_reserve(&owner, deposit), Error::<T>::InsufficientBalance);
+        T::Currency::reserve(&owner, deposit)?;
+        <Namespaces<T>>::insert(&name, Namespace { owner, deposit });
+        Ok(())
+    }
+
+    // drop the namespace name of who and its grants.
+    pub(crate) fn release_namespace(who: &T::AccountId, name: &[u8]) -> Result<(), Error<T>> {
+        let ns = Self::namespace(name).ok_or(Error::<T>::NoNamespace)?;
+        ensure!(ns.owner == *who, Error::<T>::PermissionDenied);
+        <Namespaces<T>>::remove(name);
+        <NamespaceGrants<T>>::remove_prefix(name);
+        let _ = T::Currency::unreserve(who, ns.deposit);
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+//! Pinning values in content-addressed backends.
+//!
+//! Backends such as IPFS drop the values nobody pins. Any account that may read an entry
+//! asks for its value to be kept with `pin_data`, reserving `T::PinDeposit` until it calls
+//! `unpin_data`. The first pin of an entry pins the backend keys of its value, those of its
+//! shards or of the object it shares included, through `ExternalStorage::pin`, and the last
+//! unpin unpins them. `Pins` records who pinned each entry. A pinned value written again or
+//! renamed is pinned under its new keys. Deleting the entry drops its pins and releases
+//! their deposits.
+
+use super::{
+    audit::AuditOp, routing::BackendId, Data, Error, Module, Pins, RawEvent, StorageError, Trait,
+};
+use frame_support::{
+    dispatch::DispatchResult,
+    ensure,
+    traits::{Get, ReservableCurrency},
+    StorageMap,
+};
+use sp_std::vec::Vec;
+
+impl<T: Trait> Module<T> {
+    /// Pin the value of `data_id` for `who`, which must be able to read it.
+    pub fn pin_entry(who: T::AccountId, data_id: Vec<u8>) -> DispatchResult {
+        ensure!(
+            <Data<T>>::exists(&data_id) && !Self::ttl_passed(&data_id),
+            Error::<T>::NoneData
+        );
+        ensure!(Self::can_read(&who, &data_id), Error::<T>::PermissionDenied);
+        let mut pins = Self::pins_of(&data_id);
+        ensure!(pins.iter().all(|(p, _)| *p != who), Error::<T>::AlreadyPinned);
+        let deposit = T::PinDeposit::get();
+        ensure!(T::Currency::can_reserve(&who, deposit), Error::<T>::InsufficientBalance);
+        if pins.is_empty() {
+            let backend = Self::get_data(&data_id).backend;
+            Self::backend(&data_id, Self::pin_value(&data_id, backend))?;
+        }
+        T::Currency::reserve(&who, deposit)?;
+        pins.push((who.clone(), deposit));
+        <Pins<T>>::insert(&data_id, pins);
+        Self::audit(&data_id, &who, AuditOp::Pinned);
+        Self::deposit_event(RawEvent::DataPinned(data_id, who));
+        Ok(())
+    }
+
+    /// Drop the pin `who` has on `data_id`.
+    pub fn unpin_entry(who: T::AccountId, data_id: Vec<u8>) -> DispatchResult {
+        let mut pins = Self::pins_of(&data_id);
+        let index = pins.iter().position(|(p, _)| *p == who).ok_or(Error::<T>::NotPinned)?;
+        if pins.len() == 1 {
+            let backend = Self::get_data(&data_id).backend;
+            Self::backend(&data_id, Self::unpin_value(&data_id, backend))?;
+        }
+        let (_, deposit) = pins.remove(index);
+        let _ = T::Currency::unreserve(&who, deposit);
+        if pins.is_empty() {
+            <Pins<T>>::remove(&data_id);
+        } else {
+            <Pins<T>>::insert(&data_id, pins);
+        }
+        Self::audit(&data_id, &who, AuditOp::Unpinned);
+        Self::deposit_event(RawEvent::DataUnpinned(data_id, who));
+        Ok(())
+    }
+
+    // pin the backend keys of the value of data_id in backend.
+    pub(crate) fn pin_value(data_id: &[u8], backend: BackendId) -> Result<(), StorageError> {
+        Self::value_keys(data_id).into_iter().try_for_each(|key| Self::routed_pin(backend, key))
+    }
+
+    fn unpin_value(data_id: &[u8], backend: BackendId) -> Result<(), StorageError> {
+        Self::value_keys(data_id).into_iter().try_for_each(|key| Self::routed_unpin(backend, key))
+    }
+
+    // the backend keys the value of data_id is kept under.
+    fn value_keys(data_id: &[u8]) -> Vec<Vec<u8>> {
+        match Self::shards_of(data_id) {
+            Some(info) => (0..info.hashes.len()).map(|i| info.key(data_id, i as u8)).collect(),
+            None => sp_std::vec![Self::value_key(data_id)],
+        }
+    }
+
+    // drop the pins of data_id, whose value goes away, and release their deposits.
+    pub(crate) fn drop_pins(data_id: &[u8]) {
+        for (who, deposit) in <Pins<T>>::take(data_id) {
+            let _ = T::Currency::unreserve(&who, deposit);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Registry of storage providers.
+//!
+//! Besides the accounts in `T::Providers`, fixed by the runtime, anyone can register as a
+//! provider with `register_provider`, telling clients where its backend is, how much it
+//! can store and what it charges. An endpoint may be `T::MaxEndpointLength` bytes long, and
+//! the registration reserves `T::ProviderDeposit` for the space it takes until it is
+//! removed. The registry alone only makes a provider discoverable:
+//! it answers challenges, keeps replicas and bonds stake like the others once
+//! `T::AdminOrigin` approves it with `approve_provider`. Otherwise any account could
+//! register and copy every replicated value into a backend of its own.
+
+use super::{Module, Providers, Trait};
+use codec::{Decode, Encode};
+use frame_support::{traits::Contains, StorageMap};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// What a registered provider offers.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct ProviderInfo<Balance> {
+    /// Where clients reach its backend, e.g. a URL or multiaddr.
+    pub endpoint: Vec<u8>,
+    /// How many bytes it offers to store.
+    pub capacity: u64,
+    /// What it asks for storing one byte.
+    pub price_per_byte: Balance,
+    /// Whether `T::AdminOrigin` approved it as a provider.
+    pub approved: bool,
+    /// What it reserved with the registration.
+    pub deposit: Balance,
+}
+
+impl<T: Trait> Module<T> {
+    /// Whether `who` is a storage provider, fixed by the runtime or registered and approved.
+    pub fn is_provider(who: &T::AccountId) -> bool {
+        T::Providers::contains(who) || Self::provider_info(who).map_or(false, |p| p.approved)
+    }
+}
@@ -0,0 +1,308 @@
+//! Renaming entries.
+//!
+//! `rename_data` moves an entry to a new data_id: its value, shards and replicas in the
+//! backends, and everything kept about it on chain, grants, pins, settings, open challenge,
+//! staged value, history and audit trail included. Pending quorum reads and read receipts
+//! are dropped, and storage requests posted for the old data_id are not carried over.
+//!
+//! Renames in a transaction don't wait on a backend. They only move what is kept on chain
+//! and record a [`PendingCopy`]: the entry reads its values from under the old data_id
+//! until the offchain worker copied them, then submits `confirm_copy` through
+//! `T::Confirmations` and the old keys go to the garbage collector. A copy without a
+//! confirmation is made again in the next block. Until then the old data_id can't be
+//! written and the entry can't be renamed again.
+
+use super::{
+    compression::Compressor, gc::GcItem, history, replication::ReplicaStorage, staging,
+    upload::SubmitConfirmation, Agreements, AuditTrail, Audited, Call, ChallengeDeadlines,
+    ChallengeNonces, Challenges, ChunkRoots, ContentSignatures, CopySources, Data, DataKeyrings,
+    Deposits, Error, ExternalStorage, GrantedEntries, Grantees, Mirrors, MissedChallenges, Module,
+    OperatorAccess, PendingCopies, PendingReads, PendingUploads, Pins, ReadGrants, ReadPrices,
+    ReadQuorums, ReadReceipts, Rent, ReplicaConfirmations, Reviewers, RevisionCount, Revisions,
+    SessionGrants, SharedKeys, Staged, Trait, Trash, UserData, WrappedKeys, WriteDelegations,
+};
+use codec::{Decode, Encode};
+use frame_support::{
+    ensure, storage::StoragePrefixedMap, traits::Get, StorageDoubleMap, StorageMap,
+};
+use sp_runtime::RuntimeDebug;
+use sp_std::{ops::RangeInclusive, vec::Vec};
+
+/// The values of an entry renamed in a transaction, kept under its old data_id until the
+/// offchain worker copied them.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct PendingCopy<Hash> {
+    /// The data_id the values are kept under.
+    pub from: Vec<u8>,
+    /// Hash of the value renamed, one written since is kept under the new data_id.
+    pub content_hash: Hash,
+    /// Hash of the staged value renamed, if there was one.
+    pub staged: Option<Hash>,
+    /// The revisions whose values are kept under the old data_id.
+    pub versions: Vec<u32>,
+}
+
+impl<T: Trait> Module<T> {
+    // check that user may rename old to new.
+    pub(crate) fn ensure_may_rename(
+        user: &T::AccountId,
+        old: &[u8],
+        new: &[u8],
+    ) -> Result<(), Error<T>> {
+        ensure!(<Data<T>>::exists(old), Error::<T>::NoneData);
+        ensure!(!<Mirrors<T>>::exists(old), Error::<T>::ReadOnlyMirror);
+        ensure!(Self::get_data(old).author == *user, Error::<T>::PermissionDenied);
+        ensure!(Self::fits_data_id(new), Error::<T>::KeyTooLong);
+        ensure!(Self::is_typed(new), Error::<T>::BadDataId);
+        ensure!(!<Data<T>>::exists(new) && !<Trash<T>>::exists(new), Error::<T>::DataExists);
+        ensure!(!Self::is_protected(new) || Self::is_attested(user), Error::<T>::Unattested);
+        ensure!(Self::may_create(user, new), Error::<T>::NamespaceReserved);
+        ensure!(!Self::is_expired(old), Error::<T>::Expired);
+        ensure!(!<PendingUploads<T>>::exists(old), Error::<T>::UploadPending);
+        ensure!(!<PendingCopies<T>>::exists(old), Error::<T>::CopyPending);
+        ensure!(!CopySources::exists(new), Error::<T>::CopyPending);
+        Ok(())
+    }
+
+    // move the entry old to new, which must be unused.
+    pub(crate) fn move_entry(old: &[u8], new: &[u8]) -> Result<(), Error<T>> {
+        let data = Self::get_data(old);
+        let copy = Self::copy_of(old);
+        // the backends are written first, nothing changes on chain if they fail.
+        Self::copy_values(old, new)?;
+        Self::move_records(old, new);
+        Self::release_copied(new, copy);
+        // the old value goes to the garbage collector if the backend fails to delete it now.
+        if Self::delete_external_storage(old, &data).is_err() {
+            Self::queue_deletion(old, &data);
+        }
+        Ok(())
+    }
+
+    // move the entry old to new, which must be unused, leaving its values under old until
+    // the offchain worker copies them.
+    pub(crate) fn defer_move(old: &[u8], new: &[u8]) {
+        let copy = Self::copy_of(old);
+        Self::move_records(old, new);
+        CopySources::insert(old, new);
+        <PendingCopies<T>>::insert(new, copy);
+    }
+
+    // the values of old, to be copied to another data_id.
+    fn copy_of(old: &[u8]) -> PendingCopy<T::Hash> {
+        PendingCopy {
+            from: old.to_vec(),
+            content_hash: Self::get_data(old).content_hash,
+            staged: Self::staged_of(old).map(|staged| staged.content_hash),
+            versions: Self::kept_revisions(old)
+                .filter(|number| <Revisions<T>>::exists(old, number))
+                .collect(),
+        }
+    }
+
+    // stop keeping the values of data_id under the data_id it was renamed from, once they
+    // are copied or data_id is gone, and leave them to the garbage collector. Returns
+    // whether a copy was pending.
+    pub(crate) fn finish_copy(
+        data_id: &[u8],
+        data: &UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) -> bool {
+        match <PendingCopies<T>>::take(data_id) {
+            Some(copy) => {
+                CopySources::remove(&copy.from);
+                Self::queue_deletion(&copy.from, data);
+                Self::release_copied(data_id, copy);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // point the revisions of data_id at their copies and leave the staged value and
+    // versions kept under the old data_id of copy to the garbage collector.
+    fn release_copied(data_id: &[u8], copy: PendingCopy<T::Hash>) {
+        let mut items = Vec::new();
+        for number in copy.versions {
+            <Revisions<T>>::mutate(data_id, number, |revision| {
+                if let Some(revision) = revision {
+                    revision.key = history::version_key(data_id, number);
+                }
+            });
+            items.push(GcItem::Version(copy.from.clone(), number));
+        }
+        if copy.staged.is_some() {
+            items.push(GcItem::Staged(copy.from));
+        }
+        Self::queue_items(items);
+    }
+
+    // the data_id the value of data_id hashing to content_hash is kept under, the one it
+    // was renamed from until the value is copied.
+    pub(crate) fn value_source(data_id: &[u8], content_hash: &T::Hash) -> Vec<u8> {
+        match Self::pending_copy(data_id) {
+            Some(copy) if copy.content_hash == *content_hash => copy.from,
+            _ => data_id.to_vec(),
+        }
+    }
+
+    // like value_source, for the staged value hashing to content_hash.
+    pub(crate) fn staged_source(data_id: &[u8], content_hash: &T::Hash) -> Vec<u8> {
+        match Self::pending_copy(data_id) {
+            Some(copy) if copy.staged == Some(*content_hash) => copy.from,
+            _ => data_id.to_vec(),
+        }
+    }
+
+    // copy the values of the entries renamed in transactions and submit the confirmations.
+    // Runs in the offchain worker.
+    pub(crate) fn copy_pending() {
+        for data_id in CopySources::iter() {
+            let (copy, data) = match (Self::pending_copy(&data_id), Self::kept_data(&data_id)) {
+                (Some(copy), Some(data)) => (copy, data),
+                _ => continue,
+            };
+            if Self::copy_kept(&data_id, &copy, &data).is_ok() {
+                let _ = T::Confirmations::submit(Call::confirm_copy(data_id));
+            }
+        }
+    }
+
+    // copy what data_id still reads from under the old data_id of copy, the value whole as
+    // deferred uploads store it.
+    fn copy_kept(
+        data_id: &[u8],
+        copy: &PendingCopy<T::Hash>,
+        data: &UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) -> Result<(), Error<T>> {
+        if data.content_hash == copy.content_hash && !<PendingUploads<T>>::exists(data_id) {
+            let value = Self::get_external_storage(data_id, data)?;
+            let stored = T::Compressor::compress(data.compression, value.clone());
+            Self::backend(data_id, Self::routed_set(data.backend, data_id.to_vec(), stored))?;
+            if <Pins<T>>::exists(data_id) {
+                Self::backend(data_id, Self::pin_value(data_id, data.backend))?;
+            }
+            for p in &data.providers {
+                T::Replicas::set(p, data_id.to_vec(), value.clone());
+            }
+        }
+        if let Some(staged) = Self::staged_of(data_id) {
+            if copy.staged == Some(staged.content_hash) {
+                let value = Self::get_staged(data_id, &staged)?;
+                Self::backend(data_id, T::Storage::set(staging::staged_key(data_id), value))?;
+            }
+        }
+        for number in &copy.versions {
+            let key = history::version_key(data_id, *number);
+            match Self::revision(data_id, *number) {
+                Some(revision) if revision.key != key => {
+                    let value = Self::backend(&copy.from, T::Storage::get(revision.key))?;
+                    Self::backend(data_id, T::Storage::set(key, value))?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // copy the value of old, its staged value and the values of its kept revisions to new
+    // in the backends.
+    fn copy_values(old: &[u8], new: &[u8]) -> Result<(), Error<T>> {
+        let data = Self::get_data(old);
+        let value = Self::get_external_storage(old, &data)?;
+        Self::set_external_storage(new, value, &data)?;
+        if <Pins<T>>::exists(old) {
+            Self::backend(new, Self::pin_value(new, data.backend))?;
+        }
+        if Self::staged_of(old).is_some() {
+            let staged = Self::backend(old, T::Storage::get(staging::staged_key(old)))?;
+            Self::backend(new, T::Storage::set(staging::staged_key(new), staged))?;
+        }
+        for number in Self::kept_revisions(old) {
+            if let Some(revision) = <Revisions<T>>::get(old, number) {
+                let value = Self::backend(old, T::Storage::get(revision.key))?;
+                let key = history::version_key(new, number);
+                Self::backend(new, T::Storage::set(key, value))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn kept_revisions(data_id: &[u8]) -> RangeInclusive<u32> {
+        let count = RevisionCount::get(data_id);
+        count.saturating_sub(T::MaxRevisions::get())..=count
+    }
+
+    // move everything kept about old on chain to new. Its values are left in the backends.
+    pub(crate) fn move_records(old: &[u8], new: &[u8]) {
+        let data = Self::get_data(old);
+        <Data<T>>::swap(old, new);
+        <Deposits<T>>::swap(old, new);
+        <Rent<T>>::swap(old, new);
+        <ChunkRoots<T>>::swap(old, new);
+        <ContentSignatures<T>>::swap(old, new);
+        <ReadPrices<T>>::swap(old, new);
+        OperatorAccess::swap(old, new);
+        ReadQuorums::swap(old, new);
+        <PendingReads<T>>::remove_prefix(old);
+        <ReadReceipts<T>>::remove_prefix(old);
+        <Reviewers<T>>::swap(old, new);
+        <Pins<T>>::swap(old, new);
+        Audited::swap(old, new);
+        <AuditTrail<T>>::swap(old, new);
+        let expires_at = Self::ttl_of(old);
+        Self::set_ttl(old, None);
+        Self::set_ttl(new, expires_at);
+
+        if let Some(challenge) = Self::challenge_of(old) {
+            <ChallengeDeadlines<T>>::remove(challenge.deadline, old);
+            <ChallengeDeadlines<T>>::insert(challenge.deadline, new, new);
+        }
+        <Challenges<T>>::swap(old, new);
+        ChallengeNonces::swap(old, new);
+        MissedChallenges::swap(old, new);
+
+        let grantees = <Grantees<T>>::take(old);
+        for who in &grantees {
+            <ReadGrants<T>>::swap(old, who, new, who);
+            <SessionGrants<T>>::swap(old, who, new, who);
+            <WriteDelegations<T>>::swap(old, who, new, who);
+            <SharedKeys<T>>::swap(old, who, new, who);
+        }
+        if !grantees.is_empty() {
+            <Grantees<T>>::insert(new, grantees);
+        }
+        if let Some(keyring) = Self::keyring_of(old).and_then(|id| Self::keyring(id)) {
+            for (member, _) in &keyring.members {
+                <WrappedKeys<T>>::swap(old, member, new, member);
+            }
+        }
+        DataKeyrings::swap(old, new);
+        for provider in &data.providers {
+            <Agreements<T>>::swap(old, provider, new, provider);
+        }
+        // providers confirm the replicas under the new data_id anew.
+        <ReplicaConfirmations<T>>::remove_prefix(old);
+        if <GrantedEntries<T>>::exists(&data.author) {
+            <GrantedEntries<T>>::mutate(&data.author, |ids| {
+                for id in ids.iter_mut().filter(|id| id[..] == *old) {
+                    *id = new.to_vec();
+                }
+            });
+        }
+        Self::relist_owned(&data.author, old, new);
+        <Staged<T>>::swap(old, new);
+
+        // revisions keep the keys of their values until release_copied points them at the
+        // copies.
+        let kept = Self::kept_revisions(old);
+        let count = RevisionCount::take(old);
+        for number in kept {
+            if let Some(revision) = <Revisions<T>>::take(old, number) {
+                <Revisions<T>>::insert(new, number, revision);
+            }
+        }
+        if count > 0 {
+            RevisionCount::insert(new, count);
+        }
+    }
+}
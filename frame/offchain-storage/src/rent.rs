@@ -0,0 +1,99 @@
+//! Storage rent.
+//!
+//! Besides the deposit, authors pay `T::RentPerByte` for every byte of a value and every
+//! block it is stored. Like contract rent it is charged lazily: when the value is written
+//! again, or when anyone calls `collect_rent`. If the author can't pay, the entry expires:
+//! it can still be read but no longer written, and once `T::RentGracePeriod` has passed
+//! anyone may remove it with `remove_expired`. A `T::ProtocolFee` share of the rent goes to
+//! `T::ProtocolFeeDestination`, the rest to `T::RentPayment`.
+
+use super::{BalanceOf, Module, RawEvent, Rent, Trait};
+use codec::{Decode, Encode};
+use frame_support::{
+    traits::{Currency, ExistenceRequirement, Get, Imbalance, OnUnbalanced, WithdrawReason},
+    StorageMap,
+};
+use sp_runtime::{
+    traits::{SaturatedConversion, Saturating, Zero},
+    RuntimeDebug,
+};
+
+/// The rent account of an entry.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct RentInfo<BlockNumber> {
+    /// Length of the value rent is charged for.
+    pub size: u32,
+    /// The block rent is paid up to.
+    pub charged_until: BlockNumber,
+    /// The block the entry expired in, if the author couldn't pay.
+    pub expired_at: Option<BlockNumber>,
+}
+
+impl<T: Trait> Module<T> {
+    /// Whether `data_id` expired for want of rent.
+    pub fn is_expired(data_id: &[u8]) -> bool {
+        Self::rent_of(data_id).map_or(false, |rent| rent.expired_at.is_some())
+    }
+
+    // the rent due for data_id since it was last charged, none once it has expired.
+    pub(crate) fn rent_due(data_id: &[u8]) -> BalanceOf<T> {
+        match Self::rent_of(data_id) {
+            Some(rent) if rent.expired_at.is_none() => {
+                let now = <frame_system::Module<T>>::block_number();
+                let blocks: u32 = (now - rent.charged_until).saturated_into();
+                T::RentPerByte::get()
+                    .saturating_mul(BalanceOf::<T>::from(rent.size))
+                    .saturating_mul(BalanceOf::<T>::from(blocks))
+            }
+            _ => Zero::zero(),
+        }
+    }
+
+    // whether who can pay due as rent and stay alive, as charge_rent needs.
+    pub(crate) fn can_pay_rent(who: &T::AccountId, due: BalanceOf<T>) -> bool {
+        if due.is_zero() {
+            return true;
+        }
+        let left = T::Currency::free_balance(who).saturating_sub(due);
+        left >= T::Currency::minimum_balance()
+            && T::Currency::ensure_can_withdraw(who, due, WithdrawReason::Fee.into(), left).is_ok()
+    }
+
+    // charge the rent due for data_id since it was last charged, expiring it if the
+    // author can't pay. Returns whether the entry is still live.
+    pub(crate) fn charge_rent(data_id: &[u8]) -> bool {
+        let mut rent = match Self::rent_of(data_id) {
+            Some(rent) => rent,
+            None => return true,
+        };
+        if rent.expired_at.is_some() {
+            return false;
+        }
+        let now = <frame_system::Module<T>>::block_number();
+        let due = Self::rent_due(data_id);
+        if !due.is_zero() {
+            let author = Self::get_data(data_id).author;
+            match T::Currency::withdraw(
+                &author,
+                due,
+                WithdrawReason::Fee.into(),
+                ExistenceRequirement::KeepAlive,
+            ) {
+                Ok(imbalance) => {
+                    let (fee, rent) = imbalance.split(T::ProtocolFee::get() * due);
+                    T::ProtocolFeeDestination::on_unbalanced(fee);
+                    T::RentPayment::on_unbalanced(rent);
+                }
+                Err(_) => {
+                    rent.expired_at = Some(now);
+                    <Rent<T>>::insert(data_id, rent);
+                    Self::deposit_event(RawEvent::DataExpired(data_id.to_vec()));
+                    return false;
+                }
+            }
+        }
+        rent.charged_until = now;
+        <Rent<T>>::insert(data_id, rent);
+        true
+    }
+}
@@ -0,0 +1,110 @@
+//! Record/replay backends for reproducing runs against external storage services.
+//!
+//! Wrap a real backend in [`Record`] while running a test, save what
+//! [`take_recording`] returns (it is SCALE encodable), and later feed it to
+//! [`load_replay`] and use [`Replay`] as the backend. Replay serves the recorded
+//! responses in order and panics as soon as the run diverges from the recording,
+//! so a flaky failure involving an external service can be reproduced and bisected
+//! without the service.
+
+use super::ExternalStorage;
+use codec::{Decode, Encode};
+use sp_runtime::RuntimeDebug;
+use std::{cell::RefCell, collections::VecDeque, marker::PhantomData};
+
+/// One call made to a backend, together with its response.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum Interaction {
+    Get { key: Vec<u8>, value: Vec<u8> },
+    Set { key: Vec<u8>, value: Vec<u8> },
+    Delete { key: Vec<u8> },
+}
+
+thread_local! {
+    static RECORDING: RefCell<Vec<Interaction>> = RefCell::new(Vec::new());
+    static REPLAYING: RefCell<VecDeque<Interaction>> = RefCell::new(VecDeque::new());
+}
+
+/// Forwards every call to `S` and records it.
+pub struct Record<S>(PhantomData<S>);
+
+impl<S: ExternalStorage> ExternalStorage for Record<S> {
+    fn get(key: Vec<u8>) -> Vec<u8> {
+        let value = S::get(key.clone());
+        record(Interaction::Get {
+            key,
+            value: value.clone(),
+        });
+        value
+    }
+
+    fn set(key: Vec<u8>, value: Vec<u8>) {
+        S::set(key.clone(), value.clone());
+        record(Interaction::Set { key, value });
+    }
+
+    fn delete(key: Vec<u8>) {
+        S::delete(key.clone());
+        record(Interaction::Delete { key });
+    }
+}
+
+fn record(interaction: Interaction) {
+    RECORDING.with(|r| r.borrow_mut().push(interaction));
+}
+
+/// Take everything recorded on this thread so far.
+pub fn take_recording() -> Vec<Interaction> {
+    RECORDING.with(|r| r.borrow_mut().split_off(0))
+}
+
+/// Serves the interactions given to `load_replay`, in order.
+pub struct Replay;
+
+impl ExternalStorage for Replay {
+    fn get(key: Vec<u8>) -> Vec<u8> {
+        match next() {
+            Interaction::Get { key: k, value } if k == key => value,
+            other => diverged(Interaction::Get { key, value: Vec::new() }, other),
+        }
+    }
+
+    fn set(key: Vec<u8>, value: Vec<u8>) {
+        let expected = Interaction::Set { key, value };
+        let recorded = next();
+        if recorded != expected {
+            diverged(expected, recorded)
+        }
+    }
+
+    fn delete(key: Vec<u8>) {
+        let expected = Interaction::Delete { key };
+        let recorded = next();
+        if recorded != expected {
+            diverged(expected, recorded)
+        }
+    }
+}
+
+/// Make `Replay` serve `interactions` on this thread.
+pub fn load_replay(interactions: Vec<Interaction>) {
+    REPLAYING.with(|r| *r.borrow_mut() = interactions.into());
+}
+
+/// Whether every loaded interaction has been replayed.
+pub fn replay_finished() -> bool {
+    REPLAYING.with(|r| r.borrow().is_empty())
+}
+
+fn next() -> Interaction {
+    REPLAYING
+        .with(|r| r.borrow_mut().pop_front())
+        .expect("replay ran past the end of the recording")
+}
+
+fn diverged(expected: Interaction, recorded: Interaction) -> ! {
+    panic!(
+        "replay diverged from the recording: got {:?}, recorded {:?}",
+        expected, recorded
+    )
+}
@@ -0,0 +1,24 @@
+//! Replication of values to storage providers.
+//!
+//! Besides `T::Storage`, every value can be kept by up to its replication factor of
+//! providers, each in its own backend. Writes and deletes go to all of them, and reads
+//! fall back to the replicas when a backend returns a value that fails the integrity
+//! check, so one unavailable backend doesn't make the data unreadable.
+
+use sp_std::vec::Vec;
+
+/// The backends of the storage providers, addressed by provider.
+pub trait ReplicaStorage<AccountId> {
+    fn get(provider: &AccountId, key: Vec<u8>) -> Vec<u8>;
+    fn set(provider: &AccountId, key: Vec<u8>, value: Vec<u8>);
+    fn delete(provider: &AccountId, key: Vec<u8>);
+}
+
+/// No replicas, every value is only kept by `T::Storage`.
+impl<AccountId> ReplicaStorage<AccountId> for () {
+    fn get(_: &AccountId, _: Vec<u8>) -> Vec<u8> {
+        Vec::new()
+    }
+    fn set(_: &AccountId, _: Vec<u8>, _: Vec<u8>) {}
+    fn delete(_: &AccountId, _: Vec<u8>) {}
+}
@@ -0,0 +1,53 @@
+//! Provider reputation.
+//!
+//! Every provider's track record is counted: reads its replica served, challenges it
+//! proved, and challenges it failed or let expire. Offchain workers of `T::Operators` also
+//! report how long providers take to answer. `reputation` turns the counts into a score
+//! placement logic and users can rank providers by.
+
+use super::{Metrics, Module, Trait};
+use codec::{Decode, Encode};
+use frame_support::StorageMap;
+use sp_runtime::{Perbill, RuntimeDebug};
+
+/// The track record of a provider.
+#[derive(Encode, Decode, Clone, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct ProviderMetrics {
+    /// Reads served from its replica.
+    pub served_reads: u32,
+    /// Challenges it answered correctly.
+    pub proved: u32,
+    /// Challenges it answered wrongly, or of its replicas that nobody answered.
+    pub failed: u32,
+    /// How many latencies were reported.
+    pub latency_reports: u32,
+    /// Sum of the reported latencies, in milliseconds.
+    pub total_latency: u64,
+}
+
+impl ProviderMetrics {
+    /// The average reported latency in milliseconds, `None` if none was reported.
+    pub fn average_latency(&self) -> Option<u64> {
+        if self.latency_reports == 0 {
+            None
+        } else {
+            Some(self.total_latency / self.latency_reports as u64)
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// The share of successful outcomes among all outcomes of `provider`, counting one
+    /// success and one failure extra so new providers start in the middle.
+    pub fn reputation(provider: &T::AccountId) -> Perbill {
+        let m = Self::metrics_of(provider);
+        let good = m.served_reads as u64 + m.proved as u64 + 1;
+        let bad = m.failed as u64 + 1;
+        Perbill::from_rational_approximation(good, good + bad)
+    }
+
+    // count an outcome for provider.
+    pub(crate) fn note_metric(provider: &T::AccountId, f: impl FnOnce(&mut ProviderMetrics)) {
+        <Metrics<T>>::mutate(provider, f);
+    }
+}
@@ -0,0 +1,222 @@
+//! Routing values to several storage backends.
+//!
+//! Besides `T::Storage`, the default backend with id [`DEFAULT_BACKEND`], a runtime can
+//! configure further backends in `T::Backends`, e.g. IPFS as the default and S3 as backend
+//! 1. An entry is created in a backend with `write_data_to`, every other write creates it
+//! in the default one, and `UserData` keeps the backend it was created in. The value of the
+//! entry, whole or in shards, is read, written and deleted there, for as long as it exists.
+//!
+//! Past versions, staged values and chunked uploads are kept in `T::Storage` whatever the
+//! backend of their entry.
+
+use super::{ExternalStorage, Module, StorageError, Trait};
+use sp_std::vec::Vec;
+
+/// Tells the storage backends of a runtime apart.
+pub type BackendId = u8;
+
+/// The id of `T::Storage`.
+pub const DEFAULT_BACKEND: BackendId = 0;
+
+/// The storage backends besides `T::Storage`, by id.
+pub trait StorageBackends {
+    /// Whether there is a backend with the id `id`.
+    fn exists(id: BackendId) -> bool;
+    fn get(id: BackendId, key: Vec<u8>) -> Result<Vec<u8>, StorageError>;
+    fn set(id: BackendId, key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError>;
+    fn delete(id: BackendId, key: Vec<u8>) -> Result<(), StorageError>;
+    fn get_range(
+        id: BackendId,
+        key: Vec<u8>,
+        offset: u32,
+        len: u32,
+    ) -> Result<Vec<u8>, StorageError>;
+    fn append(id: BackendId, key: Vec<u8>, bytes: Vec<u8>) -> Result<(), StorageError>;
+    fn pin(id: BackendId, key: Vec<u8>) -> Result<(), StorageError>;
+    fn unpin(id: BackendId, key: Vec<u8>) -> Result<(), StorageError>;
+}
+
+/// No backends besides `T::Storage`.
+impl StorageBackends for () {
+    fn exists(_: BackendId) -> bool {
+        false
+    }
+
+    fn get(_: BackendId, _: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        Err(StorageError::Unavailable)
+    }
+
+    fn set(_: BackendId, _: Vec<u8>, _: Vec<u8>) -> Result<(), StorageError> {
+        Err(StorageError::Unavailable)
+    }
+
+    fn delete(_: BackendId, _: Vec<u8>) -> Result<(), StorageError> {
+        Err(StorageError::Unavailable)
+    }
+
+    fn get_range(_: BackendId, _: Vec<u8>, _: u32, _: u32) -> Result<Vec<u8>, StorageError> {
+        Err(StorageError::Unavailable)
+    }
+
+    fn append(_: BackendId, _: Vec<u8>, _: Vec<u8>) -> Result<(), StorageError> {
+        Err(StorageError::Unavailable)
+    }
+
+    fn pin(_: BackendId, _: Vec<u8>) -> Result<(), StorageError> {
+        Err(StorageError::Unavailable)
+    }
+
+    fn unpin(_: BackendId, _: Vec<u8>) -> Result<(), StorageError> {
+        Err(StorageError::Unavailable)
+    }
+}
+
+// implement StorageBackends for a tuple of ExternalStorage, its members having the ids 1, 2...
+macro_rules! impl_backends {
+    ($($id:expr => $backend:ident),+) => {
+        impl<$($backend: ExternalStorage),+> StorageBackends for ($($backend,)+) {
+            fn exists(id: BackendId) -> bool {
+                $(id == $id)||+
+            }
+
+            fn get(id: BackendId, key: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+                match id {
+                    $($id => $backend::get(key),)+
+                    _ => Err(StorageError::Unavailable),
+                }
+            }
+
+            fn set(id: BackendId, key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+                match id {
+                    $($id => $backend::set(key, value),)+
+                    _ => Err(StorageError::Unavailable),
+                }
+            }
+
+            fn delete(id: BackendId, key: Vec<u8>) -> Result<(), StorageError> {
+                match id {
+                    $($id => $backend::delete(key),)+
+                    _ => Err(StorageError::Unavailable),
+                }
+            }
+
+            fn get_range(
+                id: BackendId,
+                key: Vec<u8>,
+                offset: u32,
+                len: u32,
+            ) -> Result<Vec<u8>, StorageError> {
+                match id {
+                    $($id => $backend::get_range(key, offset, len),)+
+                    _ => Err(StorageError::Unavailable),
+                }
+            }
+
+            fn append(id: BackendId, key: Vec<u8>, bytes: Vec<u8>) -> Result<(), StorageError> {
+                match id {
+                    $($id => $backend::append(key, bytes),)+
+                    _ => Err(StorageError::Unavailable),
+                }
+            }
+
+            fn pin(id: BackendId, key: Vec<u8>) -> Result<(), StorageError> {
+                match id {
+                    $($id => $backend::pin(key),)+
+                    _ => Err(StorageError::Unavailable),
+                }
+            }
+
+            fn unpin(id: BackendId, key: Vec<u8>) -> Result<(), StorageError> {
+                match id {
+                    $($id => $backend::unpin(key),)+
+                    _ => Err(StorageError::Unavailable),
+                }
+            }
+        }
+    };
+}
+
+impl_backends!(1 => A);
+impl_backends!(1 => A, 2 => B);
+impl_backends!(1 => A, 2 => B, 3 => C);
+
+impl<T: Trait> Module<T> {
+    /// Whether entries can be kept in the backend `id`.
+    pub fn has_backend(id: BackendId) -> bool {
+        id == DEFAULT_BACKEND || T::Backends::exists(id)
+    }
+
+    /// The name of the backend `id` in URIs: the one set with `set_backend_name`, or the id
+    /// in decimal.
+    pub fn provider_name(id: BackendId) -> Vec<u8> {
+        let name = Self::backend_name(id);
+        if !name.is_empty() {
+            return name;
+        }
+        let mut digits = Vec::new();
+        let mut n = id;
+        loop {
+            digits.insert(0, b'0' + n % 10);
+            n /= 10;
+            if n == 0 {
+                return digits;
+            }
+        }
+    }
+
+    pub(crate) fn routed_get(id: BackendId, key: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        if id == DEFAULT_BACKEND {
+            T::Storage::get(key)
+        } else {
+            T::Backends::get(id, key)
+        }
+    }
+
+    pub(crate) fn routed_set(
+        id: BackendId,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Result<(), StorageError> {
+        if id == DEFAULT_BACKEND {
+            T::Storage::set(key, value)
+        } else {
+            T::Backends::set(id, key, value)
+        }
+    }
+
+    pub(crate) fn routed_delete(id: BackendId, key: Vec<u8>) -> Result<(), StorageError> {
+        if id == DEFAULT_BACKEND {
+            T::Storage::delete(key)
+        } else {
+            T::Backends::delete(id, key)
+        }
+    }
+
+    pub(crate) fn routed_append(
+        id: BackendId,
+        key: Vec<u8>,
+        bytes: Vec<u8>,
+    ) -> Result<(), StorageError> {
+        if id == DEFAULT_BACKEND {
+            T::Storage::append(key, bytes)
+        } else {
+            T::Backends::append(id, key, bytes)
+        }
+    }
+
+    pub(crate) fn routed_pin(id: BackendId, key: Vec<u8>) -> Result<(), StorageError> {
+        if id == DEFAULT_BACKEND {
+            T::Storage::pin(key)
+        } else {
+            T::Backends::pin(id, key)
+        }
+    }
+
+    pub(crate) fn routed_unpin(id: BackendId, key: Vec<u8>) -> Result<(), StorageError> {
+        if id == DEFAULT_BACKEND {
+            T::Storage::unpin(key)
+        } else {
+            T::Backends::unpin(id, key)
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Snapshots of the metadata.
+//!
+//! Every `T::SnapshotPeriod` blocks the offchain worker serializes the `Data` map and the
+//! grants on entries into a [`MetadataSnapshot`] and stores it in `T::Storage` under
+//! [`snapshot_key`], replacing the previous one. The `metadata_snapshot` runtime API
+//! returns the same on demand. A snapshot holds the raw storage keys and values, so a
+//! chain that lost its state can put them back with `frame_system::set_storage` without
+//! resyncing from genesis.
+
+use super::{
+    Data, ExternalStorage, GrantedEntries, Module, ReadGrants, SessionGrants, Trait,
+    WriteDelegations,
+};
+use codec::{Decode, Encode};
+use frame_support::{storage::StoragePrefixedMap, traits::Get};
+use sp_runtime::{traits::Zero, RuntimeDebug};
+use sp_std::vec::Vec;
+
+/// The metadata of the module at a block.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct MetadataSnapshot<BlockNumber> {
+    /// The block the snapshot was taken at.
+    pub at: BlockNumber,
+    /// The raw storage keys and values.
+    pub pairs: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// The backend key the last snapshot is stored under.
+pub fn snapshot_key() -> Vec<u8> {
+    b"offchain-storage/snapshot".to_vec()
+}
+
+impl<T: Trait> Module<T> {
+    /// The entries and grants on them as they are now. Backs the `metadata_snapshot`
+    /// runtime API.
+    pub fn metadata_snapshot() -> MetadataSnapshot<T::BlockNumber> {
+        let prefixes = [
+            <Data<T>>::final_prefix(),
+            <WriteDelegations<T>>::final_prefix(),
+            <GrantedEntries<T>>::final_prefix(),
+            <ReadGrants<T>>::final_prefix(),
+            <SessionGrants<T>>::final_prefix(),
+        ];
+        let mut pairs = Vec::new();
+        for prefix in prefixes.iter() {
+            let mut key = prefix.to_vec();
+            while let Some(next) =
+                sp_io::storage::next_key(&key).filter(|next| next.starts_with(&prefix[..]))
+            {
+                if let Some(value) = sp_io::storage::get(&next) {
+                    pairs.push((next.clone(), value));
+                }
+                key = next;
+            }
+        }
+        MetadataSnapshot { at: <frame_system::Module<T>>::block_number(), pairs }
+    }
+
+    // store a snapshot in the backend if one is due at block n.
+    pub(crate) fn store_snapshot(n: T::BlockNumber) {
+        let period = T::SnapshotPeriod::get();
+        if !period.is_zero() && (n % period).is_zero() {
+            // a snapshot the backend fails to store is taken again next period.
+            let _ = T::Storage::set(snapshot_key(), Self::metadata_snapshot().encode());
+        }
+    }
+}
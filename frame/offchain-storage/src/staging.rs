@@ -0,0 +1,119 @@
+//! Staged writes.
+//!
+//! A writer can put a new value of an entry in its staging slot with `write_staged`,
+//! without changing what the entry reads as. Apps can fetch the staged value from the
+//! backend and check it, then `promote_staged` writes it live, or `discard_staged` drops
+//! it. The writer reserves the deposit for the staged value until then.
+//!
+//! For gradual rollouts the author can serve the staged value to a canary share of the
+//! readers with `set_canary`. Whether a reader is in it only depends on the hash of their
+//! account, so they read the same version every time, and the canary reads are counted so
+//! the author can watch them before promoting.
+//!
+//! The author can also name reviewers of an entry with `set_reviewers`. A staged value is
+//! then only promoted once each of them signed it off with `approve_staged`, naming the
+//! hash of the value they reviewed. Staging a new value starts the review over.
+
+use super::{Error, ExternalStorage, Module, Staged, Trait};
+use codec::{Decode, Encode};
+use frame_support::{ensure, traits::ReservableCurrency, StorageMap};
+use sp_runtime::{traits::Hash, Perbill, RuntimeDebug};
+use sp_std::vec::Vec;
+
+/// A value waiting in the staging slot of an entry.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct StagedWrite<AccountId, Hash, Balance> {
+    /// Who staged it, the write is made as them when it is promoted.
+    pub writer: AccountId,
+    /// Hash of the staged value.
+    pub content_hash: Hash,
+    /// The deposit the writer reserved for it.
+    pub deposit: Balance,
+    /// The share of readers served the staged value instead of the live one.
+    pub canary: Perbill,
+    /// How many reads were served the staged value.
+    pub canary_reads: u32,
+    /// The reviewers who signed the staged value off.
+    pub approvals: Vec<AccountId>,
+}
+
+/// The backend key the staged value of `data_id` is stored under.
+pub fn staged_key(data_id: &[u8]) -> Vec<u8> {
+    (&b"offchain-storage/staged"[..], data_id).encode()
+}
+
+impl<T: Trait> Module<T> {
+    /// The staged value of `data_id`, if there is one and the backend returns it intact.
+    pub fn staged_value(data_id: &[u8]) -> Option<Vec<u8>> {
+        let staged = Self::staged_of(data_id)?;
+        Self::get_staged(data_id, &staged).ok()
+    }
+
+    /// Whether `reader` is in the canary share of `data_id`, and reads its staged value.
+    pub fn reads_staged(data_id: &[u8], reader: &T::AccountId) -> bool {
+        let canary = match Self::staged_of(data_id) {
+            Some(staged) => staged.canary,
+            None => return false,
+        };
+        let seed = T::Hashing::hash(&reader.encode());
+        let point = u32::decode(&mut seed.as_ref()).unwrap_or_default() % 1_000_000_000;
+        Perbill::from_parts(point) < canary
+    }
+
+    // the value reader gets for data_id, the staged one if reader is in the canary share.
+    pub(crate) fn read_value(
+        data_id: &[u8],
+        reader: &T::AccountId,
+        data: &super::UserData<T::AccountId, T::Hash, T::BlockNumber>,
+    ) -> Result<Vec<u8>, Error<T>> {
+        let staged = match Self::staged_of(data_id) {
+            Some(staged) if Self::reads_staged(data_id, reader) => staged,
+            _ => return Self::get_external_storage(data_id, data),
+        };
+        let value = Self::get_staged(data_id, &staged)?;
+        <Staged<T>>::mutate(data_id, |staged| {
+            if let Some(staged) = staged {
+                staged.canary_reads = staged.canary_reads.saturating_add(1);
+            }
+        });
+        Ok(value)
+    }
+
+    // the staged value of data_id, which the backend must return intact.
+    pub(crate) fn get_staged(
+        data_id: &[u8],
+        staged: &StagedWrite<T::AccountId, T::Hash, super::BalanceOf<T>>,
+    ) -> Result<Vec<u8>, Error<T>> {
+        let key = staged_key(&Self::staged_source(data_id, &staged.content_hash));
+        Self::get_intact(data_id, key, &staged.content_hash)
+    }
+
+    // check that every reviewer of data_id signed staged off.
+    pub(crate) fn ensure_approved(
+        data_id: &[u8],
+        staged: &StagedWrite<T::AccountId, T::Hash, super::BalanceOf<T>>,
+    ) -> Result<(), Error<T>> {
+        let reviewers = Self::reviewers_of(data_id);
+        ensure!(reviewers.iter().all(|r| staged.approvals.contains(r)), Error::<T>::Unapproved);
+        Ok(())
+    }
+
+    // drop the staged value of data_id and release its deposit.
+    pub(crate) fn drop_staged(data_id: &[u8]) {
+        if Self::forget_staged(data_id) {
+            // a staged value left behind is replaced by the next one.
+            let _ = T::Storage::delete(staged_key(data_id));
+        }
+    }
+
+    // like drop_staged, leaving the backend alone. Returns whether something was staged.
+    pub(crate) fn forget_staged(data_id: &[u8]) -> bool {
+        match <Staged<T>>::take(data_id) {
+            Some(staged) => {
+                let _ = T::Currency::unreserve(&staged.writer, staged.deposit);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Provider stake.
+//!
+//! Storage providers bond stake with `bond_provider`, reserved from their balance. A
+//! `T::SlashFraction` share of it is slashed every time they answer a challenge wrongly,
+//! and for every challenge of an entry they keep a replica of that nobody answered, as the
+//! value is then likely lost. Unbonded stake stays slashable for `T::UnbondingDelay`
+//! blocks before it can be withdrawn.
+
+use super::{BalanceOf, Module, RawEvent, Stakes, Trait};
+use codec::{Decode, Encode};
+use frame_support::{
+    traits::{Get, OnUnbalanced, ReservableCurrency},
+    StorageMap,
+};
+use sp_runtime::{
+    traits::{Saturating, Zero},
+    RuntimeDebug,
+};
+
+/// The stake of a provider.
+#[derive(Encode, Decode, Clone, Default, PartialEq, Eq, RuntimeDebug)]
+pub struct ProviderStake<Balance, BlockNumber> {
+    /// Stake that is bonded.
+    pub bonded: Balance,
+    /// Stake that is being unbonded.
+    pub unbonding: Balance,
+    /// The block the unbonding stake can be withdrawn from.
+    pub unlock_at: BlockNumber,
+}
+
+impl<T: Trait> Module<T> {
+    // slash the stake of provider, which failed the challenge of data_id.
+    pub(crate) fn slash_provider(provider: &T::AccountId, data_id: &[u8]) {
+        let mut stake = match Self::stake_of(provider) {
+            Some(stake) => stake,
+            None => return,
+        };
+        let amount = T::SlashFraction::get() * stake.bonded.saturating_add(stake.unbonding);
+        if amount.is_zero() {
+            return;
+        }
+        let (imbalance, _) = T::Currency::slash_reserved(provider, amount);
+        // bonded stake goes first, so unbonding doesn't shield from slashing.
+        let from_bonded = amount.min(stake.bonded);
+        stake.bonded -= from_bonded;
+        stake.unbonding = stake.unbonding.saturating_sub(amount - from_bonded);
+        if stake.bonded.is_zero() && stake.unbonding.is_zero() {
+            <Stakes<T>>::remove(provider);
+        } else {
+            <Stakes<T>>::insert(provider, stake);
+        }
+        T::Slash::on_unbalanced(imbalance);
+        Self::deposit_event(RawEvent::ProviderSlashed(data_id.to_vec()));
+    }
+}
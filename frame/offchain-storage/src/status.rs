@@ -0,0 +1,40 @@
+//! Progress of deferred writes.
+//!
+//! With `T::DeferredUploads` on, a write is only done once the offchain worker uploaded its
+//! value, which may be many blocks after it was made, see the upload module. Every
+//! deferred write gets an id, given by `UploadQueued`, and a [`RequestStatus`] that says
+//! how far its upload got, so UIs can show the progress through the `upload_status`
+//! runtime API instead of waiting for events. A write is `Queued` until the worker takes it
+//! up with `start_upload`, then `Executing` until the worker confirms it `Committed` with
+//! the content hash of the value uploaded, or reports it `Failed` with the code of the
+//! backend failure. A write whose value is overwritten or deleted before it is uploaded
+//! fails with the code of `UploadSuperseded`. Statuses of finished writes are kept for
+//! `T::StatusRetention` blocks.
+
+use super::{Module, RequestStatus, StatusQueue, Trait, UploadStatuses};
+use frame_support::{traits::Get, StorageDoubleMap, StorageMap};
+use sp_runtime::traits::{One, Saturating};
+use sp_std::vec::Vec;
+
+impl<T: Trait> Module<T> {
+    // set the status of deferred write id, dropping it later if it is final.
+    pub(crate) fn set_upload_status(id: u64, status: RequestStatus<T::Hash>) {
+        if status.is_final() {
+            // on_initialize of this block is over, so the status is dropped next block at the
+            // earliest.
+            let retention = T::StatusRetention::get().max(One::one());
+            let now = <frame_system::Module<T>>::block_number();
+            <StatusQueue<T>>::insert(now.saturating_add(retention), id, id);
+        }
+        <UploadStatuses<T>>::insert(id, status);
+    }
+
+    // forget the statuses kept until block n.
+    pub(crate) fn forget_upload_statuses(n: T::BlockNumber) {
+        let ids: Vec<u64> = <StatusQueue<T>>::iter_prefix(n).collect();
+        <StatusQueue<T>>::remove_prefix(n);
+        for id in ids {
+            <UploadStatuses<T>>::remove(id);
+        }
+    }
+}
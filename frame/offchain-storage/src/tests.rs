@@ -0,0 +1,3616 @@
+//! Tests for the module.
+
+use super::*;
+use mock::*;
+
+use frame_support::{assert_noop, assert_ok};
+use sp_core::H256;
+use sp_runtime::{
+    testing::Header,
+    traits::{BlakeTwo256, OffchainWorker, OnFinalize, OnInitialize},
+    DispatchError,
+};
+
+#[test]
+fn do_external_storage() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let value: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(
+            Origin::signed(1),
+            key.clone(),
+            value.clone()
+        ));
+        assert_eq!(stored(&key), Some(value));
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        OffchainStorage::on_initialize(2);
+        assert_eq!(stored(&key), None);
+    });
+}
+
+#[test]
+fn only_author_can_overwrite_or_delete() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_eq!(OffchainStorage::get_data(&key).author, 1);
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(2), key.clone(), b"b".to_vec()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_noop!(
+            OffchainStorage::delete_data(Origin::signed(2), key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+    });
+}
+
+#[test]
+fn delegated_writes_are_counted() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::delegate_write(Origin::signed(2), key.clone(), 3, 2),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 3, 2));
+
+        assert_ok!(OffchainStorage::write_data(Origin::signed(3), key.clone(), b"b".to_vec()));
+        assert_eq!(OffchainStorage::write_delegations(&key, 3), 1);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(3), key.clone(), b"c".to_vec()));
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(3), key.clone(), b"d".to_vec()),
+            Error::<Test>::DelegationExhausted
+        );
+        // the author is kept when a delegate writes.
+        assert_eq!(OffchainStorage::get_data(&key).author, 1);
+        assert_eq!(stored(&key), Some(b"c".to_vec()));
+    });
+}
+
+#[test]
+fn revoked_delegation_denies_writes() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 3, 5));
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 3, 0));
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(3), key.clone(), b"b".to_vec()),
+            Error::<Test>::PermissionDenied
+        );
+    });
+}
+
+#[test]
+fn force_delete_bypasses_author() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::force_delete_data(Origin::signed(1), key.clone()),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(OffchainStorage::force_delete_data(Origin::ROOT, key.clone()));
+        assert!(!<Data<Test>>::exists(&key));
+        assert_eq!(stored(&key), None);
+        assert_noop!(
+            OffchainStorage::force_delete_data(Origin::ROOT, key),
+            Error::<Test>::NoneData
+        );
+    });
+}
+
+#[test]
+fn force_set_access_bypasses_author() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::force_set_access(Origin::signed(2), key.clone(), Access::Write),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert_eq!(OffchainStorage::get_data(&key).access, Access::Avoid);
+        assert_noop!(
+            OffchainStorage::read_data(Origin::signed(2), key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+    });
+}
+
+#[test]
+fn recorded_backend_calls_replay_deterministically() {
+    use crate::replay::{self, Interaction, Record, Replay};
+
+    let key: Vec<u8> = b"key".to_vec();
+    new_test_ext().execute_with(|| {
+        Record::<DB>::set(key.clone(), b"a".to_vec());
+        assert_eq!(Record::<DB>::get(key.clone()), b"a".to_vec());
+        Record::<DB>::delete(key.clone());
+    });
+    let recording = replay::take_recording();
+    assert_eq!(
+        recording,
+        vec![
+            Interaction::Set { key: key.clone(), value: b"a".to_vec() },
+            Interaction::Get { key: key.clone(), value: b"a".to_vec() },
+            Interaction::Delete { key: key.clone() },
+        ]
+    );
+
+    // the backend is gone, only the recording drives the second run.
+    replay::load_replay(Decode::decode(&mut &recording.encode()[..]).unwrap());
+    Replay::set(key.clone(), b"a".to_vec());
+    assert_eq!(Replay::get(key.clone()), b"a".to_vec());
+    Replay::delete(key);
+    assert!(replay::replay_finished());
+}
+
+#[test]
+fn tampered_value_fails_integrity_check() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_eq!(
+            OffchainStorage::get_data(&key).content_hash,
+            BlakeTwo256::hash(b"a")
+        );
+        DB::set(key.clone(), b"evil".to_vec());
+        assert_noop!(
+            OffchainStorage::read_for(&2, &key).map_err(DispatchError::from),
+            Error::<Test>::IntegrityError
+        );
+    });
+}
+
+#[test]
+fn content_addressed_write_derives_id() {
+    new_test_ext().execute_with(|| {
+        let value: Vec<u8> = b"shared".to_vec();
+        let hash = BlakeTwo256::hash(&value);
+        let key = hash.encode();
+        assert_eq!(
+            OffchainStorage::validate_content_addressed(&1, &hash, 6),
+            Ok(FeeEstimate { kind: WriteKind::Create, chunks: 2, deposit: 8 })
+        );
+        assert_ok!(OffchainStorage::write_content_addressed(Origin::signed(1), value.clone()));
+        assert_eq!(stored(&key), Some(value.clone()));
+        assert_eq!(OffchainStorage::get_data(&key).author, 1);
+
+        // the same content from someone else maps to the same entry.
+        assert_eq!(
+            OffchainStorage::validate_content_addressed(&2, &hash, 6),
+            Ok(FeeEstimate { kind: WriteKind::Duplicate, chunks: 0, deposit: 0 })
+        );
+        assert_ok!(OffchainStorage::write_content_addressed(Origin::signed(2), value));
+        assert_eq!(OffchainStorage::get_data(&key).author, 1);
+    });
+}
+
+// Build the state of a remote chain holding `data` under `data_id` and prove that entry.
+fn remote_state(data_id: &[u8], data: &UserData<u64, H256, u64>) -> (Header, Vec<Vec<u8>>) {
+    use sp_trie::{
+        trie_types::{TrieDB, TrieDBMut},
+        MemoryDB, Recorder, Trie, TrieMut,
+    };
+    type Hasher = <BlakeTwo256 as Hash>::Hasher;
+
+    let mut db = MemoryDB::<Hasher>::default();
+    let mut root = H256::default();
+    let key = <Data<Test>>::hashed_key_for(data_id);
+    {
+        let mut trie = TrieDBMut::<Hasher>::new(&mut db, &mut root);
+        trie.insert(b"unrelated", b"entry").unwrap();
+        trie.insert(&key, &data.encode()).unwrap();
+    }
+    let trie = TrieDB::<Hasher>::new(&db, &root).unwrap();
+    let mut recorder = Recorder::new();
+    trie.get_with(&key, &mut recorder).unwrap();
+    let proof = recorder.drain().into_iter().map(|r| r.data).collect();
+
+    let header = Header {
+        parent_hash: Default::default(),
+        number: 10,
+        state_root: root,
+        extrinsics_root: Default::default(),
+        digest: Default::default(),
+    };
+    (header, proof)
+}
+
+#[test]
+fn mirror_remote_data_verifies_proof() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let remote = UserData {
+            author: 7,
+            access: Access::Read,
+            content_hash: BlakeTwo256::hash(b"remote"),
+            ..Default::default()
+        };
+        let (header, proof) = remote_state(&key, &remote);
+
+        // the header must be trusted.
+        assert_noop!(
+            OffchainStorage::mirror_remote_data(
+                Origin::signed(1),
+                b"remote".to_vec(),
+                header.clone(),
+                key.clone(),
+                proof.clone()
+            ),
+            Error::<Test>::InvalidRemoteProof
+        );
+        TRUSTED.with(|t| t.borrow_mut().push(header.hash()));
+
+        // the proof must be for the requested data_id.
+        assert_noop!(
+            OffchainStorage::mirror_remote_data(
+                Origin::signed(1),
+                b"remote".to_vec(),
+                header.clone(),
+                b"other".to_vec(),
+                proof.clone()
+            ),
+            Error::<Test>::InvalidRemoteProof
+        );
+
+        assert_ok!(OffchainStorage::mirror_remote_data(
+            Origin::signed(1),
+            b"remote".to_vec(),
+            header.clone(),
+            key.clone(),
+            proof
+        ));
+        assert!(OffchainStorage::get_data(&key) == remote);
+        assert_eq!(
+            OffchainStorage::mirror_of(&key),
+            Some((b"remote".to_vec(), header.hash()))
+        );
+
+        // mirrored data can be read but not changed.
+        DB::set(key.clone(), b"remote".to_vec());
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(7), key.clone(), b"b".to_vec()),
+            Error::<Test>::ReadOnlyMirror
+        );
+        assert_noop!(
+            OffchainStorage::delete_data(Origin::signed(7), key),
+            Error::<Test>::ReadOnlyMirror
+        );
+    });
+}
+
+#[test]
+fn remote_requests_act_as_sovereign_account() {
+    use crate::bridge::{HandleRemoteRequest, RemoteRequest, SovereignAccount};
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let sovereign: u64 =
+            bridge::HashedSovereignAccount::sovereign_account(b"spoke", b"alice");
+        let _ = Balances::deposit_creating(&sovereign, 100);
+        assert_ok!(OffchainStorage::handle_remote_request(
+            b"spoke",
+            b"alice",
+            RemoteRequest::Write { data_id: key.clone(), value: b"a".to_vec() }
+        ));
+        assert_eq!(OffchainStorage::get_data(&key).author, sovereign);
+        assert_eq!(stored(&key), Some(b"a".to_vec()));
+
+        // another remote sender is subject to the usual permission checks.
+        assert_noop!(
+            OffchainStorage::handle_remote_request(
+                b"spoke",
+                b"bob",
+                RemoteRequest::Delete { data_id: key.clone() }
+            ),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::handle_remote_request(
+            b"spoke",
+            b"alice",
+            RemoteRequest::Delete { data_id: key.clone() }
+        ));
+        assert_eq!(stored(&key), None);
+    });
+}
+
+#[test]
+fn large_values_commit_to_chunk_merkle_root() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let value: Vec<u8> = b"0123456789".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), value.clone()));
+
+        let leaves = merkle::leaves::<BlakeTwo256>(&value, 4);
+        assert_eq!(
+            OffchainStorage::chunk_root(&key),
+            Some((merkle::root::<BlakeTwo256>(&leaves), 3))
+        );
+        for (index, chunk) in value.chunks(4).enumerate() {
+            let proof = merkle::proof::<BlakeTwo256>(&leaves, index);
+            assert!(OffchainStorage::verify_chunk(&key, index as u32, chunk, &proof));
+            assert!(!OffchainStorage::verify_chunk(&key, index as u32, b"evil", &proof));
+        }
+        let proof = merkle::proof::<BlakeTwo256>(&leaves, 0);
+        assert!(!OffchainStorage::verify_chunk(&key, 1, b"0123", &proof));
+
+        // small values have no chunk root.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"0".to_vec()));
+        assert_eq!(OffchainStorage::chunk_root(&key), None);
+    });
+}
+
+#[test]
+fn signed_writes_keep_verified_signature() {
+    use sp_core::{ed25519, sr25519, Pair};
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let value: Vec<u8> = b"signed".to_vec();
+        let pair = sr25519::Pair::from_seed(&[1; 32]);
+        let signer: MultiSigner = pair.public().into();
+        let signature: MultiSignature = pair.sign(&value).into();
+
+        assert_noop!(
+            OffchainStorage::write_signed_data(
+                Origin::signed(1),
+                key.clone(),
+                b"other".to_vec(),
+                signer.clone(),
+                signature.clone()
+            ),
+            Error::<Test>::BadSignature
+        );
+        assert_ok!(OffchainStorage::write_signed_data(
+            Origin::signed(1),
+            key.clone(),
+            value.clone(),
+            signer.clone(),
+            signature.clone()
+        ));
+        assert_eq!(
+            OffchainStorage::content_signature(&key),
+            Some((signer, signature))
+        );
+
+        let pair = ed25519::Pair::from_seed(&[2; 32]);
+        assert_ok!(OffchainStorage::write_signed_data(
+            Origin::signed(1),
+            key.clone(),
+            value.clone(),
+            pair.public().into(),
+            pair.sign(&value).into()
+        ));
+
+        // an unsigned overwrite drops the signature of the old value.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"new".to_vec()));
+        assert_eq!(OffchainStorage::content_signature(&key), None);
+    });
+}
+
+#[test]
+fn challenges_are_answered_with_chunk_proofs() {
+    use crate::challenge::StorageProof;
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let value: Vec<u8> = b"0123456789".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), value.clone()));
+        assert_ok!(OffchainStorage::challenge_storage(Origin::signed(2), key.clone()));
+        assert_noop!(
+            OffchainStorage::challenge_storage(Origin::signed(2), key.clone()),
+            Error::<Test>::AlreadyChallenged
+        );
+
+        let open = OffchainStorage::challenge_of(&key).unwrap();
+        assert_eq!(open.deadline, 6);
+        let index = open.chunk_index as usize;
+        let leaves = merkle::leaves::<BlakeTwo256>(&value, 4);
+        let proof = StorageProof {
+            chunk: value.chunks(4).nth(index).unwrap().to_vec(),
+            path: merkle::proof::<BlakeTwo256>(&leaves, index),
+        };
+
+        assert_noop!(
+            OffchainStorage::submit_storage_proof(Origin::signed(1), key.clone(), 1, proof.clone()),
+            Error::<Test>::NotProvider
+        );
+        assert_noop!(
+            OffchainStorage::submit_storage_proof(Origin::signed(9), key.clone(), 2, proof.clone()),
+            Error::<Test>::NoChallenge
+        );
+
+        // a wrong answer is counted and the challenge stays open.
+        let wrong = StorageProof { chunk: b"evil".to_vec(), ..proof.clone() };
+        assert_ok!(OffchainStorage::submit_storage_proof(Origin::signed(9), key.clone(), 1, wrong));
+        assert_eq!(OffchainStorage::failed_proofs(9), 1);
+        assert!(OffchainStorage::challenge_of(&key).is_some());
+
+        assert_ok!(OffchainStorage::submit_storage_proof(Origin::signed(9), key.clone(), 1, proof));
+        assert_eq!(OffchainStorage::challenge_of(&key), None);
+    });
+}
+
+#[test]
+fn unanswered_challenges_expire() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::challenge_storage(Origin::signed(2), key.clone()));
+
+        OffchainStorage::on_initialize(5);
+        assert!(OffchainStorage::challenge_of(&key).is_some());
+        OffchainStorage::on_initialize(6);
+        assert_eq!(OffchainStorage::challenge_of(&key), None);
+        assert_eq!(OffchainStorage::missed_challenges(&key), 1);
+    });
+}
+
+#[test]
+fn call_and_event_indices_are_stable() {
+    // clients decode by these indices, see types.json.
+    let calls = [
+        Call::<Test>::read_data(vec![]).encode()[0],
+        Call::<Test>::write_data(vec![], vec![]).encode()[0],
+        Call::<Test>::delete_data(vec![]).encode()[0],
+        Call::<Test>::force_delete_data(vec![]).encode()[0],
+        Call::<Test>::challenge_storage(vec![]).encode()[0],
+    ];
+    assert_eq!(calls, [0, 1, 4, 6, 8]);
+
+    let events = [
+        Event::<Test>::GetData(Default::default()).encode()[0],
+        Event::<Test>::ContentWritten(vec![]).encode()[0],
+        Event::<Test>::ChallengeExpired(vec![], 0).encode()[0],
+    ];
+    assert_eq!(events, [0, 3, 9]);
+}
+
+#[test]
+fn validate_write_matches_write_data() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_eq!(
+            OffchainStorage::validate_write(&1, &key, 10),
+            Ok(FeeEstimate { kind: WriteKind::Create, chunks: 3, deposit: 12 })
+        );
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_eq!(
+            OffchainStorage::validate_write(&1, &key, 4),
+            Ok(FeeEstimate { kind: WriteKind::Update, chunks: 0, deposit: 3 })
+        );
+        assert_eq!(
+            OffchainStorage::validate_write(&2, &key, 1),
+            Err(ValidationError::PermissionDenied)
+        );
+
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 2, 1));
+        assert!(OffchainStorage::validate_write(&2, &key, 1).is_ok());
+        // validating doesn't use up the delegation.
+        assert_eq!(OffchainStorage::write_delegations(&key, 2), 1);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(2), b"k2".to_vec(), b"b".to_vec()));
+        assert_eq!(
+            OffchainStorage::validate_write(&2, &key, 1),
+            Err(ValidationError::DelegationExhausted)
+        );
+    });
+}
+
+#[test]
+fn replicas_follow_writes_and_serve_reads() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::accept_replica(Origin::signed(9), key.clone()),
+            Error::<Test>::ReplicationFull
+        );
+        assert_noop!(
+            OffchainStorage::set_replication(Origin::signed(2), key.clone(), 1),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::set_replication(Origin::signed(1), key.clone(), 1));
+        assert_noop!(
+            OffchainStorage::accept_replica(Origin::signed(2), key.clone()),
+            Error::<Test>::NotProvider
+        );
+        assert_ok!(OffchainStorage::accept_replica(Origin::signed(9), key.clone()));
+        assert_eq!(replica(9, &key), Some(b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::accept_replica(Origin::signed(9), key.clone()),
+            Error::<Test>::AlreadyReplica
+        );
+
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"b".to_vec()));
+        assert_eq!(replica(9, &key), Some(b"b".to_vec()));
+
+        // the replica answers when the main backend loses the value.
+        DB::delete(key.clone());
+        assert_eq!(OffchainStorage::read_for(&1, &key).ok(), Some(b"b".to_vec()));
+
+        assert_ok!(OffchainStorage::set_replication(Origin::signed(1), key.clone(), 0));
+        assert_eq!(replica(9, &key), None);
+        assert_noop!(
+            OffchainStorage::read_for(&1, &key).map_err(DispatchError::from),
+            Error::<Test>::IntegrityError
+        );
+    });
+}
+
+#[test]
+fn deleting_data_removes_replicas() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::set_replication(Origin::signed(1), key.clone(), 1));
+        assert_ok!(OffchainStorage::accept_replica(Origin::signed(9), key.clone()));
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        OffchainStorage::on_initialize(2);
+        OffchainStorage::on_initialize(3);
+        assert_eq!(replica(9, &key), None);
+        assert_eq!(stored(&key), None);
+    });
+}
+
+#[test]
+fn erasure_coded_values_survive_lost_shards() {
+    new_test_ext().execute_with(|| {
+        CODING.with(|c| *c.borrow_mut() = Some((2, 4)));
+        let key: Vec<u8> = b"key".to_vec();
+        let value = b"0123456789".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), value));
+        assert_eq!(stored(&key), None);
+        assert_eq!(stored(&erasure::shard_key(&key, 1, 0)), Some(b"01234".to_vec()));
+        assert_eq!(OffchainStorage::shards_of(&key).unwrap().hashes.len(), 4);
+
+        // any two shards rebuild the value, tampered ones count as lost.
+        DB::delete(erasure::shard_key(&key, 1, 0));
+        DB::set(erasure::shard_key(&key, 1, 2), b"evil".to_vec());
+        assert_eq!(OffchainStorage::read_for(&1, &key).ok(), Some(b"0123456789".to_vec()));
+        DB::delete(erasure::shard_key(&key, 1, 3));
+        assert_noop!(
+            OffchainStorage::read_for(&1, &key).map_err(DispatchError::from),
+            Error::<Test>::IntegrityError
+        );
+
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        OffchainStorage::on_initialize(2);
+        OffchainStorage::on_initialize(3);
+        assert_eq!(stored(&erasure::shard_key(&key, 1, 1)), None);
+        assert_eq!(OffchainStorage::shards_of(&key), None);
+    });
+}
+
+#[test]
+fn keyring_changes_make_wrapped_keys_stale() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::create_keyring(Origin::signed(1)));
+        assert_ok!(OffchainStorage::add_keyring_member(Origin::signed(1), 0, 2, [2; 32]));
+        assert_ok!(OffchainStorage::add_keyring_member(Origin::signed(1), 0, 3, [3; 32]));
+        assert_noop!(
+            OffchainStorage::add_keyring_member(Origin::signed(2), 0, 4, [4; 32]),
+            Error::<Test>::PermissionDenied
+        );
+        assert_noop!(
+            OffchainStorage::wrap_keys(Origin::signed(1), key.clone(), 2, vec![]),
+            Error::<Test>::NoKeyring
+        );
+        assert_ok!(OffchainStorage::attach_keyring(Origin::signed(1), key.clone(), Some(0)));
+
+        let wrapped = vec![(2, b"for 2".to_vec()), (3, b"for 3".to_vec())];
+        assert_noop!(
+            OffchainStorage::wrap_keys(Origin::signed(1), key.clone(), 1, wrapped.clone()),
+            Error::<Test>::StaleEpoch
+        );
+        assert_noop!(
+            OffchainStorage::wrap_keys(Origin::signed(1), key.clone(), 2, vec![(4, vec![])]),
+            Error::<Test>::NotMember
+        );
+        assert_ok!(OffchainStorage::wrap_keys(Origin::signed(1), key.clone(), 2, wrapped));
+        assert_eq!(OffchainStorage::wrapped_key_for(&key, &2), Some(b"for 2".to_vec()));
+
+        // removing a member leaves nobody with a key until the author wraps again.
+        assert_ok!(OffchainStorage::remove_keyring_member(Origin::signed(1), 0, 3));
+        assert_eq!(OffchainStorage::keyring(0).unwrap().epoch, 3);
+        assert_eq!(OffchainStorage::wrapped_key_for(&key, &2), None);
+        assert_ok!(OffchainStorage::wrap_keys(
+            Origin::signed(1),
+            key.clone(),
+            3,
+            vec![(2, b"new for 2".to_vec())]
+        ));
+        assert_eq!(OffchainStorage::wrapped_key_for(&key, &2), Some(b"new for 2".to_vec()));
+        assert_eq!(OffchainStorage::wrapped_key_for(&key, &3), None);
+    });
+}
+
+#[test]
+fn deposits_follow_value_size() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), vec![0; 10]));
+        assert_eq!(Balances::reserved_balance(1), 12);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), vec![0; 4]));
+        assert_eq!(Balances::reserved_balance(1), 6);
+
+        // the author pays for the writes of a delegate.
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 2, 1));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(2), key.clone(), vec![0; 8]));
+        assert_eq!(Balances::reserved_balance(1), 10);
+        assert_eq!(Balances::reserved_balance(2), 0);
+
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(1), key.clone(), vec![0; 200]),
+            Error::<Test>::InsufficientBalance
+        );
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        assert_eq!(Balances::reserved_balance(1), 10);
+        OffchainStorage::on_initialize(2);
+        assert_eq!(Balances::reserved_balance(1), 0);
+        assert_eq!(Balances::free_balance(1), 100);
+    });
+}
+
+#[test]
+fn guardian_revokes_all_grants() {
+    new_test_ext().execute_with(|| {
+        let (a, b): (Vec<u8>, Vec<u8>) = (b"a".to_vec(), b"b".to_vec());
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), b.clone(), b"b".to_vec()));
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), a.clone(), 2, 5));
+        assert_ok!(OffchainStorage::create_keyring(Origin::signed(1)));
+        assert_ok!(OffchainStorage::add_keyring_member(Origin::signed(1), 0, 2, [2; 32]));
+        assert_ok!(OffchainStorage::attach_keyring(Origin::signed(1), b.clone(), Some(0)));
+        assert_ok!(OffchainStorage::wrap_keys(Origin::signed(1), b.clone(), 1, vec![(2, vec![2])]));
+
+        assert_noop!(
+            OffchainStorage::revoke_all_grants(Origin::signed(3), 1),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::set_guardian(Origin::signed(1), Some(3)));
+        assert_ok!(OffchainStorage::revoke_all_grants(Origin::signed(3), 1));
+
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(2), a.clone(), b"c".to_vec()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_eq!(OffchainStorage::keyring_of(&b), None);
+        assert_eq!(OffchainStorage::wrapped_key_for(&b, &2), None);
+        assert!(OffchainStorage::granted_entries(1).is_empty());
+    });
+}
+
+#[test]
+fn unpaid_rent_expires_data() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        System::set_block_number(1);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(7), key.clone(), vec![0; 10]));
+        assert_eq!(Balances::free_balance(7), 88);
+
+        // ten bytes for four blocks.
+        System::set_block_number(5);
+        assert_ok!(OffchainStorage::collect_rent(Origin::signed(2), key.clone()));
+        assert_eq!(Balances::free_balance(7), 48);
+        assert_eq!(Balances::free_balance(TREASURY), 4);
+        assert!(!OffchainStorage::is_expired(&key));
+
+        System::set_block_number(10);
+        assert_ok!(OffchainStorage::collect_rent(Origin::signed(2), key.clone()));
+        assert!(OffchainStorage::is_expired(&key));
+        assert_eq!(Balances::free_balance(7), 48);
+
+        // expired data can be read, but not written.
+        assert_ok!(OffchainStorage::read_data(Origin::signed(7), key.clone()));
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(7), key.clone(), vec![1]),
+            Error::<Test>::Expired
+        );
+        assert_noop!(
+            OffchainStorage::remove_expired(Origin::signed(2), key.clone()),
+            Error::<Test>::NotExpired
+        );
+        System::set_block_number(13);
+        assert_ok!(OffchainStorage::remove_expired(Origin::signed(2), key.clone()));
+        assert_eq!(Balances::reserved_balance(7), 0);
+        // the value is left to the garbage collector.
+        OffchainStorage::on_initialize(14);
+        assert_eq!(stored(&key), None);
+    });
+}
+
+#[test]
+fn read_access_can_be_bought() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert_noop!(
+            OffchainStorage::purchase_read_access(Origin::signed(2), key.clone()),
+            Error::<Test>::NotForSale
+        );
+        assert_ok!(OffchainStorage::set_read_price(Origin::signed(1), key.clone(), Some(10)));
+
+        assert_ok!(OffchainStorage::purchase_read_access(Origin::signed(2), key.clone()));
+        assert_eq!(Balances::free_balance(2), 90);
+        assert_eq!(Balances::free_balance(1), 106);
+        assert_eq!(Balances::free_balance(TREASURY), 1);
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+        assert_noop!(
+            OffchainStorage::read_data(Origin::signed(3), key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_noop!(
+            OffchainStorage::purchase_read_access(Origin::signed(2), key.clone()),
+            Error::<Test>::AlreadyReadable
+        );
+
+        // buyers keep access when the author stops selling it, until it is revoked.
+        assert_ok!(OffchainStorage::set_read_price(Origin::signed(1), key.clone(), None));
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+        assert_ok!(OffchainStorage::revoke_all_grants(Origin::signed(1), 1));
+        assert_noop!(
+            OffchainStorage::read_data(Origin::signed(2), key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+    });
+}
+
+#[test]
+fn session_grants_lapse_at_rotation() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert_ok!(OffchainStorage::grant_session_access(
+            Origin::signed(1),
+            key.clone(),
+            2,
+            Access::Write
+        ));
+        assert_ok!(OffchainStorage::grant_session_access(
+            Origin::signed(1),
+            key.clone(),
+            3,
+            Access::Read
+        ));
+        assert_ok!(OffchainStorage::read_data(Origin::signed(3), key.clone()));
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(3), key.clone(), b"b".to_vec()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::write_data(Origin::signed(2), b"k2".to_vec(), b"b".to_vec()));
+
+        SESSION.with(|s| *s.borrow_mut() = 1);
+        assert_eq!(OffchainStorage::session_access(&key, &2), None);
+        assert_noop!(
+            OffchainStorage::read_data(Origin::signed(3), key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(2), key.clone(), b"c".to_vec()),
+            Error::<Test>::PermissionDenied
+        );
+    });
+}
+
+#[test]
+fn operator_access_follows_the_validator_set() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert_noop!(
+            OffchainStorage::set_operator_access(Origin::signed(2), key.clone(), Access::Read),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::set_operator_access(
+            Origin::signed(1),
+            key.clone(),
+            Access::Read
+        ));
+        assert_noop!(
+            OffchainStorage::read_data(Origin::signed(3), key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+
+        OPERATORS.with(|o| *o.borrow_mut() = vec![3]);
+        assert_ok!(OffchainStorage::read_data(Origin::signed(3), key.clone()));
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(3), key.clone(), b"b".to_vec()),
+            Error::<Test>::PermissionDenied
+        );
+
+        // leaving the set takes the access away, with no grant to revoke.
+        OPERATORS.with(|o| *o.borrow_mut() = vec![2]);
+        assert_noop!(
+            OffchainStorage::read_data(Origin::signed(3), key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+    });
+}
+
+#[test]
+fn failed_challenges_slash_provider_stake() {
+    use crate::challenge::StorageProof;
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_noop!(
+            OffchainStorage::bond_provider(Origin::signed(1), 40),
+            Error::<Test>::NotProvider
+        );
+        assert_ok!(OffchainStorage::bond_provider(Origin::signed(9), 40));
+        assert_eq!(Balances::reserved_balance(9), 40);
+
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::challenge_storage(Origin::signed(2), key.clone()));
+        let wrong = StorageProof { chunk: b"evil".to_vec(), path: vec![] };
+        assert_ok!(OffchainStorage::submit_storage_proof(Origin::signed(9), key.clone(), 1, wrong));
+        assert_eq!(OffchainStorage::stake_of(9).unwrap().bonded, 20);
+        assert_eq!(Balances::reserved_balance(9), 20);
+        assert_eq!(Balances::free_balance(TREASURY), 20);
+
+        System::set_block_number(1);
+        assert_noop!(
+            OffchainStorage::unbond_provider(Origin::signed(9), 30),
+            Error::<Test>::InsufficientBond
+        );
+        assert_ok!(OffchainStorage::unbond_provider(Origin::signed(9), 20));
+        assert_noop!(
+            OffchainStorage::withdraw_unbonded(Origin::signed(9)),
+            Error::<Test>::NotUnbonded
+        );
+        System::set_block_number(3);
+        assert_ok!(OffchainStorage::withdraw_unbonded(Origin::signed(9)));
+        assert_eq!(OffchainStorage::stake_of(9), None);
+        assert_eq!(Balances::free_balance(9), 80);
+    });
+}
+
+#[test]
+fn quorum_reads_need_co_signers() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::approve_read(Origin::signed(2), key.clone(), 2),
+            Error::<Test>::NoQuorum
+        );
+        assert_ok!(OffchainStorage::set_read_quorum(Origin::signed(1), key.clone(), 2));
+
+        // the reader's own request is the first signature.
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+        assert_eq!(OffchainStorage::pending_read(&key, 2), vec![2]);
+        assert_noop!(
+            OffchainStorage::approve_read(Origin::signed(2), key.clone(), 2),
+            Error::<Test>::AlreadySigned
+        );
+        assert_noop!(
+            OffchainStorage::approve_read(Origin::signed(3), key.clone(), 7),
+            Error::<Test>::NoPendingRead
+        );
+
+        assert_ok!(OffchainStorage::approve_read(Origin::signed(3), key.clone(), 2));
+        assert!(OffchainStorage::pending_read(&key, 2).is_empty());
+
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+        assert_ok!(OffchainStorage::set_read_quorum(Origin::signed(1), key.clone(), 0));
+        assert!(OffchainStorage::pending_read(&key, 2).is_empty());
+    });
+}
+
+#[test]
+fn protected_namespaces_need_attested_writers() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"hsm/key".to_vec();
+        assert_noop!(
+            OffchainStorage::set_protected_namespace(Origin::signed(1), b"hsm/".to_vec(), true),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(OffchainStorage::set_protected_namespace(
+            Origin::ROOT,
+            b"hsm/".to_vec(),
+            true
+        ));
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()),
+            Error::<Test>::Unattested
+        );
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), b"key".to_vec(), b"a".to_vec()));
+
+        assert_noop!(
+            OffchainStorage::register_attestation(Origin::signed(1), Some(b"laptop".to_vec())),
+            Error::<Test>::Unattested
+        );
+        assert_ok!(OffchainStorage::register_attestation(
+            Origin::signed(1),
+            Some(b"enclave".to_vec())
+        ));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_eq!(
+            OffchainStorage::validate_write(&2, &key, 1),
+            Err(ValidationError::Unattested)
+        );
+
+        assert_ok!(OffchainStorage::set_protected_namespace(
+            Origin::ROOT,
+            b"hsm/".to_vec(),
+            false
+        ));
+        assert!(!OffchainStorage::is_protected(&key));
+    });
+}
+
+#[test]
+fn registered_providers_join_the_provider_set_once_approved() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert_ok!(OffchainStorage::set_replication(Origin::signed(1), key.clone(), 1));
+        assert!(!OffchainStorage::is_provider(&2));
+        assert_ok!(OffchainStorage::register_provider(
+            Origin::signed(2),
+            b"https://provider.example".to_vec(),
+            1 << 20,
+            1
+        ));
+        assert_eq!(OffchainStorage::provider_info(2).unwrap().capacity, 1 << 20);
+        // registering only makes 2 discoverable, it can't take a copy of the value.
+        assert!(!OffchainStorage::is_provider(&2));
+        assert_noop!(
+            OffchainStorage::accept_replica(Origin::signed(2), key.clone()),
+            Error::<Test>::NotProvider
+        );
+        assert_noop!(
+            OffchainStorage::bond_provider(Origin::signed(2), 10),
+            Error::<Test>::NotProvider
+        );
+
+        assert_noop!(
+            OffchainStorage::approve_provider(Origin::signed(1), 2),
+            DispatchError::BadOrigin
+        );
+        assert_noop!(
+            OffchainStorage::approve_provider(Origin::ROOT, 3),
+            Error::<Test>::NotRegistered
+        );
+        assert_ok!(OffchainStorage::approve_provider(Origin::ROOT, 2));
+        assert!(OffchainStorage::is_provider(&2));
+        assert_ok!(OffchainStorage::bond_provider(Origin::signed(2), 10));
+        // updating the offer keeps the approval.
+        assert_ok!(OffchainStorage::register_provider(Origin::signed(2), vec![], 1, 0));
+        assert!(OffchainStorage::is_provider(&2));
+
+        assert_ok!(OffchainStorage::deregister_provider(Origin::signed(2)));
+        assert!(!OffchainStorage::is_provider(&2));
+        assert_noop!(
+            OffchainStorage::deregister_provider(Origin::signed(2)),
+            Error::<Test>::NotRegistered
+        );
+        assert_ok!(OffchainStorage::register_provider(Origin::signed(2), vec![], 1, 0));
+        assert!(!OffchainStorage::is_provider(&2));
+
+        // providers registered in the fifth layout are not approved.
+        let raw = (b"https://old.example".to_vec(), 1u64, 1u64).encode();
+        sp_io::storage::set(&<Providers<Test>>::hashed_key_for(&3), &raw);
+        StorageVersion::put(5);
+        OffchainStorage::on_initialize(1);
+        assert_eq!(OffchainStorage::storage_version(), migration::CURRENT_VERSION);
+        let info = OffchainStorage::provider_info(3).unwrap();
+        assert_eq!((info.endpoint, info.approved), (b"https://old.example".to_vec(), false));
+        assert!(!OffchainStorage::is_provider(&3));
+    });
+}
+
+#[test]
+fn provider_reputation_follows_its_record() {
+    use crate::challenge::StorageProof;
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_eq!(OffchainStorage::reputation(&9), Perbill::from_percent(50));
+
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::challenge_storage(Origin::signed(2), key.clone()));
+        let proof = StorageProof { chunk: b"a".to_vec(), path: vec![] };
+        assert_ok!(OffchainStorage::submit_storage_proof(Origin::signed(9), key.clone(), 1, proof));
+        assert_eq!(OffchainStorage::metrics_of(9).proved, 1);
+        assert_eq!(OffchainStorage::reputation(&9), Perbill::from_rational_approximation(2u32, 3));
+
+        assert_noop!(
+            OffchainStorage::report_latency(Origin::signed(3), 9, 100),
+            Error::<Test>::PermissionDenied
+        );
+        OPERATORS.with(|o| *o.borrow_mut() = vec![3]);
+        assert_noop!(
+            OffchainStorage::report_latency(Origin::signed(3), 2, 100),
+            Error::<Test>::NotProvider
+        );
+        assert_ok!(OffchainStorage::report_latency(Origin::signed(3), 9, 100));
+        assert_ok!(OffchainStorage::report_latency(Origin::signed(3), 9, 200));
+        assert_eq!(OffchainStorage::metrics_of(9).average_latency(), Some(150));
+    });
+}
+
+#[test]
+fn offline_bundles_apply_once() {
+    use crate::bundle::WriteBundle;
+
+    new_test_ext().execute_with(|| {
+        let bundle = WriteBundle {
+            author: 1,
+            nonce: 1,
+            expires_at: 5,
+            writes: vec![(b"a".to_vec(), b"1".to_vec()), (b"b".to_vec(), b"2".to_vec())],
+        };
+        let signature = TestSignature(1, bundle.signing_payload());
+        assert_noop!(
+            OffchainStorage::submit_bundle(
+                Origin::signed(2),
+                bundle.clone(),
+                TestSignature(2, bundle.signing_payload())
+            ),
+            Error::<Test>::BadBundle
+        );
+        assert_ok!(OffchainStorage::submit_bundle(
+            Origin::signed(2),
+            bundle.clone(),
+            signature.clone()
+        ));
+        assert_eq!(OffchainStorage::get_data(b"b".to_vec()).author, 1);
+        // the offchain worker uploads the values.
+        assert_eq!(OffchainStorage::pending_upload(b"b".to_vec()).unwrap().value, b"2".to_vec());
+        assert_noop!(
+            OffchainStorage::submit_bundle(Origin::signed(2), bundle, signature),
+            Error::<Test>::StaleBundle
+        );
+
+        // a data_id written twice, and a bundle submitted too late.
+        let twice = WriteBundle {
+            author: 1,
+            nonce: 2,
+            expires_at: 5,
+            writes: vec![(b"c".to_vec(), b"1".to_vec()), (b"c".to_vec(), b"2".to_vec())],
+        };
+        let signature = TestSignature(1, twice.signing_payload());
+        assert_noop!(
+            OffchainStorage::submit_bundle(Origin::signed(2), twice, signature),
+            Error::<Test>::BadBundle
+        );
+
+        // writes that fit the quota alone but not together apply none of them.
+        let over = WriteBundle {
+            author: 1,
+            nonce: 2,
+            expires_at: 5,
+            writes: vec![(b"d".to_vec(), vec![0; 33]), (b"e".to_vec(), vec![0; 33])],
+        };
+        let signature = TestSignature(1, over.signing_payload());
+        assert_noop!(
+            OffchainStorage::submit_bundle(Origin::signed(2), over, signature),
+            Error::<Test>::ByteQuotaExceeded
+        );
+        assert_eq!(OffchainStorage::bundle_nonce(1), 1);
+
+        System::set_block_number(6);
+        let late = WriteBundle { author: 1, nonce: 3, expires_at: 5, writes: vec![] };
+        let signature = TestSignature(1, late.signing_payload());
+        assert_noop!(
+            OffchainStorage::submit_bundle(Origin::signed(2), late, signature),
+            Error::<Test>::BundleExpired
+        );
+    });
+}
+
+#[test]
+fn relayed_bundles_are_verified_together() {
+    use crate::bundle::WriteBundle;
+
+    new_test_ext().execute_with(|| {
+        let bundle = |author: u64, nonce: u64| WriteBundle {
+            author,
+            nonce,
+            expires_at: 5,
+            writes: vec![(vec![author as u8, nonce as u8], b"v".to_vec())],
+        };
+        let signed = |b: WriteBundle<u64, u64>| {
+            let signature = TestSignature(b.author, b.signing_payload());
+            (b, signature)
+        };
+        let forged = (bundle(3, 1), TestSignature(2, bundle(3, 1).signing_payload()));
+        assert_noop!(
+            OffchainStorage::submit_bundles(Origin::signed(7), vec![signed(bundle(1, 1)), forged]),
+            Error::<Test>::BadBundle
+        );
+
+        // the replayed bundle is skipped, the others are applied.
+        assert_ok!(OffchainStorage::submit_bundles(
+            Origin::signed(7),
+            vec![signed(bundle(1, 1)), signed(bundle(1, 1)), signed(bundle(2, 4))]
+        ));
+        assert!(<Data<Test>>::exists(vec![1, 1]));
+        assert!(<Data<Test>>::exists(vec![2, 4]));
+        assert_eq!(OffchainStorage::bundle_nonce(2), 4);
+    });
+}
+
+#[test]
+fn storage_requests_match_the_cheapest_fitting_offer() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::post_storage_offer(Origin::signed(9), 10, 3));
+        assert_noop!(
+            OffchainStorage::post_storage_request(Origin::signed(2), key.clone(), 1, 10, 2),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::post_storage_request(Origin::signed(1), key.clone(), 1, 10, 2));
+        // too expensive, the request stays open.
+        assert!(OffchainStorage::storage_request(1).is_some());
+
+        assert_ok!(OffchainStorage::register_provider(Origin::signed(2), vec![], 1, 0));
+        assert_ok!(OffchainStorage::approve_provider(Origin::ROOT, 2));
+        assert_ok!(OffchainStorage::post_storage_offer(Origin::signed(2), 1, 2));
+        assert_eq!(OffchainStorage::storage_request(1), None);
+        let agreement = OffchainStorage::agreement(&key, 2).unwrap();
+        assert_eq!((agreement.price, agreement.until), (2, 10));
+        assert_eq!(replica(2, &key), Some(b"a".to_vec()));
+        // the offer of 2 is used up, 9's is left.
+        assert_eq!(OffchainStorage::storage_offers().len(), 1);
+
+        assert_ok!(OffchainStorage::post_storage_request(Origin::signed(1), key.clone(), 1, 10, 1));
+        assert_noop!(
+            OffchainStorage::cancel_storage_request(Origin::signed(2), 2),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::cancel_storage_request(Origin::signed(1), 2));
+        assert_eq!(OffchainStorage::storage_request(2), None);
+    });
+}
+
+#[test]
+fn challenges_of_unrelated_entries_share_no_keys() {
+    use crate::challenge::StorageProof;
+
+    new_test_ext().execute_with(|| {
+        let (a, b): (Vec<u8>, Vec<u8>) = (b"a".to_vec(), b"b".to_vec());
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a.clone(), b"1".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), b.clone(), b"2".to_vec()));
+        assert_ok!(OffchainStorage::challenge_storage(Origin::signed(2), a.clone()));
+        assert_ok!(OffchainStorage::challenge_storage(Origin::signed(2), b.clone()));
+        // nonces count per entry.
+        assert_eq!(OffchainStorage::challenge_of(&a).unwrap().nonce, 1);
+        assert_eq!(OffchainStorage::challenge_of(&b).unwrap().nonce, 1);
+
+        let proof = StorageProof { chunk: b"1".to_vec(), path: vec![] };
+        assert_ok!(OffchainStorage::submit_storage_proof(Origin::signed(9), a.clone(), 1, proof));
+        assert!(!<ChallengeDeadlines<Test>>::exists(6, &a));
+
+        OffchainStorage::on_initialize(6);
+        assert_eq!(OffchainStorage::missed_challenges(&a), 0);
+        assert_eq!(OffchainStorage::missed_challenges(&b), 1);
+        assert!(!<ChallengeDeadlines<Test>>::exists(6, &b));
+    });
+}
+
+#[test]
+fn entries_are_removed_when_their_ttl_ends() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_noop!(
+            OffchainStorage::write_data_until(
+                Origin::signed(1),
+                key.clone(),
+                b"a".to_vec(),
+                Some(0)
+            ),
+            Error::<Test>::InvalidTtl
+        );
+        assert_ok!(OffchainStorage::write_data_until(
+            Origin::signed(1),
+            key.clone(),
+            b"a".to_vec(),
+            Some(3)
+        ));
+        assert_noop!(
+            OffchainStorage::write_data_until(Origin::signed(2), key.clone(), b"b".to_vec(), None),
+            Error::<Test>::PermissionDenied
+        );
+
+        // moving the end forgets the old one.
+        assert_ok!(OffchainStorage::write_data_until(
+            Origin::signed(1),
+            key.clone(),
+            b"a".to_vec(),
+            Some(4)
+        ));
+        OffchainStorage::on_initialize(3);
+        assert!(<Data<Test>>::exists(&key));
+
+        System::set_block_number(4);
+        assert_noop!(
+            OffchainStorage::read_data(Origin::signed(2), key.clone()),
+            Error::<Test>::NoneData
+        );
+        OffchainStorage::on_initialize(4);
+        assert!(!<Data<Test>>::exists(&key));
+        assert_eq!(stored(&key), None);
+        assert_eq!(OffchainStorage::ttl_of(&key), None);
+    });
+}
+
+#[test]
+fn garbage_collection_is_bounded_per_block() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        CODING.with(|c| *c.borrow_mut() = Some((2, 4)));
+        assert_ok!(OffchainStorage::write_data_until(
+            Origin::signed(1),
+            key.clone(),
+            b"0123456789".to_vec(),
+            Some(2)
+        ));
+
+        // the four shards are deleted two per block.
+        OffchainStorage::on_initialize(2);
+        assert!(!<Data<Test>>::exists(&key));
+        assert_eq!(stored(&erasure::shard_key(&key, 1, 1)), None);
+        assert!(stored(&erasure::shard_key(&key, 1, 2)).is_some());
+        assert_eq!(System::all_extrinsics_weight(), 20 + ExpiryWeight::get());
+
+        // a new entry under the same data_id keeps its value.
+        CODING.with(|c| *c.borrow_mut() = None);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        OffchainStorage::on_initialize(3);
+        assert_eq!(stored(&erasure::shard_key(&key, 1, 3)), None);
+        assert_eq!(stored(&key), Some(b"a".to_vec()));
+        // and so does its first revision, though the old one went under the same key.
+        OffchainStorage::on_initialize(4);
+        assert_eq!(stored(&history::version_key(&key, 1)), Some(b"a".to_vec()));
+        assert_eq!(OffchainStorage::gc_bounds(), (5, 5));
+    });
+}
+
+#[test]
+fn expiry_passes_are_bounded_per_block() {
+    use crate::expiry::Expiry;
+
+    new_test_ext().execute_with(|| {
+        let keys: Vec<Vec<u8>> = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()];
+        for key in &keys {
+            assert_ok!(OffchainStorage::write_data_until(
+                Origin::signed(1),
+                key.clone(),
+                b"v".to_vec(),
+                Some(2)
+            ));
+        }
+
+        // two of the three entries are removed in their block, the third in the next.
+        System::set_block_number(2);
+        OffchainStorage::on_initialize(2);
+        let left: Vec<Vec<u8>> =
+            keys.iter().filter(|key| <Data<Test>>::exists(key)).cloned().collect();
+        assert_eq!(left.len(), 1);
+        assert_noop!(
+            OffchainStorage::read_data(Origin::signed(2), left[0].clone()),
+            Error::<Test>::NoneData
+        );
+        let weight = 2 * ExpiryWeight::get() + GcKeysPerBlock::get() * GcWeightPerKey::get();
+        assert_eq!(System::all_extrinsics_weight(), weight);
+        System::set_block_number(3);
+        OffchainStorage::on_initialize(3);
+        assert!(!<Data<Test>>::exists(&left[0]));
+        assert_eq!(OffchainStorage::expiry_cursor(Expiry::Ttl), 4);
+
+        // an entry past its time in the trash can't be restored while it waits.
+        for key in &keys {
+            assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"v".to_vec()));
+            assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        }
+        System::set_block_number(5);
+        OffchainStorage::on_initialize(5);
+        let left: Vec<Vec<u8>> =
+            keys.iter().filter(|key| OffchainStorage::trash_of(key).is_some()).cloned().collect();
+        assert_eq!(left.len(), 1);
+        assert_noop!(
+            OffchainStorage::restore_data(Origin::signed(1), left[0].clone()),
+            Error::<Test>::NotInTrash
+        );
+        OffchainStorage::on_initialize(6);
+        assert!(OffchainStorage::trash_of(&left[0]).is_none());
+    });
+}
+
+#[test]
+fn staged_values_go_live_when_promoted() {
+    use crate::staging::staged_key;
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_noop!(
+            OffchainStorage::write_staged(Origin::signed(1), key.clone(), b"b".to_vec()),
+            Error::<Test>::NoneData
+        );
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::write_staged(Origin::signed(2), key.clone(), b"b".to_vec()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 2, 1));
+        assert_ok!(OffchainStorage::write_staged(Origin::signed(2), key.clone(), b"b".to_vec()));
+        assert_eq!(Balances::reserved_balance(2), 3);
+        assert_eq!(stored(&key), Some(b"a".to_vec()));
+        assert_eq!(OffchainStorage::staged_value(&key), Some(b"b".to_vec()));
+
+        assert_noop!(
+            OffchainStorage::promote_staged(Origin::signed(3), key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::promote_staged(Origin::signed(1), key.clone()));
+        assert_eq!(stored(&key), Some(b"b".to_vec()));
+        assert_eq!(stored(&staged_key(&key)), None);
+        assert_eq!(Balances::reserved_balance(2), 0);
+        // promoting used the delegated write of 2.
+        assert_eq!(OffchainStorage::write_delegations(&key, 2), 0);
+
+        assert_ok!(OffchainStorage::write_staged(Origin::signed(1), key.clone(), b"c".to_vec()));
+        assert_ok!(OffchainStorage::discard_staged(Origin::signed(1), key.clone()));
+        assert_eq!(OffchainStorage::staged_of(&key), None);
+        assert_noop!(
+            OffchainStorage::promote_staged(Origin::signed(1), key.clone()),
+            Error::<Test>::NothingStaged
+        );
+        assert_eq!(stored(&key), Some(b"b".to_vec()));
+    });
+}
+
+#[test]
+fn canary_readers_get_the_staged_value() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::set_canary(Origin::signed(1), key.clone(), Perbill::one()),
+            Error::<Test>::NothingStaged
+        );
+        assert_ok!(OffchainStorage::write_staged(Origin::signed(1), key.clone(), b"b".to_vec()));
+        assert!(!OffchainStorage::reads_staged(&key, &1));
+        assert_noop!(
+            OffchainStorage::set_canary(Origin::signed(2), key.clone(), Perbill::one()),
+            Error::<Test>::PermissionDenied
+        );
+
+        assert_ok!(OffchainStorage::set_canary(Origin::signed(1), key.clone(), Perbill::one()));
+        assert!(OffchainStorage::reads_staged(&key, &1));
+        assert_ok!(OffchainStorage::read_data(Origin::signed(1), key.clone()));
+        assert_eq!(OffchainStorage::staged_of(&key).unwrap().canary_reads, 1);
+        assert_eq!(stored(&key), Some(b"a".to_vec()));
+
+        // restaging starts a new rollout.
+        assert_ok!(OffchainStorage::write_staged(Origin::signed(1), key.clone(), b"c".to_vec()));
+        assert!(!OffchainStorage::reads_staged(&key, &1));
+        assert_eq!(OffchainStorage::staged_of(&key).unwrap().canary_reads, 0);
+    });
+}
+
+#[test]
+fn deleted_data_can_be_restored_until_purged() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        assert_noop!(
+            OffchainStorage::read_data(Origin::signed(1), key.clone()),
+            Error::<Test>::NoneData
+        );
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(2), key.clone(), b"b".to_vec()),
+            Error::<Test>::Trashed
+        );
+        assert_noop!(
+            OffchainStorage::restore_data(Origin::signed(2), key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::restore_data(Origin::signed(1), key.clone()));
+        assert_eq!(OffchainStorage::get_data(&key).author, 1);
+        assert_eq!(Balances::reserved_balance(1), 3);
+        assert_ok!(OffchainStorage::read_data(Origin::signed(1), key.clone()));
+
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        OffchainStorage::on_initialize(2);
+        assert_eq!(stored(&key), None);
+        assert_eq!(Balances::reserved_balance(1), 0);
+        assert_noop!(
+            OffchainStorage::restore_data(Origin::signed(1), key.clone()),
+            Error::<Test>::NotInTrash
+        );
+        assert_ok!(OffchainStorage::write_data(Origin::signed(2), b"k2".to_vec(), b"b".to_vec()));
+    });
+}
+
+#[test]
+fn writes_are_kept_as_revisions_with_their_message() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::write_data_with_message(
+                Origin::signed(1),
+                key.clone(),
+                b"b".to_vec(),
+                b"much too long".to_vec()
+            ),
+            Error::<Test>::MessageTooLong
+        );
+        assert_ok!(OffchainStorage::write_data_with_message(
+            Origin::signed(1),
+            key.clone(),
+            b"b".to_vec(),
+            b"fix typo".to_vec()
+        ));
+        assert_eq!(OffchainStorage::revision_count(&key), 2);
+        let revision = OffchainStorage::revision(&key, 2).unwrap();
+        assert_eq!(revision.writer, 1);
+        assert_eq!(revision.message, b"fix typo".to_vec());
+        assert_eq!(revision.content_hash, BlakeTwo256::hash(b"b"));
+        assert_eq!(OffchainStorage::revision(&key, 1).unwrap().message, Vec::<u8>::new());
+
+        // only the last MaxRevisions are kept.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"c".to_vec()));
+        assert_eq!(OffchainStorage::revision(&key, 1), None);
+        assert!(OffchainStorage::revision(&key, 2).is_some());
+    });
+}
+
+#[test]
+fn past_versions_can_be_read_and_rolled_back_to() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"b".to_vec()));
+        assert_eq!(OffchainStorage::version_value(&key, 1), Some(b"a".to_vec()));
+        assert_ok!(OffchainStorage::read_version(Origin::signed(2), key.clone(), 1));
+        assert_noop!(
+            OffchainStorage::read_version(Origin::signed(2), key.clone(), 3),
+            Error::<Test>::NoSuchVersion
+        );
+
+        assert_noop!(
+            OffchainStorage::rollback(Origin::signed(2), key.clone(), 1),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::rollback(Origin::signed(1), key.clone(), 1));
+        assert_eq!(stored(&key), Some(b"a".to_vec()));
+        assert_eq!(OffchainStorage::revision_count(&key), 3);
+
+        // the value of a revision that is no longer kept is deleted.
+        assert_eq!(stored(&history::version_key(&key, 1)), None);
+        assert_noop!(
+            OffchainStorage::rollback(Origin::signed(1), key.clone(), 1),
+            Error::<Test>::NoSuchVersion
+        );
+        DB::set(history::version_key(&key, 2), b"evil".to_vec());
+        assert_eq!(OffchainStorage::version_value(&key, 2), None);
+    });
+}
+
+#[test]
+fn metadata_snapshots_restore_lost_state() {
+    use crate::snapshot::{snapshot_key, MetadataSnapshot};
+    use frame_support::storage::StoragePrefixedMap;
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 2, 3));
+        OffchainStorage::offchain_worker(9);
+        assert_eq!(stored(&snapshot_key()), None);
+        OffchainStorage::offchain_worker(10);
+        let blob = stored(&snapshot_key()).unwrap();
+        let snapshot = MetadataSnapshot::<u64>::decode(&mut &blob[..]).unwrap();
+        assert_eq!(snapshot, OffchainStorage::metadata_snapshot());
+        assert_eq!(snapshot.pairs.len(), 3);
+
+        let entry = OffchainStorage::get_data(&key);
+        <Data<Test>>::remove_all();
+        <WriteDelegations<Test>>::remove_all();
+        for (k, v) in snapshot.pairs {
+            sp_io::storage::set(&k, &v);
+        }
+        assert_eq!(OffchainStorage::get_data(&key), entry);
+        assert_eq!(OffchainStorage::write_delegations(&key, 2), 3);
+    });
+}
+
+#[test]
+fn replicas_migrate_between_providers() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::set_replication(Origin::signed(1), key.clone(), 1));
+        assert_ok!(OffchainStorage::accept_replica(Origin::signed(9), key.clone()));
+        assert_noop!(
+            OffchainStorage::migrate_data(Origin::signed(9), key.clone(), 2),
+            Error::<Test>::NotProvider
+        );
+        assert_ok!(OffchainStorage::register_provider(
+            Origin::signed(2),
+            b"https://provider.example".to_vec(),
+            1 << 20,
+            1
+        ));
+        // a registered provider only takes replicas once approved.
+        assert_noop!(
+            OffchainStorage::migrate_data(Origin::signed(9), key.clone(), 2),
+            Error::<Test>::NotProvider
+        );
+        assert_ok!(OffchainStorage::approve_provider(Origin::ROOT, 2));
+        assert_noop!(
+            OffchainStorage::migrate_data(Origin::signed(2), key.clone(), 9),
+            Error::<Test>::NotReplica
+        );
+
+        assert_ok!(OffchainStorage::migrate_data(Origin::signed(9), key.clone(), 2));
+        assert_eq!(replica(9, &key), None);
+        assert_eq!(replica(2, &key), Some(b"a".to_vec()));
+        assert_eq!(OffchainStorage::get_data(&key).providers, vec![2]);
+    });
+}
+
+#[test]
+fn reviewers_sign_off_staged_values() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::set_reviewers(Origin::signed(2), key.clone(), vec![2, 3]),
+            Error::<Test>::PermissionDenied
+        );
+        assert_noop!(
+            OffchainStorage::set_reviewers(Origin::signed(1), key.clone(), vec![2, 3, 4, 5]),
+            Error::<Test>::TooManyReviewers
+        );
+        assert_ok!(OffchainStorage::set_reviewers(Origin::signed(1), key.clone(), vec![2, 3]));
+        assert_ok!(OffchainStorage::write_staged(Origin::signed(1), key.clone(), b"b".to_vec()));
+        let hash = BlakeTwo256::hash(b"b");
+
+        assert_noop!(
+            OffchainStorage::approve_staged(Origin::signed(4), key.clone(), hash),
+            Error::<Test>::NotReviewer
+        );
+        let other = BlakeTwo256::hash(b"c");
+        assert_noop!(
+            OffchainStorage::approve_staged(Origin::signed(2), key.clone(), other),
+            Error::<Test>::StagedChanged
+        );
+        assert_ok!(OffchainStorage::approve_staged(Origin::signed(2), key.clone(), hash));
+        assert_noop!(
+            OffchainStorage::promote_staged(Origin::signed(1), key.clone()),
+            Error::<Test>::Unapproved
+        );
+        assert_ok!(OffchainStorage::approve_staged(Origin::signed(3), key.clone(), hash));
+        assert_ok!(OffchainStorage::promote_staged(Origin::signed(1), key.clone()));
+        assert_eq!(stored(&key), Some(b"b".to_vec()));
+
+        // a new staged value needs a new review.
+        assert_ok!(OffchainStorage::write_staged(Origin::signed(1), key.clone(), b"c".to_vec()));
+        assert_noop!(
+            OffchainStorage::promote_staged(Origin::signed(1), key.clone()),
+            Error::<Test>::Unapproved
+        );
+    });
+}
+
+#[test]
+fn access_reports_list_every_grant() {
+    use crate::acl::Grant;
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 2, 3));
+        assert_ok!(OffchainStorage::grant_session_access(
+            Origin::signed(1),
+            key.clone(),
+            3,
+            Access::Read
+        ));
+        assert_ok!(OffchainStorage::set_reviewers(Origin::signed(1), key.clone(), vec![4]));
+
+        let reports = OffchainStorage::access_report(vec![key.clone(), b"missing".to_vec()], None);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].author, 1);
+        assert_eq!(
+            reports[0].grants,
+            vec![Grant::Delegate(2, 3), Grant::Session(3, 0, Access::Read), Grant::Reviewer(4)]
+        );
+        let reports = OffchainStorage::access_report(vec![key.clone()], Some(2));
+        assert_eq!(reports[0].grants, vec![Grant::Delegate(2, 3)]);
+
+        assert_ok!(OffchainStorage::revoke_all_grants(Origin::signed(1), 1));
+        let reports = OffchainStorage::access_report(vec![key], None);
+        assert_eq!(reports[0].grants, vec![Grant::Reviewer(4)]);
+    });
+}
+
+#[test]
+fn entries_can_be_renamed() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let new_key: Vec<u8> = b"new key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"b".to_vec()));
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 2, 3));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), b"other".to_vec(), vec![]));
+        assert_noop!(
+            OffchainStorage::rename_data(Origin::signed(2), key.clone(), new_key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_noop!(
+            OffchainStorage::rename_data(Origin::signed(1), key.clone(), b"other".to_vec()),
+            Error::<Test>::DataExists
+        );
+
+        assert_ok!(OffchainStorage::rename_data(Origin::signed(1), key.clone(), new_key.clone()));
+        assert_eq!(stored(&key), None);
+        assert_eq!(stored(&new_key), Some(b"b".to_vec()));
+        assert!(!<Data<Test>>::exists(&key));
+        assert_eq!(OffchainStorage::revision_count(&new_key), 2);
+        assert_eq!(OffchainStorage::version_value(&new_key, 1), Some(b"a".to_vec()));
+        // grants move with the entry.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(2), new_key.clone(), b"c".to_vec()));
+        assert_eq!(OffchainStorage::write_delegations(&new_key, 2), 2);
+    });
+}
+
+#[test]
+fn read_access_can_be_queried() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert!(!OffchainStorage::can_read(&2, &key));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert!(OffchainStorage::can_read(&1, &key));
+        assert!(!OffchainStorage::can_read(&2, &key));
+
+        assert_ok!(OffchainStorage::grant_session_access(
+            Origin::signed(1),
+            key.clone(),
+            2,
+            Access::Read
+        ));
+        assert!(OffchainStorage::can_read(&2, &key));
+    });
+}
+
+#[test]
+fn readable_data_can_be_copied() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let copy: Vec<u8> = b"copy".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert_noop!(
+            OffchainStorage::copy_data(Origin::signed(2), key.clone(), copy.clone()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Read));
+        assert_noop!(
+            OffchainStorage::copy_data(Origin::signed(2), key.clone(), key.clone()),
+            Error::<Test>::DataExists
+        );
+
+        assert_ok!(OffchainStorage::copy_data(Origin::signed(2), key.clone(), copy.clone()));
+        assert_eq!(stored(&copy), Some(b"a".to_vec()));
+        let data = OffchainStorage::get_data(&copy);
+        assert_eq!(data.author, 2);
+        assert_eq!(data.access, UserData::<u64, H256, u64>::default().access);
+        assert_eq!(OffchainStorage::get_data(&key).author, 1);
+    });
+}
+
+#[test]
+fn writes_are_weighed_by_their_length() {
+    use crate::challenge::StorageProof;
+    use frame_support::weights::GetDispatchInfo;
+
+    let weight = |len: usize| Call::<Test>::write_data(b"key".to_vec(), vec![0; len])
+        .get_dispatch_info()
+        .weight;
+    assert_eq!(weight(0), <() as WeightInfo>::write_data(3, 0));
+    assert!(weight(1024) > weight(0));
+    let read = Call::<Test>::read_data(b"key".to_vec()).get_dispatch_info();
+    assert_eq!(read.weight, <() as WeightInfo>::read_data(3));
+
+    // values read from the backends are weighed as the longest allowed.
+    let copy = Call::<Test>::copy_data(b"key".to_vec(), b"copy".to_vec()).get_dispatch_info();
+    assert_eq!(copy.weight, <() as WeightInfo>::copy_data(3, MaxValueLength::get()));
+    let rollback = Call::<Test>::rollback(b"key".to_vec(), 1).get_dispatch_info();
+    assert_eq!(rollback.weight, <() as WeightInfo>::rollback(3, MaxValueLength::get()));
+    let replica = Call::<Test>::accept_replica(b"key".to_vec()).get_dispatch_info();
+    assert_eq!(replica.weight, <() as WeightInfo>::accept_replica(3, MaxValueLength::get()));
+    let commit = Call::<Test>::commit_upload(b"key".to_vec()).get_dispatch_info();
+    assert_eq!(commit.weight, <() as WeightInfo>::commit_upload(3, MaxValueLength::get()));
+    // appends rewrite the whole value, weighed as the longest allowed too.
+    let append = Call::<Test>::append_data(b"key".to_vec(), vec![0; 4]).get_dispatch_info();
+    assert_eq!(append.weight, <() as WeightInfo>::append_data(3, MaxValueLength::get()));
+    let chunk = Call::<Test>::upload_chunk(b"key".to_vec(), 0, vec![0; 4]).get_dispatch_info();
+    assert_eq!(chunk.weight, <() as WeightInfo>::upload_chunk(3, 4));
+    let wrap = Call::<Test>::wrap_keys(b"key".to_vec(), 0, vec![(2, vec![0; 32]), (3, vec![])]);
+    assert_eq!(wrap.get_dispatch_info().weight, <() as WeightInfo>::wrap_keys(3, 2, 32));
+
+    // transactions by the data_ids and values of all their ops.
+    let ops = vec![
+        Op::Write(b"a".to_vec(), vec![0; 10]),
+        Op::Rename(b"b".to_vec(), b"cd".to_vec()),
+    ];
+    let transact = Call::<Test>::transact(ops).get_dispatch_info();
+    assert_eq!(transact.weight, <() as WeightInfo>::transact(2, 14));
+
+    // proofs by their length.
+    let proof = StorageProof { chunk: vec![0; 4], path: vec![H256::default()] };
+    let answer = Call::<Test>::submit_storage_proof(b"key".to_vec(), 0, proof);
+    let weight = <() as WeightInfo>::submit_storage_proof(3, 36);
+    assert_eq!(answer.get_dispatch_info().weight, weight);
+    let header = Header {
+        parent_hash: Default::default(),
+        number: 10,
+        state_root: Default::default(),
+        extrinsics_root: Default::default(),
+        digest: Default::default(),
+    };
+    let proof = vec![vec![0; 10], vec![0; 6]];
+    let mirror = Call::<Test>::mirror_remote_data(vec![], header, b"key".to_vec(), proof);
+    let weight = <() as WeightInfo>::mirror_remote_data(3, 16);
+    assert_eq!(mirror.get_dispatch_info().weight, weight);
+    let range = Call::<Test>::read_data_range(b"key".to_vec(), 0, 4).get_dispatch_info();
+    assert_eq!(range.weight, <() as WeightInfo>::read_data_range(3, MaxValueLength::get()));
+
+    // lists by their length.
+    let reviewers = Call::<Test>::set_reviewers(b"key".to_vec(), vec![2, 3]).get_dispatch_info();
+    assert_eq!(reviewers.weight, <() as WeightInfo>::set_reviewers(3, 2));
+}
+
+#[test]
+fn errors_have_stable_codes() {
+    assert_eq!(Error::<Test>::PermissionDenied.code(), ErrorCode(1000));
+    assert_eq!(Error::<Test>::Trashed.code().module_index(), Some(36));
+    for e in vec![
+        ValidationError::PermissionDenied,
+        ValidationError::ReadOnlyMirror,
+        ValidationError::DelegationExhausted,
+        ValidationError::InsufficientBalance,
+        ValidationError::Expired,
+        ValidationError::Unattested,
+        ValidationError::Trashed,
+        ValidationError::NamespaceReserved,
+        ValidationError::CopyPending,
+    ] {
+        assert_eq!(e.code(), Error::<Test>::from(e.clone()).code());
+    }
+    assert_eq!(ErrorCode::TRANSPORT.module_index(), None);
+}
+
+#[test]
+fn writes_with_an_idempotency_key_are_made_once() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let once = |value: &[u8], id: u8| {
+            let value = value.to_vec();
+            OffchainStorage::write_data_once(Origin::signed(1), key.clone(), value, [id; 16])
+        };
+        assert_ok!(once(b"a", 1));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"b".to_vec()));
+        // the retry doesn't overwrite the later write.
+        assert_ok!(once(b"a", 1));
+        assert_eq!(stored(&key), Some(b"b".to_vec()));
+        assert_eq!(OffchainStorage::revision_count(&key), 2);
+        assert_noop!(once(b"c", 1), Error::<Test>::IdempotencyKeyReused);
+
+        // the key is forgotten once the window is over.
+        OffchainStorage::on_initialize(5);
+        assert_ok!(once(b"a", 1));
+        assert_eq!(OffchainStorage::revision_count(&key), 3);
+    });
+}
+
+#[test]
+fn events_name_the_actor_and_data_id() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let emitted = |event: RawEvent<u64, u64, H256>| {
+            let event = TestEvent::offchain_storage(event);
+            System::events().into_iter().any(|record| record.event == event)
+        };
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"abc".to_vec()));
+        assert!(emitted(RawEvent::DataWritten(1, key.clone(), 3)));
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+        assert!(emitted(RawEvent::DataRead(2, key.clone())));
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        assert!(emitted(RawEvent::DataDeleted(1, key)));
+    });
+}
+
+#[test]
+fn deferred_writes_report_their_progress() {
+    new_offchain_test_ext().execute_with(|| {
+        DEFERRED.with(|d| *d.borrow_mut() = true);
+        OPERATORS.with(|o| *o.borrow_mut() = vec![3]);
+        let key: Vec<u8> = b"key".to_vec();
+        let hash = BlakeTwo256::hash(b"a");
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        let queued = TestEvent::offchain_storage(RawEvent::UploadQueued(key.clone(), 1));
+        assert!(System::events().into_iter().any(|record| record.event == queued));
+        assert_eq!(OffchainStorage::upload_status(1), Some(RequestStatus::Queued));
+
+        // the worker takes the write up before its first attempt.
+        OffchainStorage::offchain_worker(1);
+        let start = Call::start_upload(key.clone(), hash);
+        assert_eq!(CONFIRMATIONS.with(|c| c.borrow().first().cloned()), Some(start));
+        assert_noop!(
+            OffchainStorage::start_upload(Origin::signed(1), key.clone(), hash),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::start_upload(Origin::signed(3), key.clone(), hash));
+        assert_eq!(OffchainStorage::upload_status(1), Some(RequestStatus::Executing));
+        assert_ok!(OffchainStorage::confirm_upload(Origin::signed(3), key.clone(), hash));
+        let committed = RequestStatus::Committed(hash);
+        assert_eq!(OffchainStorage::upload_status(1), Some(committed));
+
+        // a value overwritten before its upload is never uploaded.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"b".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"c".to_vec()));
+        let superseded = RequestStatus::Failed(Error::<Test>::UploadSuperseded.code());
+        assert_eq!(OffchainStorage::upload_status(2), Some(superseded));
+        assert_ok!(OffchainStorage::report_upload_failed(
+            Origin::signed(3),
+            key.clone(),
+            BlakeTwo256::hash(b"c"),
+            StorageError::Timeout
+        ));
+        let failed = RequestStatus::Failed(StorageError::Timeout.code());
+        assert_eq!(OffchainStorage::upload_status(3), Some(failed));
+
+        OffchainStorage::on_initialize(5);
+        for id in 1..4 {
+            assert_eq!(OffchainStorage::upload_status(id), None);
+        }
+
+        // values pending upload in the sixth layout get the next id.
+        let old: Vec<u8> = b"old".to_vec();
+        let raw = (old.clone(), b"d".to_vec(), BlakeTwo256::hash(b"d"), 1u32, 4u64).encode();
+        sp_io::storage::set(&<PendingUploads<Test>>::hashed_key_for(&old), &raw);
+        StorageVersion::put(6);
+        OffchainStorage::on_initialize(6);
+        assert_eq!(OffchainStorage::pending_upload(&old).map(|upload| upload.id), Some(4));
+        assert_eq!(OffchainStorage::upload_status(4), Some(RequestStatus::Queued));
+    });
+}
+
+#[test]
+fn events_carry_accounts_and_blocks() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let emitted = |event: RawEvent<u64, u64, H256>| {
+            let event = TestEvent::offchain_storage(event);
+            System::events().into_iter().any(|record| record.event == event)
+        };
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::set_replication(Origin::signed(1), key.clone(), 1));
+        assert_ok!(OffchainStorage::accept_replica(Origin::signed(9), key.clone()));
+        assert!(emitted(RawEvent::ReplicaAssigned(key.clone(), 9)));
+
+        assert_ok!(OffchainStorage::register_provider(Origin::signed(2), vec![], 1, 0));
+        assert_ok!(OffchainStorage::approve_provider(Origin::ROOT, 2));
+        assert_ok!(OffchainStorage::post_storage_offer(Origin::signed(2), 1, 2));
+        assert_ok!(OffchainStorage::post_storage_request(Origin::signed(1), key.clone(), 1, 10, 2));
+        assert!(emitted(RawEvent::StorageAgreed(1, key, 2, 10)));
+    });
+}
+
+#[test]
+fn backend_failures_surface_as_external_errors() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let failed = |reason| {
+            let event = RawEvent::ExternalStorageFailed(key.clone(), reason);
+            let event = TestEvent::offchain_storage(event);
+            System::events().into_iter().any(|record| record.event == event)
+        };
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+
+        set_outage(Some(StorageError::OutOfSpace));
+        assert_eq!(
+            OffchainStorage::write_data(Origin::signed(1), key.clone(), b"b".to_vec()),
+            Err(Error::<Test>::ExternalError.into())
+        );
+        assert!(failed(StorageError::OutOfSpace));
+        // nothing changed on chain.
+        assert_eq!(OffchainStorage::revision_count(&key), 1);
+        set_outage(Some(StorageError::Timeout));
+        assert_eq!(
+            OffchainStorage::read_for(&1, &key).map_err(DispatchError::from),
+            Err(Error::<Test>::ExternalError.into())
+        );
+        assert!(failed(StorageError::Timeout));
+        assert_eq!(
+            OffchainStorage::force_delete_data(Origin::ROOT, key.clone()),
+            Err(Error::<Test>::ExternalError.into())
+        );
+        assert!(<Data<Test>>::exists(&key));
+
+        set_outage(None);
+        assert_ok!(OffchainStorage::read_data(Origin::signed(1), key.clone()));
+        assert_eq!(stored(&key), Some(b"a".to_vec()));
+        assert_eq!(StorageError::Timeout.code(), ErrorCode(2001));
+    });
+}
+
+#[test]
+fn cancelled_storage_requests_refund_their_deposit() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        let reserved = Balances::reserved_balance(1);
+        assert_ok!(OffchainStorage::post_storage_request(Origin::signed(1), key.clone(), 1, 10, 2));
+        assert_eq!(Balances::reserved_balance(1), reserved + 5);
+        assert_ok!(OffchainStorage::cancel_storage_request(Origin::signed(1), 1));
+        assert_eq!(Balances::reserved_balance(1), reserved);
+        assert_noop!(
+            OffchainStorage::cancel_storage_request(Origin::signed(1), 1),
+            Error::<Test>::NoStorageRequest
+        );
+
+        // a matched request releases its deposit too.
+        assert_ok!(OffchainStorage::post_storage_request(Origin::signed(1), key.clone(), 1, 10, 2));
+        assert_ok!(OffchainStorage::register_provider(Origin::signed(2), vec![], 1, 0));
+        assert_ok!(OffchainStorage::approve_provider(Origin::ROOT, 2));
+        assert_ok!(OffchainStorage::post_storage_offer(Origin::signed(2), 1, 2));
+        assert_eq!(OffchainStorage::storage_request(2), None);
+        assert_eq!(Balances::reserved_balance(1), reserved);
+    });
+}
+
+#[test]
+fn failed_storage_requests_are_dead_lettered() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::post_storage_request(Origin::signed(1), key.clone(), 1, 10, 2));
+        let reserved = Balances::reserved_balance(1);
+        assert_ok!(OffchainStorage::register_provider(Origin::signed(2), vec![], 1, 0));
+        assert_ok!(OffchainStorage::approve_provider(Origin::ROOT, 2));
+
+        // a backend failure is tried again, until the attempts run out.
+        set_outage(Some(StorageError::Unavailable));
+        assert_ok!(OffchainStorage::post_storage_offer(Origin::signed(2), 1, 2));
+        assert!(OffchainStorage::storage_request(1).is_some());
+        assert_ok!(OffchainStorage::post_storage_offer(Origin::signed(2), 1, 2));
+        assert_eq!(OffchainStorage::storage_request(1), None);
+        let reason = Error::<Test>::ExternalError.code();
+        assert_eq!(OffchainStorage::dead_letter(1).map(|letter| letter.reason), Some(reason));
+        let event = TestEvent::offchain_storage(RawEvent::OperationDeadLettered(1, reason));
+        assert!(System::events().iter().any(|record| record.event == event));
+        assert_eq!(Balances::reserved_balance(1), reserved);
+
+        set_outage(None);
+        assert_noop!(
+            OffchainStorage::retry_dead_letter(Origin::signed(3), 1),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::retry_dead_letter(Origin::signed(1), 1));
+        assert_eq!(OffchainStorage::dead_letter(1), None);
+        assert_eq!(OffchainStorage::storage_request(1), None);
+        assert_eq!(Balances::reserved_balance(1), reserved - 5);
+        assert_noop!(
+            OffchainStorage::purge_dead_letter(Origin::signed(1), 1),
+            Error::<Test>::NoDeadLetter
+        );
+    });
+}
+
+#[test]
+fn entries_of_the_first_layout_are_migrated() {
+    new_test_ext().execute_with(|| {
+        let (old, new): (Vec<u8>, Vec<u8>) = (b"old".to_vec(), b"new".to_vec());
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), new.clone(), b"b".to_vec()));
+        let written = OffchainStorage::get_data(&new);
+        sp_io::storage::set(&<Data<Test>>::hashed_key_for(&old), &(2u64, Access::Read).encode());
+        assert_ok!(DB::set(old.clone(), b"a".to_vec()));
+        assert_eq!(OffchainStorage::storage_version(), 0);
+
+        OffchainStorage::on_initialize(1);
+        assert_eq!(OffchainStorage::storage_version(), migration::CURRENT_VERSION);
+        assert_eq!(OffchainStorage::get_data(&new), written);
+        assert_eq!(OffchainStorage::get_data(&old).author, 2);
+        // the value is hashed on the first read.
+        assert_ok!(OffchainStorage::read_data(Origin::signed(1), old.clone()));
+        assert_eq!(OffchainStorage::get_data(&old).content_hash, BlakeTwo256::hash(b"a"));
+        assert_ok!(DB::set(old.clone(), b"tampered".to_vec()));
+        assert_noop!(
+            OffchainStorage::read_for(&1, &old).map_err(DispatchError::from),
+            Error::<Test>::IntegrityError
+        );
+    });
+}
+
+#[test]
+fn chains_can_start_with_entries() {
+    let mut t = frame_system::GenesisConfig::default()
+        .build_storage::<Test>()
+        .unwrap();
+    STORE.with(|s| s.borrow_mut().clear());
+    GenesisConfig::<Test> {
+        entries: vec![(b"a".to_vec(), 1, Access::Read), (b"b".to_vec(), 2, Access::Avoid)],
+        values: vec![(b"a".to_vec(), b"x".to_vec())],
+    }
+    .assimilate_storage(&mut t)
+    .unwrap();
+    sp_io::TestExternalities::from(t).execute_with(|| {
+        assert_eq!(OffchainStorage::storage_version(), migration::CURRENT_VERSION);
+        assert_eq!(stored(b"a"), Some(b"x".to_vec()));
+        assert_eq!(OffchainStorage::get_data(b"a".to_vec()).content_hash, BlakeTwo256::hash(b"x"));
+        assert_ok!(OffchainStorage::read_data(Origin::signed(3), b"a".to_vec()));
+        assert_eq!(OffchainStorage::get_data(b"b".to_vec()).author, 2);
+        assert_noop!(
+            OffchainStorage::read_data(Origin::signed(3), b"b".to_vec()),
+            Error::<Test>::PermissionDenied
+        );
+    });
+}
+
+#[test]
+fn keys_and_values_are_bounded() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let long_key = vec![1; 33];
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(1), long_key.clone(), b"a".to_vec()),
+            Error::<Test>::KeyTooLong
+        );
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(1), key.clone(), vec![0; 257]),
+            Error::<Test>::ValueTooLong
+        );
+        assert_eq!(
+            OffchainStorage::validate_write(&1, &key, 257),
+            Err(ValidationError::ValueTooLong)
+        );
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_noop!(
+            OffchainStorage::rename_data(Origin::signed(1), key, long_key),
+            Error::<Test>::KeyTooLong
+        );
+        assert_eq!(ValidationError::KeyTooLong.code(), Error::<Test>::KeyTooLong.code());
+    });
+}
+
+#[test]
+fn accounts_may_author_a_bounded_number_of_entries() {
+    new_test_ext().execute_with(|| {
+        for i in 0..16u8 {
+            assert_ok!(OffchainStorage::write_data(Origin::signed(1), vec![i], b"a".to_vec()));
+        }
+        assert_eq!(OffchainStorage::owned_count(1), 16);
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(1), vec![16], b"a".to_vec()),
+            Error::<Test>::TooManyEntries
+        );
+        // updates don't take more of the quota.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), vec![0], b"b".to_vec()));
+
+        // entries in the trash still count until purged.
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), vec![0]));
+        assert_eq!(OffchainStorage::owned_count(1), 16);
+        OffchainStorage::on_initialize(2);
+        assert_eq!(OffchainStorage::owned_count(1), 15);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), vec![16], b"a".to_vec()));
+        assert_eq!(
+            OffchainStorage::validate_write(&1, &[17], 1),
+            Err(ValidationError::TooManyEntries)
+        );
+    });
+}
+
+#[test]
+fn accounts_may_store_a_bounded_number_of_bytes() {
+    new_test_ext().execute_with(|| {
+        let (a, b): (Vec<u8>, Vec<u8>) = (b"a".to_vec(), b"b".to_vec());
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a.clone(), vec![0; 40]));
+        assert_eq!(OffchainStorage::used_bytes(1), 40);
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(1), b.clone(), vec![0; 30]),
+            Error::<Test>::ByteQuotaExceeded
+        );
+        // overwriting releases the bytes of the old value.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a.clone(), vec![0; 10]));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), b.clone(), vec![0; 30]));
+        assert_eq!(OffchainStorage::used_bytes(1), 40);
+        assert_eq!(
+            OffchainStorage::validate_write(&1, &a, 40),
+            Err(ValidationError::ByteQuotaExceeded)
+        );
+
+        // values in the trash still count until purged.
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), b));
+        assert_eq!(OffchainStorage::used_bytes(1), 40);
+        OffchainStorage::on_initialize(2);
+        assert_eq!(OffchainStorage::used_bytes(1), 10);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a, vec![0; 64]));
+    });
+}
+
+#[test]
+fn writes_are_rate_limited_per_block() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        for _ in 0..32 {
+            assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        }
+        assert_eq!(OffchainStorage::writes_in_block(0, 1), 32);
+        assert_noop!(
+            OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()),
+            Error::<Test>::RateLimited
+        );
+        assert_eq!(OffchainStorage::validate_write(&1, &key, 1), Err(ValidationError::RateLimited));
+        // other accounts aren't held back.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(2), b"k2".to_vec(), b"b".to_vec()));
+
+        OffchainStorage::on_finalize(0);
+        assert_eq!(OffchainStorage::writes_in_block(0, 1), 0);
+        System::set_block_number(1);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key, b"a".to_vec()));
+    });
+}
+
+#[test]
+fn admin_origin_may_remove_providers() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(OffchainStorage::register_provider(
+            Origin::signed(2),
+            b"https://provider.example".to_vec(),
+            1 << 20,
+            1
+        ));
+        assert_noop!(
+            OffchainStorage::force_deregister_provider(Origin::signed(1), 2),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(OffchainStorage::force_deregister_provider(Origin::ROOT, 2));
+        assert!(!OffchainStorage::is_provider(&2));
+        assert_noop!(
+            OffchainStorage::force_deregister_provider(Origin::ROOT, 2),
+            Error::<Test>::NotRegistered
+        );
+    });
+}
+
+#[test]
+fn provider_registrations_are_bounded_and_reserve_a_deposit() {
+    use frame_support::weights::GetDispatchInfo;
+
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            OffchainStorage::register_provider(Origin::signed(2), vec![1; 33], 1, 0),
+            Error::<Test>::EndpointTooLong
+        );
+        assert_noop!(
+            OffchainStorage::register_provider(Origin::signed(4), vec![], 1, 0),
+            Error::<Test>::InsufficientBalance
+        );
+        assert_ok!(OffchainStorage::register_provider(Origin::signed(2), vec![1; 32], 1, 0));
+        assert_eq!(Balances::reserved_balance(2), ProviderDeposit::get());
+        // updates keep the deposit reserved.
+        assert_ok!(OffchainStorage::register_provider(Origin::signed(2), vec![], 2, 0));
+        assert_eq!(Balances::reserved_balance(2), ProviderDeposit::get());
+        assert_ok!(OffchainStorage::deregister_provider(Origin::signed(2)));
+        assert_eq!(Balances::reserved_balance(2), 0);
+        assert_ok!(OffchainStorage::register_provider(Origin::signed(2), vec![], 1, 0));
+        assert_ok!(OffchainStorage::force_deregister_provider(Origin::ROOT, 2));
+        assert_eq!(Balances::reserved_balance(2), 0);
+
+        let call = Call::<Test>::register_provider(vec![1; 32], 1, 0);
+        assert_eq!(call.get_dispatch_info().weight, <() as WeightInfo>::register_provider(32));
+
+        // providers registered in the eighth layout reserved nothing, until they register
+        // again.
+        let raw = (b"https://old.example".to_vec(), 1u64, 1u64, true).encode();
+        sp_io::storage::set(&<Providers<Test>>::hashed_key_for(&3), &raw);
+        StorageVersion::put(8);
+        OffchainStorage::on_initialize(1);
+        assert_eq!(OffchainStorage::storage_version(), migration::CURRENT_VERSION);
+        let info = OffchainStorage::provider_info(3).unwrap();
+        assert_eq!((info.approved, info.deposit), (true, 0));
+        assert_ok!(OffchainStorage::register_provider(Origin::signed(3), vec![], 1, 0));
+        assert_eq!(Balances::reserved_balance(3), ProviderDeposit::get());
+    });
+}
+
+#[test]
+fn offchain_workers_upload_deferred_writes() {
+    new_offchain_test_ext().execute_with(|| {
+        DEFERRED.with(|d| *d.borrow_mut() = true);
+        OPERATORS.with(|o| *o.borrow_mut() = vec![3]);
+        let key: Vec<u8> = b"key".to_vec();
+        let hash = BlakeTwo256::hash(b"a");
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_eq!(stored(&key), None);
+        assert_eq!(OffchainStorage::pending_upload(&key).unwrap().value, b"a".to_vec());
+        assert_eq!(OffchainStorage::pending_upload_count(), 1);
+        assert_noop!(
+            OffchainStorage::rename_data(Origin::signed(1), key.clone(), b"new".to_vec()),
+            Error::<Test>::UploadPending
+        );
+
+        OffchainStorage::offchain_worker(1);
+        assert_eq!(stored(&key), Some(b"a".to_vec()));
+        assert_eq!(stored(&history::version_key(&key, 1)), Some(b"a".to_vec()));
+        let confirmations = CONFIRMATIONS.with(|c| c.borrow().clone());
+        let start = Call::start_upload(key.clone(), hash);
+        assert_eq!(confirmations, vec![start, Call::confirm_upload(key.clone(), hash)]);
+
+        assert_noop!(
+            OffchainStorage::confirm_upload(Origin::signed(1), key.clone(), hash),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::confirm_upload(Origin::signed(3), key.clone(), hash));
+        assert_eq!(OffchainStorage::pending_upload(&key), None);
+        assert_eq!(OffchainStorage::pending_upload_count(), 0);
+        assert_noop!(
+            OffchainStorage::confirm_upload(Origin::signed(3), key, hash),
+            Error::<Test>::NoPendingUpload
+        );
+    });
+}
+
+#[test]
+fn providers_confirm_without_fees() {
+    use crate::confirmation::{Confirmed, ProviderConfirmation};
+    #[allow(deprecated)]
+    use frame_support::unsigned::ValidateUnsigned;
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::set_replication(Origin::signed(1), key.clone(), 1));
+        assert_ok!(OffchainStorage::accept_replica(Origin::signed(9), key.clone()));
+
+        let kept = ProviderConfirmation {
+            provider: 9,
+            nonce: 1,
+            expires_at: 10,
+            confirmed: Confirmed::Stored(key.clone(), BlakeTwo256::hash(b"a")),
+        };
+        let signature = TestSignature(9, kept.signing_payload());
+        let call = Call::confirm_unsigned(kept.clone(), signature.clone());
+        assert!(OffchainStorage::validate_unsigned(&call).is_ok());
+        assert_noop!(
+            OffchainStorage::confirm_unsigned(Origin::NONE, kept.clone(), TestSignature(1, vec![])),
+            Error::<Test>::BadConfirmation
+        );
+        assert_noop!(
+            OffchainStorage::confirm_unsigned(Origin::signed(9), kept.clone(), signature.clone()),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(OffchainStorage::confirm_unsigned(
+            Origin::NONE,
+            kept.clone(),
+            signature.clone()
+        ));
+        assert_eq!(OffchainStorage::replica_confirmed_at(&key, 9), Some(0));
+
+        // a confirmation can't be replayed.
+        assert!(OffchainStorage::validate_unsigned(&call).is_err());
+        assert_noop!(
+            OffchainStorage::confirm_unsigned(Origin::NONE, kept, signature),
+            Error::<Test>::StaleConfirmation
+        );
+
+        // only reads the reader paid for are served.
+        let served = ProviderConfirmation {
+            provider: 9,
+            nonce: 2,
+            expires_at: 10,
+            confirmed: Confirmed::Retrieved(key.clone(), 2),
+        };
+        let reads = OffchainStorage::metrics_of(9).served_reads;
+        let signature = TestSignature(9, served.signing_payload());
+        let call = Call::confirm_unsigned(served.clone(), signature.clone());
+        assert!(OffchainStorage::validate_unsigned(&call).is_err());
+        assert_noop!(
+            OffchainStorage::confirm_unsigned(Origin::NONE, served.clone(), signature.clone()),
+            Error::<Test>::NoReadReceipt
+        );
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key));
+        assert!(OffchainStorage::validate_unsigned(&call).is_ok());
+        assert_ok!(OffchainStorage::confirm_unsigned(Origin::NONE, served, signature));
+        assert_eq!(OffchainStorage::metrics_of(9).served_reads, reads + 1);
+    });
+}
+
+#[test]
+fn failing_uploads_back_off_and_are_reported() {
+    new_offchain_test_ext().execute_with(|| {
+        DEFERRED.with(|d| *d.borrow_mut() = true);
+        OPERATORS.with(|o| *o.borrow_mut() = vec![3]);
+        let key: Vec<u8> = b"key".to_vec();
+        let hash = BlakeTwo256::hash(b"a");
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        set_outage(Some(StorageError::Timeout));
+        let confirmations = || CONFIRMATIONS.with(|c| c.borrow().clone());
+
+        // tried in blocks 1, 2 and 4, waiting twice as long after every failure.
+        for n in 1..4 {
+            OffchainStorage::offchain_worker(n);
+        }
+        let start = Call::start_upload(key.clone(), hash);
+        assert_eq!(confirmations(), vec![start.clone()]);
+        OffchainStorage::offchain_worker(4);
+        let failed = Call::report_upload_failed(key.clone(), hash, StorageError::Timeout);
+        assert_eq!(confirmations(), vec![start, failed]);
+        set_outage(None);
+
+        assert_noop!(
+            OffchainStorage::report_upload_failed(
+                Origin::signed(1),
+                key.clone(),
+                hash,
+                StorageError::Timeout
+            ),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::report_upload_failed(
+            Origin::signed(3),
+            key.clone(),
+            hash,
+            StorageError::Timeout
+        ));
+        assert_eq!(OffchainStorage::pending_upload(&key), None);
+        let event = TestEvent::offchain_storage(RawEvent::WriteFailed(key, StorageError::Timeout));
+        assert!(System::events().into_iter().any(|record| record.event == event));
+    });
+}
+
+#[test]
+fn metadata_and_owned_entries_can_be_queried() {
+    new_test_ext().execute_with(|| {
+        let (a, b): (Vec<u8>, Vec<u8>) = (b"a".to_vec(), b"b".to_vec());
+        assert_eq!(OffchainStorage::metadata(&a), None);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a.clone(), b"abc".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), b.clone(), b"x".to_vec()));
+        assert_eq!(
+            OffchainStorage::metadata(&a),
+            Some((1, Access::Read, 3, BlakeTwo256::hash(b"abc")))
+        );
+        assert_eq!(OffchainStorage::owned_by(1), vec![a.clone(), b.clone()]);
+
+        assert_ok!(OffchainStorage::rename_data(Origin::signed(1), a, b"c".to_vec()));
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), b.clone()));
+        assert_eq!(OffchainStorage::owned_by(1), vec![b"c".to_vec()]);
+        assert_ok!(OffchainStorage::restore_data(Origin::signed(1), b.clone()));
+        assert_eq!(OffchainStorage::owned_by(1), vec![b"c".to_vec(), b]);
+    });
+}
+
+#[test]
+fn entries_keep_their_size_and_times() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        System::set_block_number(2);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"abc".to_vec()));
+        System::set_block_number(5);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"ab".to_vec()));
+        let data = OffchainStorage::get_data(&key);
+        assert_eq!((data.size, data.created_at, data.updated_at), (2, 2, 5));
+
+        // entries of the second layout get a size and blocks of zero.
+        let old: Vec<u8> = b"old".to_vec();
+        let raw = (2u64, Access::Read, BlakeTwo256::hash(b"a"), 1u32, vec![9u64]).encode();
+        sp_io::storage::set(&<Data<Test>>::hashed_key_for(&old), &raw);
+        StorageVersion::put(1);
+        OffchainStorage::on_initialize(6);
+        assert_eq!(OffchainStorage::storage_version(), migration::CURRENT_VERSION);
+        let data = OffchainStorage::get_data(&old);
+        assert_eq!((data.author, data.replication, data.providers), (2, 1, vec![9]));
+        assert_eq!((data.size, data.created_at, data.updated_at), (0, 0, 0));
+        assert_eq!(OffchainStorage::get_data(&key).updated_at, 5);
+    });
+}
+
+#[test]
+fn owned_entries_are_counted_and_listed_anew() {
+    new_test_ext().execute_with(|| {
+        for key in &[b"a", b"b", b"c"] {
+            assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.to_vec(), b"x".to_vec()));
+        }
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), b"c".to_vec()));
+
+        // entries written before they were counted, "b" listed in the single list of before.
+        <OwnedCount<Test>>::remove(1);
+        TotalEntries::kill();
+        <OwnedEntries<Test>>::remove(1, 1);
+        OwnedPositions::remove(b"b".to_vec());
+        let list = [
+            &sp_io::hashing::twox_128(b"OffchainStorage")[..],
+            &sp_io::hashing::twox_128(b"OwnedEntries")[..],
+            &sp_io::hashing::blake2_256(&1u64.encode())[..],
+        ]
+        .concat();
+        sp_io::storage::set(&list, &vec![b"b".to_vec(), b"c".to_vec()].encode());
+        StorageVersion::put(7);
+        OffchainStorage::on_initialize(1);
+        assert_eq!(OffchainStorage::storage_version(), migration::CURRENT_VERSION);
+        // the trashed entry counts, but only live entries are listed.
+        assert_eq!(OffchainStorage::owned_count(1), 3);
+        assert_eq!(OffchainStorage::total_entries(), 3);
+        assert_eq!(OffchainStorage::owned_by(1), vec![b"a".to_vec(), b"b".to_vec()]);
+        assert_eq!(OffchainStorage::owned_position(b"b".to_vec()), Some(3));
+        assert_eq!(sp_io::storage::get(&list), None);
+    });
+}
+
+#[test]
+fn used_bytes_are_charged_anew() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), b"a".to_vec(), b"abc".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), b"b".to_vec(), b"de".to_vec()));
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), b"b".to_vec()));
+
+        // entries written before bytes were charged, their size zeroed by version 2.
+        <UsedBytes<Test>>::remove(1);
+        TotalBytesStored::kill();
+        <Data<Test>>::mutate(b"a".to_vec(), |data| data.size = 0);
+        <Trash<Test>>::mutate(b"b".to_vec(), |entry| entry.as_mut().unwrap().data.size = 0);
+        StorageVersion::put(7);
+        OffchainStorage::on_initialize(1);
+        assert_eq!(OffchainStorage::used_bytes(1), 5);
+        assert_eq!(OffchainStorage::total_bytes_stored(), 5);
+        assert_eq!(OffchainStorage::get_data(b"a".to_vec()).size, 3);
+        assert_eq!(OffchainStorage::trash_of(b"b".to_vec()).unwrap().data.size, 2);
+
+        // deleting them releases what was charged.
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), b"a".to_vec()));
+        OffchainStorage::on_initialize(2);
+        assert_eq!(OffchainStorage::used_bytes(1), 0);
+    });
+}
+
+#[test]
+fn owned_entries_are_listed_in_pages() {
+    new_test_ext().execute_with(|| {
+        let keys: Vec<Vec<u8>> = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()];
+        for key in &keys {
+            assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"x".to_vec()));
+        }
+        let first = OffchainStorage::owned_page(1, 0, 2);
+        assert_eq!(first, Page { entries: keys[..2].to_vec(), next: Some(2) });
+
+        // entries deleted or created in between don't move the next page.
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), keys[0].clone()));
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), keys[2].clone()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), b"e".to_vec(), b"x".to_vec()));
+        let second = OffchainStorage::owned_page(1, 2, 2);
+        assert_eq!(second, Page { entries: vec![keys[3].clone(), b"e".to_vec()], next: None });
+        let listed = vec![keys[1].clone(), keys[3].clone(), b"e".to_vec()];
+        assert_eq!(OffchainStorage::owned_by(1), listed);
+        assert_eq!(OffchainStorage::owned_page(2, 0, 2), Page { entries: vec![], next: None });
+    });
+}
+
+#[test]
+fn totals_follow_every_entry() {
+    new_test_ext().execute_with(|| {
+        let (a, b): (Vec<u8>, Vec<u8>) = (b"a".to_vec(), b"b".to_vec());
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a.clone(), b"abc".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(2), b.clone(), b"xy".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a.clone(), b"abcd".to_vec()));
+        assert_eq!(OffchainStorage::statistics(), Statistics { entries: 2, bytes: 6 });
+
+        // entries in the trash still count, until they are purged.
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), a));
+        assert_eq!(OffchainStorage::statistics(), Statistics { entries: 2, bytes: 6 });
+        OffchainStorage::on_initialize(2);
+        assert_eq!(OffchainStorage::total_entries(), 1);
+        assert_eq!(OffchainStorage::total_bytes_stored(), 2);
+    });
+}
+
+#[test]
+fn transactions_apply_all_ops_or_none() {
+    new_test_ext().execute_with(|| {
+        let key = |s: &[u8]| s.to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key(b"c"), b"old".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key(b"e"), b"gone".to_vec()));
+
+        assert_noop!(
+            OffchainStorage::transact(
+                Origin::signed(1),
+                vec![Op::Write(key(b"a"), b"x".to_vec()), Op::Rename(key(b"c"), key(b"a"))]
+            ),
+            Error::<Test>::ConflictingOps
+        );
+        // nothing changes when a transaction is refused, not even the rent charged.
+        System::set_block_number(2);
+        assert_eq!(
+            OffchainStorage::transact(
+                Origin::signed(1),
+                vec![Op::Write(key(b"c"), b"x".to_vec()), Op::Delete(key(b"missing"))]
+            ),
+            Err(Error::<Test>::NoneData.into())
+        );
+        assert_eq!(OffchainStorage::rent_of(key(b"c")).unwrap().charged_until, 0);
+        assert_eq!(OffchainStorage::get_data(key(b"c")).size, 3);
+
+        let ops = vec![
+            Op::Write(key(b"a"), b"new".to_vec()),
+            Op::Rename(key(b"c"), key(b"d")),
+            Op::Delete(key(b"e")),
+        ];
+        assert_ok!(OffchainStorage::transact(Origin::signed(1), ops));
+        // the value written is uploaded by the offchain worker.
+        assert_eq!(OffchainStorage::pending_upload(key(b"a")).unwrap().value, b"new".to_vec());
+        assert_eq!(stored(b"a"), None);
+        assert!(!<Data<Test>>::exists(key(b"c")));
+        // the renamed value is read from under the old data_id until it is copied.
+        assert_eq!(stored(b"d"), None);
+        assert_eq!(OffchainStorage::read_for(&1, b"d").ok(), Some(b"old".to_vec()));
+        assert!(OffchainStorage::trash_of(key(b"e")).is_some());
+        let event = TestEvent::offchain_storage(RawEvent::Transacted(1, 3));
+        assert!(System::events().into_iter().any(|record| record.event == event));
+    });
+}
+
+#[test]
+fn offchain_workers_copy_values_renamed_in_transactions() {
+    new_offchain_test_ext().execute_with(|| {
+        OPERATORS.with(|o| *o.borrow_mut() = vec![3]);
+        let (old, new): (Vec<u8>, Vec<u8>) = (b"old".to_vec(), b"new".to_vec());
+        let write = |value: &[u8]| {
+            OffchainStorage::write_data(Origin::signed(1), old.clone(), value.to_vec())
+        };
+        assert_ok!(write(b"a"));
+        assert_ok!(write(b"b"));
+        let rename = vec![Op::Rename(old.clone(), new.clone())];
+        assert_ok!(OffchainStorage::transact(Origin::signed(1), rename));
+        assert_eq!(stored(&new), None);
+        assert_eq!(OffchainStorage::read_for(&1, &new).ok(), Some(b"b".to_vec()));
+        let revision = OffchainStorage::revision(&new, 1).unwrap();
+        assert_eq!(revision.key, history::version_key(&old, 1));
+        // the old data_id stays taken, and the entry can't move on, until the copy is in.
+        assert_noop!(write(b"c"), Error::<Test>::CopyPending);
+        assert_noop!(
+            OffchainStorage::rename_data(Origin::signed(1), new.clone(), b"other".to_vec()),
+            Error::<Test>::CopyPending
+        );
+
+        OffchainStorage::offchain_worker(1);
+        assert_eq!(stored(&new), Some(b"b".to_vec()));
+        assert_eq!(stored(&history::version_key(&new, 1)), Some(b"a".to_vec()));
+        let confirmations = CONFIRMATIONS.with(|c| c.borrow().clone());
+        assert_eq!(confirmations, vec![Call::confirm_copy(new.clone())]);
+
+        assert_noop!(
+            OffchainStorage::confirm_copy(Origin::signed(1), new.clone()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::confirm_copy(Origin::signed(3), new.clone()));
+        assert_eq!(OffchainStorage::pending_copy(&new), None);
+        let revision = OffchainStorage::revision(&new, 1).unwrap();
+        assert_eq!(revision.key, history::version_key(&new, 1));
+        assert_noop!(
+            OffchainStorage::confirm_copy(Origin::signed(3), new.clone()),
+            Error::<Test>::NoPendingUpload
+        );
+
+        // the old keys go to the garbage collector and the old data_id is free again.
+        OffchainStorage::on_initialize(2);
+        assert_eq!(stored(&old), None);
+        assert_eq!(stored(&history::version_key(&old, 1)), None);
+        assert_eq!(OffchainStorage::read_for(&1, &new).ok(), Some(b"b".to_vec()));
+        assert_ok!(write(b"c"));
+    });
+}
+
+#[test]
+fn large_values_are_uploaded_in_chunks() {
+    new_test_ext().execute_with(|| {
+        let (key, other): (Vec<u8>, Vec<u8>) = (b"key".to_vec(), b"other".to_vec());
+        let value = b"hello world!!".to_vec();
+        let leaves = merkle::leaves::<BlakeTwo256>(&value, 4);
+        let root = merkle::root::<BlakeTwo256>(&leaves);
+        let chunks: Vec<&[u8]> = value.chunks(4).collect();
+        let upload = |who: u64, data_id: &[u8], index: u32, bytes: &[u8]| {
+            let (data_id, bytes) = (data_id.to_vec(), bytes.to_vec());
+            OffchainStorage::upload_chunk(Origin::signed(who), data_id, index, bytes)
+        };
+
+        assert_noop!(upload(1, &key, 0, chunks[0]), Error::<Test>::NoChunkedUpload);
+        assert_ok!(OffchainStorage::begin_upload(Origin::signed(1), key.clone(), 4, root));
+        assert_noop!(upload(1, &key, 0, b"hel"), Error::<Test>::BadChunk);
+        for index in 0..3 {
+            assert_ok!(upload(1, &key, index, chunks[index as usize]));
+        }
+        assert_noop!(
+            OffchainStorage::commit_upload(Origin::signed(1), key.clone()),
+            Error::<Test>::IncompleteUpload
+        );
+        assert_ok!(upload(1, &key, 3, chunks[3]));
+        assert_ok!(OffchainStorage::commit_upload(Origin::signed(1), key.clone()));
+        assert_eq!(stored(&key), Some(value.clone()));
+        assert_eq!(OffchainStorage::chunked_upload(1, &key), None);
+        assert_eq!(stored(&chunked::chunk_key(&1u64, &key, 0)), None);
+
+        // chunks that don't match the declared root aren't written.
+        assert_ok!(OffchainStorage::begin_upload(Origin::signed(2), other.clone(), 1, root));
+        assert_ok!(upload(2, &other, 0, b"a"));
+        assert_noop!(
+            OffchainStorage::commit_upload(Origin::signed(2), other),
+            Error::<Test>::IntegrityError
+        );
+    });
+}
+
+#[test]
+fn stale_chunked_uploads_are_dropped() {
+    new_test_ext().execute_with(|| {
+        let begin = |who: u64, data_id: &[u8], total: u32| {
+            let (data_id, root) = (data_id.to_vec(), H256::default());
+            OffchainStorage::begin_upload(Origin::signed(who), data_id, total, root)
+        };
+        // the deposit is that of an entry as long as all the chunks.
+        assert_ok!(begin(1, b"a", 3));
+        assert_eq!(Balances::reserved_balance(1), 2 + 3 * 4);
+        assert_ok!(begin(1, b"a", 1));
+        assert_eq!(Balances::reserved_balance(1), 2 + 4);
+        assert_noop!(begin(2, b"a", 64), Error::<Test>::InsufficientBalance);
+        assert_ok!(begin(1, b"b", 1));
+        assert_noop!(begin(1, b"c", 1), Error::<Test>::TooManyUploads);
+        let chunk = b"x".to_vec();
+        assert_ok!(OffchainStorage::upload_chunk(Origin::signed(1), b"a".to_vec(), 0, chunk));
+        let key = chunked::chunk_key(&1u64, b"a", 0);
+        assert_eq!(stored(&key), Some(b"x".to_vec()));
+
+        // uploads not committed within ChunkedUploadPeriod are dropped with their chunks.
+        OffchainStorage::on_initialize(3);
+        assert_eq!(OffchainStorage::chunked_upload(1, b"a".to_vec()), None);
+        assert_eq!(OffchainStorage::chunked_upload_count(1), 0);
+        assert_eq!(Balances::reserved_balance(1), 0);
+        assert_eq!(stored(&key), None);
+        let event = TestEvent::offchain_storage(RawEvent::ChunkedUploadExpired(1, b"a".to_vec()));
+        assert!(System::events().into_iter().any(|record| record.event == event));
+        assert_ok!(begin(1, b"c", 1));
+    });
+}
+
+#[test]
+fn slices_of_values_are_read() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let emitted = |event: RawEvent<u64, u64, H256>| {
+            let event = TestEvent::offchain_storage(event);
+            System::events().into_iter().any(|record| record.event == event)
+        };
+        let value = b"hello world!!".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), value));
+        assert_ok!(OffchainStorage::read_data_range(Origin::signed(2), key.clone(), 4, 5));
+        assert!(emitted(RawEvent::GetDataRange(4, 5)));
+        assert!(emitted(RawEvent::DataRead(2, key.clone())));
+        assert_ok!(OffchainStorage::read_data_range(Origin::signed(2), key.clone(), 13, 0));
+        assert_noop!(
+            OffchainStorage::read_data_range(Origin::signed(2), key.clone(), 10, 4),
+            Error::<Test>::BadRange
+        );
+        assert_noop!(
+            OffchainStorage::read_data_range(Origin::signed(2), b"none".to_vec(), 0, 1),
+            Error::<Test>::NoneData
+        );
+    });
+}
+
+#[test]
+fn bytes_are_appended_to_values() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"log".to_vec();
+        let append = |who: u64, bytes: &[u8]| {
+            OffchainStorage::append_data(Origin::signed(who), key.clone(), bytes.to_vec())
+        };
+        assert_ok!(append(1, b"abc"));
+        assert_eq!(stored(&key), Some(b"abc".to_vec()));
+        assert_ok!(append(1, b"de"));
+        assert_eq!(stored(&key), Some(b"abcde".to_vec()));
+        let data = OffchainStorage::get_data(&key);
+        assert_eq!(data.size, 5);
+        assert_eq!(data.content_hash, BlakeTwo256::hash(b"abcde"));
+        assert_eq!(OffchainStorage::used_bytes(1), 5);
+        assert_eq!(OffchainStorage::total_bytes_stored(), 5);
+        assert_eq!(OffchainStorage::total_entries(), 1);
+
+        // others only read entries with the default access.
+        assert_noop!(append(2, b"f"), Error::<Test>::PermissionDenied);
+    });
+}
+
+#[test]
+fn values_carry_how_they_are_encrypted() {
+    new_test_ext().execute_with(|| {
+        let (key, copy): (Vec<u8>, Vec<u8>) = (b"key".to_vec(), b"copy".to_vec());
+        let info = EncryptionInfo {
+            scheme: b"xchacha20poly1305".to_vec(),
+            key_id: b"team".to_vec(),
+            nonce: vec![7; 24],
+        };
+        let write = |info: EncryptionInfo| {
+            OffchainStorage::write_encrypted(Origin::signed(1), key.clone(), b"ab".to_vec(), info)
+        };
+        assert_ok!(write(info.clone()));
+        assert_eq!(OffchainStorage::encryption_of(&key), Some(info.clone()));
+        assert_ok!(OffchainStorage::append_data(Origin::signed(1), key.clone(), b"c".to_vec()));
+        assert_ok!(OffchainStorage::copy_data(Origin::signed(2), key.clone(), copy.clone()));
+        assert_eq!(OffchainStorage::encryption_of(&key), Some(info.clone()));
+        assert_eq!(OffchainStorage::encryption_of(&copy), Some(info.clone()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"ab".to_vec()));
+        assert_eq!(OffchainStorage::encryption_of(&key), None);
+        let long = EncryptionInfo { nonce: vec![0; MAX_ENCRYPTION_FIELD_LENGTH + 1], ..info };
+        assert_noop!(write(long), Error::<Test>::BadEncryptionInfo);
+
+        // entries of the third layout are taken as unencrypted.
+        let old: Vec<u8> = b"old".to_vec();
+        let data = (2u64, Access::Read, BlakeTwo256::hash(b"a"), 0u32, Vec::<u64>::new());
+        let raw = (data, 3u32, 4u64, 5u64).encode();
+        sp_io::storage::set(&<Data<Test>>::hashed_key_for(&old), &raw);
+        StorageVersion::put(2);
+        OffchainStorage::on_initialize(6);
+        assert_eq!(OffchainStorage::storage_version(), migration::CURRENT_VERSION);
+        let data = OffchainStorage::get_data(&old);
+        assert_eq!((data.size, data.created_at, data.updated_at), (3, 4, 5));
+        assert_eq!(data.encryption, None);
+        assert_eq!(OffchainStorage::encryption_of(&copy), Some(info));
+    });
+}
+
+#[test]
+fn data_keys_are_wrapped_to_registered_keys() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let share = |who: u64, public_key: [u8; 32]| {
+            let wrapped = b"wrapped".to_vec();
+            OffchainStorage::share_key(Origin::signed(who), key.clone(), 3, public_key, wrapped)
+        };
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert_noop!(share(1, [1; 32]), Error::<Test>::PermissionDenied);
+        assert_ok!(OffchainStorage::grant_session_access(
+            Origin::signed(1),
+            key.clone(),
+            3,
+            Access::Read
+        ));
+        assert_noop!(share(1, [1; 32]), Error::<Test>::NoEncryptionKey);
+        assert_ok!(OffchainStorage::register_encryption_key(Origin::signed(3), [1; 32]));
+        assert_noop!(share(1, [2; 32]), Error::<Test>::StaleEncryptionKey);
+        assert_noop!(share(2, [1; 32]), Error::<Test>::PermissionDenied);
+        assert_ok!(share(1, [1; 32]));
+        assert_eq!(OffchainStorage::shared_key_for(&key, &3), Some(b"wrapped".to_vec()));
+
+        // a key wrapped to a replaced public key isn't handed out.
+        assert_ok!(OffchainStorage::register_encryption_key(Origin::signed(3), [2; 32]));
+        assert_eq!(OffchainStorage::shared_key_for(&key, &3), None);
+        assert_ok!(share(1, [2; 32]));
+        assert_ok!(OffchainStorage::revoke_all_grants(Origin::signed(1), 1));
+        assert_eq!(OffchainStorage::shared_key_for(&key, &3), None);
+    });
+}
+
+#[test]
+fn grants_of_access_are_announced() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert_ok!(OffchainStorage::set_read_price(Origin::signed(1), key.clone(), Some(10)));
+        assert_ok!(OffchainStorage::purchase_read_access(Origin::signed(2), key.clone()));
+        assert_ok!(OffchainStorage::grant_session_access(
+            Origin::signed(1),
+            key.clone(),
+            3,
+            Access::Write
+        ));
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 7, 2));
+        // revocations aren't grants.
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 7, 0));
+        assert_ok!(OffchainStorage::grant_session_access(
+            Origin::signed(1),
+            key.clone(),
+            3,
+            Access::Avoid
+        ));
+        assert_eq!(
+            granted(),
+            vec![
+                (key.clone(), 2, Access::Read),
+                (key.clone(), 3, Access::Write),
+                (key, 7, Access::Write),
+            ]
+        );
+    });
+}
+
+#[test]
+fn namespaces_reserve_data_id_prefixes() {
+    new_test_ext().execute_with(|| {
+        let write = |who: u64, data_id: &[u8]| {
+            OffchainStorage::write_data(Origin::signed(who), data_id.to_vec(), b"a".to_vec())
+        };
+        let create = |who: u64, name: &[u8]| {
+            OffchainStorage::create_namespace(Origin::signed(who), name.to_vec())
+        };
+        assert_ok!(write(2, b"app/old"));
+        assert_ok!(create(1, b"app/"));
+        assert_eq!(Balances::reserved_balance(1), 5);
+        assert_noop!(create(2, b"app/"), Error::<Test>::NamespaceTaken);
+        assert_noop!(create(2, b"app/sub/"), Error::<Test>::NamespaceReserved);
+        assert_noop!(create(2, b""), Error::<Test>::NamespaceReserved);
+
+        assert_noop!(write(2, b"app/x"), Error::<Test>::NamespaceReserved);
+        assert_eq!(
+            OffchainStorage::validate_write(&2, b"app/x", 1),
+            Err(ValidationError::NamespaceReserved)
+        );
+        // entries that existed before stay their authors'.
+        assert_ok!(write(2, b"app/old"));
+        assert_noop!(
+            OffchainStorage::rename_data(Origin::signed(2), b"app/old".to_vec(), b"app/y".to_vec()),
+            Error::<Test>::NamespaceReserved
+        );
+        assert_ok!(write(1, b"app/x"));
+        assert_ok!(OffchainStorage::set_namespace_grant(
+            Origin::signed(1),
+            b"app/".to_vec(),
+            2,
+            true
+        ));
+        assert_ok!(write(2, b"app/y"));
+
+        // an outer namespace created later doesn't take over the inner one.
+        assert_ok!(create(3, b"ap"));
+        assert_noop!(write(2, b"apx"), Error::<Test>::NamespaceReserved);
+        assert_ok!(write(2, b"app/z"));
+        assert_eq!(OffchainStorage::namespace_of(b"app/z"), Some(b"app/".to_vec()));
+
+        assert_noop!(
+            OffchainStorage::remove_namespace(Origin::signed(2), b"app/".to_vec()),
+            Error::<Test>::PermissionDenied
+        );
+        let reserved = Balances::reserved_balance(1);
+        assert_ok!(OffchainStorage::remove_namespace(Origin::signed(1), b"app/".to_vec()));
+        assert_eq!(Balances::reserved_balance(1), reserved - 5);
+        assert_eq!(OffchainStorage::namespace_of(b"app/w"), Some(b"ap".to_vec()));
+        assert_ok!(write(3, b"app/w"));
+    });
+}
+
+#[test]
+fn entries_are_routed_to_their_backend() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let write_to = |value: &[u8], backend: BackendId| {
+            OffchainStorage::write_data_to(Origin::signed(1), key.clone(), value.to_vec(), backend)
+        };
+        assert_noop!(write_to(b"a", 2), Error::<Test>::NoBackend);
+        assert_ok!(write_to(b"a", 1));
+        assert_eq!(cold(&key), Some(b"a".to_vec()));
+        assert_eq!(stored(&key), None);
+        assert_eq!(OffchainStorage::get_data(&key).backend, 1);
+
+        // other writes stay in the backend the entry was created in.
+        assert_noop!(write_to(b"b", DEFAULT_BACKEND), Error::<Test>::BackendMismatch);
+        assert_ok!(OffchainStorage::append_data(Origin::signed(1), key.clone(), b"b".to_vec()));
+        assert_eq!(cold(&key), Some(b"ab".to_vec()));
+        let data = OffchainStorage::get_data(&key);
+        assert_eq!(OffchainStorage::get_external_storage(&key, &data).ok(), Some(b"ab".to_vec()));
+        assert_ok!(OffchainStorage::read_data_range(Origin::signed(2), key.clone(), 1, 1));
+
+        let copy: Vec<u8> = b"copy".to_vec();
+        assert_ok!(OffchainStorage::copy_data(Origin::signed(2), key.clone(), copy.clone()));
+        assert_eq!(cold(&copy), Some(b"ab".to_vec()));
+        assert_eq!(stored(&copy), None);
+
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        OffchainStorage::on_initialize(2);
+        assert_eq!(cold(&key), None);
+        assert_eq!(cold(&copy), Some(b"ab".to_vec()));
+    });
+}
+
+#[test]
+fn versions_of_failed_writes_are_collected() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let write = |value: &[u8]| {
+            OffchainStorage::write_data_to(Origin::signed(1), key.clone(), value.to_vec(), 1)
+        };
+        let version = history::version_key(&key, 2);
+        assert_ok!(write(b"a"));
+        COLD_OUTAGE.with(|o| *o.borrow_mut() = Some(StorageError::Timeout));
+        assert!(write(b"b").is_err());
+        // the version stored before the backend failed belongs to no revision.
+        assert_eq!(OffchainStorage::revision_count(&key), 1);
+        assert_eq!(stored(&version), Some(b"b".to_vec()));
+        OffchainStorage::on_initialize(1);
+        assert_eq!(stored(&version), None);
+
+        // a write made before the collector runs keeps its version under the same key.
+        assert!(write(b"c").is_err());
+        COLD_OUTAGE.with(|o| *o.borrow_mut() = None);
+        assert_ok!(write(b"d"));
+        OffchainStorage::on_initialize(2);
+        assert_eq!(stored(&version), Some(b"d".to_vec()));
+        assert_eq!(cold(&key), Some(b"d".to_vec()));
+    });
+}
+
+#[test]
+fn erasure_coded_overwrites_keep_the_old_shards_until_stored() {
+    new_test_ext().execute_with(|| {
+        CODING.with(|c| *c.borrow_mut() = Some((2, 4)));
+        let key: Vec<u8> = b"key".to_vec();
+        let write = |value: &[u8]| {
+            OffchainStorage::write_data_to(Origin::signed(1), key.clone(), value.to_vec(), 1)
+        };
+        assert_ok!(write(b"0123456789"));
+        COLD_OUTAGE.with(|o| *o.borrow_mut() = Some(StorageError::Timeout));
+        assert!(write(b"abcdefghij").is_err());
+        // the entry still reads the shards of the value before.
+        assert_eq!(OffchainStorage::shards_of(&key).unwrap().version, 1);
+        assert_eq!(OffchainStorage::read_for(&1, &key).ok(), Some(b"0123456789".to_vec()));
+
+        // the shards of the next write go under the keys the failed one used.
+        COLD_OUTAGE.with(|o| *o.borrow_mut() = None);
+        assert_ok!(write(b"abcdefghij"));
+        assert_eq!(OffchainStorage::shards_of(&key).unwrap().version, 2);
+        for n in 1..4 {
+            OffchainStorage::on_initialize(n);
+        }
+        assert_eq!(cold(&erasure::shard_key(&key, 1, 0)), None);
+        assert_eq!(cold(&erasure::shard_key(&key, 2, 0)), Some(b"abcde".to_vec()));
+        assert_eq!(OffchainStorage::read_for(&1, &key).ok(), Some(b"abcdefghij".to_vec()));
+    });
+}
+
+#[test]
+fn shards_stored_before_versions_are_migrated() {
+    new_test_ext().execute_with(|| {
+        CODING.with(|c| *c.borrow_mut() = Some((2, 4)));
+        let key: Vec<u8> = b"key".to_vec();
+        let write = |value: &[u8]| {
+            OffchainStorage::write_data(Origin::signed(1), key.clone(), value.to_vec())
+        };
+        assert_ok!(write(b"0123456789"));
+        // the shards and their info as version 9 kept them.
+        let info = OffchainStorage::shards_of(&key).unwrap();
+        for i in 0..4 {
+            DB::set(erasure::shard_key(&key, 0, i), stored(&info.key(&key, i)).unwrap());
+            DB::delete(info.key(&key, i));
+        }
+        let raw = (info.k, info.len, info.hashes).encode();
+        sp_io::storage::set(&<Shards<Test>>::hashed_key_for(&key), &raw);
+        StorageVersion::put(9);
+        OffchainStorage::on_initialize(1);
+        assert_eq!(OffchainStorage::storage_version(), migration::CURRENT_VERSION);
+        assert_eq!(OffchainStorage::shards_of(&key).unwrap().version, 0);
+        assert_eq!(OffchainStorage::read_for(&1, &key).ok(), Some(b"0123456789".to_vec()));
+
+        // an overwrite leaves them to the garbage collector.
+        assert_ok!(write(b"abcdefghij"));
+        for n in 2..5 {
+            OffchainStorage::on_initialize(n);
+        }
+        assert_eq!(stored(&erasure::shard_key(&key, 0, 0)), None);
+        assert_eq!(stored(&erasure::shard_key(&key, 1, 0)), Some(b"abcde".to_vec()));
+    });
+}
+
+#[test]
+fn typed_data_ids_are_checked() {
+    new_test_ext().execute_with(|| {
+        let write = |data_id: &[u8]| {
+            OffchainStorage::write_data(Origin::signed(1), data_id.to_vec(), b"a".to_vec())
+        };
+        assert_ok!(write(b"old"));
+        TYPED.with(|t| *t.borrow_mut() = true);
+
+        assert_noop!(write(b"key"), Error::<Test>::BadDataId);
+        assert_eq!(OffchainStorage::validate_write(&1, b"key", 1), Err(ValidationError::BadDataId));
+        // an identity multihash, a CIDv1 of raw bytes with one, and one too short.
+        let v0 = DataId::Cid(vec![0, 4, 1, 2, 3, 4]);
+        let v1 = DataId::Cid(vec![1, 0x55, 0, 2, 1, 2]);
+        let bad = DataId::Cid(vec![0x12, 5, 1, 2]);
+        for id in &[v0.clone(), v1, DataId::Raw(b"key".to_vec()), DataId::Uuid([7; 16])] {
+            assert_eq!(DataId::from_key(&id.key()), Some(id.clone()));
+            assert_ok!(write(&id.key()));
+        }
+        assert_eq!(DataId::from_key(&bad.key()), None);
+        assert_noop!(write(&bad.key()), Error::<Test>::BadDataId);
+        assert_eq!(DataId::from_key(&[&v0.key()[..], &[0][..]].concat()), None);
+
+        // entries created before keep their data_ids, new ones must be typed.
+        assert_ok!(write(b"old"));
+        assert_noop!(
+            OffchainStorage::rename_data(Origin::signed(1), b"old".to_vec(), b"new".to_vec()),
+            Error::<Test>::BadDataId
+        );
+        let new = DataId::Raw(b"new".to_vec()).key();
+        assert_ok!(OffchainStorage::rename_data(Origin::signed(1), b"old".to_vec(), new));
+        assert_eq!(ValidationError::BadDataId.code(), Error::<Test>::BadDataId.code());
+    });
+}
+
+#[test]
+fn values_are_referenced_by_uris() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"a key/1".to_vec();
+        assert_eq!(OffchainStorage::uri_of(&key), None);
+        assert_ok!(OffchainStorage::write_data_to(
+            Origin::signed(1),
+            key.clone(),
+            b"a".to_vec(),
+            1
+        ));
+        let hash = BlakeTwo256::hash(b"a");
+        let uri = OffchainStorage::uri_of(&key).unwrap();
+        let prefix = b"offchain://1/a%20key%2F1?hash=";
+        assert!(uri.starts_with(prefix));
+        assert_eq!(uri.len(), prefix.len() + 64);
+        let reference = Reference::parse(&uri).unwrap();
+        assert_eq!(reference.data_id, key);
+        assert_eq!(reference.hash, Some(hash.as_ref().to_vec()));
+        assert_eq!(OffchainStorage::resolve_uri(&uri), Some(key.clone()));
+        assert_eq!(OffchainStorage::resolve_uri(b"offchain://1/a%20key%2F1"), Some(key.clone()));
+        // only the canonical form parses.
+        assert_eq!(Reference::parse(b"offchain://1/a%20key%2f1"), None);
+        assert_eq!(Reference::parse(b"offchain://1/a key"), None);
+        assert_eq!(Reference::parse(b"offchain:///key"), None);
+        assert_eq!(Reference::parse(b"offchain://1/key?hash="), None);
+
+        assert_noop!(
+            OffchainStorage::set_backend_name(Origin::signed(1), 1, b"cold".to_vec()),
+            DispatchError::BadOrigin
+        );
+        let name = |backend: BackendId, name: &[u8]| {
+            OffchainStorage::set_backend_name(Origin::ROOT, backend, name.to_vec())
+        };
+        assert_noop!(name(1, b"12"), Error::<Test>::BadBackendName);
+        assert_noop!(name(2, b"cold"), Error::<Test>::NoBackend);
+        assert_ok!(name(1, b"cold"));
+        assert_noop!(name(0, b"cold"), Error::<Test>::BadBackendName);
+        assert_eq!(OffchainStorage::resolve_uri(&uri), None);
+        let uri = OffchainStorage::uri_of(&key).unwrap();
+        assert!(uri.starts_with(b"offchain://cold/"));
+        assert_eq!(OffchainStorage::resolve_uri(&uri), Some(key.clone()));
+
+        // a reference to the value stops resolving once it changes.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"b".to_vec()));
+        assert_eq!(OffchainStorage::resolve_uri(&uri), None);
+        assert_ok!(name(1, b""));
+        assert_eq!(OffchainStorage::provider_name(1), b"1".to_vec());
+    });
+}
+
+#[test]
+fn remote_authors_grant_reads_and_hand_entries_over() {
+    use crate::bridge::{HandleRemoteRequest, RemoteRequest, SovereignAccount};
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let sovereign = |chain_id: &[u8], account: &[u8]| -> u64 {
+            bridge::HashedSovereignAccount::sovereign_account(chain_id, account)
+        };
+        let (alice, carol) = (sovereign(b"spoke", b"alice"), sovereign(b"other", b"carol"));
+        let _ = Balances::deposit_creating(&alice, 100);
+        assert_ok!(OffchainStorage::handle_remote_request(
+            b"spoke",
+            b"alice",
+            RemoteRequest::Write { data_id: key.clone(), value: b"a".to_vec() }
+        ));
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert!(!OffchainStorage::can_read(&carol, &key));
+
+        let grant = RemoteRequest::GrantRead {
+            data_id: key.clone(),
+            chain_id: b"other".to_vec(),
+            account: b"carol".to_vec(),
+        };
+        assert_noop!(
+            OffchainStorage::handle_remote_request(b"spoke", b"bob", grant.clone()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::handle_remote_request(b"spoke", b"alice", grant));
+        assert!(OffchainStorage::can_read(&carol, &key));
+        assert_eq!(granted(), vec![(key.clone(), carol, Access::Read)]);
+
+        let transfer = RemoteRequest::Transfer {
+            data_id: key.clone(),
+            chain_id: b"other".to_vec(),
+            account: b"carol".to_vec(),
+        };
+        // the new author reserves the deposit.
+        assert_noop!(
+            OffchainStorage::handle_remote_request(b"spoke", b"alice", transfer.clone()),
+            Error::<Test>::InsufficientBalance
+        );
+        let _ = Balances::deposit_creating(&carol, 100);
+        let deposit = OffchainStorage::deposit_of(&key);
+        assert_ok!(OffchainStorage::handle_remote_request(b"spoke", b"alice", transfer));
+        assert_eq!(OffchainStorage::get_data(&key).author, carol);
+        assert_eq!(Balances::reserved_balance(alice), 0);
+        assert_eq!(Balances::reserved_balance(carol), deposit);
+        assert_eq!(OffchainStorage::owned_count(alice), 0);
+        assert_eq!(OffchainStorage::owned_count(carol), 1);
+        assert_eq!(OffchainStorage::used_bytes(carol), 1);
+        assert_noop!(
+            OffchainStorage::delete_data(Origin::signed(alice), key.clone()),
+            Error::<Test>::PermissionDenied
+        );
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(carol), key.clone()));
+    });
+}
+
+#[cfg(feature = "xcm")]
+#[test]
+fn xcm_messages_carry_remote_requests() {
+    use crate::bridge::{RemoteRequest, SovereignAccount};
+    use crate::xcm::{para_chain_id, XcmHandler};
+
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let sender: u64 =
+            bridge::HashedSovereignAccount::sovereign_account(&para_chain_id(2000), b"alice");
+        let _ = Balances::deposit_creating(&sender, 100);
+        let request = RemoteRequest::Write { data_id: key.clone(), value: b"a".to_vec() };
+        let trailing = [request.encode(), vec![0]].concat();
+        assert_noop!(
+            XcmHandler::<Test>::handle_transact(2000, b"alice", &trailing),
+            Error::<Test>::BadRemoteMessage
+        );
+        assert_ok!(XcmHandler::<Test>::handle_transact(2000, b"alice", &request.encode()));
+        assert_eq!(OffchainStorage::get_data(&key).author, sender);
+    });
+}
+
+#[test]
+fn clients_are_adapted_to_external_storage() {
+    use crate::adapter::{BackendMetrics, Operation, StorageClient};
+    use std::{cell::RefCell, collections::BTreeMap};
+
+    thread_local! {
+        static VALUES: RefCell<BTreeMap<Vec<u8>, Vec<u8>>> = RefCell::new(BTreeMap::new());
+        static CALLS: RefCell<Vec<(Operation, usize, Result<(), StorageError>)>> =
+            RefCell::new(vec![]);
+    }
+
+    struct Client;
+
+    impl StorageClient for Client {
+        type Error = &'static str;
+        const MAX_KEY_LENGTH: usize = 4;
+        const MAX_VALUE_LENGTH: usize = 3;
+
+        fn get(key: &[u8]) -> Result<Option<Vec<u8>>, &'static str> {
+            Ok(VALUES.with(|v| v.borrow().get(key).cloned()))
+        }
+
+        fn set(key: &[u8], value: &[u8]) -> Result<(), &'static str> {
+            if value == b"bad" {
+                return Err("refused");
+            }
+            VALUES.with(|v| v.borrow_mut().insert(key.to_vec(), value.to_vec()));
+            Ok(())
+        }
+
+        fn delete(key: &[u8]) -> Result<(), &'static str> {
+            VALUES.with(|v| v.borrow_mut().remove(key));
+            Ok(())
+        }
+
+        fn map_error(_: &'static str) -> StorageError {
+            StorageError::Rejected
+        }
+    }
+
+    struct Calls;
+
+    impl BackendMetrics for Calls {
+        fn record(op: Operation, bytes: usize, outcome: Result<(), StorageError>) {
+            CALLS.with(|c| c.borrow_mut().push((op, bytes, outcome)));
+        }
+    }
+
+    crate::impl_external_storage!(Client, Calls);
+
+    let storage = |key: &[u8], value: &[u8]| {
+        <Client as ExternalStorage>::set(key.to_vec(), value.to_vec())
+    };
+    assert_eq!(storage(b"key", b"abc"), Ok(()));
+    assert_eq!(<Client as ExternalStorage>::get(b"key".to_vec()), Ok(b"abc".to_vec()));
+    assert_eq!(<Client as ExternalStorage>::get(b"none".to_vec()), Ok(vec![]));
+    // the limits are checked before the client is called.
+    assert_eq!(storage(b"key", b"abcd"), Err(StorageError::OutOfSpace));
+    assert_eq!(storage(b"long key", b"a"), Err(StorageError::Rejected));
+    assert_eq!(storage(b"", b"a"), Err(StorageError::Rejected));
+    assert_eq!(storage(b"key", b"bad"), Err(StorageError::Rejected));
+    assert_eq!(<Client as ExternalStorage>::delete(b"key".to_vec()), Ok(()));
+    assert_eq!(
+        <adapter::Adapter<Client> as ExternalStorage>::get(b"key".to_vec()),
+        Ok(vec![])
+    );
+    assert_eq!(
+        CALLS.with(|c| c.borrow().clone()),
+        vec![
+            (Operation::Set, 3, Ok(())),
+            (Operation::Get, 3, Ok(())),
+            (Operation::Get, 0, Ok(())),
+            (Operation::Set, 4, Err(StorageError::OutOfSpace)),
+            (Operation::Set, 1, Err(StorageError::Rejected)),
+            (Operation::Set, 1, Err(StorageError::Rejected)),
+            (Operation::Set, 3, Err(StorageError::Rejected)),
+            (Operation::Delete, 0, Ok(())),
+        ]
+    );
+}
+
+#[test]
+fn user_data_is_built_and_read_through_its_api() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        System::set_block_number(3);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        let expected = UserData::new(1)
+            .with_content(BlakeTwo256::hash(b"a"), 1)
+            .with_blocks(3, 3);
+        let data = OffchainStorage::get_data(&key);
+        assert_eq!(data, expected);
+        assert_eq!(data.author(), &1);
+        assert_eq!(data.access(), &Access::Read);
+        assert_eq!(data.content_hash(), &BlakeTwo256::hash(b"a"));
+        assert_eq!((data.size(), data.replication(), data.backend()), (1, 0, DEFAULT_BACKEND));
+        assert_eq!((data.created_at(), data.updated_at()), (&3, &3));
+        assert!(data.providers().is_empty() && data.encryption().is_none());
+
+        let info = EncryptionInfo { scheme: b"x".to_vec(), key_id: vec![], nonce: vec![] };
+        let built = UserData::<u64, H256, u64>::new(2)
+            .with_access(Access::Avoid)
+            .with_replication(2)
+            .with_providers(vec![7, 9])
+            .with_encryption(Some(info.clone()))
+            .with_backend(1);
+        assert_eq!(built.providers(), &[7, 9][..]);
+        assert_eq!(built.encryption(), Some(&info));
+        assert_eq!(built.clone().with_access(Access::Avoid), built);
+    });
+}
+
+#[test]
+fn typed_values_are_encoded_and_decoded() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        let read = |data_id: &[u8]| {
+            OffchainStorage::read_typed::<(u32, Vec<u8>)>(&2, data_id).map_err(DispatchError::from)
+        };
+        assert_eq!(read(&key), Err(Error::<Test>::NoneData.into()));
+        assert_ok!(OffchainStorage::write_typed(1, key.clone(), &(42u32, b"a".to_vec())));
+        assert_eq!(stored(&key), Some((42u32, b"a".to_vec()).encode()));
+        assert_eq!(read(&key), Ok((42, b"a".to_vec())));
+        // bytes left over don't decode in full.
+        assert_eq!(
+            OffchainStorage::read_typed::<u32>(&2, &key).map_err(DispatchError::from),
+            Err(Error::<Test>::UndecodableValue.into())
+        );
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert_eq!(read(&key), Err(Error::<Test>::PermissionDenied.into()));
+    });
+}
+
+#[test]
+fn hooks_are_told_about_writes_and_deletions() {
+    new_test_ext().execute_with(|| {
+        let key: Vec<u8> = b"key".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"abc".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"ab".to_vec()));
+        // a write that fails isn't reported.
+        let long = vec![0; 33];
+        assert!(OffchainStorage::write_data(Origin::signed(1), long, b"a".to_vec()).is_err());
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        assert_ok!(OffchainStorage::restore_data(Origin::signed(1), key.clone()));
+        assert_eq!(
+            activity(),
+            vec![
+                (true, 1, key.clone(), 3),
+                (true, 1, key.clone(), 2),
+                (false, 1, key.clone(), 2),
+                (true, 1, key, 2),
+            ]
+        );
+    });
+}
+
+#[test]
+fn values_are_compressed_in_the_backend() {
+    use offchain_storage_runtime_api::compression::snappy;
+    new_test_ext().execute_with(|| {
+        let (key, plain): (Vec<u8>, Vec<u8>) = (b"key".to_vec(), b"plain".to_vec());
+        let value = b"ab".repeat(15);
+        COMPRESSION.with(|c| *c.borrow_mut() = Compression::Snappy);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), value.clone()));
+        let data = OffchainStorage::get_data(&key);
+        assert_eq!(data.compression(), Compression::Snappy);
+        assert_eq!(stored(&key), Some(snappy::compress(&value)));
+        assert!(stored(&key).unwrap().len() < value.len());
+        assert_eq!(OffchainStorage::get_external_storage(&key, &data).ok(), Some(value.clone()));
+
+        // writes may store their value as it is, or ask for what the runtime can't do.
+        let write = |data_id: &[u8], compression| {
+            let (data_id, value) = (data_id.to_vec(), b"xyz".to_vec());
+            OffchainStorage::write_data_compressed(Origin::signed(1), data_id, value, compression)
+        };
+        assert_noop!(write(&plain, Compression::Zstd), Error::<Test>::UnsupportedCompression);
+        assert_ok!(write(&plain, Compression::None));
+        assert_eq!(stored(&plain), Some(b"xyz".to_vec()));
+
+        // entries compressed before stay readable, and appends keep them compressed.
+        COMPRESSION.with(|c| *c.borrow_mut() = Compression::None);
+        assert_ok!(OffchainStorage::append_data(Origin::signed(1), key.clone(), b"ab".to_vec()));
+        let data = OffchainStorage::get_data(&key);
+        assert_eq!(data.compression(), Compression::Snappy);
+        assert_eq!(stored(&key), Some(snappy::compress(&b"ab".repeat(16))));
+        assert_ok!(OffchainStorage::read_data_range(Origin::signed(2), key.clone(), 30, 2));
+
+        let long: Vec<u8> = (0..=255u8).chain(0..=255u8).collect();
+        for value in &[vec![], b"a".to_vec(), vec![7; 100], long] {
+            assert_eq!(snappy::decompress(&snappy::compress(value)).as_ref(), Some(value));
+        }
+        assert_eq!(snappy::decompress(&[5, 0x04]), None);
+    });
+}
+
+#[test]
+fn identical_values_share_one_object() {
+    new_test_ext().execute_with(|| {
+        let (a, b): (Vec<u8>, Vec<u8>) = (b"a".to_vec(), b"b".to_vec());
+        let object = |value: &[u8]| dedup::object_key(&BlakeTwo256::hash(value), Compression::None);
+        DEDUP.with(|d| *d.borrow_mut() = true);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a.clone(), b"same".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(2), b.clone(), b"same".to_vec()));
+        assert_eq!(stored(&object(b"same")), Some(b"same".to_vec()));
+        assert_eq!((stored(&a), stored(&b)), (None, None));
+        assert_eq!(OffchainStorage::object_refs(DEFAULT_BACKEND, object(b"same")), 2);
+        let data = OffchainStorage::get_data(&b);
+        assert_eq!(OffchainStorage::get_external_storage(&b, &data).ok(), Some(b"same".to_vec()));
+
+        // the object stays until the last entry sharing it lets go.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a.clone(), b"new".to_vec()));
+        assert_eq!(stored(&object(b"same")), Some(b"same".to_vec()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(2), b.clone(), b"new".to_vec()));
+        assert_eq!(stored(&object(b"same")), None);
+        assert_eq!(OffchainStorage::object_refs(DEFAULT_BACKEND, object(b"new")), 2);
+
+        // values written once it is off are kept under their data_id again.
+        DEDUP.with(|d| *d.borrow_mut() = false);
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), a.clone(), b"own".to_vec()));
+        assert_eq!(stored(&a), Some(b"own".to_vec()));
+        assert_eq!(OffchainStorage::shared_value(&a), None);
+        assert_ok!(OffchainStorage::append_data(Origin::signed(2), b.clone(), b"!".to_vec()));
+        assert_eq!((stored(&b), stored(&object(b"new"))), (Some(b"new!".to_vec()), None));
+    });
+}
+
+#[test]
+fn values_are_pinned_and_unpinned() {
+    new_test_ext().execute_with(|| {
+        let key = b"pinned".to_vec();
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"v".to_vec()));
+        assert_noop!(
+            OffchainStorage::unpin_data(Origin::signed(2), key.clone()),
+            Error::<Test>::NotPinned
+        );
+        assert_ok!(OffchainStorage::pin_data(Origin::signed(2), key.clone()));
+        assert_eq!(pinned(), vec![key.clone()]);
+        assert_eq!(Balances::reserved_balance(2), 3);
+        assert_noop!(
+            OffchainStorage::pin_data(Origin::signed(2), key.clone()),
+            Error::<Test>::AlreadyPinned
+        );
+
+        // the value stays pinned until the last pin is dropped.
+        assert_ok!(OffchainStorage::pin_data(Origin::signed(3), key.clone()));
+        assert_ok!(OffchainStorage::unpin_data(Origin::signed(2), key.clone()));
+        assert_eq!(Balances::reserved_balance(2), 0);
+        assert_eq!(pinned(), vec![key.clone()]);
+        assert_ok!(OffchainStorage::unpin_data(Origin::signed(3), key.clone()));
+        assert!(pinned().is_empty());
+        assert_eq!(OffchainStorage::pins_of(&key), vec![]);
+
+        // deleting the entry releases the deposits of its pins.
+        assert_ok!(OffchainStorage::pin_data(Origin::signed(7), key.clone()));
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        assert_eq!(Balances::reserved_balance(7), 0);
+        assert_noop!(
+            OffchainStorage::pin_data(Origin::signed(7), key.clone()),
+            Error::<Test>::NoneData
+        );
+    });
+}
+
+#[test]
+fn audit_trail_records_access_once_on() {
+    new_test_ext().execute_with(|| {
+        let key = b"audited".to_vec();
+        let ops = |key: &[u8]| -> Vec<(u64, audit::AuditOp)> {
+            OffchainStorage::audit_trail(key).into_iter().map(|r| (r.who, r.op)).collect()
+        };
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"v".to_vec()));
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+        assert!(ops(&key).is_empty());
+        assert_noop!(
+            OffchainStorage::set_audit(Origin::signed(2), key.clone(), true),
+            Error::<Test>::PermissionDenied
+        );
+
+        System::set_block_number(4);
+        assert_ok!(OffchainStorage::set_audit(Origin::signed(1), key.clone(), true));
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"w".to_vec()));
+        assert_ok!(OffchainStorage::grant_session_access(
+            Origin::signed(1),
+            key.clone(),
+            3,
+            Access::Read
+        ));
+        assert_eq!(
+            ops(&key),
+            vec![
+                (2, audit::AuditOp::Read),
+                (1, audit::AuditOp::Write),
+                (3, audit::AuditOp::Granted(Access::Read)),
+            ]
+        );
+        assert_eq!(OffchainStorage::audit_trail(&key)[0].at, 4);
+
+        // only the last MaxAuditRecords records are kept.
+        assert_ok!(OffchainStorage::pin_data(Origin::signed(2), key.clone()));
+        assert_eq!(ops(&key)[0], (1, audit::AuditOp::Write));
+        assert_eq!(ops(&key)[2], (2, audit::AuditOp::Pinned));
+
+        // turning the trail off keeps the records, renaming moves them.
+        assert_ok!(OffchainStorage::set_audit(Origin::signed(1), key.clone(), false));
+        assert_ok!(OffchainStorage::read_data(Origin::signed(3), key.clone()));
+        assert_eq!(ops(&key).len(), 3);
+        let renamed = b"audited2".to_vec();
+        assert_ok!(OffchainStorage::rename_data(Origin::signed(1), key.clone(), renamed.clone()));
+        assert_eq!((ops(&key).len(), ops(&renamed).len()), (0, 3));
+
+        // they stay through the trash and go with the entry once it is purged.
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), renamed.clone()));
+        assert_eq!(ops(&renamed).len(), 3);
+        OffchainStorage::on_initialize(6);
+        assert!(ops(&renamed).is_empty());
+    });
+}
+
+#[test]
+fn audit_trail_outlives_deletion_and_restoration() {
+    new_test_ext().execute_with(|| {
+        use audit::AuditOp::*;
+        let key = b"kept".to_vec();
+        let ops = |key: &[u8]| -> Vec<(u64, audit::AuditOp)> {
+            OffchainStorage::audit_trail(key).into_iter().map(|r| (r.who, r.op)).collect()
+        };
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"v".to_vec()));
+        assert_ok!(OffchainStorage::set_audit(Origin::signed(1), key.clone(), true));
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 2, 1));
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 2, 0));
+        assert_eq!(ops(&key), vec![(2, Granted(Access::Write)), (2, Revoked)]);
+
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        assert_ok!(OffchainStorage::restore_data(Origin::signed(1), key.clone()));
+        assert_eq!(ops(&key), vec![(2, Revoked), (1, Deleted), (1, Restored)]);
+        assert!(OffchainStorage::is_audited(&key));
+
+        let renamed = b"kept2".to_vec();
+        assert_ok!(OffchainStorage::rename_data(Origin::signed(1), key.clone(), renamed.clone()));
+        assert_eq!(ops(&renamed)[2], (1, Renamed(key)));
+    });
+}
+
+#[test]
+fn metered_backends_pass_calls_on() {
+    new_test_ext().execute_with(|| {
+        type Metered = metrics::Metered<DB, DbName>;
+        let key = b"metered".to_vec();
+        assert_ok!(Metered::set(key.clone(), b"value".to_vec()));
+        assert_eq!(stored(&key), Some(b"value".to_vec()));
+        assert_eq!(Metered::get_range(key.clone(), 1, 3), Ok(b"alu".to_vec()));
+        assert_ok!(Metered::pin(key.clone()));
+        assert_eq!(pinned(), vec![key.clone()]);
+
+        set_outage(Some(StorageError::Timeout));
+        assert_eq!(Metered::get(key.clone()), Err(StorageError::Timeout));
+        set_outage(None);
+        assert_ok!(Metered::delete(key.clone()));
+        assert_eq!(stored(&key), None);
+    });
+}
+
+#[test]
+fn reads_are_delivered_off_chain() {
+    new_test_ext().execute_with(|| {
+        let key = b"delivered".to_vec();
+        let emitted = |event: RawEvent<u64, u64, H256>| {
+            let event = TestEvent::offchain_storage(event);
+            System::events().into_iter().any(|record| record.event == event)
+        };
+        let fetch = |who: u64| {
+            OffchainStorage::locate(&who, &key).and_then(|location| DB::get(location.key).ok())
+        };
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert_eq!(OffchainStorage::locate(&2, &key), None);
+
+        // the read only carries the hash, the value is fetched where locate says.
+        assert_ok!(OffchainStorage::read_data(Origin::signed(2), key.clone()));
+        assert!(emitted(RawEvent::GetData(BlakeTwo256::hash(b"a"))));
+        assert_eq!(OffchainStorage::read_receipt(&key, 2), Some(BlakeTwo256::hash(b"a")));
+        assert_eq!(fetch(2), Some(b"a".to_vec()));
+
+        // a receipt is for the value read, past revisions are read from their own keys.
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"b".to_vec()));
+        assert_eq!(fetch(2), Some(b"a".to_vec()));
+        assert_eq!(
+            OffchainStorage::locate(&2, &key).map(|location| location.key),
+            Some(history::version_key(&key, 1))
+        );
+        assert_ok!(OffchainStorage::read_version(Origin::signed(3), key.clone(), 2));
+        assert_eq!(fetch(3), Some(b"b".to_vec()));
+
+        // losing access, or the entry, voids the receipts.
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert_eq!(OffchainStorage::locate(&3, &key), None);
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Read));
+        assert_ok!(OffchainStorage::delete_data(Origin::signed(1), key.clone()));
+        assert_eq!(OffchainStorage::read_receipt(&key, 3), None);
+    });
+}
+
+#[test]
+fn access_levels_can_be_queried() {
+    new_test_ext().execute_with(|| {
+        let key = b"queried".to_vec();
+        assert!(!OffchainStorage::can_access(&1, &key, Access::Avoid));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(1), key.clone(), b"a".to_vec()));
+        assert!(OffchainStorage::can_access(&1, &key, Access::Avoid));
+        assert!(OffchainStorage::can_access(&1, &key, Access::Write));
+        assert!(!OffchainStorage::can_access(&2, &key, Access::Avoid));
+        assert!(OffchainStorage::can_access(&2, &key, Access::Read));
+        assert!(!OffchainStorage::can_access(&2, &key, Access::Write));
+
+        // a delegated write counts until it is used.
+        assert_ok!(OffchainStorage::delegate_write(Origin::signed(1), key.clone(), 2, 1));
+        assert!(OffchainStorage::can_access(&2, &key, Access::Write));
+        assert_ok!(OffchainStorage::write_data(Origin::signed(2), key.clone(), b"b".to_vec()));
+        assert!(!OffchainStorage::can_access(&2, &key, Access::Write));
+
+        assert_ok!(OffchainStorage::force_set_access(Origin::ROOT, key.clone(), Access::Avoid));
+        assert!(!OffchainStorage::can_access(&2, &key, Access::Read));
+        assert!(OffchainStorage::can_access(&1, &key, Access::Read));
+    });
+}
@@ -0,0 +1,146 @@
+//! Atomic transactions.
+//!
+//! `transact` applies several writes, deletes and renames as one. Every [`Op`] is checked
+//! before any is applied, against the quotas and the balances of the authors all ops
+//! together use, so applying them doesn't fail and either all take effect or none does.
+//! A data_id may only be touched by one op of a transaction.
+//!
+//! Nothing waits on a backend while the ops are applied, nor changes before every op is
+//! checked: values written are uploaded by the offchain worker as with
+//! `T::DeferredUploads`, renamed values are copied by it, see the rename module, and deleted
+//! values are left to the garbage collector. Rent due for the entries written is charged as
+//! they are, so the check counts it against the balances of their authors.
+
+use super::{audit::AuditOp, BalanceOf, Error, Module, RawEvent, Trait, Upload, WriteKind};
+use codec::{Decode, Encode};
+use frame_support::{
+    dispatch::DispatchResult,
+    ensure,
+    traits::{Get, ReservableCurrency},
+};
+use sp_runtime::{
+    traits::{Saturating, Zero},
+    RuntimeDebug,
+};
+use sp_std::{collections::btree_map::BTreeMap, vec::Vec};
+
+/// An operation of a transaction.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub enum Op {
+    /// Write the value `.1` to `.0`, as `write_data`.
+    Write(Vec<u8>, Vec<u8>),
+    /// Delete `.0`, as `delete_data`.
+    Delete(Vec<u8>),
+    /// Rename `.0` to `.1`, as `rename_data`.
+    Rename(Vec<u8>, Vec<u8>),
+}
+
+impl Op {
+    /// The data_ids the op touches.
+    pub fn data_ids(&self) -> Vec<&[u8]> {
+        match self {
+            Op::Write(data_id, _) | Op::Delete(data_id) => sp_std::vec![&data_id[..]],
+            Op::Rename(old, new) => sp_std::vec![&old[..], &new[..]],
+        }
+    }
+}
+
+// what the writes of a transaction add for one author.
+#[derive(Default)]
+struct Usage<Balance> {
+    deposit: Balance,
+    rent: Balance,
+    added: u64,
+    freed: u64,
+}
+
+impl<T: Trait> Module<T> {
+    // apply ops for user, all of them or none.
+    pub(crate) fn apply_ops(user: T::AccountId, ops: Vec<Op>) -> DispatchResult {
+        Self::validate_ops(&user, &ops)?;
+        for op in ops {
+            match op {
+                Op::Write(data_id, value) => {
+                    let upload = Upload::Deferred;
+                    let user = user.clone();
+                    Self::write_entry(user, data_id, value, Vec::new(), upload, None, None, None)?;
+                }
+                Op::Delete(data_id) => {
+                    Self::audit(&data_id, &user, AuditOp::Deleted);
+                    let period = T::TrashPeriod::get();
+                    if period.is_zero() {
+                        Self::evict_entry(&data_id);
+                    } else {
+                        let now = <frame_system::Module<T>>::block_number();
+                        Self::trash_entry(&data_id, now.saturating_add(period));
+                    }
+                    Self::deposit_event(RawEvent::DataDeleted(user.clone(), data_id));
+                }
+                Op::Rename(old, new) => {
+                    Self::defer_move(&old, &new);
+                    Self::audit(&new, &user, AuditOp::Renamed(old.clone()));
+                    Self::deposit_event(RawEvent::DataRenamed(old, new));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // check that every op of user would succeed, with all of them together.
+    fn validate_ops(user: &T::AccountId, ops: &[Op]) -> Result<(), Error<T>> {
+        let mut ids: Vec<&[u8]> = ops.iter().flat_map(|op| op.data_ids()).collect();
+        let touched = ids.len();
+        ids.sort();
+        ids.dedup();
+        ensure!(ids.len() == touched, Error::<T>::ConflictingOps);
+
+        let mut usage: BTreeMap<T::AccountId, Usage<BalanceOf<T>>> = BTreeMap::new();
+        let (mut writes, mut created) = (0u32, 0u32);
+        for op in ops {
+            match op {
+                Op::Write(data_id, value) => {
+                    let len = value.len() as u32;
+                    let estimate = Self::validate_write(user, data_id, len)?;
+                    let author = if estimate.kind == WriteKind::Create {
+                        created += 1;
+                        user.clone()
+                    } else {
+                        Self::get_data(data_id).author
+                    };
+                    let used = usage.entry(author).or_insert_with(Default::default);
+                    used.deposit = used.deposit.saturating_add(estimate.deposit);
+                    used.rent = used.rent.saturating_add(Self::rent_due(data_id));
+                    used.added = used.added.saturating_add(u64::from(len));
+                    used.freed = used.freed.saturating_add(u64::from(Self::size_of(data_id)));
+                    writes += 1;
+                }
+                Op::Delete(data_id) => Self::ensure_may_delete(user, data_id)?,
+                Op::Rename(old, new) => Self::ensure_may_rename(user, old, new)?,
+            }
+        }
+
+        let now = <frame_system::Module<T>>::block_number();
+        ensure!(
+            Self::writes_in_block(now, user).saturating_add(writes) <= T::MaxWritesPerBlock::get(),
+            Error::<T>::RateLimited
+        );
+        ensure!(
+            Self::owned_count(user).saturating_add(created) <= T::MaxEntriesPerAccount::get(),
+            Error::<T>::TooManyEntries
+        );
+        for (author, used) in usage {
+            // an entry whose author can't pay its rent expires as it is written.
+            ensure!(Self::can_pay_rent(&author, used.rent), Error::<T>::Expired);
+            ensure!(
+                T::Currency::can_reserve(&author, used.deposit.saturating_add(used.rent)),
+                Error::<T>::InsufficientBalance
+            );
+            let bytes = Self::used_bytes(&author).saturating_sub(used.freed);
+            ensure!(
+                bytes.saturating_add(used.added) <= T::MaxBytesPerAccount::get(),
+                Error::<T>::ByteQuotaExceeded
+            );
+        }
+        Ok(())
+    }
+}
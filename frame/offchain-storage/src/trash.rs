@@ -0,0 +1,99 @@
+//! Soft deletion.
+//!
+//! `delete_data` doesn't remove an entry at once but moves it to the trash for
+//! `T::TrashPeriod` blocks. The entry reads as if it didn't exist and its data_id can't be
+//! written, but its value stays in the backends and the author keeps the deposit reserved,
+//! so `restore_data` brings it back as it was. Grants, delegations and other settings kept
+//! beside the entry are dropped on deletion and not restored; its audit trail is kept. Once
+//! the period is over, `on_initialize` releases the deposit, drops the audit trail and
+//! hands the value to the garbage collector.
+
+use super::{
+    expiry::Expiry, hooks::OnDataWritten, Data, Deposits, Module, PendingCopies, RawEvent, Rent,
+    RentInfo, Trait, Trash, TrashQueue, UserData,
+};
+use codec::{Decode, Encode};
+use frame_support::{traits::ReservableCurrency, StorageDoubleMap, StorageMap};
+use sp_runtime::{traits::Zero, RuntimeDebug};
+
+/// A deleted entry that may still be restored.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct TrashedEntry<AccountId, Hash, Balance, BlockNumber> {
+    /// The entry as it was when deleted.
+    pub data: UserData<AccountId, Hash, BlockNumber>,
+    /// The deposit still reserved from the author.
+    pub deposit: Balance,
+    /// Its rent account, rent keeps accruing in the trash.
+    pub rent: Option<RentInfo<BlockNumber>>,
+    /// The block the entry is deleted for good in.
+    pub purge_at: BlockNumber,
+}
+
+impl<T: Trait> Module<T> {
+    /// The entry of `data_id`, whether live or in the trash.
+    pub fn kept_data(data_id: &[u8]) -> Option<UserData<T::AccountId, T::Hash, T::BlockNumber>> {
+        if <Data<T>>::exists(data_id) {
+            Some(Self::get_data(data_id))
+        } else {
+            Self::trash_of(data_id).map(|entry| entry.data)
+        }
+    }
+
+    // move data_id to the trash until the block purge_at.
+    pub(crate) fn trash_entry(data_id: &[u8], purge_at: T::BlockNumber) {
+        let data = Self::get_data(data_id);
+        // taken first so forget_entry leaves the deposit reserved.
+        let deposit = <Deposits<T>>::take(data_id);
+        let rent = <Rent<T>>::take(data_id);
+        // the value may still be uploaded or copied and the entry restored.
+        let upload = Self::drop_upload(data_id);
+        let copy = <PendingCopies<T>>::take(data_id);
+        Self::forget_entry(data_id);
+        if let Some(upload) = upload {
+            Self::queue_upload(upload);
+        }
+        if let Some(copy) = copy {
+            <PendingCopies<T>>::insert(data_id, copy);
+        }
+        // the entry still counts against the quotas of its author, rent taken above keeps
+        // its bytes from being released.
+        Self::count_entry(&data.author);
+        <Trash<T>>::insert(data_id, TrashedEntry { data, deposit, rent, purge_at });
+        <TrashQueue<T>>::insert(purge_at, data_id, data_id);
+    }
+
+    // put data_id back where trash_entry took it from.
+    pub(crate) fn restore_entry(data_id: &[u8]) {
+        if let Some(entry) = <Trash<T>>::take(data_id) {
+            <TrashQueue<T>>::remove(entry.purge_at, data_id);
+            if !entry.deposit.is_zero() {
+                <Deposits<T>>::insert(data_id, entry.deposit);
+            }
+            if let Some(rent) = entry.rent {
+                <Rent<T>>::insert(data_id, rent);
+            }
+            Self::list_owned(&entry.data.author, data_id);
+            <Data<T>>::insert(data_id, &entry.data);
+            T::OnDataWritten::on_data_written(&entry.data.author, data_id, entry.data.size);
+        }
+    }
+
+    // delete the entries whose time in the trash ended by block n for good, as many as the
+    // block takes.
+    pub(crate) fn purge_trash(n: T::BlockNumber) {
+        for (_, data_id) in Self::take_expired::<TrashQueue<T>, _>(Expiry::Trash, n) {
+            if let Some(entry) = <Trash<T>>::take(&data_id) {
+                let _ = T::Currency::unreserve(&entry.data.author, entry.deposit);
+                Self::uncount_entry(&entry.data.author);
+                Self::abandon_upload(&data_id);
+                if let Some(rent) = &entry.rent {
+                    Self::release_bytes(&entry.data.author, rent.size);
+                }
+                Self::queue_deletion(&data_id, &entry.data);
+                Self::finish_copy(&data_id, &entry.data);
+                Self::drop_audit(&data_id);
+                Self::deposit_event(RawEvent::DataPurged(data_id));
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Time to live of entries.
+//!
+//! Writers can give an entry a block it lives until with `write_data_until`, e.g. for
+//! caches or data that must not be kept longer than allowed. From that block on the
+//! entry reads as if it didn't exist, and `on_initialize` of that block, or of a later one
+//! if many expire at once, see the expiry module, removes everything kept about it and
+//! hands its value to the garbage collector.
+
+use super::{expiry::Expiry, Module, RawEvent, Trait, TtlQueue, Ttls};
+use frame_support::{StorageDoubleMap, StorageMap};
+
+impl<T: Trait> Module<T> {
+    /// Whether the time to live of `data_id` is over.
+    pub fn ttl_passed(data_id: &[u8]) -> bool {
+        Self::ttl_of(data_id).map_or(false, |at| at <= <frame_system::Module<T>>::block_number())
+    }
+
+    // let data_id live until the block expires_at, or forever.
+    pub(crate) fn set_ttl(data_id: &[u8], expires_at: Option<T::BlockNumber>) {
+        if let Some(old) = <Ttls<T>>::take(data_id) {
+            <TtlQueue<T>>::remove(old, data_id);
+        }
+        if let Some(at) = expires_at {
+            <Ttls<T>>::insert(data_id, at);
+            <TtlQueue<T>>::insert(at, data_id, data_id);
+        }
+    }
+
+    // remove the entries whose time to live ended by block n, as many as the block takes.
+    pub(crate) fn remove_ttl_passed(n: T::BlockNumber) {
+        for (_, data_id) in Self::take_expired::<TtlQueue<T>, _>(Expiry::Ttl, n) {
+            Self::evict_entry(&data_id);
+            Self::deposit_event(RawEvent::TtlPassed(data_id));
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Typed values.
+//!
+//! Other modules keep structured values here without handling SCALE themselves.
+//! `write_typed` encodes a value and writes it for an account as `write_data` would.
+//! `read_typed` reads a value for an account as `read_for` would and decodes it; a value
+//! that doesn't decode in full as the type asked for fails with `UndecodableValue`. The
+//! value read isn't emitted in an event, the caller gets it.
+
+use super::{audit::AuditOp, Data, Error, Module, RawEvent, Trait};
+use codec::{Decode, Encode};
+use frame_support::{dispatch::DispatchResult, ensure, StorageMap};
+use sp_std::vec::Vec;
+
+impl<T: Trait> Module<T> {
+    /// Write the encoding of `value` to `data_id` for `who`, as `write_data` would.
+    pub fn write_typed<V: Encode>(
+        who: T::AccountId,
+        data_id: Vec<u8>,
+        value: &V,
+    ) -> DispatchResult {
+        Self::do_write(who, data_id, value.encode())
+    }
+
+    /// The value of `data_id` as a `V`, read for `who` as `read_for` would.
+    pub fn read_typed<V: Decode>(who: &T::AccountId, data_id: &[u8]) -> Result<V, Error<T>> {
+        ensure!(
+            <Data<T>>::exists(data_id) && !Self::ttl_passed(data_id),
+            Error::<T>::NoneData
+        );
+        let data = Self::get_data(data_id);
+        // values behind a read quorum are only read with the readers' consent.
+        ensure!(
+            Self::may_read(who, data_id, data.clone()) && Self::read_quorum(data_id).is_none(),
+            Error::<T>::PermissionDenied
+        );
+        let raw = Self::read_value(data_id, who, &data)?;
+        let input = &mut &raw[..];
+        let value = V::decode(input)
+            .ok()
+            .filter(|_| input.is_empty())
+            .ok_or(Error::<T>::UndecodableValue)?;
+        Self::audit(data_id, who, AuditOp::Read);
+        Self::deposit_event(RawEvent::DataRead(who.clone(), data_id.to_vec()));
+        Ok(value)
+    }
+}
@@ -0,0 +1,194 @@
+//! Deferred uploads.
+//!
+//! Writes normally store the value in its backend while the block executes, which a slow
+//! backend stalls. With `T::DeferredUploads` on, a write only records the value as a
+//! [`PendingUpload`] and the entry reads from it. The offchain worker uploads every pending
+//! value, its version for the history and its replicas, then submits `confirm_upload`
+//! through `T::Confirmations`, signed by an account of `T::Operators`. The confirmation
+//! drops the pending value; until it is in, the worker uploads it again every block. Before
+//! its first attempt at a write the worker submits `start_upload`, so the status of the
+//! write tells it is being carried out, see the status module.
+//!
+//! The worker keeps its failed attempts at each upload in the persistent local storage of
+//! the node, under [`attempts_key`], and waits twice as many blocks after every failure
+//! before trying again. After `T::MaxUploadAttempts` failures it submits
+//! `report_upload_failed` instead, which drops the pending value and emits `WriteFailed`.
+//! The entry keeps the hash of the lost value, so reads fail until it is written again.
+//!
+//! Deferred values are stored whole, erasure coding only applies to writes made in place.
+//! A value overwritten before its upload is not kept as a past version.
+
+use super::{
+    compression::Compressor, history, replication::ReplicaStorage, Call, Error, ExternalStorage,
+    Module, NextUploadId, PendingUploadCount, PendingUploads, Pins, RawEvent, RequestStatus,
+    Shards, StorageError, Trait,
+};
+use codec::{Decode, Encode};
+use frame_support::{storage::StoragePrefixedMap, traits::Get, StorageMap, StorageValue};
+use sp_core::offchain::StorageKind;
+use sp_runtime::{
+    traits::{Hash, Saturating},
+    RuntimeDebug,
+};
+use sp_std::vec::Vec;
+
+/// The longest the worker waits between two attempts at an upload, as a power of two.
+const MAX_BACKOFF_EXPONENT: u32 = 8;
+
+/// A value written on chain and not yet confirmed stored in the backend.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct PendingUpload<Hash, BlockNumber> {
+    /// The id of the write, whose status is kept under it.
+    pub id: u64,
+    pub data_id: Vec<u8>,
+    pub value: Vec<u8>,
+    pub content_hash: Hash,
+    /// The revision the value is stored as in the history.
+    pub revision: u32,
+    /// The block the value was written in.
+    pub at: BlockNumber,
+}
+
+/// The failed attempts of the worker at an upload, kept in the local storage of the node.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct UploadAttempts<Hash, BlockNumber> {
+    /// The hash of the value attempted, attempts at an earlier value don't count.
+    pub content_hash: Hash,
+    pub failures: u32,
+    /// The first block the upload is tried again in.
+    pub retry_at: BlockNumber,
+}
+
+/// The key of the local storage the attempts at uploading the value of `data_id` are kept
+/// under.
+pub fn attempts_key(data_id: &[u8]) -> Vec<u8> {
+    (&b"offchain-storage/upload-attempts"[..], data_id).encode()
+}
+
+/// Signs and submits the confirmations of the offchain worker, e.g. with
+/// `frame_system::offchain::SubmitSignedTransaction` and an operator key of the node.
+pub trait SubmitConfirmation<T: Trait> {
+    /// Submit `call` signed by an account of `T::Operators`.
+    fn submit(call: Call<T>) -> Result<(), ()>;
+}
+
+impl<T: Trait> SubmitConfirmation<T> for () {
+    fn submit(_: Call<T>) -> Result<(), ()> {
+        Err(())
+    }
+}
+
+impl<T: Trait> Module<T> {
+    // record value as the next revision of data_id, to be uploaded by the offchain worker.
+    pub(crate) fn defer_upload(data_id: &[u8], value: Vec<u8>) {
+        if let Some(info) = <Shards<T>>::take(data_id) {
+            Self::queue_shards(data_id, Self::get_data(data_id).backend, &info);
+        }
+        // the value written before is never uploaded.
+        if let Some(upload) = Self::pending_upload(data_id) {
+            let superseded = Error::<T>::UploadSuperseded.code();
+            Self::set_upload_status(upload.id, RequestStatus::Failed(superseded));
+        }
+        let id = NextUploadId::mutate(|n| {
+            *n += 1;
+            *n
+        });
+        Self::set_upload_status(id, RequestStatus::Queued);
+        Self::deposit_event(RawEvent::UploadQueued(data_id.to_vec(), id));
+        let upload = PendingUpload {
+            id,
+            data_id: data_id.to_vec(),
+            content_hash: T::Hashing::hash(&value),
+            value,
+            revision: Self::revision_count(data_id) + 1,
+            at: <frame_system::Module<T>>::block_number(),
+        };
+        Self::queue_upload(upload);
+    }
+
+    // record upload as the pending value of its data_id, replacing the one before.
+    pub(crate) fn queue_upload(upload: PendingUpload<T::Hash, T::BlockNumber>) {
+        if !<PendingUploads<T>>::exists(&upload.data_id) {
+            PendingUploadCount::mutate(|count| *count = count.saturating_add(1));
+        }
+        <PendingUploads<T>>::insert(upload.data_id.clone(), upload);
+    }
+
+    // drop the pending value of data_id, returning it.
+    pub(crate) fn drop_upload(data_id: &[u8]) -> Option<PendingUpload<T::Hash, T::BlockNumber>> {
+        let upload = <PendingUploads<T>>::take(data_id);
+        if upload.is_some() {
+            PendingUploadCount::mutate(|count| *count = count.saturating_sub(1));
+        }
+        upload
+    }
+
+    // drop the pending value of data_id, if there is one, and end its write with status.
+    pub(crate) fn finish_upload(data_id: &[u8], status: RequestStatus<T::Hash>) {
+        if let Some(upload) = Self::drop_upload(data_id) {
+            Self::set_upload_status(upload.id, status);
+        }
+    }
+
+    // drop the pending value of data_id, if there is one, whose write failed as it was
+    // overwritten or deleted before it was uploaded.
+    pub(crate) fn abandon_upload(data_id: &[u8]) {
+        let superseded = Error::<T>::UploadSuperseded.code();
+        Self::finish_upload(data_id, RequestStatus::Failed(superseded));
+    }
+
+    // upload the pending values due at block n and submit the confirmations, or the
+    // failures of those out of attempts. Runs in the offchain worker.
+    pub(crate) fn upload_pending(n: T::BlockNumber) {
+        for upload in <PendingUploads<T>>::iter() {
+            let key = attempts_key(&upload.data_id);
+            let attempts = sp_io::offchain::local_storage_get(StorageKind::PERSISTENT, &key)
+                .and_then(|raw| UploadAttempts::decode(&mut &raw[..]).ok())
+                .filter(|attempts| attempts.content_hash == upload.content_hash);
+            if attempts.as_ref().map_or(false, |attempts| n < attempts.retry_at) {
+                continue;
+            }
+            // the first attempt since the write, or since a submission that didn't get in.
+            let queued = Self::upload_status(upload.id) == Some(RequestStatus::Queued);
+            if attempts.is_none() && queued {
+                let start = Call::start_upload(upload.data_id.clone(), upload.content_hash);
+                let _ = T::Confirmations::submit(start);
+            }
+            let call = match Self::upload(&upload) {
+                Ok(()) => Call::confirm_upload(upload.data_id, upload.content_hash),
+                Err(reason) => {
+                    let failures = attempts.map_or(0, |attempts| attempts.failures) + 1;
+                    if failures < T::MaxUploadAttempts::get() {
+                        let exponent = (failures - 1).min(MAX_BACKOFF_EXPONENT);
+                        let retry_at = n.saturating_add(T::BlockNumber::from(1u32 << exponent));
+                        let content_hash = upload.content_hash;
+                        let attempts = UploadAttempts { content_hash, failures, retry_at };
+                        let raw = attempts.encode();
+                        sp_io::offchain::local_storage_set(StorageKind::PERSISTENT, &key, &raw);
+                        continue;
+                    }
+                    Call::report_upload_failed(upload.data_id, upload.content_hash, reason)
+                }
+            };
+            // nothing decodes from an empty value, the next attempt starts over.
+            sp_io::offchain::local_storage_set(StorageKind::PERSISTENT, &key, &[]);
+            // an upload without a confirmation is made again in the next block.
+            let _ = T::Confirmations::submit(call);
+        }
+    }
+
+    fn upload(upload: &PendingUpload<T::Hash, T::BlockNumber>) -> Result<(), StorageError> {
+        let key = history::version_key(&upload.data_id, upload.revision);
+        T::Storage::set(key, upload.value.clone())?;
+        let data = Self::get_data(&upload.data_id);
+        let stored = T::Compressor::compress(data.compression, upload.value.clone());
+        Self::routed_set(data.backend, upload.data_id.clone(), stored)?;
+        if <Pins<T>>::exists(&upload.data_id) {
+            Self::pin_value(&upload.data_id, data.backend)?;
+        }
+        for p in data.providers {
+            T::Replicas::set(&p, upload.data_id.clone(), upload.value.clone());
+        }
+        Ok(())
+    }
+}
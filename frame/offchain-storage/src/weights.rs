@@ -0,0 +1,380 @@
+//! Weights of the calls.
+//!
+//! The cost of reading, writing and deleting an entry grows with the length of its
+//! data_id and, for writes, of the value, which is hashed, chunked for the merkle root
+//! and copied to every backend. `T::WeightInfo` gives these costs; the calls carrying a
+//! data_id or a value are weighed with it through [`ByKeyLen`] and [`ByValueLen`], as is
+//! `register_provider` with the length of the endpoint it stores. Calls storing a list are
+//! weighed with its length too, e.g. `set_reviewers` through [`ByReviewers`].
+//!
+//! Calls that read a value from the backends and write it again, e.g. `copy_data`,
+//! `rollback` or `append_data`, don't carry it whole, and its length isn't known before
+//! they run. They are weighed through [`UpToValueLen`] as if it were as long as
+//! `T::MaxValueLength` allows. So is `read_data_range`, which may read the value to check
+//! it. Storage proofs and the proofs of mirrored entries are hashed node by node and weighed
+//! with their length through [`ByProofLen`].
+//!
+//! `benches/benchmarking.rs` runs the calls over a range of key and value lengths, so a
+//! runtime can fit its own `WeightInfo` to its hardware and backend. The figures of `()`
+//! are only a floor above the default weight of a call.
+
+use super::{bundle::WriteBundle, challenge::StorageProof, transaction::Op};
+use frame_support::weights::{ClassifyDispatch, DispatchClass, PaysFee, WeighData, Weight};
+use sp_std::{mem, vec::Vec};
+
+/// The weights of the calls whose cost depends on the lengths of their arguments.
+pub trait WeightInfo {
+    /// `read_data` of a data_id of `key_len` bytes.
+    fn read_data(key_len: u32) -> Weight;
+    /// A write of a value of `len` bytes to a data_id of `key_len` bytes.
+    fn write_data(key_len: u32, len: u32) -> Weight;
+    /// `delete_data` of a data_id of `key_len` bytes.
+    fn delete_data(key_len: u32) -> Weight;
+    /// `copy_data` of a value of `len` bytes to a data_id of `key_len` bytes.
+    fn copy_data(key_len: u32, len: u32) -> Weight;
+    /// `migrate_data` of a replica of `len` bytes of a data_id of `key_len` bytes.
+    fn migrate_data(key_len: u32, len: u32) -> Weight;
+    /// `promote_staged` of a staged value of `len` bytes of a data_id of `key_len` bytes.
+    fn promote_staged(key_len: u32, len: u32) -> Weight;
+    /// `rollback` to a revision of `len` bytes of a data_id of `key_len` bytes.
+    fn rollback(key_len: u32, len: u32) -> Weight;
+    /// `wrap_keys` of `keys` wrapped keys of `len` bytes in all for a data_id of `key_len`
+    /// bytes.
+    fn wrap_keys(key_len: u32, keys: u32, len: u32) -> Weight;
+    /// `submit_bundles` of `bundles` bundles of `writes` writes in all, whose data_ids and
+    /// values take `len` bytes.
+    fn submit_bundles(bundles: u32, writes: u32, len: u32) -> Weight;
+    /// `transact` of `ops` ops whose data_ids and values take `len` bytes.
+    fn transact(ops: u32, len: u32) -> Weight;
+    /// `upload_chunk` of a chunk of `len` bytes to a data_id of `key_len` bytes.
+    fn upload_chunk(key_len: u32, len: u32) -> Weight;
+    /// `commit_upload` of a value of `len` bytes to a data_id of `key_len` bytes.
+    fn commit_upload(key_len: u32, len: u32) -> Weight;
+    /// `append_data` to a data_id of `key_len` bytes, reading its value and writing it again
+    /// `len` bytes long with the bytes appended.
+    fn append_data(key_len: u32, len: u32) -> Weight;
+    /// `register_provider` with an endpoint of `len` bytes.
+    fn register_provider(len: u32) -> Weight;
+    /// `set_reviewers` of `reviewers` reviewers for a data_id of `key_len` bytes.
+    fn set_reviewers(key_len: u32, reviewers: u32) -> Weight;
+    /// `accept_replica` of a value of `len` bytes of a data_id of `key_len` bytes, read from
+    /// the backends and written to the replica.
+    fn accept_replica(key_len: u32, len: u32) -> Weight;
+    /// `read_data_range` of a data_id of `key_len` bytes, whose value of `len` bytes may be
+    /// read to check it.
+    fn read_data_range(key_len: u32, len: u32) -> Weight;
+    /// `submit_storage_proof` for a data_id of `key_len` bytes of a proof of `len` bytes.
+    fn submit_storage_proof(key_len: u32, len: u32) -> Weight;
+    /// `mirror_remote_data` of a data_id of `key_len` bytes with a proof of `len` bytes.
+    fn mirror_remote_data(key_len: u32, len: u32) -> Weight;
+}
+
+impl WeightInfo for () {
+    fn read_data(key_len: u32) -> Weight {
+        10_000u32.saturating_add(key_len.saturating_mul(10))
+    }
+
+    fn write_data(key_len: u32, len: u32) -> Weight {
+        20_000u32
+            .saturating_add(key_len.saturating_mul(10))
+            .saturating_add(len.saturating_mul(100))
+    }
+
+    fn delete_data(key_len: u32) -> Weight {
+        10_000u32.saturating_add(key_len.saturating_mul(10))
+    }
+
+    fn copy_data(key_len: u32, len: u32) -> Weight {
+        Self::read_data(key_len).saturating_add(Self::write_data(key_len, len))
+    }
+
+    fn migrate_data(key_len: u32, len: u32) -> Weight {
+        Self::read_data(key_len).saturating_add(Self::write_data(key_len, len))
+    }
+
+    fn promote_staged(key_len: u32, len: u32) -> Weight {
+        Self::read_data(key_len).saturating_add(Self::write_data(key_len, len))
+    }
+
+    fn rollback(key_len: u32, len: u32) -> Weight {
+        Self::read_data(key_len).saturating_add(Self::write_data(key_len, len))
+    }
+
+    fn wrap_keys(key_len: u32, keys: u32, len: u32) -> Weight {
+        10_000u32
+            .saturating_add(key_len.saturating_mul(10))
+            .saturating_add(keys.saturating_mul(1_000))
+            .saturating_add(len.saturating_mul(10))
+    }
+
+    fn submit_bundles(bundles: u32, writes: u32, len: u32) -> Weight {
+        10_000u32
+            .saturating_add(bundles.saturating_mul(50_000))
+            .saturating_add(writes.saturating_mul(20_000))
+            .saturating_add(len.saturating_mul(100))
+    }
+
+    fn transact(ops: u32, len: u32) -> Weight {
+        10_000u32.saturating_add(ops.saturating_mul(20_000)).saturating_add(len.saturating_mul(100))
+    }
+
+    fn upload_chunk(key_len: u32, len: u32) -> Weight {
+        Self::write_data(key_len, len)
+    }
+
+    fn commit_upload(key_len: u32, len: u32) -> Weight {
+        Self::read_data(key_len).saturating_add(Self::write_data(key_len, len))
+    }
+
+    fn append_data(key_len: u32, len: u32) -> Weight {
+        Self::read_data(key_len).saturating_add(Self::write_data(key_len, len))
+    }
+
+    fn register_provider(len: u32) -> Weight {
+        10_000u32.saturating_add(len.saturating_mul(10))
+    }
+
+    fn set_reviewers(key_len: u32, reviewers: u32) -> Weight {
+        10_000u32
+            .saturating_add(key_len.saturating_mul(10))
+            .saturating_add(reviewers.saturating_mul(1_000))
+    }
+
+    fn accept_replica(key_len: u32, len: u32) -> Weight {
+        Self::read_data(key_len).saturating_add(Self::write_data(key_len, len))
+    }
+
+    fn read_data_range(key_len: u32, len: u32) -> Weight {
+        Self::read_data(key_len).saturating_add(len.saturating_mul(10))
+    }
+
+    fn submit_storage_proof(key_len: u32, len: u32) -> Weight {
+        10_000u32.saturating_add(key_len.saturating_mul(10)).saturating_add(len.saturating_mul(10))
+    }
+
+    fn mirror_remote_data(key_len: u32, len: u32) -> Weight {
+        Self::write_data(key_len, 0).saturating_add(len.saturating_mul(10))
+    }
+}
+
+/// Weighs a call taking a data_id first with the function of its length.
+pub struct ByKeyLen(pub fn(u32) -> Weight);
+
+impl WeighData<(&Vec<u8>,)> for ByKeyLen {
+    fn weigh_data(&self, (data_id,): (&Vec<u8>,)) -> Weight {
+        (self.0)(data_id.len() as u32)
+    }
+}
+
+/// An endpoint, before the capacity and price of a provider, is weighed with its length.
+impl<A, B> WeighData<(&Vec<u8>, &A, &B)> for ByKeyLen {
+    fn weigh_data(&self, (endpoint, ..): (&Vec<u8>, &A, &B)) -> Weight {
+        (self.0)(endpoint.len() as u32)
+    }
+}
+
+/// Weighs a call taking a data_id and a value first with the function of their lengths.
+/// A value alone, stored under its hash, is weighed with a data_id of no length.
+pub struct ByValueLen(pub fn(u32, u32) -> Weight);
+
+impl WeighData<(&Vec<u8>,)> for ByValueLen {
+    fn weigh_data(&self, (value,): (&Vec<u8>,)) -> Weight {
+        (self.0)(0, value.len() as u32)
+    }
+}
+
+impl WeighData<(&Vec<u8>, &Vec<u8>)> for ByValueLen {
+    fn weigh_data(&self, (data_id, value): (&Vec<u8>, &Vec<u8>)) -> Weight {
+        (self.0)(data_id.len() as u32, value.len() as u32)
+    }
+}
+
+impl<A> WeighData<(&Vec<u8>, &Vec<u8>, &A)> for ByValueLen {
+    fn weigh_data(&self, (data_id, value, _): (&Vec<u8>, &Vec<u8>, &A)) -> Weight {
+        (self.0)(data_id.len() as u32, value.len() as u32)
+    }
+}
+
+impl<A, B> WeighData<(&Vec<u8>, &Vec<u8>, &A, &B)> for ByValueLen {
+    fn weigh_data(&self, (data_id, value, ..): (&Vec<u8>, &Vec<u8>, &A, &B)) -> Weight {
+        (self.0)(data_id.len() as u32, value.len() as u32)
+    }
+}
+
+/// A chunk, after its data_id and index, is weighed with its length.
+impl WeighData<(&Vec<u8>, &u32, &Vec<u8>)> for ByValueLen {
+    fn weigh_data(&self, (data_id, _, chunk): (&Vec<u8>, &u32, &Vec<u8>)) -> Weight {
+        (self.0)(data_id.len() as u32, chunk.len() as u32)
+    }
+}
+
+/// Weighs a call taking a data_id first, whose value it reads rather than carries, with the
+/// function of the length of the data_id and of the longest value, `.1`.
+pub struct UpToValueLen(pub fn(u32, u32) -> Weight, pub u32);
+
+impl WeighData<(&Vec<u8>,)> for UpToValueLen {
+    fn weigh_data(&self, (data_id,): (&Vec<u8>,)) -> Weight {
+        (self.0)(data_id.len() as u32, self.1)
+    }
+}
+
+impl<A> WeighData<(&Vec<u8>, &A)> for UpToValueLen {
+    fn weigh_data(&self, (data_id, _): (&Vec<u8>, &A)) -> Weight {
+        (self.0)(data_id.len() as u32, self.1)
+    }
+}
+
+impl<A, B> WeighData<(&Vec<u8>, &A, &B)> for UpToValueLen {
+    fn weigh_data(&self, (data_id, ..): (&Vec<u8>, &A, &B)) -> Weight {
+        (self.0)(data_id.len() as u32, self.1)
+    }
+}
+
+/// Weighs a call carrying a proof with the function of the length of its data_id and of the
+/// proof, which is hashed to check it.
+pub struct ByProofLen(pub fn(u32, u32) -> Weight);
+
+/// The answer to a challenge, after its data_id and nonce.
+impl<H> WeighData<(&Vec<u8>, &u64, &StorageProof<H>)> for ByProofLen {
+    fn weigh_data(&self, (data_id, _, proof): (&Vec<u8>, &u64, &StorageProof<H>)) -> Weight {
+        let len = proof.chunk.len().saturating_add(proof.path.len() * mem::size_of::<H>());
+        (self.0)(data_id.len() as u32, len as u32)
+    }
+}
+
+/// The trie nodes proving a remote entry, after its chain, header and data_id.
+impl<A, B> WeighData<(&A, &B, &Vec<u8>, &Vec<Vec<u8>>)> for ByProofLen {
+    fn weigh_data(&self, (_, _, data_id, proof): (&A, &B, &Vec<u8>, &Vec<Vec<u8>>)) -> Weight {
+        let len = proof.iter().fold(0u32, |len, node| len.saturating_add(node.len() as u32));
+        (self.0)(data_id.len() as u32, len)
+    }
+}
+
+/// Weighs `wrap_keys` with the function of the length of its data_id, the number of keys
+/// and their length in all.
+pub struct ByWrappedKeys(pub fn(u32, u32, u32) -> Weight);
+
+impl<A, B> WeighData<(&Vec<u8>, &A, &Vec<(B, Vec<u8>)>)> for ByWrappedKeys {
+    fn weigh_data(&self, (data_id, _, wrapped): (&Vec<u8>, &A, &Vec<(B, Vec<u8>)>)) -> Weight {
+        let len = wrapped.iter().fold(0u32, |len, (_, key)| len.saturating_add(key.len() as u32));
+        (self.0)(data_id.len() as u32, wrapped.len() as u32, len)
+    }
+}
+
+/// Weighs `set_reviewers` with the function of the length of its data_id and the number of
+/// reviewers.
+pub struct ByReviewers(pub fn(u32, u32) -> Weight);
+
+impl<A> WeighData<(&Vec<u8>, &Vec<A>)> for ByReviewers {
+    fn weigh_data(&self, (data_id, reviewers): (&Vec<u8>, &Vec<A>)) -> Weight {
+        (self.0)(data_id.len() as u32, reviewers.len() as u32)
+    }
+}
+
+/// Weighs `submit_bundle` and `submit_bundles` with the function of the number of bundles,
+/// of their writes and of the length of the data_ids and values written.
+pub struct ByBundleLen(pub fn(u32, u32, u32) -> Weight);
+
+impl ByBundleLen {
+    fn weigh<A, B>(&self, bundles: &[&WriteBundle<A, B>]) -> Weight {
+        let writes = bundles.iter().flat_map(|bundle| bundle.writes.iter());
+        let len = writes.clone().fold(0u32, |len, (data_id, value)| {
+            len.saturating_add((data_id.len() + value.len()) as u32)
+        });
+        (self.0)(bundles.len() as u32, writes.count() as u32, len)
+    }
+}
+
+impl<A, B, S> WeighData<(&WriteBundle<A, B>, &S)> for ByBundleLen {
+    fn weigh_data(&self, (bundle, _): (&WriteBundle<A, B>, &S)) -> Weight {
+        self.weigh(&[bundle])
+    }
+}
+
+impl<A, B, S> WeighData<(&Vec<(WriteBundle<A, B>, S)>,)> for ByBundleLen {
+    fn weigh_data(&self, (bundles,): (&Vec<(WriteBundle<A, B>, S)>,)) -> Weight {
+        self.weigh(&bundles.iter().map(|(bundle, _)| bundle).collect::<Vec<_>>())
+    }
+}
+
+/// Weighs `transact` with the function of the number of ops and of the length of the
+/// data_ids and values they carry.
+pub struct ByOpsLen(pub fn(u32, u32) -> Weight);
+
+impl WeighData<(&Vec<Op>,)> for ByOpsLen {
+    fn weigh_data(&self, (ops,): (&Vec<Op>,)) -> Weight {
+        let len = ops.iter().fold(0u32, |len, op| {
+            let value = match op {
+                Op::Write(_, value) => value.len(),
+                _ => 0,
+            };
+            let ids = op.data_ids().iter().map(|data_id| data_id.len()).sum::<usize>();
+            len.saturating_add((ids + value) as u32)
+        });
+        (self.0)(ops.len() as u32, len)
+    }
+}
+
+impl<T> ClassifyDispatch<T> for ByKeyLen {
+    fn classify_dispatch(&self, _: T) -> DispatchClass {
+        DispatchClass::Normal
+    }
+}
+
+impl<T> ClassifyDispatch<T> for ByValueLen {
+    fn classify_dispatch(&self, _: T) -> DispatchClass {
+        DispatchClass::Normal
+    }
+}
+
+impl<T> ClassifyDispatch<T> for UpToValueLen {
+    fn classify_dispatch(&self, _: T) -> DispatchClass {
+        DispatchClass::Normal
+    }
+}
+
+impl<T> ClassifyDispatch<T> for ByProofLen {
+    fn classify_dispatch(&self, _: T) -> DispatchClass {
+        DispatchClass::Normal
+    }
+}
+
+impl<T> ClassifyDispatch<T> for ByWrappedKeys {
+    fn classify_dispatch(&self, _: T) -> DispatchClass {
+        DispatchClass::Normal
+    }
+}
+
+impl<T> ClassifyDispatch<T> for ByReviewers {
+    fn classify_dispatch(&self, _: T) -> DispatchClass {
+        DispatchClass::Normal
+    }
+}
+
+impl<T> ClassifyDispatch<T> for ByBundleLen {
+    fn classify_dispatch(&self, _: T) -> DispatchClass {
+        DispatchClass::Normal
+    }
+}
+
+impl<T> ClassifyDispatch<T> for ByOpsLen {
+    fn classify_dispatch(&self, _: T) -> DispatchClass {
+        DispatchClass::Normal
+    }
+}
+
+impl PaysFee for ByKeyLen {}
+
+impl PaysFee for ByValueLen {}
+
+impl PaysFee for UpToValueLen {}
+
+impl PaysFee for ByProofLen {}
+
+impl PaysFee for ByWrappedKeys {}
+
+impl PaysFee for ByReviewers {}
+
+impl PaysFee for ByBundleLen {}
+
+impl PaysFee for ByOpsLen {}
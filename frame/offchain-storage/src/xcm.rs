@@ -0,0 +1,39 @@
+//! Remote requests delivered as XCM messages.
+//!
+//! [`XcmHandler`] lets the XCM executor of a parachain runtime hand the module the
+//! `Transact` messages of other chains whose call is the SCALE encoding of a
+//! [`RemoteRequest`]. The request is applied as `HandleRemoteRequest` would, for the
+//! sending account of the parachain, whose chain id is [`para_chain_id`]. Requests that
+//! grant access to, or transfer entries to, accounts of parachains name the chain the same
+//! way.
+//!
+//! The crate doesn't depend on XCM itself: the runtime turns the origin `MultiLocation`
+//! of a message into the id of the parachain and the account that sent it.
+
+use super::{
+    bridge::{HandleRemoteRequest, RemoteRequest},
+    Error, Module, Trait,
+};
+use codec::{Decode, Encode};
+use frame_support::dispatch::DispatchResult;
+use sp_std::{marker::PhantomData, vec::Vec};
+
+/// The chain id the parachain `para_id` has in requests and sovereign accounts.
+pub fn para_chain_id(para_id: u32) -> Vec<u8> {
+    (&b"para"[..], para_id).encode()
+}
+
+/// Applies the requests of XCM messages.
+pub struct XcmHandler<T>(PhantomData<T>);
+
+impl<T: Trait> XcmHandler<T> {
+    /// Apply the request `call` encodes, sent by `sender` of the parachain `para_id`.
+    pub fn handle_transact(para_id: u32, sender: &[u8], call: &[u8]) -> DispatchResult {
+        let input = &mut &call[..];
+        let request = RemoteRequest::decode(input)
+            .ok()
+            .filter(|_| input.is_empty())
+            .ok_or(Error::<T>::BadRemoteMessage)?;
+        <Module<T>>::handle_remote_request(&para_chain_id(para_id), sender, request)
+    }
+}
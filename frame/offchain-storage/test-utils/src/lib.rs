@@ -0,0 +1,224 @@
+//! Test helpers for runtimes using the offchain-storage module.
+//!
+//! [`MockStorage`] is an `ExternalStorage` backed by a map of the current thread that
+//! records every call made to it, so a test can check what reached the backend rather than
+//! only what a dispatch returned:
+//!
+//! ```ignore
+//! impl offchain_storage::Trait for Test {
+//!     type Storage = MockStorage;
+//!     ...
+//! }
+//!
+//! MockStorage::reset();
+//! assert_ok!(OffchainStorage::write_data(Origin::signed(1), b"key".to_vec(), b"a".to_vec()));
+//! MockStorage::assert_set(b"key", b"a");
+//! ```
+//!
+//! Tests run each on their own thread, so they don't see each other's calls, but a test
+//! should still `reset` the mock before it starts.
+
+use offchain_storage::{ExternalStorage, StorageError};
+use std::{cell::RefCell, collections::BTreeMap};
+
+/// A call made to the backend.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Call {
+    Get(Vec<u8>),
+    Set(Vec<u8>, Vec<u8>),
+    Delete(Vec<u8>),
+    /// The key, offset and length of a ranged read.
+    GetRange(Vec<u8>, u32, u32),
+    Append(Vec<u8>, Vec<u8>),
+    Pin(Vec<u8>),
+    Unpin(Vec<u8>),
+}
+
+impl Call {
+    /// The key the call was made for.
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Call::Get(key)
+            | Call::Set(key, _)
+            | Call::Delete(key)
+            | Call::GetRange(key, ..)
+            | Call::Append(key, _)
+            | Call::Pin(key)
+            | Call::Unpin(key) => key,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    values: BTreeMap<Vec<u8>, Vec<u8>>,
+    calls: Vec<Call>,
+    failure: Option<StorageError>,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+/// A backend that keeps values in memory and records the calls made to it.
+pub struct MockStorage;
+
+impl MockStorage {
+    /// Drop every value and call recorded, and work again if made to fail.
+    pub fn reset() {
+        STATE.with(|s| *s.borrow_mut() = State::default());
+    }
+
+    /// Every call made since the last reset, in order, failed ones included.
+    pub fn calls() -> Vec<Call> {
+        STATE.with(|s| s.borrow().calls.clone())
+    }
+
+    /// The calls made for `key` since the last reset.
+    pub fn calls_for(key: &[u8]) -> Vec<Call> {
+        Self::calls().into_iter().filter(|call| call.key() == key).collect()
+    }
+
+    /// Forget the calls recorded so far, keeping the values.
+    pub fn clear_calls() {
+        STATE.with(|s| s.borrow_mut().calls.clear());
+    }
+
+    /// The value under `key`, if any.
+    pub fn value(key: &[u8]) -> Option<Vec<u8>> {
+        STATE.with(|s| s.borrow().values.get(key).cloned())
+    }
+
+    /// Make every call fail with `reason` from now on, or work again with `None`.
+    pub fn fail_with(reason: Option<StorageError>) {
+        STATE.with(|s| s.borrow_mut().failure = reason);
+    }
+
+    /// Panic unless `value` was set under `key` since the last reset.
+    pub fn assert_set(key: &[u8], value: &[u8]) {
+        let set = Call::Set(key.to_vec(), value.to_vec());
+        assert!(
+            Self::calls().contains(&set),
+            "{:?} wasn't set to {:?}, calls for it: {:?}",
+            key,
+            value,
+            Self::calls_for(key)
+        );
+    }
+
+    /// Panic unless `key` was deleted since the last reset.
+    pub fn assert_deleted(key: &[u8]) {
+        assert!(
+            Self::calls().contains(&Call::Delete(key.to_vec())),
+            "{:?} wasn't deleted, calls for it: {:?}",
+            key,
+            Self::calls_for(key)
+        );
+    }
+
+    /// Panic if any call was made for `key` since the last reset.
+    pub fn assert_untouched(key: &[u8]) {
+        let calls = Self::calls_for(key);
+        assert!(calls.is_empty(), "{:?} was touched: {:?}", key, calls);
+    }
+
+    // record call, then fail if made to or apply f to the values.
+    fn record<R>(
+        call: Call,
+        f: impl FnOnce(&mut BTreeMap<Vec<u8>, Vec<u8>>) -> R,
+    ) -> Result<R, StorageError> {
+        STATE.with(|s| {
+            let mut state = s.borrow_mut();
+            state.calls.push(call);
+            match state.failure {
+                Some(reason) => Err(reason),
+                None => Ok(f(&mut state.values)),
+            }
+        })
+    }
+}
+
+impl ExternalStorage for MockStorage {
+    fn get(key: Vec<u8>) -> Result<Vec<u8>, StorageError> {
+        Self::record(Call::Get(key.clone()), |values| {
+            values.get(&key).cloned().unwrap_or_default()
+        })
+    }
+
+    fn set(key: Vec<u8>, value: Vec<u8>) -> Result<(), StorageError> {
+        Self::record(Call::Set(key.clone(), value.clone()), |values| {
+            values.insert(key, value);
+        })
+    }
+
+    fn delete(key: Vec<u8>) -> Result<(), StorageError> {
+        Self::record(Call::Delete(key.clone()), |values| {
+            values.remove(&key);
+        })
+    }
+
+    fn get_range(key: Vec<u8>, offset: u32, len: u32) -> Result<Vec<u8>, StorageError> {
+        Self::record(Call::GetRange(key.clone(), offset, len), |values| {
+            let value = values.get(&key).map_or(&[][..], |value| &value[..]);
+            let start = (offset as usize).min(value.len());
+            let end = start.saturating_add(len as usize).min(value.len());
+            value[start..end].to_vec()
+        })
+    }
+
+    fn append(key: Vec<u8>, bytes: Vec<u8>) -> Result<(), StorageError> {
+        Self::record(Call::Append(key.clone(), bytes.clone()), |values| {
+            values.entry(key).or_insert_with(Vec::new).extend(bytes);
+        })
+    }
+
+    fn pin(key: Vec<u8>) -> Result<(), StorageError> {
+        Self::record(Call::Pin(key), |_| ())
+    }
+
+    fn unpin(key: Vec<u8>) -> Result<(), StorageError> {
+        Self::record(Call::Unpin(key), |_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_are_recorded() {
+        MockStorage::reset();
+        assert_eq!(MockStorage::set(b"key".to_vec(), b"ab".to_vec()), Ok(()));
+        assert_eq!(MockStorage::append(b"key".to_vec(), b"c".to_vec()), Ok(()));
+        assert_eq!(MockStorage::get_range(b"key".to_vec(), 1, 5), Ok(b"bc".to_vec()));
+        MockStorage::assert_set(b"key", b"ab");
+        assert_eq!(MockStorage::value(b"key"), Some(b"abc".to_vec()));
+        MockStorage::assert_untouched(b"other");
+
+        MockStorage::fail_with(Some(StorageError::Timeout));
+        assert_eq!(MockStorage::delete(b"key".to_vec()), Err(StorageError::Timeout));
+        assert_eq!(MockStorage::value(b"key"), Some(b"abc".to_vec()));
+        assert_eq!(
+            MockStorage::calls_for(b"key"),
+            vec![
+                Call::Set(b"key".to_vec(), b"ab".to_vec()),
+                Call::Append(b"key".to_vec(), b"c".to_vec()),
+                Call::GetRange(b"key".to_vec(), 1, 5),
+                Call::Delete(b"key".to_vec()),
+            ]
+        );
+        MockStorage::assert_deleted(b"key");
+
+        MockStorage::reset();
+        assert_eq!(MockStorage::get(b"key".to_vec()), Ok(vec![]));
+        assert_eq!(MockStorage::calls(), vec![Call::Get(b"key".to_vec())]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn missing_sets_fail_the_test() {
+        MockStorage::reset();
+        let _ = MockStorage::set(b"key".to_vec(), b"a".to_vec());
+        MockStorage::assert_set(b"key", b"b");
+    }
+}
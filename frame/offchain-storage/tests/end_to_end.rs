@@ -0,0 +1,159 @@
+//! End-to-end tests of the offchain-storage module wired into the example runtime.
+//!
+//! The runtime of `examples/runtime` dispatches through the outer `Call` and emits through
+//! the outer event, the same way a node would execute the extrinsics, and stores values in
+//! files, IPFS or RocksDB, so the bytes really leave the chain state. Its node authors
+//! blocks and runs the offchain worker after each, and the calls the worker submits make it
+//! into the next block, which takes deferred writes through the whole upload pipeline.
+//!
+//! The IPFS test needs a daemon, e.g. `docker run -p 5001:5001 ipfs/go-ipfs`, and is run
+//! with `cargo test -- --ignored`. Not covered here, to be added once a node of this tree
+//! builds a runtime with the module: the wasm runtime, a node process and its transaction
+//! pool between the worker and the next block.
+
+#[path = "../examples/runtime/mod.rs"]
+mod runtime;
+
+use frame_system::{EventRecord, Phase};
+use offchain_storage::{
+    history,
+    routing::{StorageBackends, DEFAULT_BACKEND},
+    ExternalStorage, RawEvent, StorageError,
+};
+use runtime::{
+    ipfs, node::Node, Backends, Call, FsStorage, MetaEvent, OffchainStorage, Origin, Runtime,
+    System, IPFS, ROCKSDB,
+};
+use sp_runtime::traits::{BlakeTwo256, Dispatchable, Hash};
+
+type ModuleCall = offchain_storage::Call<Runtime>;
+
+fn dispatch(who: u64, call: ModuleCall) {
+    Call::OffchainStorage(call)
+        .dispatch(Origin::signed(who))
+        .unwrap();
+}
+
+// the value under key in backend, if any.
+fn stored(backend: u8, key: &[u8]) -> Option<Vec<u8>> {
+    match backend {
+        DEFAULT_BACKEND => FsStorage::get(key.to_vec()),
+        backend => <Backends as StorageBackends>::get(backend, key.to_vec()),
+    }
+    .ok()
+    .filter(|value| !value.is_empty())
+}
+
+#[test]
+fn write_read_delete_round_trip() {
+    runtime::set_deferred_uploads(false);
+    Node::default().execute_with(|| {
+        System::initialize(&1, &[0u8; 32].into(), &[0u8; 32].into(), &Default::default());
+        let key = b"end-to-end".to_vec();
+        let value = b"hello web3".to_vec();
+
+        dispatch(1, ModuleCall::write_data(key.clone(), value.clone()));
+        assert!(FsStorage::path(&key).exists());
+
+        dispatch(2, ModuleCall::read_data(key.clone()));
+        let len = value.len() as u32;
+        assert_eq!(
+            System::events(),
+            vec![
+                RawEvent::DataUpdated(key.clone(), 1, vec![]),
+                RawEvent::DataWritten(1, key.clone(), len),
+                RawEvent::DataRead(2, key.clone()),
+                RawEvent::GetData(BlakeTwo256::hash(&value)),
+            ]
+            .into_iter()
+            .map(|event| EventRecord {
+                phase: Phase::ApplyExtrinsic(0),
+                event: MetaEvent::offchain_storage(event),
+                topics: vec![],
+            })
+            .collect::<Vec<_>>()
+        );
+        // the value itself is fetched where the runtime says, as the RPC would.
+        let location = OffchainStorage::locate(&2, &key).unwrap();
+        assert_eq!(FsStorage::get(location.key), Ok(value));
+
+        dispatch(1, ModuleCall::delete_data(key.clone()));
+        assert!(!FsStorage::path(&key).exists());
+        assert!(OffchainStorage::get_data(&key) == Default::default());
+    });
+}
+
+// write, read and delete key in backend with deferred uploads, block by block.
+fn round_trip_through_worker(backend: u8, key: &[u8]) {
+    runtime::set_deferred_uploads(true);
+    let mut node = Node::default();
+    let (key, value) = (key.to_vec(), b"hello web3".to_vec());
+    let hash = BlakeTwo256::hash(&value);
+    let version = history::version_key(&key, 1);
+
+    // the block only records the value, the worker run after it uploads it.
+    let write = ModuleCall::write_data_to(key.clone(), value.clone(), backend);
+    assert_eq!(stored(backend, &key), None);
+    assert_eq!(node.author_block(vec![(1, write.into())]), vec![Ok(())]);
+    assert_eq!(stored(backend, &key), Some(value.clone()));
+    assert_eq!(stored(DEFAULT_BACKEND, &version), Some(value.clone()));
+    assert!(node.execute_with(|| OffchainStorage::pending_upload(&key)).is_some());
+
+    // its confirmation is applied in the next block.
+    node.author_block(vec![]);
+    assert_eq!(node.events(), vec![RawEvent::UploadConfirmed(key.clone())]);
+    assert_eq!(node.execute_with(|| OffchainStorage::pending_upload(&key)), None);
+
+    let read = ModuleCall::read_data(key.clone());
+    assert_eq!(node.author_block(vec![(2, read.into())]), vec![Ok(())]);
+    assert!(node.events().contains(&RawEvent::GetData(hash)));
+    assert_eq!(node.deliver(2, &key), Some(value));
+
+    let delete = ModuleCall::delete_data(key.clone());
+    assert_eq!(node.author_block(vec![(1, delete.into())]), vec![Ok(())]);
+    assert_eq!(stored(backend, &key), None);
+    // the past versions go to the garbage collector.
+    node.author_block(vec![]);
+    assert_eq!(stored(DEFAULT_BACKEND, &version), None);
+    assert_eq!(node.deliver(2, &key), None);
+}
+
+#[test]
+fn offchain_workers_upload_to_files() {
+    round_trip_through_worker(DEFAULT_BACKEND, b"end-to-end-files");
+}
+
+#[test]
+fn offchain_workers_upload_to_rocksdb() {
+    round_trip_through_worker(ROCKSDB, b"end-to-end-rocksdb");
+}
+
+#[test]
+#[ignore]
+fn offchain_workers_upload_to_ipfs() {
+    assert!(ipfs::is_running(), "an IPFS daemon answers, see the ipfs module of the runtime");
+    round_trip_through_worker(IPFS, b"end-to-end-ipfs");
+}
+
+#[test]
+fn offchain_workers_give_up_on_unavailable_backends() {
+    // nothing listens on the discard port, every upload fails.
+    ipfs::connect_to("127.0.0.1:9");
+    runtime::set_deferred_uploads(true);
+    let mut node = Node::default();
+    let key = b"end-to-end-unavailable".to_vec();
+
+    let write = ModuleCall::write_data_to(key.clone(), b"lost".to_vec(), IPFS);
+    assert_eq!(node.author_block(vec![(1, write.into())]), vec![Ok(())]);
+    // the worker backs off between attempts, the fifth failure is reported.
+    let failed = RawEvent::WriteFailed(key.clone(), StorageError::Unavailable);
+    assert_eq!(node.author_until(32, |event| *event == failed), Some(failed.clone()));
+    node.execute_with(|| {
+        assert_eq!(System::block_number(), 17);
+        assert_eq!(OffchainStorage::pending_upload(&key), None);
+    });
+
+    // the entry keeps the hash of the lost value, nothing delivers it.
+    node.author_block(vec![(2, ModuleCall::read_data(key.clone()).into())]);
+    assert_eq!(node.deliver(2, &key), None);
+}